@@ -25,6 +25,9 @@ use crate::assuan;
 use crate::Keygrip;
 use crate::sexp::Sexp;
 
+#[cfg(unix)]
+pub mod server;
+
 /// A GnuPG context.
 #[derive(Debug)]
 pub struct Context {
@@ -0,0 +1,662 @@
+//! A gpg-agent compatible server.
+//!
+//! This module implements the server side of the subset of
+//! gpg-agent's Assuan protocol that clients use to operate on secret
+//! keys: `HAVEKEY`, `KEYINFO`, `READKEY`, `SIGKEY`/`SETKEY`,
+//! `SETHASH`, `PKSIGN`, and `PKDECRYPT`.  The keys themselves are
+//! provided by a [`Backend`], which may for example keep them in an
+//! HSM.
+//!
+//! Clients, like GnuPG or [`gnupg::KeyPair`], can use the keys without
+//! modification by pointing them at the server's socket.
+//!
+//! [`gnupg::KeyPair`]: super::KeyPair
+
+use std::convert::TryFrom;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixListener;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use sequoia_openpgp as openpgp;
+use openpgp::crypto::{mem::Protected, mpi, SessionKey};
+use openpgp::crypto::hash::Digest;
+use openpgp::fmt::hex;
+use openpgp::packet::{Key, key};
+use openpgp::parse::Parse;
+use openpgp::types::HashAlgorithm;
+
+use crate::Keygrip;
+use crate::Result;
+use crate::sexp::Sexp;
+
+// Maximum line length of the reference implementation.
+const MAX_LINE_LENGTH: usize = 1000;
+
+/// The version of gpg-agent whose protocol we implement.
+///
+/// Clients compare this to their own version, and warn if the agent
+/// is older.
+const PROTOCOL_VERSION: &str = "2.2.0";
+
+/// Error codes, as defined in `libgpg-error`.
+///
+/// The error source is encoded in the upper bits.
+const GPG_ERR_SOURCE_GPGAGENT: usize = 4 << 24;
+const GPG_ERR_GENERAL: usize = GPG_ERR_SOURCE_GPGAGENT | 1;
+const GPG_ERR_NO_SECKEY: usize = GPG_ERR_SOURCE_GPGAGENT | 17;
+const GPG_ERR_INV_VALUE: usize = GPG_ERR_SOURCE_GPGAGENT | 55;
+const GPG_ERR_NO_DATA: usize = GPG_ERR_SOURCE_GPGAGENT | 58;
+const GPG_ERR_NOT_SUPPORTED: usize = GPG_ERR_SOURCE_GPGAGENT | 60;
+const GPG_ERR_CANCELED: usize = GPG_ERR_SOURCE_GPGAGENT | 99;
+const GPG_ERR_ASS_UNKNOWN_CMD: usize = GPG_ERR_SOURCE_GPGAGENT | 275;
+
+/// The result of a `PKDECRYPT` operation.
+pub enum Plaintext {
+    /// The unpadded result of an RSA or ElGamal decryption.
+    Unpadded(SessionKey),
+    /// The shared point of an ECDH key agreement.
+    ///
+    /// The client derives the key-encryption key from it, and unwraps
+    /// the session key.
+    SharedPoint(Protected),
+}
+
+/// Provides the keys served by a [`Server`].
+///
+/// Keys are identified by their [`Keygrip`].
+pub trait Backend: Send {
+    /// Returns the keygrips of all keys.
+    fn keygrips(&self) -> Vec<Keygrip>;
+
+    /// Returns the public key with the given keygrip, if any.
+    fn public_key(&self, grip: &Keygrip)
+                  -> Option<Key<key::PublicParts, key::UnspecifiedRole>>;
+
+    /// Signs `digest` using the key with the given keygrip.
+    fn sign(&mut self, grip: &Keygrip, hash_algo: HashAlgorithm,
+            digest: &[u8])
+            -> Result<mpi::Signature>;
+
+    /// Decrypts `ciphertext` using the key with the given keygrip.
+    fn decrypt(&mut self, grip: &Keygrip, ciphertext: &mpi::Ciphertext)
+               -> Result<Plaintext>;
+}
+
+/// A gpg-agent compatible server.
+///
+/// Every connection is served by its own thread.  Operations on the
+/// backend are serialized.
+pub struct Server<B: Backend> {
+    backend: Arc<Mutex<B>>,
+}
+
+impl<B: Backend + 'static> Server<B> {
+    /// Returns a server for the keys provided by `backend`.
+    pub fn new(backend: B) -> Self {
+        Server {
+            backend: Arc::new(Mutex::new(backend)),
+        }
+    }
+
+    /// Accepts and serves connections on `listener`.
+    ///
+    /// This function only returns if accepting connections fails.
+    pub fn serve(&self, listener: UnixListener) -> Result<()> {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let backend = self.backend.clone();
+            thread::spawn(move || -> Result<()> {
+                let reader = BufReader::new(stream.try_clone()?);
+                Session::new(backend).handle(reader, stream)
+            });
+        }
+        Ok(())
+    }
+
+    /// Serves a single connection.
+    pub fn handle<R, W>(&self, reader: R, writer: W) -> Result<()>
+        where R: BufRead, W: Write
+    {
+        Session::new(self.backend.clone()).handle(reader, writer)
+    }
+}
+
+/// The state of a connection.
+struct Session<B: Backend> {
+    backend: Arc<Mutex<B>>,
+    /// The key set using `SIGKEY` or `SETKEY`.
+    key: Option<Keygrip>,
+    /// The digest set using `SETHASH`.
+    hash: Option<(HashAlgorithm, Vec<u8>)>,
+}
+
+/// The outcome of a command.
+enum Outcome {
+    /// Reply with OK, and continue.
+    Ok,
+    /// Reply with OK, and close the connection.
+    Bye,
+}
+
+/// A failed command, reported to the client using ERR.
+struct Failure(usize, String);
+
+impl Failure {
+    fn new<S: Into<String>>(code: usize, message: S) -> Self {
+        Failure(code, message.into())
+    }
+
+    fn no_seckey() -> Self {
+        Failure::new(GPG_ERR_NO_SECKEY, "No secret key")
+    }
+}
+
+impl From<anyhow::Error> for Failure {
+    fn from(e: anyhow::Error) -> Self {
+        Failure::new(GPG_ERR_GENERAL, format!("{}", e))
+    }
+}
+
+impl From<std::io::Error> for Failure {
+    fn from(e: std::io::Error) -> Self {
+        Failure::new(GPG_ERR_GENERAL, format!("{}", e))
+    }
+}
+
+type CommandResult = std::result::Result<Outcome, Failure>;
+
+impl<B: Backend> Session<B> {
+    fn new(backend: Arc<Mutex<B>>) -> Self {
+        Session {
+            backend,
+            key: None,
+            hash: None,
+        }
+    }
+
+    fn handle<R, W>(mut self, mut reader: R, mut writer: W) -> Result<()>
+        where R: BufRead, W: Write
+    {
+        writeln!(writer, "OK Pleased to meet you")?;
+        writer.flush()?;
+
+        while let Some(line) = read_line(&mut reader)? {
+            if line.is_empty() || line.starts_with(b"#") {
+                continue;
+            }
+            let line = String::from_utf8_lossy(&line).into_owned();
+            let (command, args) = match line.find(' ') {
+                Some(i) => (&line[..i], line[i + 1..].trim()),
+                None => (&line[..], ""),
+            };
+
+            let result =
+                self.command(&command.to_uppercase(), args,
+                             &mut reader, &mut writer);
+            match result {
+                Ok(Outcome::Ok) => writeln!(writer, "OK")?,
+                Ok(Outcome::Bye) => {
+                    writeln!(writer, "OK closing connection")?;
+                    writer.flush()?;
+                    return Ok(());
+                },
+                Err(Failure(code, message)) =>
+                    writeln!(writer, "ERR {} {} <GPG Agent>",
+                             code, escape_line(&message))?,
+            }
+            writer.flush()?;
+        }
+
+        Ok(())
+    }
+
+    fn command<R, W>(&mut self, command: &str, args: &str,
+                     reader: &mut R, writer: &mut W)
+                     -> CommandResult
+        where R: BufRead, W: Write
+    {
+        match command {
+            "NOP" | "OPTION" => Ok(Outcome::Ok),
+            "BYE" => Ok(Outcome::Bye),
+            "RESET" => {
+                self.key = None;
+                self.hash = None;
+                Ok(Outcome::Ok)
+            },
+            "GETINFO" => match args {
+                "version" => {
+                    send_data(writer, PROTOCOL_VERSION.as_bytes())?;
+                    Ok(Outcome::Ok)
+                },
+                "pid" => {
+                    send_data(writer,
+                              std::process::id().to_string().as_bytes())?;
+                    Ok(Outcome::Ok)
+                },
+                _ => Err(Failure::new(GPG_ERR_NOT_SUPPORTED,
+                                      "Unknown GETINFO subcommand")),
+            },
+            "HAVEKEY" => self.havekey(args, writer),
+            "KEYINFO" => self.keyinfo(args, writer),
+            "READKEY" => self.readkey(args, writer),
+            "SIGKEY" | "SETKEY" => {
+                let grip = self.known_keygrip(args)?;
+                self.key = Some(grip);
+                Ok(Outcome::Ok)
+            },
+            "SETHASH" => self.sethash(args),
+            "PKSIGN" => self.pksign(writer),
+            "PKDECRYPT" => self.pkdecrypt(reader, writer),
+            _ => Err(Failure::new(GPG_ERR_ASS_UNKNOWN_CMD,
+                                  "Unknown IPC command")),
+        }
+    }
+
+    /// Parses `arg` as keygrip, and checks that we have the key.
+    fn known_keygrip(&self, arg: &str) -> std::result::Result<Keygrip, Failure> {
+        let grip: Keygrip = arg.parse()
+            .map_err(|_| Failure::new(GPG_ERR_INV_VALUE, "Invalid keygrip"))?;
+        if self.backend.lock().unwrap().public_key(&grip).is_some() {
+            Ok(grip)
+        } else {
+            Err(Failure::no_seckey())
+        }
+    }
+
+    fn havekey<W: Write>(&self, args: &str, writer: &mut W) -> CommandResult {
+        let grips = self.backend.lock().unwrap().keygrips();
+
+        if args.split_whitespace().any(|a| a.starts_with("--list")) {
+            let mut data = Vec::with_capacity(grips.len() * 20);
+            for grip in grips {
+                data.extend_from_slice(grip.as_ref());
+            }
+            send_data(writer, &data)?;
+            return Ok(Outcome::Ok);
+        }
+
+        // Succeeds if we have any of the given keys.
+        let have_any = args.split_whitespace()
+            .filter_map(|a| a.parse::<Keygrip>().ok())
+            .any(|grip| grips.contains(&grip));
+        if have_any {
+            Ok(Outcome::Ok)
+        } else {
+            Err(Failure::no_seckey())
+        }
+    }
+
+    fn keyinfo<W: Write>(&self, args: &str, writer: &mut W) -> CommandResult {
+        let list = args.split_whitespace().any(|a| a == "--list");
+        let grips = if list {
+            self.backend.lock().unwrap().keygrips()
+        } else {
+            let grip = args.split_whitespace()
+                .find(|a| ! a.starts_with("--"))
+                .ok_or_else(|| Failure::new(GPG_ERR_INV_VALUE,
+                                            "No keygrip given"))?;
+            vec![self.known_keygrip(grip)?]
+        };
+
+        for grip in grips {
+            // KEYINFO <keygrip> <type> <serialno> <idstr> <cached>
+            //         <protection> <fpr> <ttl> <flags>
+            writeln!(writer, "S KEYINFO {} D - - - - - - -", grip)?;
+        }
+        Ok(Outcome::Ok)
+    }
+
+    fn readkey<W: Write>(&self, args: &str, writer: &mut W) -> CommandResult {
+        let grip = args.split_whitespace()
+            .find(|a| ! a.starts_with("--"))
+            .ok_or_else(|| Failure::new(GPG_ERR_INV_VALUE,
+                                        "No keygrip given"))?;
+        let grip = self.known_keygrip(grip)?;
+        let key = self.backend.lock().unwrap().public_key(&grip)
+            .ok_or_else(Failure::no_seckey)?;
+
+        let mut buf = Vec::new();
+        Sexp::try_from(key.mpis())?.serialize(&mut buf)?;
+        send_data(writer, &buf)?;
+        Ok(Outcome::Ok)
+    }
+
+    fn sethash(&mut self, args: &str) -> CommandResult {
+        let mut algo = None;
+        let mut digest = None;
+        for arg in args.split_whitespace() {
+            if let Some(name) = arg.strip_prefix("--hash=") {
+                algo = Some(match name {
+                    "sha1" => HashAlgorithm::SHA1,
+                    "sha256" => HashAlgorithm::SHA256,
+                    "sha384" => HashAlgorithm::SHA384,
+                    "sha512" => HashAlgorithm::SHA512,
+                    _ => return Err(Failure::new(GPG_ERR_NOT_SUPPORTED,
+                                                 "Unsupported hash algorithm")),
+                });
+            } else if arg.starts_with("--") {
+                return Err(Failure::new(GPG_ERR_NOT_SUPPORTED,
+                                        format!("Unsupported option {}", arg)));
+            } else if algo.is_none() {
+                // The algorithm identifiers used by libgcrypt
+                // coincide with OpenPGP's.
+                let id: u8 = arg.parse().map_err(
+                    |_| Failure::new(GPG_ERR_INV_VALUE,
+                                     "Invalid hash algorithm"))?;
+                algo = Some(HashAlgorithm::from(id));
+            } else {
+                digest = Some(hex::decode(arg).map_err(
+                    |_| Failure::new(GPG_ERR_INV_VALUE, "Invalid digest"))?);
+            }
+        }
+
+        match (algo, digest) {
+            (Some(algo), Some(digest)) => {
+                // Only accept what all backends can sign, so that a
+                // client can't make a backend fail half-way.
+                match algo {
+                    HashAlgorithm::SHA1
+                        | HashAlgorithm::SHA256
+                        | HashAlgorithm::SHA384
+                        | HashAlgorithm::SHA512 => (),
+                    _ => return Err(Failure::new(
+                        GPG_ERR_NOT_SUPPORTED,
+                        format!("Unsupported hash algorithm {}", algo))),
+                }
+                let size = algo.context().map(|ctx| ctx.digest_size())
+                    .map_err(|_| Failure::new(
+                        GPG_ERR_NOT_SUPPORTED,
+                        format!("Unsupported hash algorithm {}", algo)))?;
+                if digest.len() != size {
+                    return Err(Failure::new(GPG_ERR_INV_VALUE,
+                                            "Digest has the wrong length"));
+                }
+                self.hash = Some((algo, digest));
+                Ok(Outcome::Ok)
+            },
+            _ => Err(Failure::new(GPG_ERR_INV_VALUE,
+                                  "Expected an algorithm and a digest")),
+        }
+    }
+
+    fn pksign<W: Write>(&mut self, writer: &mut W) -> CommandResult {
+        let grip = self.key.clone().ok_or_else(Failure::no_seckey)?;
+        let (algo, digest) = self.hash.take()
+            .ok_or_else(|| Failure::new(GPG_ERR_NO_DATA, "No hash set"))?;
+
+        let sig = self.backend.lock().unwrap().sign(&grip, algo, &digest)?;
+
+        let mut buf = Vec::new();
+        Sexp::try_from(&sig)?.serialize(&mut buf)?;
+        send_data(writer, &buf)?;
+        Ok(Outcome::Ok)
+    }
+
+    fn pkdecrypt<R, W>(&mut self, reader: &mut R, writer: &mut W)
+                       -> CommandResult
+        where R: BufRead, W: Write
+    {
+        let grip = self.key.clone().ok_or_else(Failure::no_seckey)?;
+
+        writeln!(writer, "INQUIRE CIPHERTEXT")?;
+        writer.flush()?;
+        let data = read_inquiry(reader)?;
+        let ciphertext = Sexp::from_bytes(&data)?.to_ciphertext()?;
+
+        let plaintext =
+            self.backend.lock().unwrap().decrypt(&grip, &ciphertext)?;
+        let value: &[u8] = match plaintext {
+            Plaintext::Unpadded(ref sk) => {
+                writeln!(writer, "S PADDING 0")?;
+                &sk[..]
+            },
+            Plaintext::SharedPoint(ref point) => &point[..],
+        };
+
+        let mut buf = Vec::new();
+        Sexp::List(vec![
+            Sexp::String("value".into()),
+            Sexp::String(value.into()),
+        ]).serialize(&mut buf)?;
+        send_data(writer, &buf)?;
+        Ok(Outcome::Ok)
+    }
+}
+
+/// Reads a line, stripping the line terminator.
+///
+/// Returns `None` on EOF.
+fn read_line<R: BufRead>(reader: &mut R) -> Result<Option<Vec<u8>>> {
+    let mut line = Vec::new();
+    if reader.read_until(b'\n', &mut line)? == 0 {
+        return Ok(None);
+    }
+    while line.last() == Some(&b'\n') || line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    Ok(Some(line))
+}
+
+/// Reads the client's response to an inquiry.
+fn read_inquiry<R: BufRead>(reader: &mut R)
+                            -> std::result::Result<Vec<u8>, Failure> {
+    let mut data = Vec::new();
+    loop {
+        let line = read_line(reader)?
+            .ok_or_else(|| Failure::new(GPG_ERR_CANCELED,
+                                        "Connection closed"))?;
+        if line == b"END" {
+            return Ok(data);
+        } else if line == b"CAN" {
+            return Err(Failure::new(GPG_ERR_CANCELED, "Operation cancelled"));
+        } else if let Some(chunk) = line.strip_prefix(b"D ") {
+            unescape(chunk, &mut data)?;
+        } else {
+            return Err(Failure::new(GPG_ERR_INV_VALUE,
+                                    "Unexpected response to inquiry"));
+        }
+    }
+}
+
+/// Sends `data` using D lines, escaping as necessary.
+fn send_data<W: Write>(writer: &mut W, mut data: &[u8]) -> Result<()> {
+    while ! data.is_empty() {
+        let mut line = Vec::with_capacity(MAX_LINE_LENGTH);
+        line.extend_from_slice(b"D ");
+        while ! data.is_empty() && line.len() < MAX_LINE_LENGTH - 4 {
+            let c = data[0];
+            data = &data[1..];
+            match c {
+                b'%' | b'\n' | b'\r' =>
+                    write!(&mut line, "%{:02X}", c)?,
+                _ => line.push(c),
+            }
+        }
+        line.push(b'\n');
+        writer.write_all(&line)?;
+    }
+    Ok(())
+}
+
+/// Removes percent-escaping from `data`, appending the result to
+/// `out`.
+fn unescape(mut data: &[u8], out: &mut Vec<u8>)
+            -> std::result::Result<(), Failure> {
+    while let Some((&c, rest)) = data.split_first() {
+        if c == b'%' {
+            let escaped = rest.get(..2)
+                .and_then(|h| std::str::from_utf8(h).ok())
+                .and_then(|h| u8::from_str_radix(h, 16).ok())
+                .ok_or_else(|| Failure::new(GPG_ERR_INV_VALUE,
+                                            "Invalid escape sequence"))?;
+            out.push(escaped);
+            data = &rest[2..];
+        } else {
+            out.push(c);
+            data = rest;
+        }
+    }
+    Ok(())
+}
+
+/// Makes `s` safe for use in a status line.
+fn escape_line(s: &str) -> String {
+    s.replace('%', "%25").replace('\n', "%0A").replace('\r', "%0D")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+    use std::io::BufReader;
+    use std::os::unix::net::UnixStream;
+
+    use openpgp::cert::prelude::*;
+    use openpgp::crypto::{self, Decryptor, Signer};
+    use openpgp::types::PublicKeyAlgorithm;
+
+    /// Serves in-memory keys.
+    struct InMemory(HashMap<Keygrip, crypto::KeyPair>);
+
+    impl InMemory {
+        fn new(cert: &Cert) -> Self {
+            InMemory(cert.keys().unencrypted_secret()
+                     .map(|ka| {
+                         let pair = ka.key().clone().into_keypair().unwrap();
+                         (Keygrip::of(ka.key().mpis()).unwrap(),
+                          pair)
+                     })
+                     .collect())
+        }
+    }
+
+    impl Backend for InMemory {
+        fn keygrips(&self) -> Vec<Keygrip> {
+            self.0.keys().cloned().collect()
+        }
+
+        fn public_key(&self, grip: &Keygrip)
+                      -> Option<Key<key::PublicParts, key::UnspecifiedRole>>
+        {
+            self.0.get(grip).map(|p| Signer::public(p).clone())
+        }
+
+        fn sign(&mut self, grip: &Keygrip, hash_algo: HashAlgorithm,
+                digest: &[u8])
+                -> Result<mpi::Signature> {
+            self.0.get_mut(grip).unwrap().sign(hash_algo, digest)
+        }
+
+        fn decrypt(&mut self, grip: &Keygrip, ciphertext: &mpi::Ciphertext)
+                   -> Result<Plaintext> {
+            let pair = self.0.get_mut(grip).unwrap();
+            assert_eq!(Decryptor::public(pair).pk_algo(),
+                       PublicKeyAlgorithm::RSAEncryptSign);
+            Ok(Plaintext::Unpadded(pair.decrypt(ciphertext, None)?))
+        }
+    }
+
+    /// Connects a client to a server for `cert`.
+    fn connect(cert: &Cert) -> (BufReader<UnixStream>, UnixStream) {
+        let (client, server) = UnixStream::pair().unwrap();
+        let backend = InMemory::new(cert);
+        thread::spawn(move || {
+            let reader = BufReader::new(server.try_clone().unwrap());
+            Server::new(backend).handle(reader, server).unwrap();
+        });
+        let mut reader = BufReader::new(client.try_clone().unwrap());
+        assert!(read_line(&mut reader).unwrap().unwrap().starts_with(b"OK"));
+        (reader, client)
+    }
+
+    /// Sends `command`, and returns the data and the final line.
+    fn transact(reader: &mut BufReader<UnixStream>, w: &mut UnixStream,
+                command: &str)
+                -> (Vec<u8>, Vec<u8>) {
+        writeln!(w, "{}", command).unwrap();
+        let mut data = Vec::new();
+        loop {
+            let line = read_line(reader).unwrap().unwrap();
+            if let Some(chunk) = line.strip_prefix(b"D ") {
+                unescape(chunk, &mut data).ok().unwrap();
+            } else if line.starts_with(b"OK") || line.starts_with(b"ERR")
+                || line.starts_with(b"INQUIRE")
+            {
+                return (data, line);
+            }
+        }
+    }
+
+    #[test]
+    fn sign() {
+        let (cert, _) = CertBuilder::new()
+            .set_cipher_suite(CipherSuite::Cv25519)
+            .add_signing_subkey()
+            .generate().unwrap();
+        let key = cert.keys().subkeys().next().unwrap().key().clone();
+        let grip = Keygrip::of(key.mpis()).unwrap();
+        let (mut r, mut w) = connect(&cert);
+
+        // Hash algorithms not supported by all backends are rejected.
+        for hash in &["--hash=md5", "--hash=sha224", "1", "3"] {
+            let (_, line) = transact(
+                &mut r, &mut w,
+                &format!("SETHASH {} {}", hash, hex::encode(&[0; 16])));
+            assert!(line.starts_with(b"ERR"));
+        }
+        let (_, line) = transact(&mut r, &mut w,
+                                 &format!("SETHASH 8 {}", hex::encode(&[0; 20])));
+        assert!(line.starts_with(b"ERR"));
+
+        let (_, line) = transact(&mut r, &mut w, &format!("HAVEKEY {}", grip));
+        assert_eq!(line, b"OK");
+        let (_, line) = transact(&mut r, &mut w,
+                                 "HAVEKEY 0000000000000000000000000000000000000000");
+        assert!(line.starts_with(b"ERR"));
+
+        let (data, _) = transact(&mut r, &mut w, &format!("READKEY {}", grip));
+        let mut expected = Vec::new();
+        Sexp::try_from(key.mpis()).unwrap().serialize(&mut expected).unwrap();
+        assert_eq!(data, expected);
+
+        let digest = vec![0x25; 32];
+        transact(&mut r, &mut w, &format!("SIGKEY {}", grip));
+        transact(&mut r, &mut w,
+                 &format!("SETHASH 8 {}", hex::encode(&digest)));
+        let (data, line) = transact(&mut r, &mut w, "PKSIGN");
+        assert_eq!(line, b"OK");
+        let sig = Sexp::from_bytes(&data).unwrap().to_signature().unwrap();
+        key.verify(&sig, HashAlgorithm::SHA256, &digest).unwrap();
+    }
+
+    #[test]
+    fn decrypt() {
+        let (cert, _) = CertBuilder::new()
+            .set_cipher_suite(CipherSuite::RSA2k)
+            .add_transport_encryption_subkey()
+            .generate().unwrap();
+        let key = cert.keys().subkeys().next().unwrap().key().clone();
+        let grip = Keygrip::of(key.mpis()).unwrap();
+        let (mut r, mut w) = connect(&cert);
+
+        let sk = SessionKey::new(32);
+        let ciphertext = key.encrypt(&sk).unwrap();
+
+        transact(&mut r, &mut w, &format!("SETKEY {}", grip));
+        let (_, line) = transact(&mut r, &mut w, "PKDECRYPT");
+        assert_eq!(line, b"INQUIRE CIPHERTEXT");
+        let mut buf = Vec::new();
+        Sexp::try_from(&ciphertext).unwrap().serialize(&mut buf).unwrap();
+        let mut request = Vec::new();
+        send_data(&mut request, &buf).unwrap();
+        w.write_all(&request).unwrap();
+        let (data, line) = transact(&mut r, &mut w, "END");
+        assert_eq!(line, b"OK");
+
+        let decrypted = Sexp::from_bytes(&data).unwrap()
+            .finish_decryption(&key, &ciphertext, false).unwrap();
+        assert_eq!(decrypted, sk);
+    }
+}
@@ -42,6 +42,12 @@ impl fmt::Display for Keygrip {
     }
 }
 
+impl AsRef<[u8]> for Keygrip {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
 impl std::str::FromStr for Keygrip {
     type Err = anyhow::Error;
 
@@ -68,6 +68,8 @@ pub mod keybox;
 mod keygrip;
 pub use self::keygrip::Keygrip;
 pub mod sexp;
+#[cfg(unix)]
+pub mod ssh;
 mod core;
 pub use crate::core::{Config, Context, IPCPolicy};
 
@@ -208,6 +208,47 @@ impl Sexp {
         }
     }
 
+    /// Parses this s-expression to a ciphertext.
+    ///
+    /// Such an expression is sent to gpg-agent in response to the
+    /// `INQUIRE CIPHERTEXT` inquiry of the `PKDECRYPT` command.
+    pub fn to_ciphertext(&self) -> Result<mpi::Ciphertext> {
+        let not_a_ciphertext = || -> anyhow::Error {
+            Error::MalformedMPI(
+                format!("Not a ciphertext: {:?}", self)).into()
+        };
+
+        let enc = self.get(b"enc-val")?.ok_or_else(not_a_ciphertext)?;
+        // There may be a (flags ...) list before the parameters.
+        let (algo, param) = enc.iter().find_map(|p| {
+            p.list().and_then(|l| l.get(0)).and_then(Sexp::string)
+                .filter(|tag| tag.deref() != b"flags")
+                .map(|tag| (tag.to_vec(), p))
+        }).ok_or_else(not_a_ciphertext)?;
+        let value = |name: &[u8]| -> Result<String_> {
+            param.list().unwrap_or_default().iter().find_map(|p| {
+                p.get(name).ok().unwrap_or_default()
+                    .and_then(|l| l.get(0).and_then(Sexp::string).cloned())
+            }).ok_or_else(not_a_ciphertext)
+        };
+
+        match &algo[..] {
+            b"rsa" => Ok(mpi::Ciphertext::RSA {
+                c: mpi::MPI::new(&value(b"a")?),
+            }),
+            b"elg" => Ok(mpi::Ciphertext::ElGamal {
+                e: mpi::MPI::new(&value(b"a")?),
+                c: mpi::MPI::new(&value(b"b")?),
+            }),
+            b"ecdh" => Ok(mpi::Ciphertext::ECDH {
+                e: mpi::MPI::new(&value(b"e")?),
+                key: value(b"s")?.to_vec().into_boxed_slice(),
+            }),
+            _ => Err(Error::MalformedMPI(
+                format!("Unknown ciphertext sexp: {:?}", self)).into()),
+        }
+    }
+
     /// Casts this to a string.
     pub fn string(&self) -> Option<&String_> {
         match self {
@@ -313,6 +354,129 @@ impl TryFrom<&mpi::Ciphertext> for Sexp {
     }
 }
 
+impl TryFrom<&mpi::Signature> for Sexp {
+    type Error = anyhow::Error;
+
+    /// Constructs an S-Expression representing `signature`.
+    ///
+    /// The resulting expression is suitable as response to
+    /// gpg-agent's `PKSIGN` command.
+    fn try_from(signature: &mpi::Signature) -> Result<Self> {
+        use openpgp::crypto::mpi::Signature::*;
+        fn param(name: &str, mpi: &mpi::MPI) -> Sexp {
+            Sexp::List(vec![
+                Sexp::String(name.into()),
+                Sexp::String(mpi.value().into())])
+        }
+        let (algo, params) = match signature {
+            RSA { ref s } => ("rsa", vec![param("s", s)]),
+            DSA { ref r, ref s } => ("dsa", vec![param("r", r), param("s", s)]),
+            ECDSA { ref r, ref s } =>
+                ("ecdsa", vec![param("r", r), param("s", s)]),
+            EdDSA { ref r, ref s } =>
+                ("eddsa", vec![param("r", r), param("s", s)]),
+            // crypto::mpi::Signature is non_exhaustive, match on &_ to handle
+            // future additions.
+            &Unknown { .. } | &_ =>
+                return Err(Error::InvalidArgument(
+                    format!("Don't know how to convert {:?}", signature))
+                    .into()),
+        };
+
+        let mut algo = vec![Sexp::String(algo.into())];
+        algo.extend(params);
+        Ok(Sexp::List(vec![
+            Sexp::String("sig-val".into()),
+            Sexp::List(algo)]))
+    }
+}
+
+impl TryFrom<&mpi::PublicKey> for Sexp {
+    type Error = anyhow::Error;
+
+    /// Constructs an S-Expression representing `key`.
+    ///
+    /// The resulting expression is suitable as response to
+    /// gpg-agent's `READKEY` command.
+    fn try_from(key: &mpi::PublicKey) -> Result<Self> {
+        use openpgp::crypto::mpi::PublicKey::*;
+        use openpgp::types::Curve;
+
+        // libgcrypt's MPIs are signed, prefix a zero if need be.
+        fn param(name: &str, mpi: &mpi::MPI) -> Sexp {
+            let v = mpi.value();
+            let v = if v.get(0).map(|b| b & 0x80 != 0).unwrap_or(false) {
+                let mut p = vec![0];
+                p.extend_from_slice(v);
+                p
+            } else {
+                v.to_vec()
+            };
+            Sexp::List(vec![
+                Sexp::String(name.into()),
+                Sexp::String(v[..].into())])
+        }
+        fn ecc(curve: &Curve, flags: Option<&str>, q: &mpi::MPI)
+               -> Result<Vec<Sexp>> {
+            let name = match curve {
+                Curve::NistP256 => "NIST P-256",
+                Curve::NistP384 => "NIST P-384",
+                Curve::NistP521 => "NIST P-521",
+                Curve::BrainpoolP256 => "brainpoolP256r1",
+                Curve::BrainpoolP512 => "brainpoolP512r1",
+                Curve::Ed25519 => "Ed25519",
+                Curve::Cv25519 => "Curve25519",
+                _ => return Err(Error::InvalidArgument(
+                    format!("Don't know how to convert curve {}", curve))
+                                .into()),
+            };
+            let mut params = vec![
+                Sexp::String("ecc".into()),
+                Sexp::List(vec![
+                    Sexp::String("curve".into()),
+                    Sexp::String(name.into())]),
+            ];
+            if let Some(flags) = flags {
+                params.push(Sexp::List(vec![
+                    Sexp::String("flags".into()),
+                    Sexp::String(flags.into())]));
+            }
+            params.push(Sexp::List(vec![
+                Sexp::String("q".into()),
+                Sexp::String(q.value().into())]));
+            Ok(params)
+        }
+
+        let params = match key {
+            RSA { ref e, ref n } =>
+                vec![Sexp::String("rsa".into()), param("n", n), param("e", e)],
+            DSA { ref p, ref q, ref g, ref y } =>
+                vec![Sexp::String("dsa".into()),
+                     param("p", p), param("q", q), param("g", g),
+                     param("y", y)],
+            ElGamal { ref p, ref g, ref y } =>
+                vec![Sexp::String("elg".into()),
+                     param("p", p), param("g", g), param("y", y)],
+            EdDSA { ref curve, ref q } => ecc(curve, Some("eddsa"), q)?,
+            ECDSA { ref curve, ref q } => ecc(curve, None, q)?,
+            ECDH { ref curve, ref q, .. } => ecc(
+                curve,
+                if *curve == Curve::Cv25519 { Some("djb-tweak") } else { None },
+                q)?,
+            // crypto::mpi::PublicKey is non_exhaustive, match on &_ to handle
+            // future additions.
+            &Unknown { .. } | &_ =>
+                return Err(Error::InvalidArgument(
+                    format!("Don't know how to convert {:?}", key))
+                    .into()),
+        };
+
+        Ok(Sexp::List(vec![
+            Sexp::String("public-key".into()),
+            Sexp::List(params)]))
+    }
+}
+
 #[cfg(test)]
 impl Arbitrary for Sexp {
     fn arbitrary(g: &mut Gen) -> Self {
@@ -470,4 +634,35 @@ mod tests {
                 RSA { .. }
         ));
     }
+
+    #[test]
+    fn signature_roundtrip() {
+        for f in &["sexp/dsa-signature.sexp", "sexp/ecdsa-signature.sexp",
+                   "sexp/eddsa-signature.sexp", "sexp/rsa-signature.sexp"] {
+            let sig = Sexp::from_bytes(crate::tests::file(f)).unwrap()
+                .to_signature().unwrap();
+            let sexp = Sexp::try_from(&sig).unwrap();
+            assert_eq!(sexp.to_signature().unwrap(), sig);
+        }
+    }
+
+    #[test]
+    fn ciphertext_roundtrip() {
+        use openpgp::crypto::mpi::{Ciphertext, MPI};
+        let ciphertexts = vec![
+            Ciphertext::RSA { c: MPI::new(&[1, 2, 3]) },
+            Ciphertext::ElGamal {
+                e: MPI::new(&[1, 2, 3]),
+                c: MPI::new(&[4, 5, 6]),
+            },
+            Ciphertext::ECDH {
+                e: MPI::new(&[0x40, 1, 2, 3]),
+                key: vec![4, 5, 6].into_boxed_slice(),
+            },
+        ];
+        for ciphertext in ciphertexts {
+            let sexp = Sexp::try_from(&ciphertext).unwrap();
+            assert_eq!(sexp.to_ciphertext().unwrap(), ciphertext);
+        }
+    }
 }
@@ -0,0 +1,534 @@
+//! An ssh-agent compatible server.
+//!
+//! This module implements the server side of the subset of the SSH
+//! agent protocol that clients use to authenticate:
+//! `SSH_AGENTC_REQUEST_IDENTITIES` and `SSH_AGENTC_SIGN_REQUEST`.
+//! Requests to add, remove, or lock keys are refused.  The keys
+//! themselves are provided by a [`Backend`], which may for example
+//! keep them in an HSM.
+//!
+//! OpenSSH clients use the keys by pointing `SSH_AUTH_SOCK` at the
+//! server's socket.  [`authorized_keys_line`] formats the
+//! corresponding entries for `~/.ssh/authorized_keys`.
+
+use std::io::{Read, Write};
+use std::os::unix::net::UnixListener;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use sequoia_openpgp as openpgp;
+use openpgp::crypto::mpi;
+use openpgp::packet::{Key, key};
+use openpgp::types::{Curve, HashAlgorithm};
+use openpgp::Fingerprint;
+
+use crate::Result;
+
+/// Maximum size of a request.
+///
+/// Requests we serve are small, this merely protects against
+/// allocating arbitrary amounts of memory.
+const MAX_MESSAGE_LENGTH: usize = 256 * 1024;
+
+// Message numbers, see draft-miller-ssh-agent.
+const SSH_AGENT_FAILURE: u8 = 5;
+const SSH_AGENTC_REQUEST_IDENTITIES: u8 = 11;
+const SSH_AGENT_IDENTITIES_ANSWER: u8 = 12;
+const SSH_AGENTC_SIGN_REQUEST: u8 = 13;
+const SSH_AGENT_SIGN_RESPONSE: u8 = 14;
+
+// Signature flags for RSA keys.
+const SSH_AGENT_RSA_SHA2_256: u32 = 2;
+const SSH_AGENT_RSA_SHA2_512: u32 = 4;
+
+/// Provides the keys served by a [`Server`].
+///
+/// Keys are identified by their OpenPGP [`Fingerprint`].
+pub trait Backend: Send {
+    /// Returns all keys, together with a comment shown to the user.
+    fn identities(&self)
+                  -> Vec<(Key<key::PublicParts, key::UnspecifiedRole>, String)>;
+
+    /// Signs `digest` using the key with the given fingerprint.
+    ///
+    /// For EdDSA keys, `digest` is the message itself, and `hash_algo`
+    /// is SHA512.
+    fn sign(&mut self, fingerprint: &Fingerprint, hash_algo: HashAlgorithm,
+            digest: &[u8])
+            -> Result<mpi::Signature>;
+}
+
+/// An ssh-agent compatible server.
+///
+/// Every connection is served by its own thread.  Operations on the
+/// backend are serialized.
+pub struct Server<B: Backend> {
+    backend: Arc<Mutex<B>>,
+}
+
+impl<B: Backend + 'static> Server<B> {
+    /// Returns a server for the keys provided by `backend`.
+    pub fn new(backend: B) -> Self {
+        Server {
+            backend: Arc::new(Mutex::new(backend)),
+        }
+    }
+
+    /// Accepts and serves connections on `listener`.
+    ///
+    /// This function only returns if accepting connections fails.
+    pub fn serve(&self, listener: UnixListener) -> Result<()> {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let backend = self.backend.clone();
+            thread::spawn(move || -> Result<()> {
+                handle(&*backend, stream.try_clone()?, stream)
+            });
+        }
+        Ok(())
+    }
+
+    /// Serves a single connection.
+    pub fn handle<R, W>(&self, reader: R, writer: W) -> Result<()>
+        where R: Read, W: Write
+    {
+        handle(&*self.backend, reader, writer)
+    }
+}
+
+fn handle<B, R, W>(backend: &Mutex<B>, mut reader: R, mut writer: W)
+                   -> Result<()>
+    where B: Backend, R: Read, W: Write
+{
+    while let Some(request) = read_message(&mut reader)? {
+        let response = match request.split_first() {
+            Some((&SSH_AGENTC_REQUEST_IDENTITIES, _)) =>
+                identities(backend),
+            Some((&SSH_AGENTC_SIGN_REQUEST, body)) =>
+                sign(backend, body),
+            _ => Err(unsupported("Unsupported request")),
+        };
+
+        let response = response.unwrap_or_else(|_| vec![SSH_AGENT_FAILURE]);
+        writer.write_all(&(response.len() as u32).to_be_bytes())?;
+        writer.write_all(&response)?;
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+fn identities<B: Backend>(backend: &Mutex<B>) -> Result<Vec<u8>> {
+    let keys = backend.lock().unwrap().identities();
+
+    // Skip keys that cannot be used with SSH.
+    let keys = keys.iter()
+        .filter_map(|(key, comment)| {
+            public_key_blob(key.mpis()).ok().map(|blob| (blob, comment))
+        })
+        .collect::<Vec<_>>();
+
+    let mut response = vec![SSH_AGENT_IDENTITIES_ANSWER];
+    response.extend_from_slice(&(keys.len() as u32).to_be_bytes());
+    for (blob, comment) in keys {
+        put_string(&mut response, &blob);
+        put_string(&mut response, comment.as_bytes());
+    }
+    Ok(response)
+}
+
+fn sign<B: Backend>(backend: &Mutex<B>, mut body: &[u8]) -> Result<Vec<u8>> {
+    let blob = get_string(&mut body)?;
+    let data = get_string(&mut body)?;
+    let flags = get_u32(&mut body)?;
+
+    let mut backend = backend.lock().unwrap();
+    let key = backend.identities().into_iter()
+        .map(|(key, _)| key)
+        .find(|key| public_key_blob(key.mpis()).ok().as_deref() == Some(blob))
+        .ok_or_else(|| unsupported("Unknown key"))?;
+
+    let mut signature = Vec::new();
+    match key.mpis() {
+        mpi::PublicKey::RSA { n, .. } => {
+            let (name, hash_algo) = if flags & SSH_AGENT_RSA_SHA2_512 != 0 {
+                ("rsa-sha2-512", HashAlgorithm::SHA512)
+            } else if flags & SSH_AGENT_RSA_SHA2_256 != 0 {
+                ("rsa-sha2-256", HashAlgorithm::SHA256)
+            } else {
+                ("ssh-rsa", HashAlgorithm::SHA1)
+            };
+            let digest = hash(hash_algo, data)?;
+            match backend.sign(&key.fingerprint(), hash_algo, &digest)? {
+                mpi::Signature::RSA { s } => {
+                    put_string(&mut signature, name.as_bytes());
+                    put_string(&mut signature,
+                               &s.value_padded(n.value().len())?);
+                },
+                _ => return Err(unsupported("Unexpected signature")),
+            }
+        },
+        mpi::PublicKey::EdDSA { curve: Curve::Ed25519, .. } => {
+            match backend.sign(&key.fingerprint(), HashAlgorithm::SHA512,
+                               data)? {
+                mpi::Signature::EdDSA { r, s } => {
+                    let mut rs = r.value_padded(32)?.into_owned();
+                    rs.extend_from_slice(&s.value_padded(32)?);
+                    put_string(&mut signature, b"ssh-ed25519");
+                    put_string(&mut signature, &rs);
+                },
+                _ => return Err(unsupported("Unexpected signature")),
+            }
+        },
+        mpi::PublicKey::ECDSA { curve, .. } => {
+            let (name, hash_algo) = ecdsa_curve(curve)?;
+            let digest = hash(hash_algo, data)?;
+            match backend.sign(&key.fingerprint(), hash_algo, &digest)? {
+                mpi::Signature::ECDSA { r, s } => {
+                    let mut rs = Vec::new();
+                    put_mpint(&mut rs, r.value());
+                    put_mpint(&mut rs, s.value());
+                    put_string(&mut signature,
+                               format!("ecdsa-sha2-{}", name).as_bytes());
+                    put_string(&mut signature, &rs);
+                },
+                _ => return Err(unsupported("Unexpected signature")),
+            }
+        },
+        _ => return Err(unsupported("Unsupported key type")),
+    }
+
+    let mut response = vec![SSH_AGENT_SIGN_RESPONSE];
+    put_string(&mut response, &signature);
+    Ok(response)
+}
+
+/// Returns the SSH wire encoding of `key`.
+///
+/// RSA, Ed25519, and ECDSA keys on the NIST curves are supported.
+pub fn public_key_blob(key: &mpi::PublicKey) -> Result<Vec<u8>> {
+    let mut blob = Vec::new();
+    match key {
+        mpi::PublicKey::RSA { e, n } => {
+            put_string(&mut blob, b"ssh-rsa");
+            put_mpint(&mut blob, e.value());
+            put_mpint(&mut blob, n.value());
+        },
+        mpi::PublicKey::EdDSA { curve: curve @ Curve::Ed25519, q } => {
+            let (x, _) = q.decode_point(curve)?;
+            put_string(&mut blob, b"ssh-ed25519");
+            put_string(&mut blob, x);
+        },
+        mpi::PublicKey::ECDSA { curve, q } => {
+            let (name, _) = ecdsa_curve(curve)?;
+            put_string(&mut blob, format!("ecdsa-sha2-{}", name).as_bytes());
+            put_string(&mut blob, name.as_bytes());
+            put_string(&mut blob, q.value());
+        },
+        _ => return Err(unsupported("Unsupported key type")),
+    }
+    Ok(blob)
+}
+
+/// Returns an `authorized_keys` line for `key`.
+pub fn authorized_keys_line(key: &mpi::PublicKey, comment: &str)
+                            -> Result<String> {
+    let blob = public_key_blob(key)?;
+    let algo = get_string(&mut &blob[..])?;
+    let mut line = format!("{} {}",
+                           String::from_utf8_lossy(algo),
+                           base64::encode(&blob));
+    if ! comment.is_empty() {
+        line.push(' ');
+        line.push_str(comment);
+    }
+    Ok(line)
+}
+
+/// Returns the SSH name of `curve`, and the hash algorithm used with
+/// it.
+fn ecdsa_curve(curve: &Curve) -> Result<(&'static str, HashAlgorithm)> {
+    match curve {
+        Curve::NistP256 => Ok(("nistp256", HashAlgorithm::SHA256)),
+        Curve::NistP384 => Ok(("nistp384", HashAlgorithm::SHA384)),
+        Curve::NistP521 => Ok(("nistp521", HashAlgorithm::SHA512)),
+        _ => Err(unsupported("Unsupported curve")),
+    }
+}
+
+fn hash(algo: HashAlgorithm, data: &[u8]) -> Result<Vec<u8>> {
+    let mut ctx = algo.context()?;
+    ctx.update(data);
+    ctx.into_digest()
+}
+
+fn unsupported(message: &str) -> anyhow::Error {
+    openpgp::Error::InvalidArgument(message.into()).into()
+}
+
+/// Reads a length-prefixed message.
+///
+/// Returns `None` on EOF.
+fn read_message<R: Read>(reader: &mut R) -> Result<Option<Vec<u8>>> {
+    let mut len = [0; 4];
+    match reader.read_exact(&mut len) {
+        Ok(()) => (),
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof =>
+            return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_MESSAGE_LENGTH {
+        return Err(unsupported("Message too large"));
+    }
+
+    let mut message = vec![0; len];
+    reader.read_exact(&mut message)?;
+    Ok(Some(message))
+}
+
+fn get_u32(data: &mut &[u8]) -> Result<u32> {
+    if data.len() < 4 {
+        return Err(unsupported("Truncated message"));
+    }
+    let (value, rest) = data.split_at(4);
+    *data = rest;
+    Ok(u32::from_be_bytes([value[0], value[1], value[2], value[3]]))
+}
+
+fn get_string<'a>(data: &mut &'a [u8]) -> Result<&'a [u8]> {
+    let len = get_u32(data)? as usize;
+    if data.len() < len {
+        return Err(unsupported("Truncated message"));
+    }
+    let (value, rest) = data.split_at(len);
+    *data = rest;
+    Ok(value)
+}
+
+fn put_string(buf: &mut Vec<u8>, value: &[u8]) {
+    buf.extend_from_slice(&(value.len() as u32).to_be_bytes());
+    buf.extend_from_slice(value);
+}
+
+/// Appends `value`, an unsigned big-endian integer, as mpint.
+fn put_mpint(buf: &mut Vec<u8>, value: &[u8]) {
+    let start = value.iter().position(|&b| b != 0).unwrap_or(value.len());
+    let value = &value[start..];
+    if value.first().map(|&b| b & 0x80 != 0).unwrap_or(false) {
+        buf.extend_from_slice(&(value.len() as u32 + 1).to_be_bytes());
+        buf.push(0);
+        buf.extend_from_slice(value);
+    } else {
+        put_string(buf, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+    use std::os::unix::net::UnixStream;
+
+    use openpgp::cert::prelude::*;
+    use openpgp::crypto::{self, Signer};
+
+    /// Serves in-memory keys.
+    struct InMemory(HashMap<Fingerprint, crypto::KeyPair>);
+
+    impl InMemory {
+        fn new(cert: &Cert) -> Self {
+            InMemory(cert.keys().unencrypted_secret()
+                     .map(|ka| {
+                         let pair = ka.key().clone().into_keypair().unwrap();
+                         (ka.key().fingerprint(), pair)
+                     })
+                     .collect())
+        }
+    }
+
+    impl Backend for InMemory {
+        fn identities(&self)
+                      -> Vec<(Key<key::PublicParts, key::UnspecifiedRole>,
+                              String)>
+        {
+            self.0.iter()
+                .map(|(fp, p)| (p.public().clone(), fp.to_hex()))
+                .collect()
+        }
+
+        fn sign(&mut self, fingerprint: &Fingerprint,
+                hash_algo: HashAlgorithm, digest: &[u8])
+                -> Result<mpi::Signature> {
+            self.0.get_mut(fingerprint).unwrap().sign(hash_algo, digest)
+        }
+    }
+
+    /// Connects a client to a server for `cert`.
+    fn connect(cert: &Cert) -> UnixStream {
+        let (client, server) = UnixStream::pair().unwrap();
+        let backend = InMemory::new(cert);
+        thread::spawn(move || {
+            Server::new(backend)
+                .handle(server.try_clone().unwrap(), server).unwrap();
+        });
+        client
+    }
+
+    /// Sends `request`, and returns the response.
+    fn transact(client: &mut UnixStream, request: &[u8]) -> Vec<u8> {
+        client.write_all(&(request.len() as u32).to_be_bytes()).unwrap();
+        client.write_all(request).unwrap();
+        read_message(client).unwrap().unwrap()
+    }
+
+    /// Requests a signature over `data`, and returns the signature
+    /// blob.
+    fn sign_request(client: &mut UnixStream, blob: &[u8], data: &[u8],
+                    flags: u32)
+                    -> Vec<u8> {
+        let mut request = vec![SSH_AGENTC_SIGN_REQUEST];
+        put_string(&mut request, blob);
+        put_string(&mut request, data);
+        request.extend_from_slice(&flags.to_be_bytes());
+
+        let response = transact(client, &request);
+        assert_eq!(response[0], SSH_AGENT_SIGN_RESPONSE);
+        get_string(&mut &response[1..]).unwrap().to_vec()
+    }
+
+    #[test]
+    fn identities() {
+        let (cert, _) = CertBuilder::new()
+            .set_cipher_suite(CipherSuite::Cv25519)
+            .add_authentication_subkey()
+            .generate().unwrap();
+        let key = cert.keys().subkeys().next().unwrap().key().clone();
+        let mut client = connect(&cert);
+
+        let response = transact(&mut client, &[SSH_AGENTC_REQUEST_IDENTITIES]);
+        assert_eq!(response[0], SSH_AGENT_IDENTITIES_ANSWER);
+        let mut body = &response[1..];
+        // The primary key and the subkey.
+        assert_eq!(get_u32(&mut body).unwrap(), 2);
+        let blobs = (0..2)
+            .map(|_| {
+                let blob = get_string(&mut body).unwrap();
+                get_string(&mut body).unwrap();
+                blob
+            })
+            .collect::<Vec<_>>();
+        assert!(body.is_empty());
+        assert!(blobs.contains(&&public_key_blob(key.mpis()).unwrap()[..]));
+
+        // Unsupported requests fail.
+        let response = transact(&mut client, &[17]);
+        assert_eq!(response, vec![SSH_AGENT_FAILURE]);
+    }
+
+    #[test]
+    fn sign_ed25519() {
+        let (cert, _) = CertBuilder::new()
+            .set_cipher_suite(CipherSuite::Cv25519)
+            .add_authentication_subkey()
+            .generate().unwrap();
+        let key = cert.keys().subkeys().next().unwrap().key().clone();
+        let blob = public_key_blob(key.mpis()).unwrap();
+        let mut client = connect(&cert);
+
+        let data = b"session identifier";
+        let signature = sign_request(&mut client, &blob, data, 0);
+        let mut signature = &signature[..];
+        assert_eq!(get_string(&mut signature).unwrap(), b"ssh-ed25519");
+        let rs = get_string(&mut signature).unwrap();
+        assert_eq!(rs.len(), 64);
+
+        let sig = mpi::Signature::EdDSA {
+            r: rs[..32].to_vec().into(),
+            s: rs[32..].to_vec().into(),
+        };
+        key.verify(&sig, HashAlgorithm::SHA512, data).unwrap();
+    }
+
+    #[test]
+    fn sign_rsa() {
+        let (cert, _) = CertBuilder::new()
+            .set_cipher_suite(CipherSuite::RSA2k)
+            .add_authentication_subkey()
+            .generate().unwrap();
+        let key = cert.keys().subkeys().next().unwrap().key().clone();
+        let blob = public_key_blob(key.mpis()).unwrap();
+        let mut client = connect(&cert);
+
+        let data = b"session identifier";
+        let signature = sign_request(&mut client, &blob, data,
+                                     SSH_AGENT_RSA_SHA2_256);
+        let mut signature = &signature[..];
+        assert_eq!(get_string(&mut signature).unwrap(), b"rsa-sha2-256");
+        let s = get_string(&mut signature).unwrap();
+        assert_eq!(s.len(), 256);
+
+        let sig = mpi::Signature::RSA { s: s.to_vec().into() };
+        key.verify(&sig, HashAlgorithm::SHA256,
+                   &hash(HashAlgorithm::SHA256, data).unwrap()).unwrap();
+
+        // Unknown keys are refused.
+        let mut request = vec![SSH_AGENTC_SIGN_REQUEST];
+        put_string(&mut request, b"\0\0\0\x07ssh-rsa");
+        put_string(&mut request, data);
+        request.extend_from_slice(&0u32.to_be_bytes());
+        assert_eq!(transact(&mut client, &request), vec![SSH_AGENT_FAILURE]);
+    }
+
+    #[test]
+    fn sign_nistp256() {
+        let (cert, _) = CertBuilder::new()
+            .set_cipher_suite(CipherSuite::P256)
+            .add_authentication_subkey()
+            .generate().unwrap();
+        let key = cert.keys().subkeys().next().unwrap().key().clone();
+        let blob = public_key_blob(key.mpis()).unwrap();
+        let mut client = connect(&cert);
+
+        let data = b"session identifier";
+        let signature = sign_request(&mut client, &blob, data, 0);
+        let mut signature = &signature[..];
+        assert_eq!(get_string(&mut signature).unwrap(),
+                   b"ecdsa-sha2-nistp256");
+        let mut rs = get_string(&mut signature).unwrap();
+        let r = get_string(&mut rs).unwrap();
+        let s = get_string(&mut rs).unwrap();
+        assert!(rs.is_empty());
+        // Positive mpints have their high bit cleared.
+        assert!(r[0] & 0x80 == 0 && s[0] & 0x80 == 0);
+
+        let sig = mpi::Signature::ECDSA {
+            r: mpi::MPI::new(r),
+            s: mpi::MPI::new(s),
+        };
+        key.verify(&sig, HashAlgorithm::SHA256,
+                   &hash(HashAlgorithm::SHA256, data).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn authorized_keys() {
+        let (cert, _) = CertBuilder::new()
+            .set_cipher_suite(CipherSuite::Cv25519)
+            .generate().unwrap();
+        let line = authorized_keys_line(cert.primary_key().mpis(), "alice")
+            .unwrap();
+        assert!(line.starts_with("ssh-ed25519 AAAAC3NzaC1lZDI1NTE5"));
+        assert!(line.ends_with(" alice"));
+
+        let (cert, _) = CertBuilder::new()
+            .set_cipher_suite(CipherSuite::P256)
+            .generate().unwrap();
+        let line = authorized_keys_line(cert.primary_key().mpis(), "")
+            .unwrap();
+        // The uncompressed point follows the key type and curve name.
+        assert!(line.starts_with(
+            "ecdsa-sha2-nistp256 \
+             AAAAE2VjZHNhLXNoYTItbmlzdHAyNTYAAAAIbmlzdHAyNTYAAABBB"));
+        assert_eq!(line.split(' ').count(), 2);
+    }
+}
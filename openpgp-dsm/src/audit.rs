@@ -0,0 +1,389 @@
+//! Client-side audit log of DSM operations
+//!
+//! If the `SQ_DSM_AUDIT_LOG` environment variable is set, every
+//! cryptographic operation performed in DSM (sign, decrypt, encrypt,
+//! agree, export, import, update and delete) is recorded in the file it
+//! names, as one JSON object per line.
+//!
+//! Each record contains the SHA-256 hash of the previous line, so that
+//! modifications or deletions of records can be detected with
+//! [`verify_log`].  The first record refers to a hash of all zeros.
+
+use std::cell::RefCell;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Error, Result};
+use fs2::FileExt;
+use sdkms::api_model::SobjectDescriptor;
+use sequoia_openpgp::fmt::hex;
+use sequoia_openpgp::types::HashAlgorithm;
+use sequoia_openpgp::Fingerprint;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+const ENV_AUDIT_LOG: &str = "SQ_DSM_AUDIT_LOG";
+const GENESIS: &str =
+    "0000000000000000000000000000000000000000000000000000000000000000";
+
+thread_local! {
+    /// The fingerprint of the PGP key currently operated on, if known.
+    static FINGERPRINT: RefCell<Option<Fingerprint>> = RefCell::new(None);
+}
+
+/// Associates operations on this thread with a PGP key, until dropped.
+pub(crate) struct FingerprintGuard(Option<Fingerprint>);
+
+/// Records `fingerprint` in the audit records of this thread's
+/// operations, until the returned guard is dropped.
+pub(crate) fn with_fingerprint(fingerprint: Fingerprint) -> FingerprintGuard {
+    let previous = FINGERPRINT.with(|f| f.replace(Some(fingerprint)));
+    FingerprintGuard(previous)
+}
+
+impl Drop for FingerprintGuard {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        FINGERPRINT.with(|f| *f.borrow_mut() = previous);
+    }
+}
+
+/// A DSM operation.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Operation {
+    Sign,
+    Decrypt,
+    Encrypt,
+    Agree,
+    Export,
+    Import,
+    Update,
+    Delete,
+}
+
+/// The outcome of a DSM operation.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Success,
+    Failure,
+}
+
+/// An audit record, i.e., a line of the audit log.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Record {
+    /// Seconds since the Unix epoch.
+    pub timestamp:        u64,
+    pub operation:        Operation,
+    /// UUID of the Security Object, or its name if the UUID is unknown.
+    pub sobject:          Option<String>,
+    /// Fingerprint of the PGP key the Security Object belongs to.
+    pub fingerprint:      Option<String>,
+    /// SHA-256 of the operation's input, e.g., the digest to sign or the
+    /// ciphertext to decrypt.
+    pub input_sha256:     Option<String>,
+    pub approval_request: Option<String>,
+    pub outcome:          Outcome,
+    pub error:            Option<String>,
+    /// SHA-256 of the previous line of the log.
+    pub prev_sha256:      String,
+}
+
+/// Collects the details of an operation, and logs them once it is done.
+pub(crate) struct Pending {
+    operation:        Operation,
+    sobject:          Option<String>,
+    input_sha256:     Option<String>,
+    approval_request: Option<String>,
+}
+
+impl Pending {
+    pub(crate) fn new(operation: Operation) -> Self {
+        Pending {
+            operation,
+            sobject:          None,
+            input_sha256:     None,
+            approval_request: None,
+        }
+    }
+
+    /// Sets the Security Object operated on.
+    pub(crate) fn sobject(mut self, descriptor: Option<&SobjectDescriptor>)
+        -> Self {
+        self.sobject = descriptor.and_then(|d| match d {
+            SobjectDescriptor::Kid(kid) => Some(kid.to_string()),
+            SobjectDescriptor::Name(name) => Some(name.clone()),
+            _ => None,
+        });
+        self
+    }
+
+    /// Sets the Security Object operated on.
+    pub(crate) fn uuid(mut self, uuid: &Uuid) -> Self {
+        self.sobject = Some(uuid.to_string());
+        self
+    }
+
+    /// Sets the operation's input, which is hashed.
+    pub(crate) fn input(mut self, input: Option<&[u8]>) -> Self {
+        self.input_sha256 = input.map(sha256);
+        self
+    }
+
+    /// Sets the quorum approval request for this operation.
+    pub(crate) fn approval_request<D: std::fmt::Display>(&mut self, id: D) {
+        self.approval_request = Some(id.to_string());
+    }
+
+    /// Logs the operation, if logging is enabled.
+    ///
+    /// `kid` overrides the Security Object, if the operation revealed
+    /// its UUID.  The operation has already been performed, so failing
+    /// to log it is reported, but does not change its result.
+    pub(crate) fn finish<T>(self, result: &Result<T>, kid: Option<Uuid>) {
+        let path = match std::env::var_os(ENV_AUDIT_LOG) {
+            Some(path) => path,
+            None => return,
+        };
+
+        if let Err(e) = self.log(Path::new(&path), result, kid) {
+            eprintln!("Warning: could not write audit log {:?}: {:#}",
+                      path, e);
+        }
+    }
+
+    fn log<T>(self, path: &Path, result: &Result<T>, kid: Option<Uuid>)
+        -> Result<()> {
+
+        let record = Record {
+            timestamp:        SystemTime::now()
+                .duration_since(UNIX_EPOCH)?
+                .as_secs(),
+            operation:        self.operation,
+            sobject:          kid.map(|k| k.to_string()).or(self.sobject),
+            fingerprint:      FINGERPRINT.with(|f| {
+                f.borrow().as_ref().map(|fp| fp.to_hex())
+            }),
+            input_sha256:     self.input_sha256,
+            approval_request: self.approval_request,
+            outcome:          if result.is_ok() {
+                Outcome::Success
+            } else {
+                Outcome::Failure
+            },
+            error:            result.as_ref().err()
+                .map(|e| format!("{:#}", e)),
+            prev_sha256:      String::new(),
+        };
+
+        append(path, record)
+    }
+}
+
+/// Appends `record` to the log, chaining it to the last line.
+fn append(path: &Path, record: Record) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create(true)
+        .open(path)?;
+
+    // Hold the lock from reading the last line until the record is
+    // written, so that concurrent writers do not fork the chain.
+    file.lock_exclusive()?;
+    let result = append_locked(&mut file, record);
+    file.unlock()?;
+    result
+}
+
+fn append_locked(file: &mut File, mut record: Record) -> Result<()> {
+    record.prev_sha256 = match last_line(file)? {
+        Some(line) => sha256(&line),
+        None => GENESIS.to_string(),
+    };
+
+    let mut line = serde_json::to_vec(&record)?;
+    line.push(b'\n');
+    file.write_all(&line)?;
+    file.sync_data()?;
+    Ok(())
+}
+
+/// Returns the last line of the file, without the line terminator.
+fn last_line(file: &mut File) -> Result<Option<Vec<u8>>> {
+    let len = file.seek(SeekFrom::End(0))?;
+    let mut chunk = 4096;
+    loop {
+        let start = len.saturating_sub(chunk);
+        file.seek(SeekFrom::Start(start))?;
+        let mut buf = Vec::new();
+        (&mut *file).take(len - start).read_to_end(&mut buf)?;
+
+        // Strip the trailing newline, then look for the previous one.
+        if buf.last() == Some(&b'\n') {
+            buf.pop();
+        }
+        if buf.is_empty() && start == 0 {
+            return Ok(None);
+        }
+        match buf.iter().rposition(|&b| b == b'\n') {
+            Some(i) => return Ok(Some(buf[i + 1..].to_vec())),
+            None if start == 0 => return Ok(Some(buf)),
+            None => chunk *= 2,
+        }
+    }
+}
+
+pub(crate) fn sha256(data: &[u8]) -> String {
+    let mut ctx = HashAlgorithm::SHA256.context()
+        .expect("SHA256 is supported");
+    ctx.update(data);
+    hex::encode(ctx.into_digest().expect("SHA256 is supported"))
+}
+
+/// Checks the hash chain of the audit log at `path`.
+///
+/// Returns the number of records on success.
+pub fn verify_log<P: AsRef<Path>>(path: P) -> Result<usize> {
+    let file = File::open(path.as_ref())
+        .with_context(|| format!("could not open {:?}", path.as_ref()))?;
+
+    let mut prev = GENESIS.to_string();
+    let mut n = 0;
+    for line in BufReader::new(file).split(b'\n') {
+        let line = line?;
+        n += 1;
+        let record: Record = serde_json::from_slice(&line)
+            .with_context(|| format!("malformed record on line {}", n))?;
+        if record.prev_sha256 != prev {
+            return Err(Error::msg(format!(
+                "hash chain broken on line {}", n)));
+        }
+        prev = sha256(&line);
+    }
+
+    Ok(n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    fn record(operation: Operation) -> Record {
+        Record {
+            timestamp:        1650000000,
+            operation,
+            sobject:          Some("0b24a6e2-6d4b-4b9c-8f5c-0a0e7d1a7f3e".into()),
+            fingerprint:      None,
+            input_sha256:     Some(sha256(b"input")),
+            approval_request: None,
+            outcome:          Outcome::Success,
+            error:            None,
+            prev_sha256:      String::new(),
+        }
+    }
+
+    /// Writes a log with `n` records, and returns its path.
+    fn log(dir: &tempfile::TempDir, n: usize) -> std::path::PathBuf {
+        let path = dir.path().join("audit.log");
+        for _ in 0..n {
+            append(&path, record(Operation::Sign)).unwrap();
+        }
+        path
+    }
+
+    #[test]
+    fn last_line_of() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file");
+
+        let file = |content: &[u8]| {
+            fs::write(&path, content).unwrap();
+            last_line(&mut File::open(&path).unwrap()).unwrap()
+        };
+        assert_eq!(file(b""), None);
+        assert_eq!(file(b"\n"), None);
+        assert_eq!(file(b"one"), Some(b"one".to_vec()));
+        assert_eq!(file(b"one\n"), Some(b"one".to_vec()));
+        assert_eq!(file(b"one\ntwo\n"), Some(b"two".to_vec()));
+        assert_eq!(file(b"one\ntwo"), Some(b"two".to_vec()));
+
+        // Lines longer than the initial chunk.
+        let long = vec![b'x'; 10000];
+        let mut content = b"one\n".to_vec();
+        content.extend_from_slice(&long);
+        content.push(b'\n');
+        assert_eq!(file(&content), Some(long.clone()));
+        assert_eq!(file(&content[4..]), Some(long));
+    }
+
+    #[test]
+    fn append_chains() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = log(&dir, 3);
+
+        let content = fs::read(&path).unwrap();
+        let lines = content.split(|&b| b == b'\n')
+            .filter(|l| ! l.is_empty())
+            .collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+
+        let mut prev = GENESIS.to_string();
+        for line in lines {
+            let record: Record = serde_json::from_slice(line).unwrap();
+            assert_eq!(record.prev_sha256, prev);
+            assert_eq!(record.operation, Operation::Sign);
+            prev = sha256(line);
+        }
+    }
+
+    #[test]
+    fn verify_good_chain() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(verify_log(log(&dir, 3)).unwrap(), 3);
+
+        let empty = dir.path().join("empty.log");
+        fs::write(&empty, b"").unwrap();
+        assert_eq!(verify_log(&empty).unwrap(), 0);
+    }
+
+    #[test]
+    fn verify_tampered_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = log(&dir, 3);
+
+        let content = String::from_utf8(fs::read(&path).unwrap()).unwrap();
+        let mut lines = content.lines().map(String::from).collect::<Vec<_>>();
+        lines[1] = lines[1].replace("\"sign\"", "\"decrypt\"");
+        fs::write(&path, lines.join("\n") + "\n").unwrap();
+
+        let err = verify_log(&path).unwrap_err();
+        assert_eq!(err.to_string(), "hash chain broken on line 3");
+
+        // Removing a record breaks the chain, too.
+        lines.remove(1);
+        fs::write(&path, lines.join("\n") + "\n").unwrap();
+        let err = verify_log(&path).unwrap_err();
+        assert_eq!(err.to_string(), "hash chain broken on line 2");
+    }
+
+    #[test]
+    fn verify_truncated_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = log(&dir, 3);
+
+        let content = fs::read(&path).unwrap();
+        fs::write(&path, &content[..content.len() - 10]).unwrap();
+        let err = verify_log(&path).unwrap_err();
+        assert_eq!(err.to_string(), "malformed record on line 3");
+
+        // Appending to a truncated log does not repair it.
+        append(&path, record(Operation::Decrypt)).unwrap();
+        assert!(verify_log(&path).is_err());
+    }
+}
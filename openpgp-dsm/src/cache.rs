@@ -0,0 +1,577 @@
+//! Cache of the Security Objects of PGP keys
+//!
+//! Building a [DsmAgent] or extracting a certificate fetches the primary key
+//! and each of its subkeys from DSM.  With a cache, these Security Objects
+//! are kept, keyed by UUID and indexed by name, so that only signing and
+//! decryption reach DSM once the cache is warm.
+//!
+//! The cache is enabled by setting `SQ_DSM_CACHE` to
+//!
+//! - `memory`, kept by the [Credentials] and shared by their clones, or
+//! - `disk`, kept in `$XDG_CACHE_HOME/sq-dsm/sobjects.json` (or
+//!   `~/.cache/...`).
+//!
+//! Entries are kept apart per API endpoint and identity, i.e. the app, API
+//! key, bearer token, or user and account, since each of them may see
+//! different Security Objects under the same name.  Secrets are only
+//! included as their SHA-256 hash.  A user login whose account is selected
+//! interactively only uses the cache once it has logged in.
+//!
+//! Entries expire after `SQ_DSM_CACHE_TTL` seconds (300 by default), and are
+//! dropped whenever this crate updates or deletes the Security Object.
+//! Whenever DSM returns a Security Object, e.g. when listing keys or
+//! rebuilding the recipient index, its `updated_at` is compared with that
+//! of the cached copy, which is replaced if the Security Object has changed
+//! since it was cached.  Other changes made to a key outside of sq-dsm are
+//! seen once its entry expires.
+//!
+//!   [DsmAgent]: super::DsmAgent
+
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use fs2::FileExt;
+use log::info;
+use sdkms::api_model::{Sobject, SobjectDescriptor};
+use sdkms::SdkmsClient as DsmClient;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::audit::sha256;
+use super::{Auth, Credentials, Token};
+
+const ENV_CACHE:     &str = "SQ_DSM_CACHE";
+const ENV_CACHE_TTL: &str = "SQ_DSM_CACHE_TTL";
+const DEFAULT_TTL:   Duration = Duration::from_secs(300);
+
+/// Cache of Security Objects, shared by all its clones.
+#[derive(Clone)]
+pub struct SobjectCache {
+    ttl:     Duration,
+    /// Where to persist the entries, if on disk.
+    path:    Option<PathBuf>,
+    /// Cache key (see [`Credentials::cache_key`]) to cached entries.
+    entries: Arc<Mutex<Cache>>,
+}
+
+type Cache = HashMap<String, Entries>;
+
+#[derive(Clone, Default, Deserialize, Serialize)]
+struct Entries {
+    /// Security Object UUID to entry.
+    sobjects:     HashMap<String, Entry>,
+    /// Security Object name to UUID.
+    names:        HashMap<String, String>,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+struct Entry {
+    /// Seconds since the Unix epoch.
+    cached_at: u64,
+    sobject:   Sobject,
+}
+
+impl SobjectCache {
+    /// Returns an empty cache kept in memory.
+    pub fn memory(ttl: Duration) -> Self {
+        SobjectCache {
+            ttl,
+            path:    None,
+            entries: Arc::new(Mutex::new(Cache::new())),
+        }
+    }
+
+    /// Returns the cache kept on disk, loading its current entries.
+    pub fn disk(ttl: Duration) -> Result<Self> {
+        let path = cache_path().context("no cache directory")?;
+        let entries = load_cache_or_warn(&path);
+        Ok(SobjectCache {
+            ttl,
+            path:    Some(path),
+            entries: Arc::new(Mutex::new(entries)),
+        })
+    }
+
+    /// Returns the cache configured by `SQ_DSM_CACHE` and
+    /// `SQ_DSM_CACHE_TTL`, if any.
+    pub fn from_env() -> Result<Option<Self>> {
+        let ttl = match env::var(ENV_CACHE_TTL) {
+            Ok(secs) => Duration::from_secs(secs.parse().with_context(|| {
+                format!("{} must be a number of seconds", ENV_CACHE_TTL)
+            })?),
+            Err(_) => DEFAULT_TTL,
+        };
+
+        match env::var(ENV_CACHE).as_deref() {
+            Err(_) | Ok("") => Ok(None),
+            Ok("memory") => Ok(Some(Self::memory(ttl))),
+            Ok("disk") => Ok(Some(Self::disk(ttl)?)),
+            Ok(other) => Err(anyhow::anyhow!(
+                "{} must be \"memory\" or \"disk\", not {:?}", ENV_CACHE, other
+            )),
+        }
+    }
+
+    /// Drops all the entries for the given credentials.
+    pub fn clear(&self, credentials: &Credentials) {
+        let key = credentials.cache_key();
+        let mut cache = self.entries.lock().expect("poisoned");
+        if cache.remove(&key).is_some() {
+            self.store(&cache, &key);
+        }
+    }
+
+    fn get(&self, key: &str, descriptor: &SobjectDescriptor)
+           -> Option<Sobject> {
+        let mut cache = self.entries.lock().expect("poisoned");
+        let entries = cache.get_mut(key)?;
+
+        let kid = match descriptor {
+            SobjectDescriptor::Kid(kid) => kid.to_string(),
+            SobjectDescriptor::Name(name) => entries.names.get(name)?.clone(),
+            _ => return None,
+        };
+        let entry = entries.sobjects.get(&kid)?;
+        let age = now().saturating_sub(entry.cached_at);
+        if age < self.ttl.as_secs() {
+            return Some(entry.sobject.clone());
+        }
+
+        info!("expired cache entry {}", kid);
+        entries.remove(&kid);
+        self.store(&cache, key);
+        None
+    }
+
+    fn insert(&self, key: &str, sobject: &Sobject) {
+        let kid = match sobject.kid {
+            Some(kid) => kid.to_string(),
+            None => return,
+        };
+
+        let mut cache = self.entries.lock().expect("poisoned");
+        let entries = cache.entry(key.to_string()).or_default();
+
+        entries.insert(kid, sobject);
+        self.store(&cache, key);
+    }
+
+    /// Replaces the cached copies of the given Security Objects, as just
+    /// returned by DSM, that have been updated since they were cached.
+    fn observe(&self, key: &str, sobjects: &[Sobject]) {
+        let mut cache = self.entries.lock().expect("poisoned");
+        let entries = match cache.get_mut(key) {
+            Some(entries) => entries,
+            None => return,
+        };
+
+        let mut changed = false;
+        for sobject in sobjects {
+            let kid = match sobject.kid {
+                Some(kid) => kid.to_string(),
+                None => continue,
+            };
+            let stale = match entries.sobjects.get(&kid) {
+                Some(entry) =>
+                    updated_at(&entry.sobject) != updated_at(sobject),
+                None => false,
+            };
+            if stale {
+                info!("updated Security Object {}", kid);
+                entries.insert(kid, sobject);
+                changed = true;
+            }
+        }
+        if changed {
+            self.store(&cache, key);
+        }
+    }
+
+    fn invalidate(&self, key: &str, kid: &Uuid) {
+        let mut cache = self.entries.lock().expect("poisoned");
+        if let Some(entries) = cache.get_mut(key) {
+            if entries.remove(&kid.to_string()) {
+                self.store(&cache, key);
+            }
+        }
+    }
+
+    /// Writes the entries for the given key to disk, keeping those that
+    /// other processes stored for other keys.
+    fn store(&self, cache: &Cache, key: &str) {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return,
+        };
+
+        let res = (|| -> Result<()> {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+
+            // Writers take turns, so that none of them loses the entries
+            // of another.  Readers don't need the lock, because the file
+            // is replaced atomically.
+            let lock_path = path.with_extension("lock");
+            let lock = File::create(&lock_path)
+                .with_context(|| format!("could not create {:?}", lock_path))?;
+            lock.lock_exclusive()?;
+            let res = store_locked(path, cache, key);
+            lock.unlock()?;
+            res
+        })();
+        if let Err(e) = res {
+            eprintln!("Warning: could not write Security Object cache: {:#}",
+                      e);
+        }
+    }
+}
+
+/// Merges the entries for the given key into the cache on disk.
+///
+/// The caller must hold the lock.
+fn store_locked(path: &Path, cache: &Cache, key: &str)
+                -> Result<()> {
+    let mut on_disk = load_cache_or_warn(path);
+    match cache.get(key) {
+        Some(entries) => {
+            on_disk.insert(key.to_string(), entries.clone());
+        },
+        None => {
+            on_disk.remove(key);
+        },
+    }
+
+    let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+    let res = (|| -> Result<()> {
+        let file = File::create(&tmp)
+            .with_context(|| format!("could not create {:?}", tmp))?;
+        let mut sink = BufWriter::new(file);
+        serde_json::to_writer(&mut sink, &on_disk)?;
+        sink.flush()?;
+        sink.get_ref().sync_all()?;
+        fs::rename(&tmp, path)
+            .with_context(|| format!("could not replace {:?}", path))?;
+        Ok(())
+    })();
+    if res.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    res
+}
+
+impl Entries {
+    /// Caches the given Security Object.
+    fn insert(&mut self, kid: String, sobject: &Sobject) {
+        // A name now held by another Security Object retires the entry of
+        // the previous one.
+        let previous = sobject.name.as_ref()
+            .and_then(|name| self.names.get(name))
+            .filter(|previous| **previous != kid)
+            .cloned();
+        if let Some(previous) = previous {
+            self.remove(&previous);
+        }
+
+        if let Some(name) = &sobject.name {
+            self.names.insert(name.clone(), kid.clone());
+        }
+        self.sobjects.insert(kid, Entry {
+            cached_at: now(),
+            sobject:   sobject.clone(),
+        });
+    }
+
+    /// Removes the entry for the given UUID, returning whether there was
+    /// one.
+    fn remove(&mut self, kid: &str) -> bool {
+        self.names.retain(|_, v| v != kid);
+        self.sobjects.remove(kid).is_some()
+    }
+}
+
+/// Returns when the Security Object was last updated.
+///
+/// This is taken from the serialized form, so that a DSM that does not
+/// report it is handled gracefully: then, only the TTL applies.
+fn updated_at(sobject: &Sobject) -> Option<serde_json::Value> {
+    serde_json::to_value(sobject).ok()?
+        .get("updated_at")
+        .filter(|t| ! t.is_null())
+        .cloned()
+}
+
+/// Fetches Security Objects from the cache of the credentials, if any, and
+/// logs in to DSM only on a miss.
+pub(crate) struct SobjectLookup {
+    credentials: Credentials,
+    client:      Option<Arc<DsmClient>>,
+}
+
+impl SobjectLookup {
+    pub(crate) fn new(credentials: &Credentials) -> Self {
+        SobjectLookup { credentials: credentials.clone(), client: None }
+    }
+
+    pub(crate) fn get_sobject(&mut self, descriptor: &SobjectDescriptor)
+                              -> Result<Sobject> {
+        if let Some(cache) = &self.credentials.cache {
+            let key = self.credentials.cache_key();
+            if let Some(sobject) = cache.get(&key, descriptor) {
+                return Ok(sobject);
+            }
+        }
+
+        if self.client.is_none() {
+            self.client = Some(self.credentials.dsm_client()?);
+        }
+        let sobject = self.client.as_ref().expect("just set")
+            .get_sobject(None, descriptor)?;
+
+        if let Some(cache) = &self.credentials.cache {
+            cache.insert(&self.credentials.cache_key(), &sobject);
+        }
+        Ok(sobject)
+    }
+}
+
+impl Credentials {
+    /// Uses the given cache for the Security Objects of PGP keys, instead
+    /// of the one configured by `SQ_DSM_CACHE`.
+    pub fn with_cache(mut self, cache: Option<SobjectCache>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Returns the key of the cache entries for these credentials, made
+    /// of the API endpoint and the identity they authenticate as.
+    fn cache_key(&self) -> String {
+        let identity = match &self.auth {
+            Auth::ApiKey(api_key) =>
+                format!("api-key:{}", sha256(api_key.as_bytes())),
+            Auth::Cert(app_uuid, _) | Auth::Jwt(app_uuid, _) =>
+                format!("app:{}", app_uuid),
+            Auth::BearerToken(Token::Value(token)) =>
+                format!("token:{}", sha256(token.as_bytes())),
+            Auth::BearerToken(Token::File(path)) =>
+                format!("token-file:{}", path.display()),
+            Auth::User(login) => {
+                let account = login.account.lock().ok().and_then(|a| *a);
+                format!("user:{}:{}", login.username,
+                        account.map(|a| a.to_string()).unwrap_or_default())
+            },
+        };
+        format!("{} {}", self.api_endpoint, identity)
+    }
+
+    /// Drops the cached copy of the given Security Object, after it has
+    /// been updated or deleted.
+    pub(crate) fn invalidate(&self, kid: &Uuid) {
+        if let Some(cache) = &self.cache {
+            cache.invalidate(&self.cache_key(), kid);
+        }
+    }
+
+    /// Replaces the cached copies of the given Security Objects, as just
+    /// returned by DSM, if they have been updated since.
+    pub(crate) fn observe(&self, sobjects: &[Sobject]) {
+        if let Some(cache) = &self.cache {
+            cache.observe(&self.cache_key(), sobjects);
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Loads the cache at `path`, which is empty if there is no such file.
+fn load_cache(path: &Path) -> Result<Cache> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound =>
+            return Ok(Cache::new()),
+        Err(e) => return Err(e)
+            .with_context(|| format!("could not open {:?}", path)),
+    };
+    serde_json::from_reader(BufReader::new(file))
+        .with_context(|| format!("could not parse {:?}", path))
+}
+
+/// Loads the cache at `path`, discarding its content with a warning if it
+/// cannot be read.
+fn load_cache_or_warn(path: &Path) -> Cache {
+    load_cache(path).unwrap_or_else(|e| {
+        eprintln!("Warning: ignoring Security Object cache: {:#}", e);
+        Cache::new()
+    })
+}
+
+fn cache_path() -> Option<PathBuf> {
+    let dir = match env::var_os("XDG_CACHE_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".cache"),
+    };
+    Some(dir.join("sq-dsm").join("sobjects.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::UserLogin;
+
+    const KEY: &str = "https://dsm.example.org app:one";
+    const KID: &str = "0b24a6e2-6d4b-4b9c-8f5c-0a0e7d1a7f3e";
+
+    /// Returns a Security Object, as returned by DSM.
+    fn sobject(kid: &str, name: &str, updated_at: &str) -> Sobject {
+        serde_json::from_value(serde_json::json!({
+            "acct_id": "6d4f0d0c-2b6b-4b8e-9d41-3c1f3a2c8b7e",
+            "group_id": "e6f4b0f2-8c3a-4d4b-a0b4-5f3f6a1b9c2d",
+            "creator": { "app": "2b5d8f46-1a57-4b1f-9e5d-2b0c6fe2f6c4" },
+            "created_at": "20220301T120000Z",
+            "lastused_at": "19700101T000000Z",
+            "updated_at": updated_at,
+            "kid": kid,
+            "name": name,
+            "obj_type": "EC",
+            "elliptic_curve": "Ed25519",
+            "key_ops": ["SIGN", "VERIFY", "APPMANAGEABLE"],
+            "origin": "FortanixHSM",
+            "enabled": true,
+            "public_only": false,
+            "never_exportable": true,
+            "compliant_with_policies": true,
+            "state": "Active",
+        })).expect("valid Security Object")
+    }
+
+    fn cached(cache: &SobjectCache, key: &str, kid: &str) -> Option<Sobject> {
+        cache.get(key, &SobjectDescriptor::Kid(kid.parse().unwrap()))
+    }
+
+    #[test]
+    fn entries_rename() {
+        let other = "5e0f6f0a-4f5a-4d7e-9a4e-1c2b3d4e5f60";
+        let mut entries = Entries::default();
+        entries.insert(KID.into(), &sobject(KID, "alice", "20220301T120000Z"));
+        entries.insert(other.into(), &sobject(other, "alice",
+                                              "20220301T120000Z"));
+
+        // The name now belongs to the other Security Object.
+        assert_eq!(entries.names.get("alice").map(|k| &k[..]), Some(other));
+        assert!(! entries.sobjects.contains_key(KID));
+
+        assert!(entries.remove(other));
+        assert!(! entries.remove(other));
+        assert!(entries.names.is_empty());
+    }
+
+    #[test]
+    fn observe_replaces_updated() {
+        let cache = SobjectCache::memory(DEFAULT_TTL);
+        let old = sobject(KID, "alice", "20220301T120000Z");
+        cache.insert(KEY, &old);
+
+        // Unchanged Security Objects are kept.
+        cache.observe(KEY, &[old.clone()]);
+        assert_eq!(updated_at(&cached(&cache, KEY, KID).unwrap()),
+                   updated_at(&old));
+
+        // Updated ones are replaced.
+        let new = sobject(KID, "alice", "20220302T120000Z");
+        assert!(updated_at(&new).is_some());
+        assert_ne!(updated_at(&new), updated_at(&old));
+        cache.observe(KEY, &[new.clone()]);
+        assert_eq!(updated_at(&cached(&cache, KEY, KID).unwrap()),
+                   updated_at(&new));
+
+        // Security Objects that were not cached are not added.
+        let other = "5e0f6f0a-4f5a-4d7e-9a4e-1c2b3d4e5f60";
+        cache.observe(KEY, &[sobject(other, "bob", "20220302T120000Z")]);
+        assert!(cached(&cache, KEY, other).is_none());
+    }
+
+    #[test]
+    fn invalidate() {
+        let cache = SobjectCache::memory(DEFAULT_TTL);
+        cache.insert(KEY, &sobject(KID, "alice", "20220301T120000Z"));
+        assert!(cached(&cache, KEY, KID).is_some());
+        assert!(cache.get(KEY, &SobjectDescriptor::Name("alice".into()))
+                .is_some());
+
+        cache.invalidate(KEY, &KID.parse().unwrap());
+        assert!(cached(&cache, KEY, KID).is_none());
+        assert!(cache.get(KEY, &SobjectDescriptor::Name("alice".into()))
+                .is_none());
+    }
+
+    #[test]
+    fn expiration() {
+        let cache = SobjectCache::memory(Duration::from_secs(0));
+        cache.insert(KEY, &sobject(KID, "alice", "20220301T120000Z"));
+        assert!(cached(&cache, KEY, KID).is_none());
+    }
+
+    #[test]
+    fn keys_are_separate() {
+        let cache = SobjectCache::memory(DEFAULT_TTL);
+        cache.insert(KEY, &sobject(KID, "alice", "20220301T120000Z"));
+
+        let other = "https://dsm.example.org app:two";
+        assert!(cached(&cache, other, KID).is_none());
+        cache.invalidate(other, &KID.parse().unwrap());
+        assert!(cached(&cache, KEY, KID).is_some());
+    }
+
+    #[test]
+    fn cache_key() {
+        let credentials = |api_endpoint: &str, auth: Auth| Credentials {
+            api_endpoint: api_endpoint.into(),
+            auth,
+            cache:        None,
+            session:      Arc::new(Mutex::new(None)),
+        };
+        let app = "2b5d8f46-1a57-4b1f-9e5d-2b0c6fe2f6c4".parse().unwrap();
+        let user = |account: Option<&str>| Auth::User(UserLogin {
+            username: "alice@example.org".into(),
+            password: "secret".into(),
+            account:  Arc::new(Mutex::new(account.map(|a| a.parse().unwrap()))),
+        });
+
+        let keys = vec![
+            credentials("https://a.example.org",
+                        Auth::ApiKey("s3cr3t1".into())),
+            credentials("https://a.example.org",
+                        Auth::ApiKey("s3cr3t2".into())),
+            credentials("https://b.example.org",
+                        Auth::ApiKey("s3cr3t1".into())),
+            credentials("https://a.example.org",
+                        Auth::Jwt(app, Token::Value("jwt".into()))),
+            credentials("https://a.example.org",
+                        Auth::BearerToken(Token::Value("s3cr3t3".into()))),
+            credentials("https://a.example.org",
+                        user(Some("6d4f0d0c-2b6b-4b8e-9d41-3c1f3a2c8b7e"))),
+            credentials("https://a.example.org",
+                        user(Some("e6f4b0f2-8c3a-4d4b-a0b4-5f3f6a1b9c2d"))),
+        ].iter().map(|c| c.cache_key()).collect::<Vec<_>>();
+
+        for (i, a) in keys.iter().enumerate() {
+            for b in &keys[i + 1..] {
+                assert_ne!(a, b);
+            }
+            // Secrets are not disclosed.
+            assert!(! a.contains("s3cr3t"), "{}", a);
+        }
+    }
+}
@@ -0,0 +1,148 @@
+//! Index of the encryption subkeys accessible in DSM
+//!
+//! Maps the key IDs of PGP encryption subkeys to the UUIDs of the Security
+//! Objects holding them, so that the key to decrypt a message with can be
+//! found from the recipients of its PKESK packets.  The index is cached in
+//! `$XDG_CACHE_HOME/sq-dsm/recipients.json` (or `~/.cache/...`), one map
+//! per API endpoint, and rebuilt at most once per process, when none of
+//! the recipients are found.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, File};
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use log::{info, warn};
+use sdkms::api_model::ListSobjectsParams;
+use sequoia_openpgp::{Fingerprint, KeyID};
+use uuid::Uuid;
+
+use super::{Credentials, DsmAgent, KeyMetadata, DSM_LABEL_PGP};
+
+/// Finds DSM decryptors for PKESK recipients.
+pub struct RecipientIndex {
+    credentials: Credentials,
+    /// Key ID (hex) to Security Object UUID.
+    keys:        HashMap<String, String>,
+    /// Whether `keys` was built in this process.
+    fresh:       bool,
+}
+
+impl RecipientIndex {
+    /// Opens the index for the given credentials, using the cached map if
+    /// there is one.
+    pub fn new(credentials: Credentials) -> Self {
+        let keys = load_cache()
+            .and_then(|mut cache| cache.remove(&credentials.api_endpoint))
+            .unwrap_or_default();
+        RecipientIndex { credentials, keys, fresh: false }
+    }
+
+    /// Returns the credentials used to access DSM.
+    pub fn credentials(&self) -> &Credentials {
+        &self.credentials
+    }
+
+    /// Returns a decryptor for the given recipient, if the index maps
+    /// its key ID to an accessible encryption subkey.
+    ///
+    /// This only consults the index, which may be stale.  See
+    /// [`RecipientIndex::refresh`].
+    pub fn decryptor(&self, recipient: &KeyID) -> Option<DsmAgent> {
+        if recipient.is_wildcard() {
+            return None;
+        }
+
+        let kid = self.keys.get(&recipient.to_hex())?;
+        let kid = Uuid::parse_str(kid).ok()?;
+        match DsmAgent::new_decryptor_from_uuid(self.credentials.clone(), &kid) {
+            Ok(agent) => Some(agent),
+            Err(e) => {
+                info!("stale index entry {} for {}: {}", kid, recipient, e);
+                None
+            }
+        }
+    }
+
+    /// Rebuilds the index from the Security Objects accessible in DSM,
+    /// unless it was already rebuilt by this process.
+    ///
+    /// Returns whether the index was rebuilt.
+    pub fn refresh(&mut self) -> Result<bool> {
+        if self.fresh {
+            return Ok(false);
+        }
+        // Don't retry if listing the Security Objects fails.
+        self.fresh = true;
+
+        info!("dsm refresh recipient index");
+        let dsm_client = self.credentials.dsm_client()?;
+
+        let mut keys = HashMap::new();
+        for group in dsm_client.list_groups()? {
+            let params = ListSobjectsParams {
+                group_id: Some(group.group_id),
+                ..Default::default()
+            };
+
+            let sobjects = dsm_client.list_sobjects(Some(&params))?;
+            self.credentials.observe(&sobjects);
+            for sobject in sobjects {
+                match &sobject.custom_metadata {
+                    Some(md) if md.contains_key(DSM_LABEL_PGP) => (),
+                    _ => continue,
+                }
+                let (md, kid) = match (KeyMetadata::from_sobject(&sobject),
+                                       sobject.kid) {
+                    (Ok(md), Some(kid)) => (md, kid),
+                    _ => continue,
+                };
+                match md.key_flags {
+                    Some(kf) if kf.for_storage_encryption()
+                        || kf.for_transport_encryption() => (),
+                    _ => continue,
+                }
+                if let Ok(fp) = md.fingerprint.parse::<Fingerprint>() {
+                    keys.insert(KeyID::from(fp).to_hex(), kid.to_string());
+                }
+            }
+        }
+
+        self.keys = keys;
+
+        if let Err(e) = self.store_cache() {
+            warn!("could not write recipient index cache: {:#}", e);
+        }
+        Ok(true)
+    }
+
+    fn store_cache(&self) -> Result<()> {
+        let path = cache_path().context("no cache directory")?;
+        let mut cache = load_cache().unwrap_or_default();
+        cache.insert(self.credentials.api_endpoint.clone(), self.keys.clone());
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = File::create(&path)
+            .with_context(|| format!("could not create {:?}", path))?;
+        serde_json::to_writer(file, &cache)?;
+        Ok(())
+    }
+}
+
+type Cache = HashMap<String, HashMap<String, String>>;
+
+fn load_cache() -> Option<Cache> {
+    let file = File::open(cache_path()?).ok()?;
+    serde_json::from_reader(file).ok()
+}
+
+fn cache_path() -> Option<PathBuf> {
+    let dir = match env::var_os("XDG_CACHE_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".cache"),
+    };
+    Some(dir.join("sq-dsm").join("recipients.json"))
+}
@@ -9,7 +9,19 @@
 //! - `FORTANIX_API_ENDPOINT`
 //! - `FORTANIX_API_KEY`
 //! - `FORTANIX_PKCS12_ID`, absolute path for a PKCS12 file (.pfx or .p12)
-//! - `FORTANIX_APP_UUID`, required for certificate-based authentication
+//! - `FORTANIX_APP_UUID`, required for certificate-based and JWT
+//!   authentication
+//! - `FORTANIX_BEARER_TOKEN`, or `FORTANIX_BEARER_TOKEN_FILE`, a pre-issued
+//!   access token
+//! - `FORTANIX_JWT`, or `FORTANIX_JWT_FILE`, a JWT exchanged for a session
+//!   of the app
+//! - `FORTANIX_USERNAME`, `FORTANIX_PASSWORD` (prompted for if unset) and
+//!   `FORTANIX_ACCOUNT_ID` (selected interactively if unset and the user
+//!   has several accounts), for user login
+//!
+//! If several methods are configured, the first one in this list is used:
+//! API key, client certificate, bearer token, JWT, user login.  Command
+//! line options take precedence over the corresponding variables.
 //!
 //! # Proxy configuration
 //!
@@ -17,6 +29,17 @@
 //! follows the `http_proxy` / `no_proxy` environment variables convention,
 //! i.e., if `http_proxy` is set and the DSM API endpoint is not in
 //! `no_proxy`, then a connection through said proxy is established.
+//!
+//! # Audit log
+//!
+//! If `SQ_DSM_AUDIT_LOG` is set, every operation performed in DSM is
+//! recorded in the file it names.  See the [audit] module.
+//!
+//! # Cache
+//!
+//! If `SQ_DSM_CACHE` is set to `memory` or `disk`, the Security Objects of
+//! PGP keys are cached for `SQ_DSM_CACHE_TTL` seconds, so that loading a key
+//! or its certificate does not reach DSM.  See [SobjectCache].
 
 use core::fmt::Display;
 use std::borrow::Cow;
@@ -24,10 +47,11 @@ use std::collections::HashMap;
 use std::convert::{TryFrom, TryInto};
 use std::env;
 use std::fs::File;
-use std::io::Read;
+use std::io::{BufRead, BufReader, Read};
 use std::net::IpAddr;
+use std::path::PathBuf;
 use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use anyhow::{Context, Error, Result};
@@ -38,18 +62,20 @@ use hyper_native_tls::native_tls::{Identity, TlsConnector};
 use hyper_native_tls::NativeTlsClient;
 use ipnetwork::IpNetwork;
 use log::{info, warn};
-use sdkms::api_model::Algorithm::Rsa;
+use sdkms::api_model::Algorithm::{Aes, Rsa};
 use sdkms::api_model::{
-    AgreeKeyMechanism, AgreeKeyRequest, ApprovalStatus, DecryptRequest,
-    DecryptResponse, DigestAlgorithm, EllipticCurve as ApiCurve, KeyLinks,
+    AgreeKeyMechanism, AgreeKeyRequest, ApprovalStatus, CipherMode, CryptMode,
+    DecryptRequest, DecryptResponse, DigestAlgorithm, EncryptRequest,
+    EncryptResponse, EllipticCurve as ApiCurve, KeyLinks,
     KeyOperations, ObjectType, RsaEncryptionPaddingPolicy, RsaEncryptionPolicy,
     RsaOptions, RsaSignaturePaddingPolicy, RsaSignaturePolicy, SignRequest,
-    SignResponse, Sobject, SobjectDescriptor, SobjectRequest, Time as SdkmsTime,
-    ListSobjectsParams
+    SelectAccountRequest, SignResponse, Sobject, SobjectDescriptor,
+    SobjectRequest, Time as SdkmsTime, ListSobjectsParams
 };
 use sdkms::operations::Operation;
 use sdkms::{Error as DsmError, PendingApproval, SdkmsClient as DsmClient};
 use semver::{Version, VersionReq};
+use sequoia_openpgp::cert::amalgamation::ValidateAmalgamation;
 use sequoia_openpgp::cert::{ValidCert, Preferences};
 use sequoia_openpgp::crypto::mem::Protected;
 use sequoia_openpgp::crypto::mpi::{
@@ -63,6 +89,7 @@ use sequoia_openpgp::packet::key::{
 };
 use sequoia_openpgp::packet::prelude::SecretKeyMaterial;
 use sequoia_openpgp::packet::signature::SignatureBuilder;
+use sequoia_openpgp::packet::pkesk::PKESK3;
 use sequoia_openpgp::packet::{Key, UserID};
 use sequoia_openpgp::policy::StandardPolicy;
 use sequoia_openpgp::serialize::SerializeInto;
@@ -70,22 +97,33 @@ use sequoia_openpgp::types::{
     Curve as SequoiaCurve, Features, HashAlgorithm, KeyFlags,
     PublicKeyAlgorithm, SignatureType, SymmetricAlgorithm, Timestamp,
 };
-use sequoia_openpgp::{Cert, Packet};
+use sequoia_openpgp::{Cert, Fingerprint, KeyID, Packet};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+pub mod audit;
+mod cache;
 mod der;
+mod index;
+
+pub use cache::SobjectCache;
+pub use index::RecipientIndex;
+
+use cache::SobjectLookup;
 
 /// DsmAgent implements [Signer] and [Decryptor] with secrets stored inside
 /// Fortanix DSM.
 ///
 ///   [Decryptor]: ../../crypto/trait.Decryptor.html
 ///   [Signer]: ../../crypto/trait.Signer.html
+#[derive(Clone)]
 pub struct DsmAgent {
     credentials: Credentials,
     descriptor:  SobjectDescriptor,
     public:      Key<PublicParts, UnspecifiedRole>,
     role:        Role,
+    /// DSM session shared by all clones, see [DsmAgent::with_session].
+    session:     Option<Arc<DsmClient>>,
 }
 
 /// The version of this crate.
@@ -94,11 +132,21 @@ const DSM_LABEL_PGP:      &str = "sq_dsm";
 const ENV_API_KEY:        &str = "FORTANIX_API_KEY";
 const ENV_API_ENDPOINT:   &str = "FORTANIX_API_ENDPOINT";
 const ENV_APP_UUID:       &str = "FORTANIX_APP_UUID";
+const ENV_BEARER_TOKEN:   &str = "FORTANIX_BEARER_TOKEN";
+const ENV_BEARER_TOKEN_FILE: &str = "FORTANIX_BEARER_TOKEN_FILE";
+const ENV_JWT:            &str = "FORTANIX_JWT";
+const ENV_JWT_FILE:       &str = "FORTANIX_JWT_FILE";
+const ENV_USERNAME:       &str = "FORTANIX_USERNAME";
+const ENV_PASSWORD:       &str = "FORTANIX_PASSWORD";
+const ENV_ACCOUNT_ID:     &str = "FORTANIX_ACCOUNT_ID";
 const ENV_HTTP_PROXY:     &str = "http_proxy";
 const ENV_NO_PROXY:       &str = "no_proxy";
 const ENV_P12:            &str = "FORTANIX_PKCS12_ID";
 const ENV_P12_PASS:       &str = "FORTANIX_PKCS12_PASSPHRASE";
 const MIN_DSM_VERSION:    &str = "4.2.0";
+/// How long an authenticated DSM client is reused.  Tokens read from files
+/// are read again on the next login, so that they can be rotated.
+const SESSION_LIFETIME:   Duration = Duration::from_secs(5 * 60);
 // As seen on sdkms-client-rust/blob/master/examples/approval_request.rs
 const OP_APPROVAL_MSG:    &str = "This operation requires approval";
 
@@ -107,6 +155,127 @@ pub enum Auth {
     ApiKey(String),
     // App UUID and PKCS12 identity
     Cert(Uuid, Identity),
+    // Pre-issued access token, sent as is
+    BearerToken(Token),
+    // App UUID and JWT, exchanged for a session
+    Jwt(Uuid, Token),
+    // Username and password, and the account to select
+    User(UserLogin),
+}
+
+/// A token given directly, or read from a file on every login.
+///
+/// Reading from a file allows short-lived tokens to be rotated while
+/// they are in use.
+#[derive(Clone)]
+pub enum Token {
+    Value(String),
+    File(PathBuf),
+}
+
+impl Token {
+    /// Returns the token given in the environment variable `var`, or in
+    /// the file named by `var_file`.
+    fn from_env(var: &str, var_file: &str) -> Option<Self> {
+        match (env::var(var).ok(), env::var_os(var_file)) {
+            (Some(token), None) => Some(Token::Value(token)),
+            (None, Some(file)) => Some(Token::File(file.into())),
+            (Some(token), Some(_)) => {
+                warn!("{} and {} both set; ignoring {}", var, var_file, var_file);
+                Some(Token::Value(token))
+            },
+            (None, None) => None,
+        }
+    }
+
+    fn read(&self) -> Result<String> {
+        match self {
+            Token::Value(token) => Ok(token.clone()),
+            Token::File(path) => {
+                let token = std::fs::read_to_string(path)
+                    .context(format!("reading token from {:?}", path))?;
+                Ok(token.trim().to_string())
+            }
+        }
+    }
+}
+
+/// Credentials of a DSM user.
+///
+/// Users may belong to several accounts.  If no account is given, it is
+/// selected on the first login: automatically if there is only one, or
+/// interactively.  Clones share the selection.
+#[derive(Clone)]
+pub struct UserLogin {
+    username: String,
+    password: String,
+    account:  Arc<Mutex<Option<Uuid>>>,
+}
+
+impl UserLogin {
+    fn authenticate(&self, cli: DsmClient) -> Result<DsmClient> {
+        let cli = cli.authenticate_user(&self.username, &self.password)
+            .context(format!("could not log in as {}", self.username))?;
+
+        let mut account = self.account.lock().unwrap();
+        let acct_id = match *account {
+            Some(acct_id) => acct_id,
+            None => {
+                let acct_id = choose_account(&cli)?;
+                *account = Some(acct_id);
+                acct_id
+            }
+        };
+        cli.select_account(&SelectAccountRequest { acct_id })
+            .context(format!("could not select account {}", acct_id))?;
+
+        Ok(cli)
+    }
+}
+
+/// Selects the account to use, asking the user if there are several.
+fn choose_account(cli: &DsmClient) -> Result<Uuid> {
+    let accounts = cli.list_accounts(None)?;
+    match accounts.len() {
+        0 => return Err(Error::msg("user belongs to no account")),
+        1 => return Ok(accounts[0].acct_id),
+        _ => (),
+    }
+
+    // Standard input may carry the data to operate on, use the terminal.
+    let tty = File::open("/dev/tty").with_context(|| format!(
+        "several accounts found, set {} to select one", ENV_ACCOUNT_ID))?;
+    let mut tty = BufReader::new(tty);
+    for (i, acct) in accounts.iter().enumerate() {
+        eprintln!("{:>3}. {} ({})", i + 1, acct.name, acct.acct_id);
+    }
+    loop {
+        eprint!("Select an account [1-{}]: ", accounts.len());
+        let mut line = String::new();
+        if tty.read_line(&mut line)? == 0 {
+            return Err(Error::msg("no account selected"));
+        }
+        match line.trim().parse::<usize>() {
+            Ok(i) if i >= 1 && i <= accounts.len() =>
+                return Ok(accounts[i - 1].acct_id),
+            _ => eprintln!("Invalid selection"),
+        }
+    }
+}
+
+/// Authentication options given on the command line.
+///
+/// Options take precedence over the corresponding environment variables.
+#[derive(Clone, Copy, Default)]
+pub struct AuthOptions<'a> {
+    pub api_key:           Option<&'a str>,
+    pub client_cert:       Option<&'a str>,
+    pub app_uuid:          Option<&'a str>,
+    pub p12_pass:          Option<&'a str>,
+    pub bearer_token_file: Option<&'a str>,
+    pub jwt_file:          Option<&'a str>,
+    pub username:          Option<&'a str>,
+    pub account_id:        Option<&'a str>,
 }
 
 impl Auth {
@@ -116,6 +285,23 @@ impl Auth {
         cli_app_uuid:    Option<&str>,
         cli_p12_pass:    Option<&str>,
     ) -> Result<Self> {
+        Self::from_options(&AuthOptions {
+            api_key:     cli_api_key,
+            client_cert: cli_client_cert,
+            app_uuid:    cli_app_uuid,
+            p12_pass:    cli_p12_pass,
+            ..Default::default()
+        })
+    }
+
+    /// Returns the authentication method configured by `options`, or in
+    /// the environment.
+    pub fn from_options(options: &AuthOptions) -> Result<Self> {
+        let cli_api_key = options.api_key;
+        let cli_client_cert = options.client_cert;
+        let cli_app_uuid = options.app_uuid;
+        let cli_p12_pass = options.p12_pass;
+
         // Try API key
         let api_key = match (cli_api_key, env::var(ENV_API_KEY).ok()) {
             (Some(api_key), None) => Some(api_key.to_string()),
@@ -129,6 +315,18 @@ impl Auth {
             (None, None) => None,
         };
 
+        let app_uuid = match (cli_app_uuid, env::var(ENV_APP_UUID).ok()) {
+            (Some(id), None) => Some(id.to_string()),
+            (None, Some(id)) => Some(id),
+            (Some(id), Some(_)) => {
+                println!(
+                    "APP UUID both in parameters and env; ignoring env"
+                );
+                Some(id.to_string())
+            },
+            (None, None) => None,
+        };
+
         // Try client cert
         let cert_based = {
             let client_cert = match (cli_client_cert, env::var(ENV_P12).ok()) {
@@ -143,19 +341,7 @@ impl Auth {
                 (None, None) => None,
             };
 
-            let app_uuid = match (cli_app_uuid, env::var(ENV_APP_UUID).ok()) {
-                (Some(id), None) => Some(id.to_string()),
-                (None, Some(id)) => Some(id),
-                (Some(id), Some(_)) => {
-                    println!(
-                        "APP UUID both in parameters and env; ignoring env"
-                    );
-                    Some(id.to_string())
-                },
-                (None, None) => None,
-            };
-
-            match (client_cert, app_uuid) {
+            match (client_cert, app_uuid.clone()) {
                 (Some(cert), Some(uuid)) => Some((cert, uuid)),
                 _ => None,
             }
@@ -177,15 +363,81 @@ impl Auth {
                     .context("bad app UUID")?;
                 Ok(Auth::Cert(uuid, p12_id))
             }
-            (None, None) => Err(Error::msg("no auth credentials found")),
+            (None, None) => Self::from_token_or_login(options, app_uuid),
         }
     }
+
+    /// Tries the authentication methods based on tokens and user logins,
+    /// in this order: bearer token, JWT, user login.
+    fn from_token_or_login(options: &AuthOptions, app_uuid: Option<String>)
+                           -> Result<Self> {
+        let bearer = options.bearer_token_file
+            .map(|file| Token::File(file.into()))
+            .or_else(|| Token::from_env(ENV_BEARER_TOKEN,
+                                        ENV_BEARER_TOKEN_FILE));
+        let jwt = options.jwt_file
+            .map(|file| Token::File(file.into()))
+            .or_else(|| Token::from_env(ENV_JWT, ENV_JWT_FILE));
+        let username = options.username.map(Into::into)
+            .or_else(|| env::var(ENV_USERNAME).ok());
+
+        let found = [bearer.is_some(), jwt.is_some(), username.is_some()];
+        if found.iter().filter(|&&f| f).count() > 1 {
+            warn!("multiple auth methods found; precedence is bearer token, \
+                   JWT, user login");
+        }
+
+        if let Some(token) = bearer {
+            return Ok(Auth::BearerToken(token));
+        }
+
+        if let Some(jwt) = jwt {
+            let app_uuid = app_uuid.ok_or_else(|| Error::msg(format!(
+                "JWT authentication requires the app UUID ({})",
+                ENV_APP_UUID)))?;
+            let uuid = Uuid::parse_str(&app_uuid)
+                .context("bad app UUID")?;
+            return Ok(Auth::Jwt(uuid, jwt));
+        }
+
+        if let Some(username) = username {
+            let password = match env::var(ENV_PASSWORD) {
+                Ok(password) => password,
+                Err(_) => rpassword::read_password_from_tty(Some(
+                    &format!("Enter DSM password for {}: ", username)))
+                    .context("reading password from tty")?,
+            };
+            let account = match options.account_id.map(Into::into)
+                .or_else(|| env::var(ENV_ACCOUNT_ID).ok())
+            {
+                Some(id) => Some(Uuid::parse_str(&id)
+                                 .context("bad account UUID")?),
+                None => None,
+            };
+            return Ok(Auth::User(UserLogin {
+                username,
+                password,
+                account: Arc::new(Mutex::new(account)),
+            }));
+        }
+
+        Err(Error::msg("no auth credentials found"))
+    }
 }
 
 #[derive(Clone)]
 pub struct Credentials {
     api_endpoint: String,
     auth:         Auth,
+    cache:        Option<SobjectCache>,
+    /// The authenticated client, shared by all clones.
+    session:      Arc<Mutex<Option<Session>>>,
+}
+
+/// An authenticated DSM client, and when it was authenticated.
+struct Session {
+    client:     Arc<DsmClient>,
+    created_at: SystemTime,
 }
 
 trait OperateOrAskApproval<S: Into<Cow<'static, str>> + Display> {
@@ -201,9 +453,18 @@ trait OperateOrAskApproval<S: Into<Cow<'static, str>> + Display> {
     fn __decrypt(&self, req: &DecryptRequest, desc: S)
         -> Result<DecryptResponse>;
 
+    fn __encrypt(&self, req: &EncryptRequest, desc: S)
+        -> Result<EncryptResponse>;
+
     fn __export_sobject(&self, descriptor: &SobjectDescriptor, desc: S)
         -> Result<Sobject>;
 
+    fn __import_sobject(&self, req: &SobjectRequest, desc: S)
+        -> Result<Sobject>;
+
+    fn __delete_sobject(&self, uuid: &Uuid, desc: S)
+        -> Result<()>;
+
     // Currently unsupported due to backend constraints.
     fn __agree(&self, req: &AgreeKeyRequest, desc: S)
         -> Result<Sobject>;
@@ -236,67 +497,152 @@ impl <S: Into<Cow<'static, str>> + Display> OperateOrAskApproval<S> for DsmClien
     fn __update_sobject(
         &self, uuid: &Uuid, req: &SobjectRequest, desc: S
     ) -> Result<Sobject> {
-        match self.update_sobject(uuid, req) {
+        let mut audit = audit::Pending::new(audit::Operation::Update)
+            .uuid(uuid);
+        let res = match self.update_sobject(uuid, req) {
             Err(DsmError::Forbidden(ref msg)) if msg == OP_APPROVAL_MSG => {
                 info!("Creating UPDATE approval request: {}", desc);
-                let pa = self.request_approval_to_update_sobject(
+                self.request_approval_to_update_sobject(
                     uuid, req, Some(format!("sq-dsm: {}", desc))
-                )?;
-                self.__retry_until_resolved(&pa, desc)
+                ).map_err(Error::from).and_then(|pa| {
+                    audit.approval_request(pa.request_id());
+                    self.__retry_until_resolved(&pa, desc)
+                })
             }
             Err(err) => Err(err.into()),
             Ok(resp) => Ok(resp)
-        }
+        };
+        audit.finish(&res, None);
+        res
     }
 
     fn __sign(&self, req: &SignRequest, desc: S) -> Result<SignResponse> {
-        match self.sign(req) {
+        let mut audit = audit::Pending::new(audit::Operation::Sign)
+            .sobject(req.key.as_ref())
+            .input(req.hash.as_ref().or(req.data.as_ref()).map(|b| &b[..]));
+        let res = match self.sign(req) {
             Err(DsmError::Forbidden(ref msg)) if msg == OP_APPROVAL_MSG => {
                 info!("Creating SIGN approval request: {}", desc);
-                let pa = self.request_approval_to_sign(
+                self.request_approval_to_sign(
                     req, Some(format!("sq-dsm: {}", desc))
-                )?;
-                self.__retry_until_resolved(&pa, desc)
+                ).map_err(Error::from).and_then(|pa| {
+                    audit.approval_request(pa.request_id());
+                    self.__retry_until_resolved(&pa, desc)
+                })
             }
             Err(err) => Err(err.into()),
             Ok(resp) => Ok(resp)
-        }
+        };
+        let kid = res.as_ref().ok().and_then(|resp| resp.kid);
+        audit.finish(&res, kid);
+        res
     }
 
     fn __decrypt(
         &self, req: &DecryptRequest, desc: S
     ) -> Result<DecryptResponse> {
-        match self.decrypt(req) {
+        let mut audit = audit::Pending::new(audit::Operation::Decrypt)
+            .sobject(req.key.as_ref())
+            .input(Some(&req.cipher[..]));
+        let res = match self.decrypt(req) {
             Err(DsmError::Forbidden(ref msg)) if msg == OP_APPROVAL_MSG => {
                 info!("Creating DECRYPT approval request: {}", desc);
-                let pa = self.request_approval_to_decrypt(
+                self.request_approval_to_decrypt(
                     req, Some(format!("sq-dsm: {}", desc))
-                )?;
-                self.__retry_until_resolved(&pa, desc)
+                ).map_err(Error::from).and_then(|pa| {
+                    audit.approval_request(pa.request_id());
+                    self.__retry_until_resolved(&pa, desc)
+                })
             }
             Err(err) => Err(err.into()),
             Ok(resp) => Ok(resp)
-        }
+        };
+        let kid = res.as_ref().ok().and_then(|resp| resp.kid);
+        audit.finish(&res, kid);
+        res
+    }
+
+    fn __encrypt(
+        &self, req: &EncryptRequest, desc: S
+    ) -> Result<EncryptResponse> {
+        let mut audit = audit::Pending::new(audit::Operation::Encrypt)
+            .sobject(req.key.as_ref());
+        let res = match self.encrypt(req) {
+            Err(DsmError::Forbidden(ref msg)) if msg == OP_APPROVAL_MSG => {
+                info!("Creating ENCRYPT approval request: {}", desc);
+                self.request_approval_to_encrypt(
+                    req, Some(format!("sq-dsm: {}", desc))
+                ).map_err(Error::from).and_then(|pa| {
+                    audit.approval_request(pa.request_id());
+                    self.__retry_until_resolved(&pa, desc)
+                })
+            }
+            Err(err) => Err(err.into()),
+            Ok(resp) => Ok(resp)
+        };
+        let kid = res.as_ref().ok().and_then(|resp| resp.kid);
+        audit.finish(&res, kid);
+        res
     }
 
     fn __export_sobject(
         &self, descriptor: &SobjectDescriptor, desc: S
     ) -> Result<Sobject> {
-        match self.export_sobject(descriptor) {
+        let mut audit = audit::Pending::new(audit::Operation::Export)
+            .sobject(Some(descriptor));
+        let res = match self.export_sobject(descriptor) {
             Err(DsmError::Forbidden(ref msg)) if msg == OP_APPROVAL_MSG => {
                 info!("Creating EXPORT approval request: {}", desc);
-                let pa = self.request_approval_to_export_sobject(
+                self.request_approval_to_export_sobject(
                     descriptor, Some(format!("sq-dsm: {}", desc))
-                )?;
-                self.__retry_until_resolved(&pa, desc)
+                ).map_err(Error::from).and_then(|pa| {
+                    audit.approval_request(pa.request_id());
+                    self.__retry_until_resolved(&pa, desc)
+                })
             }
             Err(err) => Err(err.into()),
             Ok(resp) => Ok(resp)
-        }
+        };
+        let kid = res.as_ref().ok().and_then(|sob| sob.kid);
+        audit.finish(&res, kid);
+        res
+    }
+
+    fn __import_sobject(
+        &self, req: &SobjectRequest, _desc: S
+    ) -> Result<Sobject> {
+        // Importing does not require quorum approval.
+        let audit = audit::Pending::new(audit::Operation::Import);
+        let res = self.import_sobject(req).map_err(Error::from);
+        let kid = res.as_ref().ok().and_then(|sob| sob.kid);
+        audit.finish(&res, kid);
+        res
+    }
+
+    fn __delete_sobject(&self, uuid: &Uuid, desc: S) -> Result<()> {
+        let mut audit = audit::Pending::new(audit::Operation::Delete)
+            .uuid(uuid);
+        let res = match self.delete_sobject(uuid) {
+            Err(DsmError::Forbidden(ref msg)) if msg == OP_APPROVAL_MSG => {
+                info!("Creating DELETE approval request: {}", desc);
+                self.request_approval_to_delete_sobject(
+                    uuid, Some(format!("sq-dsm: {}", desc))
+                ).map_err(Error::from).and_then(|pa| {
+                    audit.approval_request(pa.request_id());
+                    self.__retry_until_resolved(&pa, desc)
+                })
+            }
+            Err(err) => Err(err.into()),
+            Ok(resp) => Ok(resp)
+        };
+        audit.finish(&res, None);
+        res
     }
 
     fn __agree(&self, req: &AgreeKeyRequest, _desc: S) -> Result<Sobject> {
-        match self.agree(req) {
+        let audit = audit::Pending::new(audit::Operation::Agree)
+            .sobject(Some(&req.private_key));
+        let res = match self.agree(req) {
             Err(DsmError::Forbidden(ref msg)) if msg == OP_APPROVAL_MSG => {
                 // FIXME: In DSM, AGREEKEY results in a transient key which
                 // cannot be retrieved with the DSM client.
@@ -304,7 +650,9 @@ impl <S: Into<Cow<'static, str>> + Display> OperateOrAskApproval<S> for DsmClien
             }
             Err(err) => Err(err.into()),
             Ok(resp) => Ok(resp)
-        }
+        };
+        audit.finish(&res, None);
+        res
     }
 }
 
@@ -313,37 +661,81 @@ impl Credentials {
         let api_endpoint = env::var(ENV_API_ENDPOINT)
             .with_context(|| format!("{} absent", ENV_API_ENDPOINT))?;
 
-        Ok(Self { api_endpoint, auth })
+        let cache = SobjectCache::from_env()?;
+
+        Ok(Self {
+            api_endpoint,
+            auth,
+            cache,
+            session: Arc::new(Mutex::new(None)),
+        })
     }
 
-    fn dsm_client(&self) -> Result<DsmClient> {
+    /// Returns an authenticated DSM client.
+    ///
+    /// The client is authenticated once, and reused by all clones of
+    /// these credentials, until it is older than [`SESSION_LIFETIME`].
+    fn dsm_client(&self) -> Result<Arc<DsmClient>> {
+        let mut session = self.session.lock()
+            .map_err(|_| Error::msg("DSM session poisoned"))?;
+        if let Some(s) = session.as_ref() {
+            let age = s.created_at.elapsed().unwrap_or(SESSION_LIFETIME);
+            if age < SESSION_LIFETIME {
+                return Ok(s.client.clone());
+            }
+        }
+
+        let client = Arc::new(self.authenticate()?);
+        *session = Some(Session {
+            client:     client.clone(),
+            created_at: SystemTime::now(),
+        });
+        Ok(client)
+    }
+
+    /// Logs in to DSM.
+    fn authenticate(&self) -> Result<DsmClient> {
+        let mut tls = TlsConnector::builder();
+        if let Auth::Cert(_, identity) = &self.auth {
+            tls.identity(identity.clone());
+        }
+        let ssl = NativeTlsClient::from(tls.build()?);
+        let hyper_client = maybe_proxied(&self.api_endpoint, ssl)?;
         let builder = DsmClient::builder()
-            .with_api_endpoint(&self.api_endpoint);
+            .with_api_endpoint(&self.api_endpoint)
+            .with_hyper_client(Arc::new(hyper_client));
 
         let cli = match &self.auth {
             Auth::ApiKey(api_key) => {
-                let tls_conn = TlsConnector::builder()
-                    .build()?;
-                let ssl = NativeTlsClient::from(tls_conn);
-                let hyper_client = maybe_proxied(&self.api_endpoint, ssl)?;
-                let cli = builder
-                    .with_hyper_client(Arc::new(hyper_client))
+                builder
                     .build()
-                    .context("could not initiate a DSM client")?;
-                cli.authenticate_with_api_key(api_key)?
+                    .context("could not initiate a DSM client")?
+                    .authenticate_with_api_key(api_key)?
             },
-            Auth::Cert(app_uuid, identity) => {
-                let tls_conn = TlsConnector::builder()
-                    .identity(identity.clone())
-                    .build()?;
-                let ssl = NativeTlsClient::from(tls_conn);
-                let hyper_client = maybe_proxied(&self.api_endpoint, ssl)?;
-                let cli = builder
-                    .with_hyper_client(Arc::new(hyper_client))
+            Auth::Cert(app_uuid, _) => {
+                builder
                     .build()
-                    .context("could not initiate a DSM client")?;
-                cli.authenticate_with_cert(Some(app_uuid))?
-            }
+                    .context("could not initiate a DSM client")?
+                    .authenticate_with_cert(Some(app_uuid))?
+            },
+            Auth::BearerToken(token) => {
+                builder
+                    .with_access_token(&token.read()?)
+                    .build()
+                    .context("could not initiate a DSM client")?
+            },
+            Auth::Jwt(app_uuid, jwt) => {
+                builder
+                    .build()
+                    .context("could not initiate a DSM client")?
+                    .authenticate_with_jwt(app_uuid, &jwt.read()?)?
+            },
+            Auth::User(login) => {
+                login.authenticate(
+                    builder
+                        .build()
+                        .context("could not initiate a DSM client")?)?
+            },
         };
 
         let min = VersionReq::parse(&(">=".to_string() + MIN_DSM_VERSION))?;
@@ -360,21 +752,41 @@ impl Credentials {
     }
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, PartialEq)]
 enum Role {
     Signer,
     Decryptor,
 }
 
 impl DsmAgent {
+    /// Logs in to DSM once, and performs all further operations of this
+    /// agent and its clones in this session.
+    ///
+    /// Otherwise, operations use the session of the credentials, which is
+    /// renewed periodically.  This is useful when signing or decrypting
+    /// many times, possibly from several threads.
+    pub fn with_session(mut self) -> Result<Self> {
+        self.session = Some(self.credentials.dsm_client()?);
+        Ok(self)
+    }
+
+    /// Returns the session of this agent, or a new one.
+    fn dsm_client(&self) -> Result<Arc<DsmClient>> {
+        match &self.session {
+            Some(session) => Ok(session.clone()),
+            None => Ok(self.credentials.dsm_client()
+                .context("could not initialize the http client")?),
+        }
+    }
+
     /// Returns a DsmAgent with certifying capabilities, corresponding to the
     /// primary key (flag "C").
-    fn new_certifier(credentials: Credentials, key_name: &str) -> Result<Self> {
-        let dsm_client = credentials.dsm_client()?;
+    pub fn new_certifier(credentials: Credentials, key_name: &str) -> Result<Self> {
+        let mut sobjects = SobjectLookup::new(&credentials);
 
         let descriptor = SobjectDescriptor::Name(key_name.to_string());
-        let prim_sob = dsm_client
-            .get_sobject(None, &descriptor)
+        let prim_sob = sobjects
+            .get_sobject(&descriptor)
             .context(format!("could not get primary key {:?}", descriptor))?;
         // Initialize Signer with primary key
         let key = PublicKey::from_sobject(prim_sob, KeyRole::Primary)?;
@@ -383,18 +795,107 @@ impl DsmAgent {
             descriptor,
             public: key.sequoia_key.context("key is not loaded")?,
             role: Role::Signer,
+            session: None,
         })
     }
 
+    /// Computes the ECDH shared secret between this key and the
+    /// ephemeral public key of the given ciphertext.
+    ///
+    /// This is the part of ECDH decryption that requires the secret key,
+    /// for callers that unwrap the session key themselves.
+    pub fn ecdh_shared_secret(&self, ciphertext: &MpiCiphertext)
+                              -> Result<Protected> {
+        if self.role != Role::Decryptor {
+            return Err(Error::msg("bad role for DSM agent"));
+        }
+        let _audit = audit::with_fingerprint(self.public.fingerprint());
+
+        let e = match ciphertext {
+            MpiCiphertext::ECDH { e, .. } => e,
+            _ => return Err(Error::msg("not an ECDH ciphertext")),
+        };
+
+        let cli = self.dsm_client()?;
+
+        let curve = match &self.public.mpis() {
+            MpiPublic::ECDH { curve, .. } => curve,
+            _ => panic!("inconsistent pk algo"),
+        };
+
+        let ephemeral_der = der::serialize::spki_ec(curve, e);
+
+        // Import ephemeral public key
+        let e_descriptor = {
+            let api_curve = api_curve_from_sequoia_curve(curve.clone())
+                .context("bad curve")?;
+            let req = SobjectRequest {
+                elliptic_curve: Some(api_curve),
+                key_ops: Some(KeyOperations::AGREEKEY),
+                obj_type: Some(ObjectType::Ec),
+                transient: Some(true),
+                value: Some(ephemeral_der.into()),
+                ..Default::default()
+            };
+            let e_tkey = cli
+                .__import_sobject(&req, "import ECDH ephemeral public key")
+                .context("failed import ephemeral public key into DSM")?
+                .transient_key
+                .context("could not retrieve DSM transient key \
+                         (representing ECDH ephemeral public key)")?;
+
+            SobjectDescriptor::TransientKey(e_tkey)
+        };
+
+        // Agree on a ECDH secret between the recipient private key, and
+        // the ephemeral public key.
+        let secret: Protected = {
+            let agree_req = AgreeKeyRequest {
+                activation_date:   None,
+                deactivation_date: None,
+                private_key:       self.descriptor.clone(),
+                public_key:        e_descriptor,
+                mechanism:         AgreeKeyMechanism::DiffieHellman,
+                name:              None,
+                group_id:          None,
+                key_type:          ObjectType::Secret,
+                key_size:          curve_key_size(curve).context("size")?,
+                enabled:           true,
+                description:       None,
+                custom_metadata:   None,
+                key_ops:           Some(KeyOperations::EXPORT),
+                state:             None,
+                transient:         true,
+            };
+
+            let agreed_tkey = cli
+                .__agree(&agree_req, "ECDH exchange")
+                .context("ECDH exchange")?
+                .transient_key
+                .context("could not retrieve agreed key")?;
+
+            let desc = SobjectDescriptor::TransientKey(agreed_tkey);
+
+            cli.__export_sobject(&desc, "export ECDH shared secret")
+                .context("could not export transient key")?
+                .value
+                .context("could not retrieve secret from sobject")?
+                .to_vec()
+                .into()
+        };
+
+        Ok(secret)
+    }
+
     /// Returns a DsmAgent with signing capabilities, corresponding to the first
     /// key with key flag "S" found in DSM.
     pub fn new_signer(credentials: Credentials, key_name: &str) -> Result<Self> {
-        let dsm_client = credentials.dsm_client()?;
+        let mut sobjects = SobjectLookup::new(&credentials);
 
         // Check if primary is "S"
         let descriptor = SobjectDescriptor::Name(key_name.to_string());
-        let prim_sob = dsm_client
-            .get_sobject(None, &descriptor)
+        let prim_sob = sobjects
+            .get_sobject(&descriptor)
             .context(format!("could not get signer key {:?}", descriptor))?;
         if let Some(flags) = KeyMetadata::from_sobject(&prim_sob)?.key_flags {
             if flags.for_signing() {
@@ -405,6 +906,7 @@ impl DsmAgent {
                     descriptor,
                     public: key.sequoia_key.context("key is not loaded")?,
                     role: Role::Signer,
+                    session: None,
                 });
             }
         }
@@ -414,8 +916,8 @@ impl DsmAgent {
             .ok_or_else(|| Error::msg("no subkeys found"))?;
         for uid in subkeys {
             let descriptor = SobjectDescriptor::Kid(uid);
-            let sub_sob = dsm_client
-                .get_sobject(None, &descriptor)?;
+            let sub_sob = sobjects
+                .get_sobject(&descriptor)?;
             if let Some(flags) = KeyMetadata::from_sobject(&sub_sob)?.key_flags {
                 if flags.for_signing() {
                     // Initialize Signer with subkey
@@ -425,6 +927,7 @@ impl DsmAgent {
                         descriptor,
                         public: key.sequoia_key.context("key is not loaded")?,
                         role: Role::Signer,
+                        session: None,
                     })
                 }
             }
@@ -433,13 +936,53 @@ impl DsmAgent {
         Err(anyhow::anyhow!("Found no suitable signing key in DSM"))
     }
 
+    /// Returns DsmAgents with signing capabilities, corresponding to the
+    /// keys with key flag "A" found in DSM.
+    pub fn new_authenticators(credentials: Credentials, key_name: &str) -> Result<Vec<Self>> {
+        let mut sobjects = SobjectLookup::new(&credentials);
+        let mut authenticators = Vec::new();
+
+        let prim_descriptor = SobjectDescriptor::Name(key_name.to_string());
+        let prim_sob = sobjects
+            .get_sobject(&prim_descriptor)
+            .context(format!("could not get primary key {:?}", prim_descriptor))?;
+        let subkeys = match prim_sob.links {
+            Some(KeyLinks { ref subkeys, .. }) => subkeys.clone(),
+            None => vec![],
+        };
+
+        let mut candidates = vec![(prim_descriptor, prim_sob, KeyRole::Primary)];
+        for uid in subkeys {
+            let descriptor = SobjectDescriptor::Kid(uid);
+            let sub_sob = sobjects
+                .get_sobject(&descriptor)
+                .context(format!("could not get subkey {}", uid))?;
+            candidates.push((descriptor, sub_sob, KeyRole::SigningSubkey));
+        }
+
+        for (descriptor, sob, role) in candidates {
+            if let Some(flags) = KeyMetadata::from_sobject(&sob)?.key_flags {
+                if flags.for_authentication() {
+                    let key = PublicKey::from_sobject(sob, role)?;
+                    authenticators.push(DsmAgent {
+                        credentials: credentials.clone(),
+                        descriptor,
+                        public: key.sequoia_key.context("key is not loaded")?,
+                        role: Role::Signer,
+                        session: None,
+                    });
+                }
+            }
+        }
+
+        Ok(authenticators)
+    }
+
     fn new_signing_subkey_from_descriptor(
         credentials: Credentials, desc: &SobjectDescriptor
     ) -> Result<Self> {
-        let dsm_client = credentials.dsm_client()?;
-
-        let sob = dsm_client
-            .get_sobject(None, desc)
+        let sob = SobjectLookup::new(&credentials)
+            .get_sobject(desc)
             .context(format!("could not get signer key {:?}", &desc))?;
         let key = PublicKey::from_sobject(sob, KeyRole::SigningSubkey)?;
         Ok(DsmAgent {
@@ -447,6 +990,7 @@ impl DsmAgent {
             descriptor: desc.clone(),
             public: key.sequoia_key.context("key is not loaded")?,
             role: Role::Signer,
+            session: None,
         })
     }
 
@@ -461,17 +1005,17 @@ impl DsmAgent {
     pub fn new_decryptors(credentials: Credentials, key_name: &str) -> Result<Vec<Self>> {
         let mut decryptors = Vec::<DsmAgent>::new();
 
-        let dsm_client = credentials.dsm_client()?;
+        let mut sobjects = SobjectLookup::new(&credentials);
         let prim_descriptor = SobjectDescriptor::Name(key_name.to_string());
-        let prim_sobject = dsm_client
-            .get_sobject(None, &prim_descriptor)
+        let prim_sobject = sobjects
+            .get_sobject(&prim_descriptor)
             .context(format!("could not get primary key {:?}", &prim_descriptor))?;
 
         if let Some(KeyLinks { subkeys, .. }) = prim_sobject.links {
             for uid in subkeys {
                 let descriptor = SobjectDescriptor::Kid(uid);
-                let sobject = dsm_client
-                    .get_sobject(None, &descriptor)
+                let sobject = sobjects
+                    .get_sobject(&descriptor)
                     .context("could not get subkey".to_string())?;
                 if let Some(kf) = KeyMetadata::from_sobject(&sobject)?.key_flags {
                     if kf.for_storage_encryption() || kf.for_transport_encryption() {
@@ -481,6 +1025,7 @@ impl DsmAgent {
                             descriptor,
                             public: key.sequoia_key.context("key is not loaded")?,
                             role: Role::Decryptor,
+                            session: None,
                         });
                     }
                 }
@@ -489,6 +1034,28 @@ impl DsmAgent {
 
         Ok(decryptors)
     }
+
+    /// Returns a DsmAgent with decryption capabilities, corresponding to the
+    /// encryption subkey with the given UUID.
+    pub fn new_decryptor_from_uuid(credentials: Credentials, kid: &Uuid) -> Result<Self> {
+        let descriptor = SobjectDescriptor::Kid(*kid);
+        let sobject = SobjectLookup::new(&credentials)
+            .get_sobject(&descriptor)
+            .context(format!("could not get subkey {}", kid))?;
+        match KeyMetadata::from_sobject(&sobject)?.key_flags {
+            Some(kf) if kf.for_storage_encryption() || kf.for_transport_encryption() => (),
+            _ => return Err(anyhow::anyhow!("{} is not an encryption subkey", kid)),
+        }
+
+        let key = PublicKey::from_sobject(sobject, KeyRole::EncryptionSubkey)?;
+        Ok(DsmAgent {
+            credentials,
+            descriptor,
+            public: key.sequoia_key.context("key is not loaded")?,
+            role: Role::Decryptor,
+            session: None,
+        })
+    }
 }
 
 #[derive(Clone)]
@@ -1015,7 +1582,9 @@ pub fn dsm_key_info(cred: Credentials, key_name: &str) -> Result<Option<DsmKeyIn
         ..Default::default()
     };
 
-    let key: DsmKeyInfo = match dsm_client.list_sobjects(Some(&params))?.first() {
+    let sobjects = dsm_client.list_sobjects(Some(&params))?;
+    cred.observe(&sobjects);
+    let key: DsmKeyInfo = match sobjects.first() {
         Some(key) => key.try_into()?,
         None => return Err(anyhow::anyhow!("no key with name {} exists",
                                            &key_name)),
@@ -1040,7 +1609,9 @@ pub fn list_keys(cred: Credentials) -> Result<Vec<DsmKeyInfo>> {
             ..Default::default()
         };
 
-        for key_details in dsm_client.list_sobjects(Some(&params))?
+        let sobjects = dsm_client.list_sobjects(Some(&params))?;
+        cred.observe(&sobjects);
+        for key_details in sobjects
             .iter()
             .filter(|key|
                     match &key.custom_metadata {
@@ -1060,10 +1631,8 @@ pub fn list_keys(cred: Credentials) -> Result<Vec<DsmKeyInfo>> {
 /// metadata of the Security Object representing the primary key.
 pub fn extract_cert(key_name: &str, cred: Credentials) -> Result<Cert> {
     info!("dsm extract_cert");
-    let dsm_client = cred.dsm_client()?;
-
-    let sobject = dsm_client
-        .get_sobject(None, &SobjectDescriptor::Name(key_name.to_string()))
+    let sobject = SobjectLookup::new(&cred)
+        .get_sobject(&SobjectDescriptor::Name(key_name.to_string()))
         .context(format!("could not get primary key {}", key_name))?;
 
     Cert::from_str(
@@ -1072,6 +1641,391 @@ pub fn extract_cert(key_name: &str, cred: Credentials) -> Result<Cert> {
     )
 }
 
+/// The ways in which the Security Objects backing a PGP key can be retired,
+/// see [`retire_key`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Retirement {
+    /// Disables the Security Objects. They can be enabled again in DSM.
+    Disable,
+    /// Sets the deactivation date of the Security Objects to now.
+    Deactivate,
+    /// Deletes the Security Objects from DSM. This cannot be undone.
+    Destroy,
+}
+
+/// Fetches the Security Objects of a PGP key, i.e., the primary key followed
+/// by all the subkeys linked to it.
+fn pgp_key_sobjects(dsm_client: &DsmClient, key_name: &str) -> Result<Vec<Sobject>> {
+    let prim_sob = dsm_client
+        .get_sobject(None, &SobjectDescriptor::Name(key_name.to_string()))
+        .context(format!("could not get primary key {}", key_name))?;
+    KeyMetadata::from_sobject(&prim_sob)
+        .context(format!("{} is not a PGP key", key_name))?;
+
+    let subkeys = match prim_sob.links {
+        Some(KeyLinks { ref subkeys, .. }) => subkeys.clone(),
+        None => vec![],
+    };
+
+    let mut sobjects = vec![prim_sob];
+    for uid in subkeys {
+        let sob = dsm_client
+            .get_sobject(None, &SobjectDescriptor::Kid(uid))
+            .context(format!("could not get subkey {}", uid))?;
+        sobjects.push(sob);
+    }
+
+    Ok(sobjects)
+}
+
+/// Returns details on the Security Objects of a PGP key, the primary key
+/// first.
+pub fn key_components(cred: Credentials, key_name: &str) -> Result<Vec<DsmKeyInfo>> {
+    info!("dsm key_components");
+    let dsm_client = cred.dsm_client()?;
+
+    pgp_key_sobjects(&dsm_client, key_name)?
+        .iter()
+        .map(DsmKeyInfo::try_from)
+        .collect()
+}
+
+/// A problem found by [`check_keys`] in the Security Objects of a PGP key.
+#[derive(Clone, Debug)]
+pub enum Problem {
+    /// The PGP metadata of the Security Object cannot be read.
+    BadMetadata(String),
+    /// The certificate in the primary key's metadata is missing or invalid.
+    BadCertificate(String),
+    /// The key material cannot be turned into a PGP key.
+    BadKeyMaterial(String),
+    /// The fingerprint in the metadata differs from the key material.
+    MetadataFingerprint { stored: String, actual: Fingerprint },
+    /// The key flags in the metadata differ from the certificate's.
+    MetadataKeyFlags,
+    /// The key material is not part of the certificate.
+    NotInCertificate(Fingerprint),
+    /// A key of the certificate has no Security Object.
+    MissingFromDsm(Fingerprint),
+    /// The subkey does not link back to the primary key.
+    ParentLink(Option<Uuid>),
+    /// The subkey links to the primary key, which does not list it.
+    UnlistedSubkey,
+    /// The binding signature of a key is invalid under the standard policy.
+    BadBinding(Fingerprint, String),
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Problem::BadMetadata(e) =>
+                write!(f, "unreadable PGP metadata: {}", e),
+            Problem::BadCertificate(e) =>
+                write!(f, "bad certificate in metadata: {}", e),
+            Problem::BadKeyMaterial(e) =>
+                write!(f, "unusable key material: {}", e),
+            Problem::MetadataFingerprint { stored, actual } =>
+                write!(f, "metadata fingerprint {} does not match the key \
+                           material ({})", stored, actual),
+            Problem::MetadataKeyFlags =>
+                write!(f, "metadata key flags differ from the certificate"),
+            Problem::NotInCertificate(fp) =>
+                write!(f, "key {} is not part of the certificate", fp),
+            Problem::MissingFromDsm(fp) =>
+                write!(f, "key {} of the certificate is not in DSM", fp),
+            Problem::ParentLink(Some(parent)) =>
+                write!(f, "subkey is linked to {} instead of the primary key",
+                       parent),
+            Problem::ParentLink(None) =>
+                write!(f, "subkey is not linked to the primary key"),
+            Problem::UnlistedSubkey =>
+                write!(f, "subkey is not listed by the primary key"),
+            Problem::BadBinding(fp, e) =>
+                write!(f, "invalid binding of key {}: {}", fp, e),
+        }
+    }
+}
+
+/// A [`Problem`] with a Security Object.
+#[derive(Clone, Debug)]
+pub struct Finding {
+    pub sobject:  Uuid,
+    pub problem:  Problem,
+    /// Whether the problem was repaired.
+    pub repaired: bool,
+}
+
+/// The findings of [`check_keys`] for one PGP key.
+#[derive(Clone, Debug)]
+pub struct KeyCheck {
+    pub name:     String,
+    pub findings: Vec<Finding>,
+}
+
+/// Checks that the Security Objects of PGP keys are consistent with the
+/// certificates stored in their metadata, for the given key or for all
+/// accessible keys.
+///
+/// Fingerprints are recomputed from the key material, and compared with
+/// the metadata and the certificate.  Links between primary keys and
+/// subkeys are checked in both directions, and binding signatures are
+/// validated under the standard policy.  If `repair` is set, wrong
+/// metadata fingerprints and key flags, and missing parent links, are
+/// fixed in DSM.
+pub fn check_keys(cred: Credentials, key_name: Option<&str>, repair: bool)
+                  -> Result<Vec<KeyCheck>> {
+    info!("dsm check_keys");
+    let dsm_client = cred.dsm_client()?;
+
+    let named = match key_name {
+        Some(name) => {
+            let prim_sob = dsm_client
+                .get_sobject(None, &SobjectDescriptor::Name(name.to_string()))
+                .context(format!("could not get primary key {}", name))?;
+            KeyMetadata::from_sobject(&prim_sob)
+                .context(format!("{} is not a PGP key", name))?;
+            Some(prim_sob)
+        }
+        None => None,
+    };
+
+    // Subkeys whose primary key does not list them can only be found
+    // by looking at all Security Objects, even if we check one key.
+    let mut primaries = Vec::new();
+    let mut children = HashMap::<Uuid, Vec<Uuid>>::new();
+    for group in dsm_client.list_groups()? {
+        let params = ListSobjectsParams {
+            group_id: Some(group.group_id),
+            ..Default::default()
+        };
+        let sobjects = dsm_client.list_sobjects(Some(&params))?;
+        cred.observe(&sobjects);
+        for sob in sobjects {
+            match &sob.custom_metadata {
+                Some(md) if md.contains_key(DSM_LABEL_PGP) => (),
+                _ => continue,
+            }
+            if let (Some(KeyLinks { parent: Some(parent), .. }),
+                    Some(kid)) = (&sob.links, sob.kid) {
+                children.entry(*parent).or_default().push(kid);
+            }
+            let is_primary = KeyMetadata::from_sobject(&sob)
+                .map(|md| md.certificate.is_some())
+                .unwrap_or(false);
+            if is_primary {
+                primaries.push(sob);
+            }
+        }
+    }
+    if let Some(prim_sob) = named {
+        primaries = vec![prim_sob];
+    }
+
+    primaries.into_iter()
+        .map(|prim_sob| {
+            let name = prim_sob.name.clone().unwrap_or_default();
+            let linked = prim_sob.kid
+                .and_then(|kid| children.get(&kid).cloned())
+                .unwrap_or_default();
+            Ok(KeyCheck {
+                name,
+                findings: check_key(&cred, &dsm_client, prim_sob, &linked,
+                                    repair)?,
+            })
+        })
+        .collect()
+}
+
+fn check_key(cred: &Credentials, dsm_client: &DsmClient, prim_sob: Sobject,
+             linked: &[Uuid], repair: bool) -> Result<Vec<Finding>> {
+    let p = &StandardPolicy::new();
+    let prim_kid = prim_sob.kid.context("no kid")?;
+    let subkeys = match prim_sob.links {
+        Some(KeyLinks { ref subkeys, .. }) => subkeys.clone(),
+        None => vec![],
+    };
+
+    let mut findings = Vec::new();
+    let mut finding = |sobject, problem, repaired| findings.push(Finding {
+        sobject, problem, repaired,
+    });
+
+    for kid in linked.iter().filter(|kid| ! subkeys.contains(kid)) {
+        finding(*kid, Problem::UnlistedSubkey, false);
+    }
+
+    let cert = KeyMetadata::from_sobject(&prim_sob)
+        .and_then(|md| md.certificate.context("no certificate"))
+        .and_then(|armored| Cert::from_str(&armored));
+    let cert = match cert {
+        Ok(cert) => {
+            for ka in cert.keys() {
+                if let Err(e) = ka.with_policy(p, None) {
+                    finding(prim_kid,
+                            Problem::BadBinding(ka.fingerprint(), e.to_string()),
+                            false);
+                }
+            }
+            Some(cert)
+        }
+        Err(e) => {
+            finding(prim_kid, Problem::BadCertificate(e.to_string()), false);
+            None
+        }
+    };
+
+    let mut sobjects = vec![prim_sob];
+    for kid in &subkeys {
+        sobjects.push(dsm_client
+            .get_sobject(None, &SobjectDescriptor::Kid(*kid))
+            .context(format!("could not get subkey {}", kid))?);
+    }
+
+    let mut seen = Vec::new();
+    for sob in sobjects {
+        let kid = sob.kid.context("no kid")?;
+        let mut md = match KeyMetadata::from_sobject(&sob) {
+            Ok(md) => md,
+            Err(e) => {
+                finding(kid, Problem::BadMetadata(e.to_string()), false);
+                continue;
+            }
+        };
+
+        let role = if kid == prim_kid {
+            KeyRole::Primary
+        } else if md.key_flags.as_ref()
+            .map(|f| f.for_signing() || f.for_authentication())
+            .unwrap_or(false)
+        {
+            KeyRole::SigningSubkey
+        } else {
+            KeyRole::EncryptionSubkey
+        };
+
+        if role != KeyRole::Primary {
+            let parent = sob.links.as_ref().and_then(|links| links.parent);
+            if parent != Some(prim_kid) {
+                if repair {
+                    let link_req = SobjectRequest {
+                        links: Some(KeyLinks {
+                            parent: Some(prim_kid),
+                            ..Default::default()
+                        }),
+                        ..Default::default()
+                    };
+                    dsm_client.__update_sobject(
+                        &kid, &link_req, "bind subkey to primary key"
+                    )?;
+                    cred.invalidate(&kid);
+                }
+                finding(kid, Problem::ParentLink(parent), repair);
+            }
+        }
+
+        let fp = match PublicKey::from_sobject(sob, role)
+            .and_then(|key| key.sequoia_key.context("key is not loaded"))
+        {
+            Ok(key) => key.fingerprint(),
+            Err(e) => {
+                finding(kid, Problem::BadKeyMaterial(e.to_string()), false);
+                continue;
+            }
+        };
+        seen.push(fp.clone());
+
+        // Repairable metadata problems.
+        let mut stale = Vec::new();
+        if let Some(cert) = &cert {
+            match cert.keys().find(|ka| ka.fingerprint() == fp) {
+                Some(ka) => {
+                    if md.fingerprint != fp.to_hex() {
+                        stale.push(Problem::MetadataFingerprint {
+                            stored: md.fingerprint.clone(),
+                            actual: fp.clone(),
+                        });
+                        md.fingerprint = fp.to_hex();
+                    }
+                    let flags = ka.with_policy(p, None).ok()
+                        .and_then(|vka| vka.key_flags());
+                    if flags.is_some() && flags != md.key_flags {
+                        stale.push(Problem::MetadataKeyFlags);
+                        md.key_flags = flags;
+                    }
+                }
+                None => finding(kid, Problem::NotInCertificate(fp), false),
+            }
+        }
+
+        if repair && ! stale.is_empty() {
+            let md_req = SobjectRequest {
+                custom_metadata: Some(md.to_custom_metadata()?),
+                ..Default::default()
+            };
+            dsm_client.__update_sobject(&kid, &md_req, "repair PGP metadata")?;
+            cred.invalidate(&kid);
+        }
+        for problem in stale {
+            finding(kid, problem, repair);
+        }
+    }
+
+    if let Some(cert) = &cert {
+        for key in cert.keys() {
+            if ! seen.contains(&key.fingerprint()) {
+                finding(prim_kid, Problem::MissingFromDsm(key.fingerprint()),
+                        false);
+            }
+        }
+    }
+
+    Ok(findings)
+}
+
+/// Disables, deactivates or destroys the primary key and all subkeys of a
+/// PGP key. Operations that require quorum approval go through the approval
+/// flow.
+///
+/// Subkeys are processed before the primary key, so that an interrupted run
+/// never leaves usable subkeys behind a retired primary key.
+pub fn retire_key(cred: Credentials, key_name: &str, how: Retirement) -> Result<()> {
+    info!("dsm retire_key ({:?})", how);
+    let dsm_client = cred.dsm_client()?;
+
+    let mut uuids = pgp_key_sobjects(&dsm_client, key_name)?
+        .iter()
+        .map(|sob| sob.kid.context("no kid"))
+        .collect::<Result<Vec<Uuid>>>()?;
+    uuids.rotate_left(1);
+
+    for uuid in &uuids {
+        match how {
+            Retirement::Disable => {
+                let req = SobjectRequest {
+                    enabled: Some(false),
+                    ..Default::default()
+                };
+                dsm_client.__update_sobject(uuid, &req, "disable PGP key")?;
+            },
+            Retirement::Deactivate => {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+                let req = SobjectRequest {
+                    deactivation_date: Some(SdkmsTime(now)),
+                    ..Default::default()
+                };
+                dsm_client.__update_sobject(uuid, &req, "deactivate PGP key")?;
+            },
+            Retirement::Destroy => {
+                dsm_client.__delete_sobject(uuid, "destroy PGP key")?;
+            },
+        }
+        cred.invalidate(uuid);
+        info!("{:?}: {}", how, uuid);
+    }
+
+    Ok(())
+}
+
 pub fn extract_tsk_from_dsm(key_name: &str, cred: Credentials) -> Result<Cert> {
     // Extract all secrets as packets
     let dsm_client = cred.dsm_client()?;
@@ -1125,6 +2079,7 @@ pub fn import_key_to_dsm(
     cred:       Credentials,
     exportable: bool,
 ) -> Result<()> {
+    let _audit = audit::with_fingerprint(tsk.fingerprint());
 
     fn import_constructed_sobject(
         cred:     &Credentials,
@@ -1295,7 +2250,7 @@ pub fn import_key_to_dsm(
 
         Ok(
             cred.dsm_client()?
-            .import_sobject(&req)
+            .__import_sobject(&req, "import secret key")
             .context(format!("could not import secret {}", name))?
             .kid
             .ok_or(anyhow::anyhow!("no UUID returned from DSM"))?
@@ -1327,7 +2282,7 @@ pub fn import_key_to_dsm(
         if is_secret_key{
             // SECRET KEY
             if let Some(f) = key_flags {
-                if f.for_signing() | f.for_certification() {
+                if f.for_signing() | f.for_certification() | f.for_authentication() {
                     ops |= KeyOperations::SIGN;
                 }
 
@@ -1342,7 +2297,7 @@ pub fn import_key_to_dsm(
         } else {
             // PUBLIC KEY
             if let Some(f) = key_flags {
-                if f.for_signing() | f.for_certification() {
+                if f.for_signing() | f.for_certification() | f.for_authentication() {
                     ops |= KeyOperations::VERIFY;
                 }
 
@@ -1800,14 +2755,16 @@ impl Signer for DsmAgent {
         if self.role != Role::Signer {
             return Err(Error::msg("bad role for DSM agent"));
         }
-        let dsm_client = self.credentials.dsm_client()
-            .context("could not initialize the http client")?;
+        let _audit = audit::with_fingerprint(self.public.fingerprint());
+        let dsm_client = self.dsm_client()?;
 
         let hash_alg = match hash_algo {
             HashAlgorithm::SHA1 => DigestAlgorithm::Sha1,
+            HashAlgorithm::SHA384 => DigestAlgorithm::Sha384,
             HashAlgorithm::SHA512 => DigestAlgorithm::Sha512,
             HashAlgorithm::SHA256 => DigestAlgorithm::Sha256,
-            hash => panic!("unimplemented: {}", hash),
+            hash => return Err(anyhow::anyhow!(
+                "unsupported hash algorithm {}", hash)),
         };
 
         match self.public.pk_algo() {
@@ -1881,9 +2838,9 @@ impl Decryptor for DsmAgent {
         if self.role != Role::Decryptor {
             return Err(Error::msg("bad role for DSM agent"));
         }
+        let _audit = audit::with_fingerprint(self.public.fingerprint());
 
-        let cli = self.credentials.dsm_client()
-            .context("could not initialize the http client")?;
+        let cli = self.dsm_client()?;
 
         match ciphertext {
             MpiCiphertext::RSA { c } => {
@@ -1919,72 +2876,8 @@ impl Decryptor for DsmAgent {
                     .plain.to_vec().into()
                 )
             }
-            MpiCiphertext::ECDH { e, .. } => {
-                let curve = match &self.public.mpis() {
-                    MpiPublic::ECDH { curve, .. } => curve,
-                    _ => panic!("inconsistent pk algo"),
-                };
-
-                let ephemeral_der = der::serialize::spki_ec(curve, e);
-
-                // Import ephemeral public key
-                let e_descriptor = {
-                    let api_curve = api_curve_from_sequoia_curve(curve.clone())
-                        .context("bad curve")?;
-                    let req = SobjectRequest {
-                        elliptic_curve: Some(api_curve),
-                        key_ops: Some(KeyOperations::AGREEKEY),
-                        obj_type: Some(ObjectType::Ec),
-                        transient: Some(true),
-                        value: Some(ephemeral_der.into()),
-                        ..Default::default()
-                    };
-                    let e_tkey = cli
-                        .import_sobject(&req)
-                        .context("failed import ephemeral public key into DSM")?
-                        .transient_key
-                        .context("could not retrieve DSM transient key \
-                                 (representing ECDH ephemeral public key)")?;
-
-                    SobjectDescriptor::TransientKey(e_tkey)
-                };
-
-                // Agree on a ECDH secret between the recipient private key, and
-                // the ephemeral public key.
-                let secret: Protected = {
-                    let agree_req = AgreeKeyRequest {
-                        activation_date:   None,
-                        deactivation_date: None,
-                        private_key:       self.descriptor.clone(),
-                        public_key:        e_descriptor,
-                        mechanism:         AgreeKeyMechanism::DiffieHellman,
-                        name:              None,
-                        group_id:          None,
-                        key_type:          ObjectType::Secret,
-                        key_size:          curve_key_size(curve).context("size")?,
-                        enabled:           true,
-                        description:       None,
-                        custom_metadata:   None,
-                        key_ops:           Some(KeyOperations::EXPORT),
-                        state:             None,
-                        transient:         true,
-                    };
-
-                    let agreed_tkey = cli
-                        .__agree(&agree_req, "ECDH exchange")
-                        .context("ECDH exchange")?
-                        .transient_key
-                        .context("could not retrieve agreed key")?;
-
-                    let desc = SobjectDescriptor::TransientKey(agreed_tkey);
-
-                    cli.export_sobject(&desc)
-                        .context("could not export transient key")?
-                        .value
-                        .context("could not retrieve secret from sobject")?
-                        .to_vec()
-                        .into()
-                };
+            MpiCiphertext::ECDH { .. } => {
+                let secret = self.ecdh_shared_secret(ciphertext)?;
 
                 Ok(ecdh::decrypt_unwrap(&self.public, &secret, ciphertext)
                     .context("could not unwrap the session key")?
@@ -1996,6 +2889,138 @@ impl Decryptor for DsmAgent {
     }
 }
 
+/// Public key algorithm of PKESK packets whose session key is wrapped by a
+/// DSM AES key, see [wrap_session_key].
+pub const SESSION_KEY_WRAP_ALGO: PublicKeyAlgorithm =
+    PublicKeyAlgorithm::Private(100);
+
+/// Associated data of the AES-GCM encryption of wrapped session keys.
+const SESSION_KEY_WRAP_AD: &[u8] = b"sq-dsm session key";
+
+/// Wraps a session key with the given DSM AES key.
+///
+/// The returned PKESK packet has a wildcard recipient and the
+/// [SESSION_KEY_WRAP_ALGO] algorithm.  Its ciphertext consists of the
+/// UUID of the AES key, and the IV, ciphertext and tag of the AES-GCM
+/// encryption of the algorithm identifier, the session key and the
+/// checksum, as in regular PKESK packets.
+pub fn wrap_session_key(
+    credentials: &Credentials,
+    key_name:    &str,
+    sym_algo:    SymmetricAlgorithm,
+    session_key: &SessionKey,
+) -> Result<PKESK3> {
+    let cli = credentials.dsm_client()?;
+    let descriptor = SobjectDescriptor::Name(key_name.to_string());
+    let sobject = cli.get_sobject(None, &descriptor)
+        .context(format!("could not get key {:?}", descriptor))?;
+    if sobject.obj_type != ObjectType::Aes {
+        return Err(anyhow::anyhow!("{} is not an AES key", key_name));
+    }
+    let kid = sobject.kid.context("no UUID returned from DSM")?;
+
+    let checksum = session_key
+        .iter()
+        .cloned()
+        .map(u16::from)
+        .fold(0u16, u16::wrapping_add);
+    let mut plain: Protected = vec![0; 1 + session_key.len() + 2].into();
+    plain[0] = sym_algo.into();
+    plain[1..1 + session_key.len()].copy_from_slice(session_key);
+    plain[1 + session_key.len()..].copy_from_slice(&checksum.to_be_bytes());
+
+    let req = EncryptRequest {
+        key:     Some(SobjectDescriptor::Kid(kid)),
+        alg:     Aes,
+        plain:   plain.to_vec().into(),
+        mode:    Some(CryptMode::Symmetric(CipherMode::Gcm)),
+        iv:      None,
+        ad:      Some(SESSION_KEY_WRAP_AD.to_vec().into()),
+        tag_len: Some(128),
+    };
+    let resp = cli.__encrypt(&req, "wrap session key")
+        .context("could not wrap session key")?;
+
+    let iv: Vec<u8> = resp.iv.context("no IV returned from DSM")?.into();
+    let tag: Vec<u8> = resp.tag.context("no tag returned from DSM")?.into();
+    let cipher: Vec<u8> = resp.cipher.into();
+
+    let esk = MpiCiphertext::Unknown {
+        mpis: vec![
+            wrapped_field(kid.as_bytes()),
+            wrapped_field(&iv),
+            wrapped_field(&cipher),
+            wrapped_field(&tag),
+        ].into_boxed_slice(),
+        rest: Vec::new().into_boxed_slice(),
+    };
+
+    PKESK3::new(KeyID::wildcard(), SESSION_KEY_WRAP_ALGO, esk)
+}
+
+/// Unwraps a session key wrapped by [wrap_session_key].
+///
+/// The AES key is identified by the UUID stored in the packet.
+pub fn unwrap_session_key(
+    credentials: &Credentials,
+    pkesk:       &PKESK3,
+) -> Result<(SymmetricAlgorithm, SessionKey)> {
+    let mpis = match pkesk.esk() {
+        MpiCiphertext::Unknown { mpis, .. }
+            if pkesk.pk_algo() == SESSION_KEY_WRAP_ALGO && mpis.len() == 4 =>
+            mpis,
+        _ => return Err(Error::msg("not a DSM-wrapped session key")),
+    };
+    let kid = Uuid::from_slice(unwrapped_field(&mpis[0])?)
+        .context("bad UUID in wrapped session key")?;
+
+    let req = DecryptRequest {
+        cipher: unwrapped_field(&mpis[2])?.to_vec().into(),
+        alg:    Some(Aes),
+        iv:     Some(unwrapped_field(&mpis[1])?.to_vec().into()),
+        key:    Some(SobjectDescriptor::Kid(kid)),
+        mode:   Some(CryptMode::Symmetric(CipherMode::Gcm)),
+        ad:     Some(SESSION_KEY_WRAP_AD.to_vec().into()),
+        tag:    Some(unwrapped_field(&mpis[3])?.to_vec().into()),
+    };
+    let plain: Protected = credentials.dsm_client()?
+        .__decrypt(&req, "unwrap session key")
+        .context("could not unwrap session key")?
+        .plain.to_vec().into();
+
+    if plain.len() < 3 {
+        return Err(Error::msg("wrapped session key too short"));
+    }
+    let sym_algo: SymmetricAlgorithm = plain[0].into();
+    let sk: SessionKey = plain[1..plain.len() - 2].into();
+    let checksum = sk
+        .iter()
+        .cloned()
+        .map(u16::from)
+        .fold(0u16, u16::wrapping_add);
+    if plain[plain.len() - 2..] != checksum.to_be_bytes() {
+        return Err(Error::msg("bad checksum of wrapped session key"));
+    }
+
+    Ok((sym_algo, sk))
+}
+
+// Fields of wrapped session keys are stored as MPIs.  A leading 0x01 octet
+// protects leading zeros, which MPIs do not preserve.
+fn wrapped_field(value: &[u8]) -> MPI {
+    let mut v = Vec::with_capacity(1 + value.len());
+    v.push(1);
+    v.extend_from_slice(value);
+    MPI::new(&v)
+}
+
+fn unwrapped_field(mpi: &MPI) -> Result<&[u8]> {
+    match mpi.value().split_first() {
+        Some((&1, value)) => Ok(value),
+        _ => Err(Error::msg("malformed wrapped session key")),
+    }
+}
+
 fn secret_packet_from_sobject(sobject: &Sobject) -> Result<Packet> {
     let md = KeyMetadata::from_sobject(sobject)?;
 
@@ -2003,7 +3028,7 @@ fn secret_packet_from_sobject(sobject: &Sobject) -> Result<Packet> {
         Some(f) if f.for_certification() => {
             KeyRole::Primary
         },
-        Some(f) if f.for_signing() => {
+        Some(f) if f.for_signing() || f.for_authentication() => {
             KeyRole::SigningSubkey
         },
         Some(f) if f.for_transport_encryption() || f.for_storage_encryption() => {
@@ -2376,3 +3401,61 @@ impl HumanReadable for KeyFlags {
         s.join(", ")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use sequoia_openpgp::parse::Parse;
+
+    #[test]
+    fn wrapped_field_roundtrip() {
+        for value in [&b""[..], &[0], &[0, 0, 7], &[0xff; 16]] {
+            let mpi = wrapped_field(value);
+            assert_eq!(unwrapped_field(&mpi).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn unwrapped_field_malformed() {
+        // Without the leading 0x01 octet, leading zeros are lost.
+        assert!(unwrapped_field(&MPI::new(&[0, 0, 7])).is_err());
+        assert!(unwrapped_field(&MPI::new(&[2, 7])).is_err());
+        assert!(unwrapped_field(&MPI::new(&[])).is_err());
+    }
+
+    #[test]
+    fn wrapped_session_key_serialization() {
+        let kid = Uuid::from_u128(0x0b24a6e2_6d4b_4b9c_8f5c_0a0e7d1a7f3e);
+        let iv = [0u8, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10];
+        let cipher = [0u8; 35];
+        let tag = [0u8, 0xff, 0, 0xff];
+        let esk = MpiCiphertext::Unknown {
+            mpis: vec![
+                wrapped_field(kid.as_bytes()),
+                wrapped_field(&iv),
+                wrapped_field(&cipher),
+                wrapped_field(&tag),
+            ].into_boxed_slice(),
+            rest: Vec::new().into_boxed_slice(),
+        };
+        let pkesk = PKESK3::new(KeyID::wildcard(), SESSION_KEY_WRAP_ALGO, esk)
+            .unwrap();
+
+        let bytes = Packet::from(pkesk).to_vec().unwrap();
+        let pkesk = match Packet::from_bytes(&bytes).unwrap() {
+            Packet::PKESK(pkesk) => pkesk,
+            p => panic!("expected a PKESK, got {:?}", p),
+        };
+        assert_eq!(pkesk.pk_algo(), SESSION_KEY_WRAP_ALGO);
+        let mpis = match pkesk.esk() {
+            MpiCiphertext::Unknown { mpis, .. } => mpis,
+            esk => panic!("unexpected ciphertext {:?}", esk),
+        };
+        assert_eq!(mpis.len(), 4);
+        assert_eq!(unwrapped_field(&mpis[0]).unwrap(), kid.as_bytes());
+        assert_eq!(unwrapped_field(&mpis[1]).unwrap(), &iv[..]);
+        assert_eq!(unwrapped_field(&mpis[2]).unwrap(), &cipher[..]);
+        assert_eq!(unwrapped_field(&mpis[3]).unwrap(), &tag[..]);
+    }
+}
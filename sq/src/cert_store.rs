@@ -0,0 +1,358 @@
+//! A local certificate store.
+//!
+//! Certificates are kept in the [shared OpenPGP certificate
+//! directory] format, so that the store can be shared with other
+//! OpenPGP implementations.  Every certificate is stored in its own
+//! file, named after the certificate's fingerprint: the first two hex
+//! digits name a subdirectory, the remaining ones name the file.
+//! Certificates are stored without secret key material.
+//!
+//! The format only supports lookups by fingerprint.  To look up
+//! certificates by key ID, subkey, user ID or email address, an
+//! [`Index`] is built from the store's content.
+//!
+//! [shared OpenPGP certificate directory]: https://datatracker.ietf.org/doc/draft-nwjw-openpgp-cert-d/
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::Context as _;
+use fs2::FileExt;
+use tempfile::NamedTempFile;
+
+use sequoia_openpgp as openpgp;
+use openpgp::{Cert, Fingerprint, KeyHandle, KeyID, Result};
+use openpgp::packet::UserID;
+use openpgp::parse::Parse;
+use openpgp::policy::Policy;
+use openpgp::serialize::Serialize;
+use openpgp::types::RevocationStatus;
+
+/// A certificate store in the shared certificate directory format.
+pub struct CertStore {
+    base: PathBuf,
+}
+
+/// What happened when a certificate was inserted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Insertion {
+    /// The certificate was not in the store.
+    New,
+    /// The certificate was merged with the stored one.
+    Updated,
+    /// The stored certificate already contained everything.
+    Unchanged,
+}
+
+impl CertStore {
+    /// Returns the platform's default location of the store.
+    pub fn default_location() -> Option<PathBuf> {
+        #[cfg(windows)]
+        let data = std::env::var_os("APPDATA").map(PathBuf::from);
+        #[cfg(target_os = "macos")]
+        let data = std::env::var_os("HOME")
+            .map(|h| PathBuf::from(h).join("Library")
+                 .join("Application Support"));
+        #[cfg(not(any(windows, target_os = "macos")))]
+        let data = std::env::var_os("XDG_DATA_HOME")
+            .filter(|d| ! d.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME")
+                     .map(|h| PathBuf::from(h).join(".local").join("share")));
+
+        data.map(|d| d.join("pgp.cert.d"))
+    }
+
+    /// Opens the store at `base`.
+    ///
+    /// The directory is only created when the first certificate is
+    /// inserted.  A missing directory is an empty store.
+    pub fn open<P: AsRef<Path>>(base: P) -> Self {
+        CertStore {
+            base: base.as_ref().into(),
+        }
+    }
+
+    /// Returns the location of the store.
+    pub fn base(&self) -> &Path {
+        &self.base
+    }
+
+    /// Returns the path of the file storing the given certificate.
+    fn path(&self, fp: &Fingerprint) -> PathBuf {
+        let hex = fp.to_hex().to_lowercase();
+        self.base.join(&hex[..2]).join(&hex[2..])
+    }
+
+    /// Returns the certificate with the given fingerprint, if any.
+    pub fn get(&self, fp: &Fingerprint) -> Result<Option<Cert>> {
+        let path = self.path(fp);
+        if ! path.exists() {
+            return Ok(None);
+        }
+        Cert::from_file(&path)
+            .map(Some)
+            .with_context(|| format!("Failed to read {:?}", path))
+    }
+
+    /// Inserts `cert` into the store.
+    ///
+    /// If the store already contains the certificate, the two are
+    /// merged using [`Cert::merge_public`].  Secret key material is
+    /// never stored.  Returns the stored certificate.
+    pub fn insert(&self, cert: Cert) -> Result<(Cert, Insertion)> {
+        // Hold the store's write lock while reading and replacing the
+        // stored version, so that concurrent updates are not lost.
+        let _lock = self.lock()?;
+
+        let fp = cert.fingerprint();
+        let (cert, insertion) = match self.get(&fp)? {
+            None => (cert.strip_secret_key_material(), Insertion::New),
+            Some(stored) => {
+                let merged = stored.clone().merge_public(cert)?;
+                if merged == stored {
+                    return Ok((stored, Insertion::Unchanged));
+                }
+                (merged, Insertion::Updated)
+            },
+        };
+
+        // Write to a temporary file first, and atomically replace
+        // the stored version, so that readers never see a partially
+        // written certificate.
+        let path = self.path(&fp);
+        let dir = path.parent().expect("has a parent");
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {:?}", dir))?;
+        let mut tmp = NamedTempFile::new_in(dir)?;
+        cert.serialize(&mut tmp)?;
+        tmp.persist(&path)
+            .map_err(|e| e.error)
+            .with_context(|| format!("Failed to write {:?}", path))?;
+
+        Ok((cert, insertion))
+    }
+
+    /// Takes the store's write lock, creating the store if need be.
+    ///
+    /// The lock is released when the returned file is closed.
+    fn lock(&self) -> Result<File> {
+        fs::create_dir_all(&self.base)
+            .with_context(|| format!("Failed to create {:?}", self.base))?;
+        let path = self.base.join("writelock");
+        let lock = fs::OpenOptions::new()
+            .read(true).write(true).create(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open {:?}", path))?;
+        lock.lock_exclusive()
+            .with_context(|| format!("Failed to lock {:?}", path))?;
+        Ok(lock)
+    }
+
+    /// Returns all certificates in the store.
+    ///
+    /// Files that do not follow the naming scheme are ignored.
+    /// Certificates that cannot be parsed are skipped with a warning.
+    pub fn certs(&self) -> Result<Vec<Cert>> {
+        let mut certs = Vec::new();
+        let dirs = match fs::read_dir(&self.base) {
+            Ok(dirs) => dirs,
+            Err(e) if e.kind() == io::ErrorKind::NotFound =>
+                return Ok(certs),
+            Err(e) => return Err(e)
+                .with_context(|| format!("Failed to read {:?}", self.base)),
+        };
+
+        for dir in dirs {
+            let dir = dir?;
+            match dir.file_name().to_str() {
+                Some(p) if p.len() == 2 && is_lower_hex(p) => (),
+                _ => continue,
+            }
+            if ! dir.file_type()?.is_dir() {
+                continue;
+            }
+
+            for file in fs::read_dir(dir.path())? {
+                let file = file?;
+                match file.file_name().to_str() {
+                    Some(f) if f.len() == 38 && is_lower_hex(f) => (),
+                    _ => continue,
+                }
+
+                match Cert::from_file(file.path()) {
+                    Ok(cert) => certs.push(cert),
+                    Err(e) => eprintln!("Warning: Skipping {:?}: {}",
+                                        file.path(), e),
+                }
+            }
+        }
+
+        Ok(certs)
+    }
+
+    /// Builds an index over the store's content.
+    ///
+    /// See [`Index::new`] for the role of `policy`.
+    pub fn index(&self, policy: &dyn Policy) -> Result<Index> {
+        Ok(Index::new(policy, self.certs()?))
+    }
+
+    /// Returns the certificates matching `query`.
+    pub fn lookup(&self, policy: &dyn Policy, query: &Query)
+                  -> Result<Vec<Cert>> {
+        // Certificate fingerprints can be looked up directly.
+        if let Query::Key(KeyHandle::Fingerprint(fp)) = query {
+            if let Some(cert) = self.get(fp)? {
+                return Ok(vec![cert]);
+            }
+        }
+
+        Ok(self.index(policy)?.lookup(query).into_iter().cloned().collect())
+    }
+}
+
+/// Returns whether `s` consists of lowercase hex digits.
+fn is_lower_hex(s: &str) -> bool {
+    s.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f'))
+}
+
+/// A query for certificates.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    /// Matches certificates with a primary key or subkey with the
+    /// given fingerprint or key ID.
+    Key(KeyHandle),
+    /// Matches certificates with the given user ID.
+    UserID(String),
+    /// Matches certificates with a user ID containing the given
+    /// email address.
+    Email(String),
+}
+
+impl FromStr for Query {
+    type Err = anyhow::Error;
+
+    /// Parses a query.
+    ///
+    /// Fingerprints and key IDs are recognized by their length, and
+    /// may be prefixed with `0x`.  Email addresses are either
+    /// enclosed in angle brackets, or contain an `@` and no
+    /// whitespace.  Everything else is a user ID.
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(anyhow::anyhow!("Empty query"));
+        }
+
+        let hex = s.strip_prefix("0x").unwrap_or(s);
+        let digits = hex.chars().filter(|c| ! c.is_whitespace()).count();
+        if (digits == 16 || digits == 40)
+            && hex.chars().all(|c| c.is_ascii_hexdigit() || c.is_whitespace())
+        {
+            return Ok(Query::Key(hex.parse()?));
+        }
+
+        if let Some(email) = s.strip_prefix('<').and_then(|s| s.strip_suffix('>')) {
+            return Ok(Query::Email(email.to_lowercase()));
+        }
+
+        if s.contains('@') && ! s.contains(char::is_whitespace) {
+            return Ok(Query::Email(s.to_lowercase()));
+        }
+
+        Ok(Query::UserID(s.into()))
+    }
+}
+
+/// An index over a set of certificates.
+pub struct Index {
+    certs: Vec<Cert>,
+    by_key: BTreeMap<KeyID, Vec<usize>>,
+    by_userid: BTreeMap<String, Vec<usize>>,
+    by_email: BTreeMap<String, Vec<usize>>,
+}
+
+impl Index {
+    /// Indexes the given certificates.
+    ///
+    /// Only User IDs that are bound to the certificate under `policy`
+    /// and not revoked are indexed, so that a certificate cannot be
+    /// made to match a query by attaching an unbound User ID to it.
+    pub fn new(policy: &dyn Policy, certs: Vec<Cert>) -> Self {
+        let mut by_key: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        let mut by_userid: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        let mut by_email: BTreeMap<_, Vec<_>> = BTreeMap::new();
+
+        for (i, cert) in certs.iter().enumerate() {
+            for ka in cert.keys() {
+                by_key.entry(ka.key().keyid()).or_default().push(i);
+            }
+
+            let vc = match cert.with_policy(policy, None) {
+                Ok(vc) => vc,
+                Err(_) => continue,
+            };
+            for ua in vc.userids() {
+                if let RevocationStatus::Revoked(_) = ua.revocation_status() {
+                    continue;
+                }
+                let uid: &UserID = ua.userid();
+                by_userid.entry(String::from_utf8_lossy(uid.value()).into())
+                    .or_default().push(i);
+                if let Ok(Some(email)) = uid.email_normalized() {
+                    let entry = by_email.entry(email).or_default();
+                    if entry.last() != Some(&i) {
+                        entry.push(i);
+                    }
+                }
+            }
+        }
+
+        Index { certs, by_key, by_userid, by_email }
+    }
+
+    /// Returns the certificates matching `query`.
+    pub fn lookup(&self, query: &Query) -> Vec<&Cert> {
+        let hits = match query {
+            Query::Key(handle) => {
+                return self.by_key.get(&KeyID::from(handle))
+                    .into_iter().flatten()
+                    .map(|&i| &self.certs[i])
+                    // Key IDs may collide, so check the full
+                    // fingerprint, if we have one.
+                    .filter(|cert| cert.keys()
+                            .any(|ka| ka.key().key_handle().aliases(handle)))
+                    .collect();
+            },
+            Query::UserID(userid) => self.by_userid.get(userid),
+            Query::Email(email) => self.by_email.get(email),
+        };
+
+        hits.into_iter().flatten().map(|&i| &self.certs[i]).collect()
+    }
+
+    /// Returns the certificates that have a key with the given
+    /// handle.
+    pub fn lookup_by_key(&self, handle: &KeyHandle) -> Vec<&Cert> {
+        self.lookup(&Query::Key(handle.clone()))
+    }
+}
+
+/// Merges duplicate certificates.
+pub fn merge(certs: Vec<Cert>) -> Result<Vec<Cert>> {
+    let mut merged: Vec<Cert> = Vec::with_capacity(certs.len());
+    for cert in certs {
+        match merged.iter().position(|c| c.fingerprint() == cert.fingerprint()) {
+            Some(i) => {
+                let c = merged.swap_remove(i);
+                merged.push(c.merge_public(cert)?);
+            },
+            None => merged.push(cert),
+        }
+    }
+    Ok(merged)
+}
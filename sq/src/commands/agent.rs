@@ -0,0 +1,200 @@
+//! Agents serving keys stored in Fortanix DSM.
+
+use std::collections::HashMap;
+use std::fs;
+use std::os::unix::net::UnixListener;
+use std::path::Path;
+
+use anyhow::Context as _;
+use clap::ArgMatches;
+
+use sequoia_openpgp as openpgp;
+use crate::openpgp::{Fingerprint, Result};
+use crate::openpgp::crypto::{Decryptor, Signer, mpi};
+use crate::openpgp::packet::{Key, key};
+use crate::openpgp::types::{Curve, HashAlgorithm};
+
+use sequoia_ipc::Keygrip;
+use sequoia_ipc::gnupg::server::{Backend, Plaintext, Server};
+use sequoia_ipc::ssh;
+
+use openpgp_dsm as dsm;
+use dsm::DsmAgent;
+
+use crate::Config;
+use crate::secrets::dsm_auth;
+
+/// Serves the keys of one or more DSM PGP keys via the gpg-agent
+/// protocol.
+struct DsmKeys(HashMap<Keygrip, DsmAgent>);
+
+impl DsmKeys {
+    fn new(credentials: &dsm::Credentials, names: &[&str]) -> Result<Self> {
+        let mut keys = HashMap::new();
+        for name in names {
+            let mut agents = vec![
+                DsmAgent::new_certifier(credentials.clone(), name)?,
+                DsmAgent::new_signer(credentials.clone(), name)?,
+            ];
+            agents.append(
+                &mut DsmAgent::new_decryptors(credentials.clone(), name)?);
+
+            for agent in agents {
+                let grip = Keygrip::of(Signer::public(&agent).mpis())?;
+                eprintln!("Serving {} ({}) as {}",
+                          Signer::public(&agent).keyid(), name, grip);
+                // The primary key may also be the signing key, keep
+                // the first agent.
+                keys.entry(grip).or_insert(agent);
+            }
+        }
+        Ok(DsmKeys(keys))
+    }
+
+    fn get(&mut self, grip: &Keygrip) -> Result<&mut DsmAgent> {
+        self.0.get_mut(grip)
+            .ok_or_else(|| anyhow::anyhow!("No key with keygrip {}", grip))
+    }
+}
+
+impl Backend for DsmKeys {
+    fn keygrips(&self) -> Vec<Keygrip> {
+        self.0.keys().cloned().collect()
+    }
+
+    fn public_key(&self, grip: &Keygrip)
+                  -> Option<Key<key::PublicParts, key::UnspecifiedRole>> {
+        self.0.get(grip).map(|agent| Signer::public(agent).clone())
+    }
+
+    fn sign(&mut self, grip: &Keygrip, hash_algo: HashAlgorithm,
+            digest: &[u8])
+            -> Result<mpi::Signature> {
+        self.get(grip)?.sign(hash_algo, digest)
+    }
+
+    fn decrypt(&mut self, grip: &Keygrip, ciphertext: &mpi::Ciphertext)
+               -> Result<Plaintext> {
+        let agent = self.get(grip)?;
+        let curve = match Decryptor::public(agent).mpis() {
+            mpi::PublicKey::ECDH { curve, .. } => Some(curve.clone()),
+            _ => None,
+        };
+
+        if let Some(curve) = curve {
+            // Clients expect the shared point, of which only the X
+            // coordinate enters the KDF.  DSM does not give us the Y
+            // coordinate, so we zero it.
+            let x = agent.ecdh_shared_secret(ciphertext)?;
+            let mut point = Vec::with_capacity(1 + 2 * x.len());
+            if curve == Curve::Cv25519 {
+                point.push(0x40);
+                point.extend_from_slice(&x);
+            } else {
+                point.push(0x04);
+                point.extend_from_slice(&x);
+                point.resize(1 + 2 * x.len(), 0);
+            }
+            Ok(Plaintext::SharedPoint(point.into()))
+        } else {
+            Ok(Plaintext::Unpadded(agent.decrypt(ciphertext, None)?))
+        }
+    }
+}
+
+/// Serves the authentication keys of one or more DSM PGP keys via the
+/// ssh-agent protocol.
+struct DsmAuthKeys(HashMap<Fingerprint, (DsmAgent, String)>);
+
+impl DsmAuthKeys {
+    fn new(credentials: &dsm::Credentials, names: &[&str]) -> Result<Self> {
+        let mut keys = HashMap::new();
+        for name in names {
+            let agents =
+                DsmAgent::new_authenticators(credentials.clone(), name)?;
+            if agents.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "DSM key {:?} has no authentication key", name));
+            }
+
+            for agent in agents {
+                let public = Signer::public(&agent);
+                // Fail early on key types that SSH does not support.
+                ssh::public_key_blob(public.mpis())
+                    .context(format!("Cannot serve {} ({}) via SSH",
+                                     public.keyid(), name))?;
+                eprintln!("Serving {} ({})", public.keyid(), name);
+                keys.insert(public.fingerprint(), (agent, name.to_string()));
+            }
+        }
+        Ok(DsmAuthKeys(keys))
+    }
+}
+
+impl ssh::Backend for DsmAuthKeys {
+    fn identities(&self)
+                  -> Vec<(Key<key::PublicParts, key::UnspecifiedRole>, String)>
+    {
+        self.0.values()
+            .map(|(agent, name)| (Signer::public(agent).clone(), name.clone()))
+            .collect()
+    }
+
+    fn sign(&mut self, fingerprint: &Fingerprint, hash_algo: HashAlgorithm,
+            digest: &[u8])
+            -> Result<mpi::Signature> {
+        let (agent, _) = self.0.get_mut(fingerprint)
+            .ok_or_else(|| anyhow::anyhow!("No key {}", fingerprint))?;
+        agent.sign(hash_algo, digest)
+    }
+}
+
+/// Serves DSM keys via the gpg-agent protocol until interrupted.
+pub fn gpg_agent(config: Config, m: &ArgMatches) -> Result<()> {
+    let dsm_auth = credentials(m)?;
+    let names: Vec<&str> = m.values_of("dsm-key")
+        .expect("required").collect();
+    let socket = Path::new(m.value_of("socket").expect("required"));
+
+    let backend = DsmKeys::new(&dsm_auth, &names)?;
+    let listener = bind(&config, socket)?;
+
+    Server::new(backend).serve(listener)
+}
+
+/// Serves DSM authentication keys via the ssh-agent protocol until
+/// interrupted.
+pub fn ssh_agent(config: Config, m: &ArgMatches) -> Result<()> {
+    let dsm_auth = credentials(m)?;
+    let names: Vec<&str> = m.values_of("dsm-key")
+        .expect("required").collect();
+    let socket = Path::new(m.value_of("socket").expect("required"));
+
+    let backend = DsmAuthKeys::new(&dsm_auth, &names)?;
+    let listener = bind(&config, socket)?;
+
+    ssh::Server::new(backend).serve(listener)
+}
+
+fn credentials(m: &ArgMatches) -> Result<dsm::Credentials> {
+    let dsm_secret = dsm_auth(m)?;
+    dsm::Credentials::new(dsm_secret)
+}
+
+/// Binds to `socket`, removing an existing file if `--force` is given.
+fn bind(config: &Config, socket: &Path) -> Result<UnixListener> {
+    if socket.exists() {
+        if config.force {
+            fs::remove_file(socket)
+                .context(format!("Failed to remove {:?}", socket))?;
+        } else {
+            return Err(anyhow::anyhow!(
+                "File {:?} exists, use \"sq --force ...\" to overwrite",
+                socket));
+        }
+    }
+    let listener = UnixListener::bind(socket)
+        .context(format!("Failed to bind to {:?}", socket))?;
+    eprintln!("Listening on {}", socket.display());
+    Ok(listener)
+}
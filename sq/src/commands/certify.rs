@@ -3,6 +3,7 @@ use std::time::{SystemTime, Duration};
 use sequoia_openpgp as openpgp;
 use openpgp::Result;
 use openpgp::cert::prelude::*;
+use openpgp::crypto::Signer;
 use openpgp::packet::prelude::*;
 use openpgp::packet::signature::subpacket::NotationDataFlags;
 use openpgp::parse::Parse;
@@ -10,17 +11,18 @@ use openpgp::serialize::Serialize;
 use openpgp::types::SignatureType;
 
 use crate::Config;
+use crate::secrets::{GpgAgentKey, PreSecret};
 use crate::parse_duration;
 use crate::SECONDS_IN_YEAR;
 
 pub fn certify(config: Config, m: &clap::ArgMatches)
     -> Result<()>
 {
-    let certifier = m.value_of("certifier").unwrap();
     let cert = m.value_of("certificate").unwrap();
     let userid = m.value_of("userid").unwrap();
 
-    let certifier = Cert::from_file(certifier)?;
+    let certifier = crate::load_secrets(m, "certifier")?.pop()
+        .expect("certifier is required");
     let cert = Cert::from_file(cert)?;
     let vc = cert.with_policy(&config.policy, None)?;
 
@@ -141,12 +143,19 @@ pub fn certify(config: Config, m: &clap::ArgMatches)
 
 
     // Sign it.
-    let mut signer = certifier.primary_key().key().clone()
-        .parts_into_secret()?.into_keypair()?;
+    let mut signer: Box<dyn Signer> = match certifier {
+        PreSecret::InMemory(tsk) =>
+            Box::new(tsk.primary_key().key().clone()
+                     .parts_into_secret()?.into_keypair()?),
+        PreSecret::Gpg(context, cert) =>
+            Box::new(GpgAgentKey::new(
+                context, cert.primary_key().key().clone().role_into_unspecified())),
+        PreSecret::Dsm(..) => unreachable!("not loaded from files"),
+    };
 
     let certification = builder
         .sign_userid_binding(
-            &mut signer,
+            &mut *signer,
             cert.primary_key().component(),
             userid)?;
     let cert = cert.insert_packets(certification.clone())?;
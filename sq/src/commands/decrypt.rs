@@ -1,4 +1,5 @@
 use anyhow::Context as _;
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::io;
 
@@ -19,7 +20,7 @@ use crate::openpgp::parse::{
 use crate::openpgp::parse::stream::{
     VerificationHelper, DecryptionHelper, DecryptorBuilder, MessageStructure,
 };
-use openpgp_dsm::{Credentials, DsmAgent};
+use openpgp_dsm::{self as dsm, Credentials, DsmAgent};
 
 use crate::{
     Config,
@@ -28,7 +29,8 @@ use crate::{
         VHelper,
     },
 };
-use crate::secrets::PreSecret;
+use crate::output;
+use crate::secrets::{GpgAgentKey, PreSecret};
 
 trait PrivateKey {
     fn get_unlocked(&self) -> Option<Box<dyn Decryptor>>;
@@ -91,6 +93,17 @@ impl PrivateKey for RemotePrivateKey {
     }
 }
 
+impl PrivateKey for GpgAgentKey {
+    fn get_unlocked(&self) -> Option<Box<dyn Decryptor>> {
+        // gpg-agent asks for the password, if necessary.
+        Some(Box::new(self.clone()))
+    }
+
+    fn unlock(&mut self, _: &Password) -> Result<Box<dyn Decryptor>> {
+        Ok(Box::new(self.clone()))
+    }
+}
+
 struct Helper<'a> {
     vhelper: VHelper<'a>,
     secret_keys: HashMap<KeyID, Box<dyn PrivateKey>>,
@@ -98,7 +111,10 @@ struct Helper<'a> {
     key_hints: HashMap<KeyID, String>,
     dump_session_key: bool,
     dumper: Option<PacketDumper>,
-    dsm_keys_presecrets: Vec<(Credentials, String)>
+    dsm_keys_presecrets: Vec<(Credentials, String)>,
+    dsm_index: Option<dsm::RecipientIndex>,
+    /// The key that decrypted the session key, if any.
+    decryption_key: RefCell<Option<Fingerprint>>,
 }
 
 impl<'a> Helper<'a> {
@@ -116,6 +132,16 @@ impl<'a> Helper<'a> {
                 PreSecret::Dsm(credentials, name) => {
                     dsm_keys_presecrets.push((credentials, name));
                 }
+                PreSecret::Gpg(context, cert) => {
+                    for ka in cert.keys().with_policy(&config.policy, None)
+                        .for_transport_encryption().for_storage_encryption()
+                    {
+                        let id: KeyID = ka.key().fingerprint().into();
+                        keys.insert(id.clone(), Box::new(GpgAgentKey::new(
+                            context.clone(), ka.key().clone())));
+                        identities.insert(id, cert.fingerprint());
+                    }
+                }
                 PreSecret::InMemory(tsk) => {
                     let hint = match tsk.with_policy(&config.policy, None)
                         .and_then(|valid_cert| valid_cert.primary_userid()).ok()
@@ -162,6 +188,8 @@ impl<'a> Helper<'a> {
                 None
             },
             dsm_keys_presecrets,
+            dsm_index: None,
+            decryption_key: RefCell::new(None),
         }
     }
 
@@ -184,6 +212,7 @@ impl<'a> Helper<'a> {
                 if self.dump_session_key {
                     eprintln!("Session key: {}", hex::encode(&sk));
                 }
+                self.decryption_key.replace(Some(keypair.public().fingerprint()));
                 Some(self.key_identities.get(&keyid).cloned())
             },
             None => None,
@@ -206,7 +235,14 @@ impl<'a> VerificationHelper for Helper<'a> {
         self.vhelper.get_certs(ids)
     }
     fn check(&mut self, structure: MessageStructure) -> Result<()> {
-        self.vhelper.check(structure)
+        let result = self.vhelper.check_structure(structure);
+        if self.vhelper.json() {
+            let report = &mut self.vhelper.report;
+            report.decryption_key = self.decryption_key.borrow().as_ref()
+                .map(|fp| fp.to_hex());
+            output::emit(&mut io::stderr(), report)?;
+        }
+        result
     }
 }
 
@@ -217,8 +253,41 @@ impl<'a> DecryptionHelper for Helper<'a> {
                   mut decrypt: D) -> openpgp::Result<Option<Fingerprint>>
         where D: FnMut(SymmetricAlgorithm, &SessionKey) -> bool
     {
+        self.vhelper.report.recipients = pkesks.iter()
+            .map(|pkesk| pkesk.recipient().to_hex())
+            .collect();
+        self.vhelper.report.passwords = skesks.len();
+
+        // Session keys wrapped by a DSM AES key name the key, so the
+        // credentials suffice.
+        for pkesk in pkesks.iter()
+            .filter(|p| p.pk_algo() == dsm::SESSION_KEY_WRAP_ALGO)
+        {
+            let pkesk = if let PKESK::V3(pkesk) = pkesk {
+                pkesk
+            } else {
+                continue;
+            };
+            let credentials = self.dsm_keys_presecrets.iter()
+                .map(|(credentials, _)| credentials)
+                .chain(self.dsm_index.iter().map(|i| i.credentials()));
+            for credentials in credentials {
+                match dsm::unwrap_session_key(credentials, pkesk) {
+                    Ok((algo, sk)) => if decrypt(algo, &sk) {
+                        if self.dump_session_key {
+                            eprintln!("Session key: {}", hex::encode(&sk));
+                        }
+                        return Ok(None);
+                    },
+                    Err(e) => eprintln!("Could not unwrap session key: {:#}", e),
+                }
+            }
+        }
+
         for dsm_key in &self.dsm_keys_presecrets {
-            for pkesk in pkesks {
+            for pkesk in pkesks.iter()
+                .filter(|p| p.pk_algo() != dsm::SESSION_KEY_WRAP_ALGO)
+            {
                 for decryptor in DsmAgent::new_decryptors(dsm_key.0.clone(), &dsm_key.1)? {
                     // TODO: This could be parallelized
                     if let Some(fp) = self.try_decrypt(pkesk, sym_algo, Box::new(decryptor),
@@ -229,6 +298,43 @@ impl<'a> DecryptionHelper for Helper<'a> {
             }
         }
 
+        // Look for the recipients among the keys accessible in DSM.
+        // First, try all recipients found in the cached index.  If
+        // none of them works, rebuild the index once, and try the
+        // recipients that were not found before.  Failing to reach
+        // DSM is not fatal, we may still have a local key.
+        if self.dsm_index.is_some() {
+            let mut tried: Vec<&KeyID> = Vec::new();
+            for &refresh in &[false, true] {
+                let index = self.dsm_index.as_mut().expect("checked");
+                if refresh {
+                    match index.refresh() {
+                        Ok(true) => (),
+                        Ok(false) => break,
+                        Err(e) => {
+                            eprintln!("Could not look up recipients in DSM: \
+                                       {:#}", e);
+                            break;
+                        },
+                    }
+                }
+
+                let decryptors: Vec<_> = pkesks.iter()
+                    .filter(|pkesk| ! tried.contains(&pkesk.recipient()))
+                    .filter_map(|pkesk| index.decryptor(pkesk.recipient())
+                                .map(|d| (pkesk, d)))
+                    .collect();
+                for (pkesk, decryptor) in decryptors {
+                    tried.push(pkesk.recipient());
+                    if let Some(fp) = self.try_decrypt(
+                        pkesk, sym_algo, Box::new(decryptor), &mut decrypt)
+                    {
+                        return Ok(fp);
+                    }
+                }
+            }
+        }
+
         // First, we try those keys that we can use without prompting
         // for a password.
         for pkesk in pkesks {
@@ -366,11 +472,13 @@ pub fn decrypt(config: Config,
                output: &mut dyn io::Write,
                signatures: usize, certs: Vec<Cert>,
                secrets: Vec<PreSecret>,
+               dsm_auto: Option<Credentials>,
                dump_session_key: bool,
                dump: bool, hex: bool)
                -> Result<()> {
-    let helper = Helper::new(&config, private_key_store, signatures, certs,
-                             secrets, dump_session_key, dump || hex);
+    let mut helper = Helper::new(&config, private_key_store, signatures, certs,
+                                 secrets, dump_session_key, dump || hex);
+    helper.dsm_index = dsm_auto.map(dsm::RecipientIndex::new);
     let mut decryptor = DecryptorBuilder::from_reader(input)?
         .mapping(hex)
         .with_policy(&config.policy, None, helper)
@@ -386,6 +494,54 @@ pub fn decrypt(config: Config,
     Ok(())
 }
 
+/// Decrypts encryption containers for commands that walk the packet
+/// sequence themselves, like "sq packet decrypt" and "sq packet dump".
+pub struct ContainerDecryptor<'a> {
+    helper: Helper<'a>,
+}
+
+impl<'a> ContainerDecryptor<'a> {
+    pub fn new(config: &Config<'a>, secrets: Vec<PreSecret>,
+               dump_session_key: bool)
+               -> Self
+    {
+        ContainerDecryptor {
+            helper: Helper::new(config, None, 0, Vec::new(), secrets,
+                                dump_session_key, false),
+        }
+    }
+
+    /// Decrypts the SEIP or AED container `pp` using the session key
+    /// protected by one of `pkesks` or `skesks`.
+    ///
+    /// Returns the symmetric algorithm and the session key.
+    pub fn decrypt(&mut self, pp: &mut PacketParser,
+                   pkesks: &[PKESK], skesks: &[SKESK])
+                   -> Result<(SymmetricAlgorithm, SessionKey)>
+    {
+        let sym_algo_hint = if let Packet::AED(ref aed) = pp.packet {
+            Some(aed.symmetric_algo())
+        } else {
+            None
+        };
+
+        let mut session_key = None;
+        {
+            let decrypt = |algo, secret: &SessionKey| {
+                if pp.decrypt(algo, secret).is_ok() {
+                    session_key = Some((algo, secret.clone()));
+                    true
+                } else {
+                    false
+                }
+            };
+            self.helper.decrypt(pkesks, skesks, sym_algo_hint, decrypt)?;
+        }
+        session_key.ok_or_else(|| openpgp::Error::MissingSessionKey(
+            "No session key".into()).into())
+    }
+}
+
 pub fn decrypt_unwrap(config: Config,
                       input: &mut (dyn io::Read + Sync + Send),
                       output: &mut dyn io::Write,
@@ -393,35 +549,17 @@ pub fn decrypt_unwrap(config: Config,
                       dump_session_key: bool)
                       -> Result<()>
 {
-    let mut helper = Helper::new(&config, None, 0, Vec::new(), secrets,
-                                 dump_session_key, false);
+    let mut decryptor =
+        ContainerDecryptor::new(&config, secrets, dump_session_key);
 
     let mut ppr = PacketParser::from_reader(input)?;
 
     let mut pkesks: Vec<packet::PKESK> = Vec::new();
     let mut skesks: Vec<packet::SKESK> = Vec::new();
     while let PacketParserResult::Some(mut pp) = ppr {
-        let sym_algo_hint = if let Packet::AED(ref aed) = pp.packet {
-            Some(aed.symmetric_algo())
-        } else {
-            None
-        };
-
         match pp.packet {
             Packet::SEIP(_) | Packet::AED(_) => {
-                {
-                    let decrypt = |algo, secret: &SessionKey| {
-                        pp.decrypt(algo, secret).is_ok()
-                    };
-                    helper.decrypt(&pkesks[..], &skesks[..], sym_algo_hint,
-                                   decrypt)?;
-                }
-                if pp.encrypted() {
-                    return Err(
-                        openpgp::Error::MissingSessionKey(
-                            "No session key".into()).into());
-                }
-
+                decryptor.decrypt(&mut pp, &pkesks[..], &skesks[..])?;
                 io::copy(&mut pp, output)?;
                 return Ok(());
             },
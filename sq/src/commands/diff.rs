@@ -0,0 +1,315 @@
+//! Compares certificates at the component level.
+//!
+//! `sq keyring diff` matches the certificates in two keyrings by
+//! fingerprint, and their components by identity, i.e. the key's
+//! fingerprint, the User ID, or the user attribute's content.  The
+//! signatures on each component are compared ignoring the unhashed
+//! subpacket area, which keyservers and implementations are free to
+//! modify.
+
+use std::io;
+use std::time::{Duration, SystemTime};
+
+use anyhow::Context as _;
+
+use sequoia_openpgp as openpgp;
+use openpgp::{Cert, Fingerprint, Result};
+use openpgp::cert::CertParser;
+use openpgp::cert::bundle::ComponentBundle;
+use openpgp::crypto::hash::Digest;
+use openpgp::fmt::hex;
+use openpgp::packet::Signature;
+use openpgp::parse::Parse;
+use openpgp::policy::{HashAlgoSecurity, Policy};
+use openpgp::types::HashAlgorithm;
+
+use crate::{
+    Config,
+    open_or_stdin,
+};
+use crate::output::{self, OutputFormat};
+
+/// Identifies a component across versions of a certificate.
+#[derive(PartialEq)]
+enum Id {
+    Primary,
+    UserID(Vec<u8>),
+    UserAttribute(Vec<u8>),
+    Subkey(Fingerprint),
+}
+
+/// A component and its signatures.
+struct Component {
+    id: Id,
+    /// How the self-signatures are checked by the policy.
+    sec: HashAlgoSecurity,
+    /// The signatures and their kinds.
+    sigs: Vec<(&'static str, Signature)>,
+}
+
+impl Component {
+    fn new<C>(id: Id, sec: HashAlgoSecurity, bundle: &ComponentBundle<C>)
+              -> Self
+    {
+        let sigs = bundle.self_signatures().iter()
+            .map(|s| ("self-signature", s.clone()))
+            .chain(bundle.certifications().iter()
+                   .map(|s| ("certification", s.clone())))
+            .chain(bundle.self_revocations().iter()
+                   .map(|s| ("self-revocation", s.clone())))
+            .chain(bundle.other_revocations().iter()
+                   .map(|s| ("third-party-revocation", s.clone())))
+            .collect();
+        Component { id, sec, sigs }
+    }
+}
+
+/// Returns the components of `cert`.
+fn components(cert: &Cert) -> Vec<Component> {
+    let mut components = Vec::new();
+
+    let primary = cert.primary_key();
+    components.push(Component::new(
+        Id::Primary, primary.key().hash_algo_security(), primary.bundle()));
+    for ua in cert.userids() {
+        components.push(Component::new(
+            Id::UserID(ua.userid().value().to_vec()),
+            ua.userid().hash_algo_security(), ua.bundle()));
+    }
+    for ua in cert.user_attributes() {
+        components.push(Component::new(
+            Id::UserAttribute(ua.user_attribute().value().to_vec()),
+            ua.user_attribute().hash_algo_security(), ua.bundle()));
+    }
+    for ka in cert.keys().subkeys() {
+        components.push(Component::new(
+            Id::Subkey(ka.fingerprint()),
+            ka.key().hash_algo_security(), ka.bundle()));
+    }
+
+    components
+}
+
+/// Reads the certificates in `input`, merging duplicates.
+fn read(input: &str) -> Result<Vec<Cert>> {
+    let mut certs: Vec<Cert> = Vec::new();
+    let mut input = open_or_stdin(Some(input))?;
+    for cert in CertParser::from_reader(&mut input)? {
+        let cert = cert.context("Malformed certificate in keyring")?;
+        match certs.iter().position(|c| c.fingerprint() == cert.fingerprint())
+        {
+            Some(i) => {
+                let c = certs.remove(i);
+                certs.insert(i, c.merge_public(cert)?);
+            },
+            None => certs.push(cert),
+        }
+    }
+    Ok(certs)
+}
+
+pub fn diff(config: Config, m: &clap::ArgMatches) -> Result<()> {
+    let a = m.value_of("a").expect("required");
+    let b = m.value_of("b").expect("required");
+    let a = read(a).context(format!("Failed to read {:?}", a))?;
+    let b = read(b).context(format!("Failed to read {:?}", b))?;
+
+    let mut diffs = Vec::new();
+    for cert in &a {
+        let other = b.iter().find(|c| c.fingerprint() == cert.fingerprint());
+        if let Some(diff) = diff_certs(&config.policy, Some(cert), other) {
+            diffs.push(diff);
+        }
+    }
+    for cert in &b {
+        if ! a.iter().any(|c| c.fingerprint() == cert.fingerprint()) {
+            if let Some(diff) = diff_certs(&config.policy, None, Some(cert)) {
+                diffs.push(diff);
+            }
+        }
+    }
+
+    if config.output_format == OutputFormat::Json {
+        output::emit(&mut io::stdout(),
+                     &output::KeyringDiff { certificates: diffs })?;
+        return Ok(());
+    }
+
+    for diff in &diffs {
+        print(diff);
+    }
+    if diffs.is_empty() {
+        eprintln!("No differences.");
+    } else {
+        eprintln!("{} certificates differ.", diffs.len());
+    }
+    Ok(())
+}
+
+/// Returns the differences between two versions of a certificate,
+/// if any.
+fn diff_certs(policy: &dyn Policy, a: Option<&Cert>, b: Option<&Cert>)
+              -> Option<output::CertDiff>
+{
+    let fingerprint = a.or(b).expect("one is given").fingerprint();
+    let a = a.map(components).unwrap_or_default();
+    let b = b.map(components).unwrap_or_default();
+
+    let mut diffs = Vec::new();
+    for c in &a {
+        let other = b.iter().find(|o| o.id == c.id);
+        if let Some(diff) = diff_components(policy, &fingerprint,
+                                            Some(c), other) {
+            diffs.push(diff);
+        }
+    }
+    for c in &b {
+        if ! a.iter().any(|o| o.id == c.id) {
+            if let Some(diff) = diff_components(policy, &fingerprint,
+                                                None, Some(c)) {
+                diffs.push(diff);
+            }
+        }
+    }
+
+    if diffs.is_empty() {
+        return None;
+    }
+
+    Some(output::CertDiff {
+        fingerprint: fingerprint.to_hex(),
+        status: status(a.is_empty(), b.is_empty()),
+        components: diffs,
+    })
+}
+
+/// Returns the differences between two versions of a component, if
+/// any.
+fn diff_components(policy: &dyn Policy, cert: &Fingerprint,
+                   a: Option<&Component>, b: Option<&Component>)
+                   -> Option<output::ComponentDiff>
+{
+    let c = a.or(b).expect("one is given");
+    let in_a = |s: &Signature| a.map(|a| a.sigs.iter()
+                                      .any(|(_, o)| o.normalized_eq(s)))
+        .unwrap_or(false);
+    let in_b = |s: &Signature| b.map(|b| b.sigs.iter()
+                                      .any(|(_, o)| o.normalized_eq(s)))
+        .unwrap_or(false);
+
+    let mut sigs = Vec::new();
+    for &(kind, ref sig) in a.iter().flat_map(|a| a.sigs.iter()) {
+        if ! in_b(sig) {
+            sigs.push(signature(policy, c.sec, "removed", kind, sig));
+        }
+    }
+    for &(kind, ref sig) in b.iter().flat_map(|b| b.sigs.iter()) {
+        if ! in_a(sig) {
+            sigs.push(signature(policy, c.sec, "added", kind, sig));
+        }
+    }
+
+    if a.is_some() && b.is_some() && sigs.is_empty() {
+        return None;
+    }
+
+    let (typ, id) = match &c.id {
+        Id::Primary => ("primary-key", cert.to_hex()),
+        Id::UserID(u) =>
+            ("userid", String::from_utf8_lossy(u).into()),
+        Id::UserAttribute(u) => ("user-attribute", digest(u)),
+        Id::Subkey(fp) => ("subkey", fp.to_hex()),
+    };
+
+    Some(output::ComponentDiff {
+        typ,
+        id,
+        status: status(a.is_none(), b.is_none()),
+        signatures: sigs,
+    })
+}
+
+/// Returns whether something was added, removed, or changed, given
+/// whether it is missing from the first and the second version.
+fn status(missing_a: bool, missing_b: bool) -> &'static str {
+    match (missing_a, missing_b) {
+        (true, _) => "added",
+        (_, true) => "removed",
+        _ => "changed",
+    }
+}
+
+/// Describes a signature that was added or removed.
+fn signature(policy: &dyn Policy, sec: HashAlgoSecurity,
+             status: &'static str, kind: &'static str, sig: &Signature)
+             -> output::SignatureDiff
+{
+    // Self-signatures have been verified when the certificate was
+    // canonicalized, third-party signatures have not.
+    let verified = matches!(kind, "self-signature" | "self-revocation");
+    let sec = if verified {
+        sec
+    } else {
+        HashAlgoSecurity::CollisionResistance
+    };
+    let error = sig.signature_alive(SystemTime::now(), Duration::new(0, 0))
+        .and_then(|_| policy.signature(sig, sec))
+        .err().map(|e| format!("{:#}", e));
+
+    output::SignatureDiff {
+        status,
+        kind,
+        typ: sig.typ().to_string(),
+        creation_time: sig.signature_creation_time().map(output::time),
+        issuers: sig.get_issuers().iter().map(|i| i.to_hex()).collect(),
+        verified,
+        valid: error.is_none(),
+        error,
+    }
+}
+
+/// Identifies user attributes by the SHA-256 digest of their content.
+fn digest(data: &[u8]) -> String {
+    HashAlgorithm::SHA256.context()
+        .and_then(|mut ctx| {
+            ctx.update(data);
+            ctx.into_digest()
+        })
+        .map(hex::encode)
+        .unwrap_or_else(|_| hex::encode(data))
+}
+
+/// Prints the differences in human-readable form.
+fn print(diff: &output::CertDiff) {
+    println!("Certificate {}: {}", diff.fingerprint, diff.status);
+    for c in &diff.components {
+        match c.typ {
+            "primary-key" => println!("  Primary key: {}", c.status),
+            "userid" => println!("  User ID {:?}: {}", c.id, c.status),
+            "user-attribute" =>
+                println!("  User attribute {}: {}", c.id, c.status),
+            _ => println!("  Subkey {}: {}", c.id, c.status),
+        }
+
+        for s in &c.signatures {
+            let mut line = format!(
+                "    {} {} {}",
+                if s.status == "added" { "+" } else { "-" },
+                s.kind, s.typ);
+            if ! s.verified {
+                if let Some(issuer) = s.issuers.first() {
+                    line.push_str(&format!(" by {}", issuer));
+                }
+            }
+            if let Some(t) = &s.creation_time {
+                line.push_str(&format!(", created {}", t));
+            }
+            match &s.error {
+                None if s.verified => line.push_str(", valid"),
+                None => line.push_str(", acceptable (not verified)"),
+                Some(e) => line.push_str(&format!(", invalid: {}", e)),
+            }
+            println!("{}", line);
+        }
+    }
+}
@@ -10,7 +10,16 @@ use self::openpgp::packet::header::CTB;
 use self::openpgp::packet::{Header, header::BodyLength, Signature};
 use self::openpgp::packet::signature::subpacket::{Subpacket, SubpacketValue};
 use self::openpgp::crypto::{SessionKey, S2K};
-use self::openpgp::parse::{map::Map, Parse, PacketParserResult};
+use self::openpgp::packet::header::PacketLengthType;
+use self::openpgp::parse::{
+    map::Map, Parse, PacketParser, PacketParserResult,
+};
+use self::openpgp::serialize::MarshalInto;
+
+use serde_json::{json, Value};
+
+use crate::commands::decrypt::ContainerDecryptor;
+use crate::output;
 
 #[derive(Debug)]
 pub enum Kind {
@@ -52,110 +61,257 @@ impl Convert<chrono::DateTime<chrono::offset::Utc>> for Timestamp {
     }
 }
 
+/// Options for [`dump`].
+pub struct DumpOpts<'a, 'b> {
+    /// Prints cryptographic artifacts.
+    pub mpis: bool,
+    /// Prints a hexdump.
+    pub hex: bool,
+    /// Emits machine-readable output.
+    pub json: bool,
+    /// Decrypts encryption containers using this session key.
+    pub session_key: Option<&'b SessionKey>,
+    /// Decrypts encryption containers if no session key is given.
+    pub decryptor: Option<ContainerDecryptor<'a>>,
+    /// Only dumps the packets at these paths, and the packets they
+    /// contain.  If empty, all packets are dumped.
+    pub paths: Vec<Vec<usize>>,
+    /// Omits the content of literal data packets.
+    pub skip_literal_bodies: bool,
+    /// The width of the terminal.
+    pub width: Option<usize>,
+}
+
+/// Parses a packet path like `1.0`.
+pub fn parse_path(path: &str) -> Result<Vec<usize>> {
+    path.split('.')
+        .map(|n| n.parse::<usize>().map_err(|_| anyhow::anyhow!(
+            "Malformed packet path {:?}, expected e.g. \"1.0\"", path)))
+        .collect()
+}
+
+/// Returns the depth of the packet at `path` relative to the
+/// selected packet containing it, if it is selected.
+fn selected(paths: &[Vec<usize>], path: &[usize]) -> Option<usize> {
+    if paths.is_empty() {
+        return Some(path.len() - 1);
+    }
+    paths.iter()
+        .filter(|p| path.starts_with(p))
+        .map(|p| path.len() - p.len())
+        .min()
+}
+
+/// Returns whether the packet at `path` is selected, or contains a
+/// selected packet.
+fn wanted(paths: &[Vec<usize>], path: &[usize]) -> bool {
+    paths.is_empty()
+        || paths.iter().any(|p| path.starts_with(p) || p.starts_with(path))
+}
+
+/// Returns the length of the packet header.
+fn header_length(header: &Header) -> usize {
+    1 + match header.ctb() {
+        CTB::Old(ctb) => match ctb.length_type() {
+            PacketLengthType::OneOctet => 1,
+            PacketLengthType::TwoOctets => 2,
+            PacketLengthType::FourOctets => 4,
+            PacketLengthType::Indeterminate => 0,
+        },
+        CTB::New(_) => header.length().serialized_len(),
+    }
+}
+
+/// The outcome of trying to decrypt an encryption container.
+enum Decryption {
+    NoSessionKey,
+    Decrypted {
+        session_key: SessionKey,
+        algo: Option<SymmetricAlgorithm>,
+    },
+    Failed {
+        session_key: Option<SessionKey>,
+        error: Option<String>,
+    },
+}
+
+impl Decryption {
+    /// Returns the additional fields for the human-readable dump.
+    fn fields(&self) -> Vec<String> {
+        match self {
+            Decryption::NoSessionKey =>
+                vec!["No session key supplied".into()],
+            Decryption::Decrypted { session_key, algo } => {
+                let mut fields = Vec::new();
+                fields.push(format!("Session key: {}",
+                                    hex::encode(session_key)));
+                if let Some(algo) = algo {
+                    fields.push(format!("Symmetric algo: {}", algo));
+                }
+                fields.push("Decryption successful".into());
+                fields
+            },
+            Decryption::Failed { session_key, error } => {
+                let mut fields = Vec::new();
+                if let Some(session_key) = session_key {
+                    fields.push(format!("Session key: {}",
+                                        hex::encode(session_key)));
+                }
+                if let Some(error) = error {
+                    fields.push(format!("Decryption failed: {}", error));
+                } else {
+                    fields.push("Decryption failed".into());
+                }
+                fields
+            },
+        }
+    }
+
+    /// Returns the outcome for the machine-readable dump.
+    fn to_json(&self) -> Value {
+        match self {
+            Decryption::NoSessionKey => json!({
+                "status": "no-session-key",
+            }),
+            Decryption::Decrypted { session_key, algo } => json!({
+                "status": "decrypted",
+                "session_key": hex::encode(session_key),
+                "symmetric_algorithm": algo.map(|a| a.to_string()),
+            }),
+            Decryption::Failed { session_key, error } => json!({
+                "status": "failed",
+                "session_key": session_key.as_ref().map(hex::encode),
+                "error": error,
+            }),
+        }
+    }
+}
+
 #[allow(clippy::redundant_pattern_matching)]
-pub fn dump<W>(input: &mut (dyn io::Read + Sync + Send),
-               output: &mut dyn io::Write,
-               mpis: bool, hex: bool, sk: Option<&SessionKey>,
-               width: W)
-               -> Result<Kind>
-    where W: Into<Option<usize>>
+pub fn dump(input: &mut (dyn io::Read + Sync + Send),
+            output: &mut dyn io::Write,
+            mut opts: DumpOpts)
+            -> Result<Kind>
 {
     let mut ppr
         = self::openpgp::parse::PacketParserBuilder::from_reader(input)?
-        .map(hex).build()?;
+        .map(opts.hex).build()?;
     let mut message_encrypted = false;
-    let width = width.into().unwrap_or(80);
-    let mut dumper = PacketDumper::new(width, mpis);
+    let width = opts.width.unwrap_or(80);
+    let mut dumper = PacketDumper::new(width, opts.mpis);
+    dumper.skip_literal_bodies = opts.skip_literal_bodies;
+    let sk = opts.session_key;
+    let paths = std::mem::take(&mut opts.paths);
+    let mut decryptor = opts.decryptor.take();
+
+    // The packets for the machine-readable dump.
+    let mut packets: Vec<output::PacketInfo> = Vec::new();
+    // The offset of the next packet on every level of the tree, if
+    // known.
+    let mut offsets: Vec<Option<u64>> = Vec::new();
+    let mut pkesks: Vec<PKESK> = Vec::new();
+    let mut skesks: Vec<SKESK> = Vec::new();
 
     while let PacketParserResult::Some(mut pp) = ppr {
-        let additional_fields = match pp.packet {
-            Packet::Literal(_) => {
+        let path = pp.path().to_vec();
+        let depth = path.len() - 1;
+        let header = pp.header().clone();
+        let header_length = header_length(&header);
+
+        offsets.truncate(depth + 1);
+        offsets.resize(depth + 1, Some(0));
+        let offset = offsets[depth];
+        offsets[depth] = match (offset, header.length()) {
+            (Some(offset), BodyLength::Full(n)) =>
+                Some(offset + header_length as u64 + *n as u64),
+            _ => None,
+        };
+
+        let rel_depth = selected(&paths, &path);
+        let mut content = None;
+        let mut decryption = None;
+        match pp.packet {
+            Packet::Literal(_)
+                if rel_depth.is_some() && ! opts.skip_literal_bodies =>
+            {
                 let mut prefix = vec![0; 40];
                 let n = pp.read(&mut prefix)?;
-                Some(vec![
-                    format!("Content: {:?}{}",
-                            String::from_utf8_lossy(&prefix[..n]),
-                            if n == prefix.len() { "..." } else { "" }),
-                ])
+                let truncated = n == prefix.len();
+                prefix.truncate(n);
+                content = Some((prefix, truncated));
             },
-            Packet::SEIP(_) if sk.is_none() => {
-                message_encrypted = true;
-                Some(vec!["No session key supplied".into()])
-            }
-            Packet::SEIP(_) if sk.is_some() => {
+            Packet::SEIP(_) | Packet::AED(_) => {
                 message_encrypted = true;
-                let sk = sk.as_ref().unwrap();
-                let mut decrypted_with = None;
-                for algo in 1..20 {
-                    let algo = SymmetricAlgorithm::from(algo);
-                    if let Ok(size) = algo.key_size() {
-                        if size != sk.len() { continue; }
-                    } else {
-                        continue;
-                    }
-
-                    if let Ok(_) = pp.decrypt(algo, sk) {
-                        decrypted_with = Some(algo);
-                        break;
-                    }
+                if wanted(&paths, &path) {
+                    decryption = Some(decrypt(&mut pp, sk,
+                                              decryptor.as_mut(),
+                                              &pkesks, &skesks));
                 }
+            },
+            _ => (),
+        }
+
+        let map = pp.take_map();
+
+        if let Some(rel_depth) = rel_depth {
+            if opts.json {
+                let node = packet_info(
+                    &path, offset, header_length, &header, &pp.packet,
+                    map.as_ref(), content.as_ref(), decryption.as_ref(),
+                    opts.mpis, opts.skip_literal_bodies);
+                append(&mut packets, rel_depth, node);
+            } else {
                 let mut fields = Vec::new();
-                fields.push(format!("Session key: {}", hex::encode(sk)));
-                if let Some(algo) = decrypted_with {
-                    fields.push(format!("Symmetric algo: {}", algo));
-                    fields.push("Decryption successful".into());
-                } else {
-                    fields.push("Decryption failed".into());
+                if let Some((prefix, truncated)) = content.as_ref() {
+                    fields.push(format!(
+                        "Content: {:?}{}",
+                        String::from_utf8_lossy(prefix),
+                        if *truncated { "..." } else { "" }));
                 }
-                Some(fields)
-            },
-            Packet::AED(_) if sk.is_none() => {
-                message_encrypted = true;
-                Some(vec!["No session key supplied".into()])
-            }
-            Packet::AED(_) if sk.is_some() => {
-                message_encrypted = true;
-                let sk = sk.as_ref().unwrap();
-                let algo = if let Packet::AED(ref aed) = pp.packet {
-                    aed.symmetric_algo()
+                if let Some(decryption) = decryption.as_ref() {
+                    fields.append(&mut decryption.fields());
+                }
+                let fields = if fields.is_empty() {
+                    None
                 } else {
-                    unreachable!()
+                    Some(fields)
                 };
+                dumper.packet(output, rel_depth, header, pp.packet.clone(),
+                              map, fields)?;
+            }
+        }
 
-                let _ = pp.decrypt(algo, sk);
+        match pp.packet {
+            Packet::PKESK(ref p) => pkesks.push(p.clone()),
+            Packet::SKESK(ref s) => skesks.push(s.clone()),
+            _ => (),
+        }
 
-                let mut fields = Vec::new();
-                fields.push(format!("Session key: {}", hex::encode(sk)));
-                if pp.encrypted() {
-                    fields.push("Decryption failed".into());
+        let result = if wanted(&paths, &path) {
+            pp.recurse()
+        } else {
+            pp.next()
+        };
+        let (_, ppr_) = match result {
+            Ok(v) => v,
+            Err(e) => {
+                if opts.json {
+                    let _ = output::emit(
+                        output, &output::PacketDump { packets });
                 } else {
-                    fields.push("Decryption successful".into());
+                    let _ = dumper.flush(output);
                 }
-                Some(fields)
+                return Err(e);
             },
-            _ => None,
         };
-
-        let header = pp.header().clone();
-        let map = pp.take_map();
-
-        let recursion_depth = pp.recursion_depth();
-        let packet = pp.packet.clone();
-
-        dumper.packet(output, recursion_depth as usize,
-                      header, packet, map, additional_fields)?;
-
-        let (_, ppr_) = match pp.recurse() {
-            Ok(v) => Ok(v),
-            Err(e) => {
-                let _ = dumper.flush(output);
-                Err(e)
-            },
-        }?;
         ppr = ppr_;
     }
 
-    dumper.flush(output)?;
+    if opts.json {
+        output::emit(output, &output::PacketDump { packets })?;
+    } else {
+        dumper.flush(output)?;
+    }
 
     if let PacketParserResult::EOF(eof) = ppr {
         if eof.is_message().is_ok() {
@@ -174,6 +330,553 @@ pub fn dump<W>(input: &mut (dyn io::Read + Sync + Send),
     }
 }
 
+/// Decrypts the encryption container `pp`.
+///
+/// If a session key is given, it is used.  Otherwise, `decryptor`
+/// decrypts one of the `pkesks` or `skesks`.
+#[allow(clippy::redundant_pattern_matching)]
+fn decrypt(pp: &mut PacketParser, sk: Option<&SessionKey>,
+           decryptor: Option<&mut ContainerDecryptor>,
+           pkesks: &[PKESK], skesks: &[SKESK])
+           -> Decryption
+{
+    if let Some(sk) = sk {
+        if let Packet::AED(ref aed) = pp.packet {
+            let algo = aed.symmetric_algo();
+            let _ = pp.decrypt(algo, sk);
+            return if pp.encrypted() {
+                Decryption::Failed {
+                    session_key: Some(sk.clone()),
+                    error: None,
+                }
+            } else {
+                Decryption::Decrypted {
+                    session_key: sk.clone(),
+                    algo: None,
+                }
+            };
+        }
+
+        for algo in 1..20 {
+            let algo = SymmetricAlgorithm::from(algo);
+            if let Ok(size) = algo.key_size() {
+                if size != sk.len() { continue; }
+            } else {
+                continue;
+            }
+
+            if let Ok(_) = pp.decrypt(algo, sk) {
+                return Decryption::Decrypted {
+                    session_key: sk.clone(),
+                    algo: Some(algo),
+                };
+            }
+        }
+        Decryption::Failed {
+            session_key: Some(sk.clone()),
+            error: None,
+        }
+    } else if let Some(decryptor) = decryptor {
+        match decryptor.decrypt(pp, pkesks, skesks) {
+            Ok((algo, session_key)) => Decryption::Decrypted {
+                session_key,
+                algo: Some(algo),
+            },
+            Err(e) => Decryption::Failed {
+                session_key: None,
+                error: Some(e.to_string()),
+            },
+        }
+    } else {
+        Decryption::NoSessionKey
+    }
+}
+
+/// Appends `node` to the tree `nodes` at `depth`.
+fn append(nodes: &mut Vec<output::PacketInfo>, depth: usize,
+          node: output::PacketInfo) {
+    match nodes.last_mut() {
+        Some(last) if depth > 0 => append(&mut last.children, depth - 1, node),
+        _ => nodes.push(node),
+    }
+}
+
+/// Describes a packet for the machine-readable dump.
+#[allow(clippy::too_many_arguments)]
+fn packet_info(path: &[usize], offset: Option<u64>, header_length: usize,
+               header: &Header, p: &Packet, map: Option<&Map>,
+               content: Option<&(Vec<u8>, bool)>,
+               decryption: Option<&Decryption>,
+               secret_mpis: bool, skip_literal_bodies: bool)
+               -> output::PacketInfo
+{
+    let (length_type, body_length) = match header.length() {
+        BodyLength::Full(n) => ("full", Some(*n)),
+        BodyLength::Partial(_) => ("partial", None),
+        BodyLength::Indeterminate => ("indeterminate", None),
+    };
+
+    let mut fields = serde_json::Map::new();
+    let mut mpis = Vec::new();
+    packet_fields(p, &mut fields, &mut mpis, secret_mpis);
+    if let Some((prefix, truncated)) = content {
+        fields.insert("content".into(),
+                      json!(String::from_utf8_lossy(prefix)));
+        fields.insert("content_truncated".into(), json!(truncated));
+    }
+    if let Some(decryption) = decryption {
+        fields.insert("decryption".into(), decryption.to_json());
+    }
+
+    output::PacketInfo {
+        path: path.iter().map(|n| n.to_string())
+            .collect::<Vec<_>>().join("."),
+        offset,
+        header_length,
+        ctb: if let CTB::Old(_) = header.ctb() { "old" } else { "new" },
+        length_type,
+        body_length,
+        tag: p.tag().into(),
+        typ: p.tag().to_string(),
+        fields,
+        mpis,
+        map: map.map(|map| map.iter()
+                     .filter(|f| ! (skip_literal_bodies
+                                    && f.name() == "body"
+                                    && p.tag() == Tag::Literal))
+                     .map(|f| output::FieldInfo {
+                         name: f.name().into(),
+                         offset: f.offset(),
+                         length: f.as_bytes().len(),
+                         data: hex::encode(f.as_bytes()),
+                     }).collect())
+            .unwrap_or_default(),
+        children: Vec::new(),
+    }
+}
+
+fn mpi(name: &str, value: &[u8]) -> output::MpiInfo {
+    output::MpiInfo {
+        name: name.into(),
+        value: hex::encode(value),
+    }
+}
+
+/// Returns the unknown MPIs of an algorithm we don't support.
+fn unknown_mpis(mpis: &[mpi::MPI], rest: &[u8]) -> Vec<output::MpiInfo> {
+    mpis.iter().enumerate()
+        .map(|(i, m)| mpi(&format!("mpi{}", i), m.value()))
+        .chain(std::iter::once(mpi("rest", rest)))
+        .collect()
+}
+
+#[allow(deprecated)]
+fn s2k_json(s2k: &S2K) -> Value {
+    use self::S2K::*;
+    match s2k {
+        Simple { hash } => json!({
+            "type": "Simple",
+            "hash_algorithm": hash.to_string(),
+        }),
+        Salted { hash, salt } => json!({
+            "type": "Salted",
+            "hash_algorithm": hash.to_string(),
+            "salt": hex::encode(salt),
+        }),
+        Iterated { hash, salt, hash_bytes } => json!({
+            "type": "Iterated",
+            "hash_algorithm": hash.to_string(),
+            "salt": hex::encode(salt),
+            "hash_bytes": hash_bytes,
+        }),
+        Private { tag, parameters } => json!({
+            "type": "Private",
+            "tag": tag,
+            "parameters": parameters.as_ref().map(hex::encode),
+        }),
+        Unknown { tag, parameters } => json!({
+            "type": "Unknown",
+            "tag": tag,
+            "parameters": parameters.as_ref().map(hex::encode),
+        }),
+
+        // S2K is non-exhaustive
+        u => json!({
+            "type": format!("{:?}", u),
+        }),
+    }
+}
+
+/// Collects the body fields and MPIs of `p`.
+///
+/// Secret key material is only included if `secret_mpis` is set.
+fn packet_fields(p: &Packet, fields: &mut serde_json::Map<String, Value>,
+                 mpis: &mut Vec<output::MpiInfo>, secret_mpis: bool)
+{
+    use self::openpgp::Packet::*;
+
+    let mut field = |name: &str, value: Value| {
+        fields.insert(name.into(), value);
+    };
+
+    fn key_fields<P, R>(k: &Key<P, R>,
+                        field: &mut dyn FnMut(&str, Value),
+                        mpis: &mut Vec<output::MpiInfo>,
+                        secret_mpis: bool)
+        where P: key::KeyParts,
+              R: key::KeyRole,
+    {
+        field("version", json!(k.version()));
+        field("creation_time", json!(output::time(k.creation_time())));
+        field("pk_algo", json!(k.pk_algo().to_string()));
+        field("bits", json!(k.mpis().bits()));
+        field("fingerprint", json!(k.fingerprint().to_hex()));
+        field("keyid", json!(k.keyid().to_hex()));
+
+        match k.mpis() {
+            mpi::PublicKey::RSA { e, n } => {
+                mpis.push(mpi("e", e.value()));
+                mpis.push(mpi("n", n.value()));
+            },
+            mpi::PublicKey::DSA { p, q, g, y } => {
+                mpis.push(mpi("p", p.value()));
+                mpis.push(mpi("q", q.value()));
+                mpis.push(mpi("g", g.value()));
+                mpis.push(mpi("y", y.value()));
+            },
+            mpi::PublicKey::ElGamal { p, g, y } => {
+                mpis.push(mpi("p", p.value()));
+                mpis.push(mpi("g", g.value()));
+                mpis.push(mpi("y", y.value()));
+            },
+            mpi::PublicKey::EdDSA { curve, q } => {
+                field("curve", json!(curve.to_string()));
+                mpis.push(mpi("q", q.value()));
+            },
+            mpi::PublicKey::ECDSA { curve, q } => {
+                field("curve", json!(curve.to_string()));
+                mpis.push(mpi("q", q.value()));
+            },
+            mpi::PublicKey::ECDH { curve, q, hash, sym } => {
+                field("curve", json!(curve.to_string()));
+                field("kdf_hash_algo", json!(hash.to_string()));
+                field("kdf_symmetric_algo", json!(sym.to_string()));
+                mpis.push(mpi("q", q.value()));
+            },
+            mpi::PublicKey::Unknown { mpis: m, rest } =>
+                mpis.append(&mut unknown_mpis(m, rest)),
+
+            // crypto::mpi:Publickey is non-exhaustive
+            u => field("unknown_variant", json!(format!("{:?}", u))),
+        }
+
+        match k.optional_secret() {
+            None => (),
+            Some(SecretKeyMaterial::Unencrypted(u)) => {
+                field("secret", json!("unencrypted"));
+                if secret_mpis {
+                    u.map(|secret| match secret {
+                        mpi::SecretKeyMaterial::RSA { d, p, q, u } => {
+                            mpis.push(mpi("d", d.value()));
+                            mpis.push(mpi("p", p.value()));
+                            mpis.push(mpi("q", q.value()));
+                            mpis.push(mpi("u", u.value()));
+                        },
+                        mpi::SecretKeyMaterial::DSA { x } =>
+                            mpis.push(mpi("x", x.value())),
+                        mpi::SecretKeyMaterial::ElGamal { x } =>
+                            mpis.push(mpi("x", x.value())),
+                        mpi::SecretKeyMaterial::EdDSA { scalar } =>
+                            mpis.push(mpi("scalar", scalar.value())),
+                        mpi::SecretKeyMaterial::ECDSA { scalar } =>
+                            mpis.push(mpi("scalar", scalar.value())),
+                        mpi::SecretKeyMaterial::ECDH { scalar } =>
+                            mpis.push(mpi("scalar", scalar.value())),
+                        mpi::SecretKeyMaterial::Unknown { mpis: m, rest } =>
+                            mpis.append(&mut unknown_mpis(m, rest)),
+
+                        // crypto::mpi::SecretKeyMaterial is non-exhaustive.
+                        _ => (),
+                    });
+                }
+            },
+            Some(SecretKeyMaterial::Encrypted(e)) => {
+                field("secret", json!("encrypted"));
+                field("s2k", s2k_json(e.s2k()));
+                field("secret_symmetric_algo", json!(e.algo().to_string()));
+                if secret_mpis {
+                    if let Ok(ciphertext) = e.ciphertext() {
+                        mpis.push(mpi("ciphertext", ciphertext));
+                    }
+                }
+            },
+        }
+    }
+
+    match p {
+        Unknown(ref u) => {
+            field("tag", json!(u8::from(u.tag())));
+            field("error", json!(u.error().to_string()));
+        },
+
+        PublicKey(ref k) => key_fields(k, &mut field, mpis, secret_mpis),
+        PublicSubkey(ref k) => key_fields(k, &mut field, mpis, secret_mpis),
+        SecretKey(ref k) => key_fields(k, &mut field, mpis, secret_mpis),
+        SecretSubkey(ref k) => key_fields(k, &mut field, mpis, secret_mpis),
+
+        Signature(ref s) => {
+            field("version", json!(s.version()));
+            field("sig_type", json!(s.typ().to_string()));
+            field("pk_algo", json!(s.pk_algo().to_string()));
+            field("hash_algo", json!(s.hash_algo().to_string()));
+            field("hashed_area", json!(
+                s.hashed_area().iter().map(|sp| subpacket_info(sp, s))
+                    .collect::<Vec<_>>()));
+            field("unhashed_area", json!(
+                s.unhashed_area().iter().map(|sp| subpacket_info(sp, s))
+                    .collect::<Vec<_>>()));
+            field("digest_prefix", json!(hex::encode(s.digest_prefix())));
+            field("level", json!(s.level()));
+
+            match s.mpis() {
+                mpi::Signature::RSA { s } =>
+                    mpis.push(mpi("s", s.value())),
+                mpi::Signature::DSA { r, s }
+                | mpi::Signature::ElGamal { r, s }
+                | mpi::Signature::EdDSA { r, s }
+                | mpi::Signature::ECDSA { r, s } => {
+                    mpis.push(mpi("r", r.value()));
+                    mpis.push(mpi("s", s.value()));
+                },
+                mpi::Signature::Unknown { mpis: m, rest } =>
+                    mpis.append(&mut unknown_mpis(m, rest)),
+
+                // crypto::mpi::Signature is non-exhaustive.
+                u => field("unknown_variant", json!(format!("{:?}", u))),
+            }
+        },
+
+        OnePassSig(ref o) => {
+            field("version", json!(o.version()));
+            field("sig_type", json!(o.typ().to_string()));
+            field("pk_algo", json!(o.pk_algo().to_string()));
+            field("hash_algo", json!(o.hash_algo().to_string()));
+            field("issuer", json!(o.issuer().to_hex()));
+            field("last", json!(o.last()));
+        },
+
+        Trust(ref p) => field("value", json!(hex::encode(p.value()))),
+
+        UserID(ref u) =>
+            field("value", json!(String::from_utf8_lossy(u.value()))),
+
+        UserAttribute(ref u) => {
+            use self::openpgp::packet::user_attribute::{Subpacket, Image};
+
+            field("subpackets", json!(u.subpackets().map(|sp| match sp {
+                Ok(Subpacket::Image(Image::JPEG(data))) => json!({
+                    "type": "JPEG", "length": data.len(),
+                }),
+                Ok(Subpacket::Image(Image::Private(n, data))) => json!({
+                    "type": "private image", "tag": n, "length": data.len(),
+                }),
+                Ok(Subpacket::Image(Image::Unknown(n, data))) => json!({
+                    "type": "unknown image", "tag": n, "length": data.len(),
+                }),
+                Ok(Subpacket::Unknown(n, data)) => json!({
+                    "type": "unknown", "tag": n, "length": data.len(),
+                }),
+                Err(e) => json!({
+                    "type": "invalid", "error": e.to_string(),
+                }),
+            }).collect::<Vec<_>>()));
+        },
+
+        Marker(_) => (),
+
+        Literal(ref l) => {
+            field("format", json!(l.format().to_string()));
+            field("filename", json!(
+                l.filename().map(|f| String::from_utf8_lossy(f))));
+            field("date", json!(
+                l.date().map(output::time)));
+        },
+
+        CompressedData(ref c) =>
+            field("algorithm", json!(c.algo().to_string())),
+
+        PKESK(ref p) => {
+            field("version", json!(p.version()));
+            field("recipient", json!(p.recipient().to_hex()));
+            field("pk_algo", json!(p.pk_algo().to_string()));
+
+            match p.esk() {
+                mpi::Ciphertext::RSA { c } =>
+                    mpis.push(mpi("c", c.value())),
+                mpi::Ciphertext::ElGamal { e, c } => {
+                    mpis.push(mpi("e", e.value()));
+                    mpis.push(mpi("c", c.value()));
+                },
+                mpi::Ciphertext::ECDH { e, key } => {
+                    mpis.push(mpi("e", e.value()));
+                    mpis.push(mpi("key", key));
+                },
+                mpi::Ciphertext::Unknown { mpis: m, rest } =>
+                    mpis.append(&mut unknown_mpis(m, rest)),
+
+                // crypto::mpi::Ciphertext is non-exhaustive.
+                u => field("unknown_variant", json!(format!("{:?}", u))),
+            }
+        },
+
+        SKESK(ref s) => {
+            field("version", json!(s.version()));
+            match s {
+                self::openpgp::packet::SKESK::V4(ref s) => {
+                    field("symmetric_algo",
+                          json!(s.symmetric_algo().to_string()));
+                    field("s2k", s2k_json(s.s2k()));
+                    if let Ok(Some(esk)) = s.esk() {
+                        field("esk", json!(hex::encode(esk)));
+                    }
+                },
+
+                self::openpgp::packet::SKESK::V5(ref s) => {
+                    field("symmetric_algo",
+                          json!(s.symmetric_algo().to_string()));
+                    field("aead_algo", json!(s.aead_algo().to_string()));
+                    field("s2k", s2k_json(s.s2k()));
+                    if let Ok(iv) = s.aead_iv() {
+                        field("iv", json!(hex::encode(iv)));
+                    }
+                    if let Ok(Some(esk)) = s.esk() {
+                        field("esk", json!(hex::encode(esk)));
+                    }
+                    field("digest", json!(hex::encode(s.aead_digest())));
+                },
+
+                // SKESK is non-exhaustive.
+                u => field("unknown_variant", json!(format!("{:?}", u))),
+            }
+        },
+
+        SEIP(ref s) => field("version", json!(s.version())),
+
+        MDC(ref m) => {
+            field("digest", json!(hex::encode(m.digest())));
+            field("computed_digest", json!(hex::encode(m.computed_digest())));
+        },
+
+        AED(ref a) => {
+            field("version", json!(a.version()));
+            field("symmetric_algo", json!(a.symmetric_algo().to_string()));
+            field("aead_algo", json!(a.aead().to_string()));
+            field("chunk_size", json!(a.chunk_size()));
+            field("iv", json!(hex::encode(a.iv())));
+        },
+
+        // openpgp::Packet is non-exhaustive.
+        u => field("unknown_variant", json!(format!("{:?}", u))),
+    }
+}
+
+/// Describes a signature subpacket for the machine-readable dump.
+fn subpacket_info(s: &Subpacket, sig: &Signature) -> output::SubpacketInfo {
+    use self::SubpacketValue::*;
+
+    fn names<T: ToString>(algos: &[T]) -> Value {
+        json!(algos.iter().map(|a| a.to_string()).collect::<Vec<_>>())
+    }
+    fn lossy(s: &[u8]) -> Value {
+        json!(String::from_utf8_lossy(s))
+    }
+
+    let value = match s.value() {
+        Unknown { body, .. } => json!(hex::encode(body)),
+        SignatureCreationTime(t) =>
+            json!(output::time((*t).into())),
+        SignatureExpirationTime(t) => json!({
+            "duration": t.as_secs(),
+            "time": sig.signature_creation_time()
+                .map(|creation| output::time(
+                    creation + std::time::Duration::from(*t))),
+        }),
+        ExportableCertification(e) => json!(e),
+        TrustSignature { level, trust } => json!({
+            "level": level,
+            "trust": trust,
+        }),
+        RegularExpression(ref r) => lossy(r),
+        Revocable(r) => json!(r),
+        KeyExpirationTime(t) => json!({
+            "duration": t.as_secs(),
+        }),
+        PreferredSymmetricAlgorithms(ref c) => names(&c[..]),
+        RevocationKey(rk) => {
+            let (pk_algo, fp) = rk.revoker();
+            json!({
+                "fingerprint": fp.to_hex(),
+                "pk_algo": pk_algo.to_string(),
+                "sensitive": rk.sensitive(),
+            })
+        },
+        Issuer(ref is) => json!(is.to_hex()),
+        NotationData(n) => json!({
+            "name": n.name(),
+            "flags": format!("{:?}", n.flags()),
+            "value": if n.flags().human_readable() {
+                lossy(n.value())
+            } else {
+                json!(hex::encode(n.value()))
+            },
+        }),
+        PreferredHashAlgorithms(ref h) => names(&h[..]),
+        PreferredCompressionAlgorithms(ref c) => names(&c[..]),
+        KeyServerPreferences(ref p) => json!(format!("{:?}", p)),
+        PreferredKeyServer(ref k) => lossy(k),
+        PrimaryUserID(p) => json!(p),
+        PolicyURI(ref p) => lossy(p),
+        KeyFlags(ref k) => json!(output::key_flags(k)),
+        SignersUserID(ref u) => lossy(u),
+        ReasonForRevocation { code, ref reason } => json!({
+            "code": u8::from(*code),
+            "reason": code.to_string(),
+            "message": String::from_utf8_lossy(reason),
+        }),
+        Features(ref f) => json!(format!("{:?}", f)),
+        SignatureTarget { pk_algo, hash_algo, ref digest } => json!({
+            "pk_algo": pk_algo.to_string(),
+            "hash_algo": hash_algo.to_string(),
+            "digest": hex::encode(digest),
+        }),
+        EmbeddedSignature(ref sig) => {
+            let mut fields = serde_json::Map::new();
+            let mut mpis = Vec::new();
+            packet_fields(&sig.clone().into(), &mut fields, &mut mpis, false);
+            json!({
+                "fields": fields,
+                "mpis": mpis,
+            })
+        },
+        IssuerFingerprint(ref fp) => json!(fp.to_hex()),
+        PreferredAEADAlgorithms(ref c) => names(&c[..]),
+        IntendedRecipient(ref fp) => json!(fp.to_hex()),
+        AttestedCertifications(digests) =>
+            json!(digests.iter().map(hex::encode)
+                  .collect::<Vec<_>>()),
+
+        // SubpacketValue is non-exhaustive.
+        u => json!(format!("{:?}", u)),
+    };
+
+    output::SubpacketInfo {
+        tag: s.tag().into(),
+        typ: format!("{:?}", s.tag()),
+        critical: s.critical(),
+        value,
+    }
+}
+
 struct Node {
     header: Header,
     packet: Packet,
@@ -206,6 +909,8 @@ impl Node {
 pub struct PacketDumper {
     width: usize,
     mpis: bool,
+    /// Omits the content of literal data packets from hexdumps.
+    skip_literal_bodies: bool,
     root: Option<Node>,
 }
 
@@ -214,6 +919,7 @@ impl PacketDumper {
         PacketDumper {
             width,
             mpis,
+            skip_literal_bodies: false,
             root: None,
         }
     }
@@ -733,6 +1439,11 @@ impl PacketDumper {
                     .expect("we always have one entry")));
 
             for field in map.iter() {
+                if field.name() == "body" && self.skip_literal_bodies
+                    && p.tag() == Tag::Literal
+                {
+                    continue;
+                }
                 if field.name() == "body" {
                     hd.write_ascii(field.as_bytes())?;
                 } else {
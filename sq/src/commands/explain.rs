@@ -0,0 +1,176 @@
+//! Explains why signatures failed to verify.
+//!
+//! The verifier reports failures as a [`VerificationError`] wrapping
+//! an error chain.  For `sq verify --explain`, we work out which
+//! component (the signature, the signing key, its binding, or the
+//! certificate) caused the failure, and translate the error into
+//! something a user can act upon.
+
+use sequoia_openpgp as openpgp;
+use openpgp::{Cert, Error, KeyHandle};
+use openpgp::cert::prelude::*;
+use openpgp::packet::{Key, Signature, key};
+use openpgp::parse::stream::{VerificationError, VerificationResult};
+use openpgp::types::{RevocationStatus, SignatureType};
+
+use crate::output;
+
+/// Prints an explanation of why `result` failed to stderr.
+///
+/// Good results are not explained.
+pub fn explain(result: &VerificationResult) {
+    use self::VerificationError::*;
+    match result {
+        Ok(_) => (),
+        Err(MalformedSignature { error, .. }) => {
+            component("the signature");
+            reason(error);
+        },
+        Err(MissingKey { sig }) => {
+            component("the signer's certificate");
+            let issuers = sig.get_issuers();
+            if issuers.is_empty() {
+                eprintln!("  Reason: the signature does not name its issuer");
+            } else {
+                eprintln!("  Reason: no certificate containing {} was given \
+                           or found in the certificate store",
+                          issuers.iter().map(|i| i.to_string())
+                          .collect::<Vec<_>>().join(" or "));
+                hint("Use --signer-cert to supply the signer's certificate.");
+            }
+        },
+        Err(UnboundKey { sig, cert, error }) => {
+            component(&format!("the binding of the signing key to {}",
+                               cert.fingerprint()));
+            reason(error);
+            rejected_bindings(cert, sig.get_issuers());
+        },
+        Err(BadKey { ka, error, .. }) => {
+            let cert = ka.cert();
+            if cert.alive().is_err() {
+                component(&format!("the certificate {}", cert.fingerprint()));
+                reason(error);
+            } else if ka.alive().is_err() {
+                component(&format!("the signing key {}", ka.fingerprint()));
+                reason(error);
+            } else if let RevocationStatus::Revoked(revs) =
+                cert.revocation_status()
+            {
+                component(&format!("the certificate {}", cert.fingerprint()));
+                revoked(&revs);
+            } else if let RevocationStatus::Revoked(revs) =
+                ka.revocation_status()
+            {
+                component(&format!("the signing key {}", ka.fingerprint()));
+                revoked(&revs);
+            } else {
+                component(&format!("the signing key {}", ka.fingerprint()));
+                reason(error);
+            }
+        },
+        Err(BadSignature { error, .. }) => {
+            component("the signature");
+            reason(error);
+        },
+    }
+}
+
+fn component(what: &str) {
+    eprintln!("  Failed component: {}", what);
+}
+
+fn hint(what: &str) {
+    eprintln!("  Hint: {}", what);
+}
+
+/// Translates the first OpenPGP error in `error`'s chain.
+fn reason(error: &anyhow::Error) {
+    let e = match error.chain().find_map(|e| e.downcast_ref::<Error>()) {
+        Some(e) => e,
+        None => {
+            eprintln!("  Reason: {}", error);
+            return;
+        },
+    };
+
+    match e {
+        Error::PolicyViolation(what, Some(cutoff)) => {
+            eprintln!("  Reason: {} is not considered secure since {} ({})",
+                      what, output::time(*cutoff), error);
+            hint(&format!("Use --policy-as-of with a time before {} \
+                           to evaluate the signature under the policy \
+                           in effect back then.",
+                          output::time(*cutoff)));
+        },
+        Error::PolicyViolation(what, None) => {
+            eprintln!("  Reason: the policy rejects {} ({})", what, error);
+        },
+        Error::Expired(t) => {
+            eprintln!("  Reason: expired on {}", output::time(*t));
+        },
+        Error::NotYetLive(t) => {
+            eprintln!("  Reason: not live until {}", output::time(*t));
+        },
+        Error::NoBindingSignature(t) => {
+            eprintln!("  Reason: there is no valid binding signature at {}",
+                      output::time(*t));
+        },
+        Error::BadSignature(msg) => {
+            eprintln!("  Reason: the cryptographic check failed: {}", msg);
+            hint("The signed data or the signature has been modified, \
+                  or the signature was made over different data.");
+        },
+        Error::MalformedPacket(msg) => {
+            eprintln!("  Reason: the signature is malformed: {}", msg);
+        },
+        _ => eprintln!("  Reason: {}", error),
+    }
+}
+
+/// Prints the reasons for revocation.
+fn revoked(revs: &[&Signature]) {
+    for rev in revs {
+        match rev.reason_for_revocation() {
+            Some((code, msg)) if msg.is_empty() =>
+                eprintln!("  Reason: revoked: {}", code),
+            Some((code, msg)) =>
+                eprintln!("  Reason: revoked: {}: {:?}", code,
+                          String::from_utf8_lossy(msg)),
+            None => eprintln!("  Reason: revoked, no reason given"),
+        }
+        if let Some(t) = rev.signature_creation_time() {
+            eprintln!("  Revoked on: {}", output::time(t));
+        }
+    }
+}
+
+/// Detects subkey bindings of the subkeys in `cert` named by
+/// `issuers` that were rejected when the certificate was
+/// canonicalized because they lack the primary key binding signature
+/// (backsig) required for signing-capable subkeys.
+fn rejected_bindings(cert: &Cert, issuers: Vec<KeyHandle>) {
+    let primary = cert.primary_key().key();
+    for ka in cert.keys().subkeys().key_handles(issuers.iter()) {
+        let subkey: &Key<key::PublicParts, key::SubordinateRole> = ka.key();
+        for sig in cert.bad_signatures()
+            .filter(|s| s.typ() == SignatureType::SubkeyBinding)
+        {
+            if let Err(e) = sig.clone()
+                .verify_subkey_binding(primary, primary, subkey)
+            {
+                let backsig = matches!(
+                    e.downcast_ref::<Error>(),
+                    Some(Error::BadSignature(msg))
+                        if msg == "Primary key binding signature missing");
+                if backsig {
+                    eprintln!("  Reason: the binding signature of {} lacks \
+                               the primary key binding signature (backsig) \
+                               required for signing-capable subkeys",
+                              subkey.fingerprint());
+                    hint("The certificate is broken, ask the certificate \
+                          holder to update it.");
+                }
+            }
+        }
+    }
+}
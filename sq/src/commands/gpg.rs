@@ -0,0 +1,282 @@
+//! A gpg-compatible front-end for signing and verifying.
+//!
+//! git (and other tools) delegate signing and verification to a
+//! program that understands a subset of gpg's command line
+//! interface, and that reports results using gpg's machine-readable
+//! status lines.  When `sq` is invoked as `sq-gpg` (e.g. via a
+//! symlink), it implements this subset, signing with a key stored in
+//! Fortanix DSM:
+//!
+//! ```text
+//! $ ln -s $(which sq) ~/bin/sq-gpg
+//! $ git config --global gpg.program sq-gpg
+//! $ git config --global user.signingkey "My DSM key"
+//! ```
+//!
+//! The DSM credentials are taken from the environment (see `sq key
+//! generate --help`).  Certificates used for verification are read
+//! from the files given using `--keyring`, and from the files listed
+//! in `SQ_GPG_KEYRING`.
+
+use std::env;
+use std::ffi::OsString;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+use anyhow::Context as _;
+use clap::{App, AppSettings, Arg, ArgMatches};
+use tempfile::NamedTempFile;
+
+use sequoia_openpgp as openpgp;
+use crate::openpgp::{Packet, Result};
+use crate::openpgp::packet::Signature;
+use crate::openpgp::parse::{Parse, PacketParser, PacketParserResult};
+
+use crate::{
+    Config,
+    load_certs,
+    open_or_stdin,
+};
+use crate::secrets::{Auth, Credentials, PreSecret};
+
+/// The name `sq` must be invoked as to act like gpg.
+const PERSONALITY: &str = "sq-gpg";
+
+/// Environment variable listing certificate files for verification.
+const KEYRING_ENV: &str = "SQ_GPG_KEYRING";
+
+/// Returns whether we have been invoked as `sq-gpg`.
+pub fn invoked_as_gpg() -> bool {
+    env::args_os().next()
+        .and_then(|arg0| Path::new(&arg0).file_stem()
+                  .map(|s| s == PERSONALITY))
+        .unwrap_or(false)
+}
+
+fn build() -> App<'static, 'static> {
+    App::new(PERSONALITY)
+        .about("gpg-compatible signing and verification front-end for sq")
+        .setting(AppSettings::DisableVersion)
+        .arg(Arg::with_name("sign")
+             .short("s").long("sign")
+             .help("Signs the data"))
+        .arg(Arg::with_name("detach-sign")
+             .short("b").long("detach-sign")
+             .help("Makes a detached signature"))
+        .arg(Arg::with_name("clearsign")
+             .long("clearsign").alias("clear-sign")
+             .help("Makes a cleartext signature"))
+        .arg(Arg::with_name("armor")
+             .short("a").long("armor")
+             .help("Emits ASCII armored output"))
+        .arg(Arg::with_name("local-user")
+             .short("u").long("local-user").value_name("DSM-KEY-NAME")
+             .number_of_values(1)
+             .help("Signs using the given DSM key"))
+        .arg(Arg::with_name("verify")
+             .long("verify")
+             .help("Verifies a signature"))
+        .arg(Arg::with_name("output")
+             .short("o").long("output").value_name("FILE")
+             .help("Writes to FILE instead of stdout"))
+        .arg(Arg::with_name("status-fd")
+             .long("status-fd").value_name("FD")
+             .help("Writes status lines to file descriptor FD"))
+        .arg(Arg::with_name("keyring")
+             .long("keyring").value_name("FILE")
+             .multiple(true).number_of_values(1)
+             .help("Reads certificates for verification from FILE"))
+        // Accepted for compatibility, but ignored.
+        .arg(Arg::with_name("keyid-format")
+             .long("keyid-format").value_name("FORMAT")
+             .hidden(true))
+        .arg(Arg::with_name("no-default-keyring")
+             .long("no-default-keyring").hidden(true))
+        .arg(Arg::with_name("batch")
+             .long("batch").hidden(true))
+        .arg(Arg::with_name("no-tty")
+             .long("no-tty").hidden(true))
+        .arg(Arg::with_name("yes")
+             .long("yes").hidden(true))
+        .arg(Arg::with_name("files")
+             .value_name("FILE")
+             .multiple(true)
+             .help("Input files, `-` means stdin"))
+}
+
+/// Acts like gpg.
+pub fn dispatch(config: Config) -> Result<()> {
+    dispatch_from(config, env::args_os())
+}
+
+fn dispatch_from<I>(config: Config, args: I) -> Result<()>
+where
+    I: IntoIterator<Item = OsString>,
+{
+    let m = build().get_matches_from(args);
+    let mut status = open_status_fd(m.value_of("status-fd"))?;
+
+    if m.is_present("verify") {
+        verify(config, &m, status)
+    } else if m.is_present("sign") || m.is_present("detach-sign")
+        || m.is_present("clearsign")
+    {
+        sign(config, &m, &mut status)
+    } else {
+        Err(anyhow::anyhow!(
+            "Only --sign, --detach-sign, --clearsign, and --verify \
+             are supported"))
+    }
+}
+
+/// Opens the file descriptor given to `--status-fd`.
+///
+/// Other descriptors than stdout and stderr are reopened via
+/// `/dev/fd`, which fails if the descriptor is not open, instead of
+/// taking ownership of whatever the number refers to.
+fn open_status_fd(fd: Option<&str>) -> Result<Box<dyn io::Write>> {
+    let fd: i32 = match fd {
+        None => return Ok(Box::new(io::sink())),
+        Some(fd) => fd.parse()
+            .context(format!("Bad value passed to --status-fd: {:?}", fd))?,
+    };
+    match fd {
+        1 => Ok(Box::new(io::stdout())),
+        2 => Ok(Box::new(io::stderr())),
+        #[cfg(unix)]
+        fd if fd > 2 => {
+            let file = OpenOptions::new()
+                .append(true)
+                .open(format!("/dev/fd/{}", fd))
+                .context(format!("Failed to open --status-fd {}", fd))?;
+            Ok(Box::new(file))
+        },
+        fd => Err(anyhow::anyhow!("Unsupported --status-fd {}", fd)),
+    }
+}
+
+fn sign(mut config: Config, m: &ArgMatches, status: &mut dyn io::Write)
+        -> Result<()> {
+    let name = m.value_of("local-user")
+        .context("No signing key given, use -u DSM-KEY-NAME")?;
+    let credentials =
+        Credentials::new(Auth::from_options_or_env(None, None, None, None)?)?;
+    let presecrets = vec![PreSecret::Dsm(credentials, name.to_string())];
+
+    let files: Vec<&str> = m.values_of("files").unwrap_or_default().collect();
+    if files.len() > 1 {
+        return Err(anyhow::anyhow!("Only one input file may be given"));
+    }
+    let mut input = open_or_stdin(files.get(0).cloned().filter(|f| *f != "-"))?;
+
+    // Sign to a temporary file so that we can emit SIG_CREATED for
+    // the signatures we made before handing them out.
+    let tmp = NamedTempFile::new()?;
+    let tmp_path = tmp.path().to_str()
+        .context("Temporary path is not UTF-8")?
+        .to_string();
+    config.force = true;
+
+    let (detached, clearsign) =
+        (m.is_present("detach-sign"), m.is_present("clearsign"));
+    if clearsign {
+        let output = config.create_or_stdout_safe(Some(&tmp_path))?;
+        super::sign::clearsign(config, None, &mut input, output, presecrets,
+                               None, &[])?;
+    } else {
+        super::sign(super::sign::SignOpts {
+            config,
+            private_key_store: None,
+            input: &mut input,
+            output_path: Some(&tmp_path),
+            presecrets,
+            detached,
+            binary: ! m.is_present("armor"),
+            append: false,
+            notarize: false,
+            time: None,
+            notations: &[],
+        })?;
+    }
+
+    let kind = match (detached, clearsign) {
+        (true, _) => 'D',
+        (_, true) => 'C',
+        _ => 'S',
+    };
+    for sig in signatures_in(tmp.path())? {
+        writeln!(status, "[GNUPG:] SIG_CREATED {} {} {} {:02x} {} {}",
+                 kind,
+                 u8::from(sig.pk_algo()),
+                 u8::from(sig.hash_algo()),
+                 u8::from(sig.typ()),
+                 sig.signature_creation_time()
+                     .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                     .map(|d| d.as_secs())
+                     .unwrap_or(0),
+                 sig.issuer_fingerprints().next()
+                     .map(|fp| fp.to_hex())
+                     .unwrap_or_default())?;
+    }
+
+    let mut output = match m.value_of("output") {
+        None | Some("-") => Box::new(io::stdout()) as Box<dyn io::Write>,
+        Some(f) => Box::new(File::create(f)
+                            .context("Failed to create output file")?),
+    };
+    io::copy(&mut File::open(tmp.path())?, &mut output)?;
+    Ok(())
+}
+
+/// Returns the signatures in the given OpenPGP data.
+fn signatures_in(path: &Path) -> Result<Vec<Signature>> {
+    let mut sigs = Vec::new();
+    let mut ppr = PacketParser::from_file(path)?;
+    while let PacketParserResult::Some(pp) = ppr {
+        let (packet, ppr_) = pp.recurse()?;
+        ppr = ppr_;
+        if let Packet::Signature(sig) = packet {
+            sigs.push(sig);
+        }
+    }
+    Ok(sigs)
+}
+
+fn verify(config: Config, m: &ArgMatches, status: Box<dyn io::Write>)
+          -> Result<()> {
+    let mut keyrings: Vec<String> = m.values_of("keyring")
+        .unwrap_or_default()
+        .map(Into::into)
+        .collect();
+    if let Some(paths) = env::var_os(KEYRING_ENV) {
+        for path in env::split_paths(&paths) {
+            keyrings.push(path.to_str()
+                          .context(format!("{} is not UTF-8", KEYRING_ENV))?
+                          .into());
+        }
+    }
+    let certs = load_certs(keyrings.iter().map(|s| s.as_str()))?;
+
+    let files: Vec<&str> = m.values_of("files").unwrap_or_default().collect();
+    match files.len() {
+        // An inline-signed message, or a cleartext signature.
+        0 | 1 => {
+            let mut input = open_or_stdin(files.get(0).cloned()
+                                          .filter(|f| *f != "-"))?;
+            super::verify_with_status(config, &mut input, None,
+                                      &mut io::sink(), 1, certs, status)
+        },
+        // A detached signature, then the signed data.
+        2 => {
+            let mut sig = File::open(files[0])
+                .context("Failed to open signature file")?;
+            let mut input = open_or_stdin(Some(files[1])
+                                          .filter(|f| *f != "-"))?;
+            super::verify_with_status(config, &mut input,
+                                      Some(&mut sig as &mut (dyn io::Read + Sync + Send)),
+                                      &mut io::sink(), 1, certs, status)
+        },
+        _ => Err(anyhow::anyhow!("Too many files given to --verify")),
+    }
+}
@@ -14,15 +14,28 @@ use crate::openpgp::parse::{Parse, PacketParserResult};
 use crate::openpgp::policy::Policy;
 use crate::openpgp::packet::key::SecretKeyMaterial;
 
+use crate::output::{self, OutputFormat};
 use super::dump::Convert;
 
-pub fn inspect(m: &clap::ArgMatches, policy: &dyn Policy, output: &mut dyn io::Write)
+pub fn inspect(m: &clap::ArgMatches, policy: &dyn Policy,
+               output_format: OutputFormat, output: &mut dyn io::Write)
                -> Result<()> {
     let print_certifications = m.is_present("certifications");
+    let json = output_format == OutputFormat::Json;
 
     let input = m.value_of("input");
     let input_name = input.unwrap_or("-");
-    write!(output, "{}: ", input_name)?;
+    if ! json {
+        write!(output, "{}: ", input_name)?;
+    }
+    let mut inspection = output::Inspection {
+        input: input_name.to_string(),
+        typ: "unknown",
+        certificates: Vec::new(),
+        message: None,
+        signatures: Vec::new(),
+        errors: Vec::new(),
+    };
 
     let mut type_called = false;  // Did we print the type yet?
     let mut encrypted = false;    // Is it an encrypted message?
@@ -41,15 +54,21 @@ pub fn inspect(m: &clap::ArgMatches, policy: &dyn Policy, output: &mut dyn io::W
                     && pp.possible_keyring().is_ok()
                 {
                     if ! type_called {
-                        writeln!(output, "OpenPGP Keyring.")?;
-                        writeln!(output)?;
+                        if ! json {
+                            writeln!(output, "OpenPGP Keyring.")?;
+                            writeln!(output)?;
+                        }
                         type_called = true;
                     }
                     let pp = openpgp::PacketPile::from(
                         std::mem::take(&mut packets));
                     let cert = openpgp::Cert::try_from(pp)?;
-                    inspect_cert(policy, output, &cert,
-                                 print_certifications)?;
+                    if json {
+                        inspection.certificates.push(cert_info(policy, &cert));
+                    } else {
+                        inspect_cert(policy, output, &cert,
+                                     print_certifications)?;
+                    }
                 }
             },
             Packet::Literal(_) => {
@@ -82,7 +101,45 @@ pub fn inspect(m: &clap::ArgMatches, policy: &dyn Policy, output: &mut dyn io::W
         let is_cert = eof.is_cert();
         let is_keyring = eof.is_keyring();
 
-        if is_message.is_ok() {
+        if json {
+            if is_message.is_ok() {
+                inspection.typ = "message";
+                inspection.message = Some(output::MessageInfo {
+                    encrypted,
+                    signed: ! sigs.is_empty(),
+                    recipients: pkesks.iter()
+                        .map(|pkesk| pkesk.recipient().to_hex())
+                        .collect(),
+                    passwords: n_skesks,
+                });
+                inspection.signatures =
+                    sigs.iter().map(Into::into).collect();
+            } else if is_cert.is_ok() || is_keyring.is_ok() {
+                let pp = openpgp::PacketPile::from(packets);
+                let cert = openpgp::Cert::try_from(pp)?;
+                inspection.typ = if type_called {
+                    "keyring"
+                } else if cert.is_tsk() {
+                    "transferable-secret-key"
+                } else {
+                    "certificate"
+                };
+                inspection.certificates.push(cert_info(policy, &cert));
+            } else if packets.is_empty() && ! sigs.is_empty() {
+                inspection.typ = "detached-signatures";
+                inspection.signatures =
+                    sigs.iter().map(Into::into).collect();
+            } else if packets.is_empty() {
+                inspection.typ = "no-data";
+            } else {
+                inspection.errors = vec![
+                    format!("Message: {}", is_message.unwrap_err()),
+                    format!("Cert: {}", is_cert.unwrap_err()),
+                    format!("Keyring: {}", is_keyring.unwrap_err()),
+                ];
+            }
+            output::emit(output, &inspection)?;
+        } else if is_message.is_ok() {
             writeln!(output, "{}OpenPGP Message.",
                      match (encrypted, ! sigs.is_empty()) {
                          (false, false) => "",
@@ -221,6 +278,89 @@ fn inspect_cert(policy: &dyn Policy,
     Ok(())
 }
 
+/// Describes the structure of a certificate for machine-readable output.
+fn cert_info(policy: &dyn Policy, cert: &openpgp::Cert) -> output::CertInfo {
+    fn binding_errors<C>(policy: &dyn Policy, bundle: &ComponentAmalgamation<C>)
+                         -> Vec<String> {
+        match bundle.binding_signature(policy, None) {
+            Ok(sig) => sig
+                .signature_alive(None, std::time::Duration::new(0, 0))
+                .err()
+                .map(|e| output::error_chain(&e))
+                .unwrap_or_default(),
+            Err(e) => output::error_chain(&e),
+        }
+    }
+
+    output::CertInfo {
+        fingerprint: cert.fingerprint().to_hex(),
+        secret: cert.is_tsk(),
+        revocation: cert.revocation_status(policy, None).into(),
+        primary_key: key_info(policy, cert.keys().next().unwrap()),
+        subkeys: cert.keys().subkeys()
+            .map(|ka| key_info(policy, ka.into()))
+            .collect(),
+        userids: cert.userids().map(|uidb| {
+            let errors = binding_errors(policy, &uidb);
+            output::UserIDInfo {
+                userid: String::from_utf8_lossy(uidb.userid().value()).into(),
+                valid: errors.is_empty(),
+                errors,
+                revocation: uidb.revocation_status(policy, None).into(),
+                certifications: uidb.certifications().map(Into::into).collect(),
+            }
+        }).collect(),
+        user_attributes: cert.user_attributes().count(),
+        bad_signatures: cert.bad_signatures().count(),
+    }
+}
+
+/// Describes a key for machine-readable output.
+fn key_info(policy: &dyn Policy, ka: ErasedKeyAmalgamation<PublicParts>)
+            -> output::KeyInfo {
+    let key = ka.key();
+    let bundle = ka.bundle();
+    let (vka, errors) = match ka.clone().with_policy(policy, None) {
+        Ok(vka) => {
+            let errors = vka.alive().err()
+                .map(|e| output::error_chain(&e))
+                .unwrap_or_default();
+            (Some(vka), errors)
+        },
+        Err(e) => (None, output::error_chain(&e)),
+    };
+
+    output::KeyInfo {
+        fingerprint: key.fingerprint().to_hex(),
+        algorithm: key.pk_algo().to_string(),
+        bits: key.mpis().bits(),
+        secret: key.optional_secret().map(|secret| {
+            if let SecretKeyMaterial::Unencrypted(_) = secret {
+                "unencrypted"
+            } else {
+                "encrypted"
+            }
+        }),
+        creation_time: output::time(key.creation_time()),
+        expiration_time: vka.as_ref()
+            .and_then(|vka| vka.key_validity_period())
+            .map(|d| output::time(key.creation_time() + d)),
+        flags: vka.as_ref()
+            .and_then(|vka| vka.key_flags())
+            .map(|flags| output::key_flags(&flags))
+            .unwrap_or_default(),
+        valid: vka.is_some() && errors.is_empty(),
+        errors,
+        revocation: match &vka {
+            Some(vka) => vka.revocation_status().into(),
+            None => openpgp::types::RevocationStatus::NotAsFarAsWeKnow.into(),
+        },
+        certifications: bundle.certifications().iter()
+            .map(Into::into)
+            .collect(),
+    }
+}
+
 fn inspect_key(policy: &dyn Policy,
                output: &mut dyn io::Write,
                indent: &str,
@@ -1,12 +1,15 @@
 use anyhow::Context as _;
 use clap::ArgMatches;
 use itertools::Itertools;
+use std::io::{self, Write};
+use std::path::PathBuf;
 use std::time::{SystemTime, Duration};
 
 use crate::openpgp::KeyHandle;
 use crate::openpgp::Packet;
 use crate::openpgp::Result;
 use crate::openpgp::armor::{Writer, Kind};
+use crate::openpgp::crypto::{KeyPair, Password, Signer};
 use crate::openpgp::cert::prelude::*;
 use crate::openpgp::packet::prelude::*;
 use crate::openpgp::packet::signature::subpacket::SubpacketTag;
@@ -23,20 +26,51 @@ use crate::Config;
 use crate::SECONDS_IN_YEAR;
 use crate::parse_duration;
 use crate::decrypt_key;
+use crate::secrets::dsm_auth;
 
 pub fn dispatch(config: Config, m: &clap::ArgMatches) -> Result<()> {
     match m.subcommand() {
         ("generate", Some(m)) => generate(config, m)?,
         ("export", Some(m)) => generate(config, m)?,
         ("dsm-import", Some(m)) => dsm_import(config, m)?,
+        ("dsm-disable", Some(m)) => {
+            let how = if m.is_present("deactivate") {
+                dsm::Retirement::Deactivate
+            } else {
+                dsm::Retirement::Disable
+            };
+            dsm_retire(config, m, how)?
+        },
+        ("dsm-delete", Some(m)) =>
+            dsm_retire(config, m, dsm::Retirement::Destroy)?,
+        ("dsm-check", Some(m)) => dsm_check(config, m)?,
+        ("dsm-audit-verify", Some(m)) => dsm_audit_verify(config, m)?,
         ("password", Some(m)) => password(config, m)?,
         ("extract-cert", Some(m)) => extract_cert(config, m)?,
         ("info", Some(m)) => print_dsm_key_info(config, m)?,
         ("list-dsm-keys", Some(m)) => list_dsm_keys(config, m)?,
+        #[cfg(unix)]
+        ("gpg-agent", Some(m)) => super::agent::gpg_agent(config, m)?,
+        #[cfg(not(unix))]
+        ("gpg-agent", Some(_)) => return Err(anyhow::anyhow!(
+            "\"sq key gpg-agent\" is not supported on this platform")),
+        #[cfg(unix)]
+        ("ssh-agent", Some(m)) => super::agent::ssh_agent(config, m)?,
+        #[cfg(not(unix))]
+        ("ssh-agent", Some(_)) => return Err(anyhow::anyhow!(
+            "\"sq key ssh-agent\" is not supported on this platform")),
+        #[cfg(unix)]
+        ("export-ssh", Some(m)) => export_ssh(config, m)?,
+        #[cfg(not(unix))]
+        ("export-ssh", Some(_)) => return Err(anyhow::anyhow!(
+            "\"sq key export-ssh\" is not supported on this platform")),
         ("extract-dsm-secret", Some(m)) => extract_dsm(config, m)?,
         ("adopt", Some(m)) => adopt(config, m)?,
         ("attest-certifications", Some(m)) =>
             attest_certifications(config, m)?,
+        ("subkey", Some(m)) => subkey(config, m)?,
+        ("userid", Some(m)) => userid(config, m)?,
+        ("expire", Some(m)) => expire(config, m)?,
         _ => unreachable!(),
         }
     Ok(())
@@ -77,12 +111,7 @@ fn generate(config: Config, m: &ArgMatches) -> Result<()> {
 
     if let Some(dsm_key_name) = m.value_of("dsm-key") {
         // Fortanix DSM
-        let dsm_secret = dsm::Auth::from_options_or_env(
-            m.value_of("api-key"),
-            m.value_of("client-cert"),
-            m.value_of("app-uuid"),
-            m.value_of("pkcs12-passphrase"),
-        )?;
+        let dsm_secret = dsm_auth(m)?;
 
         let mut key_flags: Vec<KeyFlags> = vec![];
         match m.value_of("key-flags") {
@@ -370,12 +399,7 @@ fn _unlock(key: Cert) -> Result<Cert> {
 }
 
 fn print_dsm_key_info(_config: Config, m: &ArgMatches) -> Result<()> {
-    let dsm_secret = dsm::Auth::from_options_or_env(
-        m.value_of("api-key"),
-        m.value_of("client-cert"),
-        m.value_of("app-uuid"),
-        m.value_of("pkcs12-passphrase"),
-    )?;
+    let dsm_secret = dsm_auth(m)?;
     let dsm_auth = dsm::Credentials::new(dsm_secret)?;
 
     let output = match m.value_of("dsm-key") {
@@ -395,12 +419,7 @@ fn print_dsm_key_info(_config: Config, m: &ArgMatches) -> Result<()> {
 }
 
 fn list_dsm_keys(_config: Config, m: &ArgMatches) -> Result<()> {
-    let dsm_secret = dsm::Auth::from_options_or_env(
-        m.value_of("api-key"),
-        m.value_of("client-cert"),
-        m.value_of("app-uuid"),
-        m.value_of("pkcs12-passphrase"),
-    )?;
+    let dsm_secret = dsm_auth(m)?;
     let dsm_auth = dsm::Credentials::new(dsm_secret)?;
     let verbose = m.is_present("long");
     let output = dsm::list_keys(dsm_auth)?;
@@ -431,40 +450,62 @@ fn list_dsm_keys(_config: Config, m: &ArgMatches) -> Result<()> {
 
 fn extract_cert(config: Config, m: &ArgMatches) -> Result<()> {
     let mut output = config.create_or_stdout_safe(m.value_of("output"))?;
+    let cert = cert_from_input_or_dsm(m)?;
+
+    if m.is_present("binary") {
+        cert.serialize(&mut output)?;
+    } else {
+        cert.armored().serialize(&mut output)?;
+    }
+    Ok(())
+}
 
-    let cert = match m.value_of("dsm-key") {
+/// Reads the cert of the DSM key given by --dsm-key, or from the input
+/// file.
+fn cert_from_input_or_dsm(m: &ArgMatches) -> Result<Cert> {
+    match m.value_of("dsm-key") {
         Some(key_name) => {
             // Fortanix DSM
-            let dsm_secret = dsm::Auth::from_options_or_env(
-                m.value_of("api-key"),
-                m.value_of("client-cert"),
-                m.value_of("app-uuid"),
-                m.value_of("pkcs12-passphrase"),
-            )?;
+            let dsm_secret = dsm_auth(m)?;
             let dsm_auth = dsm::Credentials::new(dsm_secret)?;
-            dsm::extract_cert(key_name, dsm_auth)?
+            dsm::extract_cert(key_name, dsm_auth)
         }
         None => {
             let input = open_or_stdin(m.value_of("input"))?;
-            Cert::from_reader(input)?
+            Cert::from_reader(input)
         }
+    }
+}
+
+#[cfg(unix)]
+fn export_ssh(config: Config, m: &ArgMatches) -> Result<()> {
+    let mut output = config.create_or_stdout_safe(m.value_of("output"))?;
+    let cert = cert_from_input_or_dsm(m)?;
+    let vc = cert.with_policy(&config.policy, None)?;
+
+    let comment = match vc.primary_userid() {
+        Ok(uid) => String::from_utf8_lossy(uid.value()).into_owned(),
+        Err(_) => cert.fingerprint().to_hex(),
     };
 
-    if m.is_present("binary") {
-        cert.serialize(&mut output)?;
-    } else {
-        cert.armored().serialize(&mut output)?;
+    let mut found = false;
+    for ka in vc.keys().alive().revoked(false).for_authentication() {
+        let line =
+            sequoia_ipc::ssh::authorized_keys_line(ka.key().mpis(), &comment)
+            .context(format!("Cannot export {} for SSH", ka.keyid()))?;
+        writeln!(output, "{}", line)?;
+        found = true;
+    }
+
+    if ! found {
+        return Err(anyhow::anyhow!(
+            "{} has no valid authentication key", cert.fingerprint()));
     }
     Ok(())
 }
 
 fn dsm_import(config: Config, m: &ArgMatches) -> Result<()> {
-    let dsm_secret = dsm::Auth::from_options_or_env(
-        m.value_of("api-key"),
-        m.value_of("client-cert"),
-        m.value_of("app-uuid"),
-        m.value_of("pkcs12-passphrase"),
-    )?;
+    let dsm_secret = dsm_auth(m)?;
     let dsm_auth = dsm::Credentials::new(dsm_secret)?;
     let input = open_or_stdin(m.value_of("input"))?;
     let cert = Cert::from_reader(input)?;
@@ -479,13 +520,87 @@ fn dsm_import(config: Config, m: &ArgMatches) -> Result<()> {
     }
 }
 
+fn dsm_retire(_config: Config, m: &ArgMatches, how: dsm::Retirement)
+              -> Result<()> {
+    let dsm_secret = dsm_auth(m)?;
+    let dsm_auth = dsm::Credentials::new(dsm_secret)?;
+    let key_name = m.value_of("dsm-key").expect("name is compulsory");
+
+    let components = dsm::key_components(dsm_auth.clone(), key_name)?;
+    let what = match how {
+        dsm::Retirement::Disable => "disabled",
+        dsm::Retirement::Deactivate => "deactivated",
+        dsm::Retirement::Destroy => "DESTROYED (this cannot be undone)",
+    };
+    println!("The following {} Security Objects will be {}:\n",
+             components.len(), what);
+    for key in &components {
+        println!("{}", key.format_details_long());
+    }
+
+    if ! m.is_present("yes") {
+        print!("Proceed? [y/N] ");
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        if ! matches!(answer.trim(), "y" | "Y" | "yes") {
+            return Err(anyhow::anyhow!("Aborted, no changes were made"));
+        }
+    }
+
+    dsm::retire_key(dsm_auth, key_name, how)?;
+    println!("OK");
+
+    Ok(())
+}
+
+fn dsm_check(_config: Config, m: &ArgMatches) -> Result<()> {
+    let dsm_secret = dsm_auth(m)?;
+    let dsm_auth = dsm::Credentials::new(dsm_secret)?;
+
+    let checks = dsm::check_keys(dsm_auth, m.value_of("dsm-key"),
+                                 m.is_present("repair"))?;
+
+    let mut unrepaired = 0;
+    for check in &checks {
+        if check.findings.is_empty() {
+            println!("{}: OK", check.name);
+            continue;
+        }
+        println!("{}:", check.name);
+        for finding in &check.findings {
+            println!("  {}: {}{}", finding.sobject, finding.problem,
+                     if finding.repaired { " (repaired)" } else { "" });
+            if ! finding.repaired {
+                unrepaired += 1;
+            }
+        }
+    }
+
+    if unrepaired > 0 {
+        return Err(anyhow::anyhow!("{} unrepaired problem(s) found",
+                                   unrepaired));
+    }
+    Ok(())
+}
+
+fn dsm_audit_verify(_config: Config, m: &ArgMatches) -> Result<()> {
+    let path = match m.value_of_os("log") {
+        Some(path) => PathBuf::from(path),
+        None => std::env::var_os("SQ_DSM_AUDIT_LOG")
+            .map(PathBuf::from)
+            .ok_or_else(|| anyhow::anyhow!(
+                "No audit log given, and SQ_DSM_AUDIT_LOG is not set"))?,
+    };
+
+    let records = dsm::audit::verify_log(&path)
+        .context(format!("Failed to verify {}", path.display()))?;
+    println!("{}: {} records, hash chain OK", path.display(), records);
+    Ok(())
+}
+
 fn extract_dsm(config: Config, m: &ArgMatches) -> Result<()> {
-    let dsm_secret = dsm::Auth::from_options_or_env(
-        m.value_of("api-key"),
-        m.value_of("client-cert"),
-        m.value_of("app-uuid"),
-        m.value_of("pkcs12-passphrase"),
-    )?;
+    let dsm_secret = dsm_auth(m)?;
     let dsm_auth = dsm::Credentials::new(dsm_secret)?;
     let key = match m.value_of("dsm-key") {
         Some(key_name) => dsm::extract_tsk_from_dsm(key_name, dsm_auth)?,
@@ -755,3 +870,307 @@ fn attest_certifications(config: Config, m: &ArgMatches)
 
     Ok(())
 }
+
+/// Reads the key to edit.
+fn read_key(m: &ArgMatches) -> Result<Cert> {
+    let input = open_or_stdin(m.value_of("key"))?;
+    let key = Cert::from_reader(input)?;
+    if ! key.is_tsk() {
+        return Err(anyhow::anyhow!("Input is not a Transferable Secret Key"));
+    }
+    Ok(key)
+}
+
+/// Writes the edited key.
+fn write_key(config: Config, m: &ArgMatches, key: &Cert) -> Result<()> {
+    let mut sink = config.create_or_stdout_safe(m.value_of("output"))?;
+    if m.is_present("binary") {
+        key.as_tsk().serialize(&mut sink)?;
+    } else {
+        key.as_tsk().armored().serialize(&mut sink)?;
+    }
+    Ok(())
+}
+
+/// Returns a signer for the primary key, unlocking it if necessary.
+fn primary_signer(key: &Cert, passwords: &mut Vec<String>)
+                  -> Result<KeyPair>
+{
+    decrypt_key(key.primary_key().key().clone().parts_into_secret()?,
+                passwords)?
+        .into_keypair()
+}
+
+/// Returns the expiration time given using `--expires` or
+/// `--expires-in`.
+///
+/// Returns `None` if the key shall not expire.
+fn expiration(m: &ArgMatches) -> Result<Option<SystemTime>> {
+    match (m.value_of("expires"), m.value_of("expires-in")) {
+        (None, None) => Ok(None),
+        (Some(t), None) if t == "never" => Ok(None),
+        (Some(t), None) => Ok(Some(SystemTime::from(
+            crate::parse_iso8601(t, chrono::NaiveTime::from_hms(0, 0, 0))?))),
+        (None, Some(d)) if d == "never" => Ok(None),
+        (None, Some(d)) => Ok(Some(SystemTime::now() + parse_duration(d)?)),
+        (Some(_), Some(_)) => unreachable!("conflicting args"),
+    }
+}
+
+fn subkey(config: Config, m: &ArgMatches) -> Result<()> {
+    match m.subcommand() {
+        ("add", Some(m)) => subkey_add(config, m),
+        ("expire", Some(m)) => subkey_expire(config, m),
+        _ => unreachable!(),
+    }
+}
+
+/// Generates a new subkey with the given capabilities.
+fn generate_subkey(cipher_suite: &str, flags: &KeyFlags)
+                   -> Result<Key<key::SecretParts, key::SubordinateRole>>
+{
+    use crate::openpgp::types::Curve;
+
+    let sign = flags.for_certification() || flags.for_signing()
+        || flags.for_authentication();
+    let key: Key4<_, key::SubordinateRole> = match cipher_suite {
+        "rsa2k" => Key4::generate_rsa(2048)?,
+        "rsa3k" => Key4::generate_rsa(3072)?,
+        "rsa4k" => Key4::generate_rsa(4096)?,
+        "cv25519" if sign => Key4::generate_ecc(true, Curve::Ed25519)?,
+        "cv25519" => Key4::generate_ecc(false, Curve::Cv25519)?,
+        "nistp256" => Key4::generate_ecc(sign, Curve::NistP256)?,
+        "nistp384" => Key4::generate_ecc(sign, Curve::NistP384)?,
+        "nistp521" => Key4::generate_ecc(sign, Curve::NistP521)?,
+        cs => return Err(anyhow::anyhow!("Unknown cipher suite '{}'", cs)),
+    };
+    Ok(key.into())
+}
+
+fn subkey_add(config: Config, m: &ArgMatches) -> Result<()> {
+    let key = read_key(m)?;
+    let expiration = expiration(m)?;
+
+    let flags = if m.is_present("can-sign") {
+        KeyFlags::empty().set_signing()
+    } else if m.is_present("can-authenticate") {
+        KeyFlags::empty().set_authentication()
+    } else {
+        match m.value_of("can-encrypt") {
+            Some("transport") =>
+                KeyFlags::empty().set_transport_encryption(),
+            Some("storage") =>
+                KeyFlags::empty().set_storage_encryption(),
+            Some("universal") | None =>
+                KeyFlags::empty().set_transport_encryption()
+                .set_storage_encryption(),
+            Some(cap) => return Err(
+                anyhow::anyhow!("Unknown encryption capability '{}'", cap)),
+        }
+    };
+
+    let passwords = &mut Vec::new();
+    let mut pk_signer = primary_signer(&key, passwords)?;
+    let pk = key.primary_key().key();
+
+    let subkey = generate_subkey(
+        m.value_of("cipher-suite").expect("has a default"), &flags)?;
+
+    let mut builder = SignatureBuilder::new(SignatureType::SubkeyBinding)
+        .set_key_flags(flags.clone())?
+        .set_key_expiration_time(&subkey, expiration)?;
+    if flags.for_signing() || flags.for_certification() {
+        let mut subkey_signer = subkey.clone().into_keypair()?;
+        let backsig = SignatureBuilder::new(SignatureType::PrimaryKeyBinding)
+            .sign_primary_key_binding(&mut subkey_signer, pk, &subkey)?;
+        builder = builder.set_embedded_signature(backsig)?;
+    }
+    let sig = subkey.bind(&mut pk_signer, &key, builder)?;
+
+    // Protect the new subkey like the primary key.
+    let subkey = if pk.has_unencrypted_secret() {
+        subkey
+    } else {
+        let password = passwords.last()
+            .ok_or_else(|| anyhow::anyhow!("No password to protect the \
+                                            new subkey with"))?;
+        subkey.encrypt_secret(&Password::from(&password[..]))?
+    };
+
+    let key = key.insert_packets(vec![Packet::from(subkey), sig.into()])?;
+    write_key(config, m, &key)
+}
+
+fn subkey_expire(config: Config, m: &ArgMatches) -> Result<()> {
+    let key = read_key(m)?;
+    let expiration = expiration(m)?;
+
+    let mut wanted = Vec::new();
+    for id in m.values_of("subkey").expect("required") {
+        let h = id.parse::<KeyHandle>()?;
+        if h.is_invalid() {
+            return Err(anyhow::anyhow!(
+                "Invalid Fingerprint or KeyID ('{:?}')", id));
+        }
+        wanted.push(h);
+    }
+
+    let vc = key.with_policy(&config.policy, None)?;
+    let subkeys = vc.keys().subkeys()
+        .filter(|ka| wanted.iter().any(|h| h.aliases(ka.key_handle())))
+        .collect::<Vec<_>>();
+    let missing = wanted.iter()
+        .filter(|h| ! subkeys.iter().any(|ka| h.aliases(ka.key_handle())))
+        .map(|h| h.to_hex())
+        .collect::<Vec<_>>();
+    if ! missing.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Subkeys not found: {}", missing.join(", ")));
+    }
+
+    let passwords = &mut Vec::new();
+    let mut pk_signer = primary_signer(&key, passwords)?;
+
+    let mut sigs = Vec::new();
+    for ka in subkeys {
+        // Signing-capable subkeys need a new backsig.
+        let mut subkey_signer =
+            if ka.for_signing() || ka.for_certification() {
+                Some(decrypt_key(ka.key().clone().parts_into_secret()?,
+                                 passwords)?
+                     .into_keypair()?)
+            } else {
+                None
+            };
+        sigs.append(&mut ka.set_expiration_time(
+            &mut pk_signer,
+            subkey_signer.as_mut()
+                .map(|s| s as &mut dyn Signer),
+            expiration)?);
+    }
+
+    let key = key.insert_packets(sigs)?;
+    write_key(config, m, &key)
+}
+
+fn userid(config: Config, m: &ArgMatches) -> Result<()> {
+    match m.subcommand() {
+        ("add", Some(m)) => userid_add(config, m),
+        ("strip", Some(m)) => userid_strip(config, m),
+        ("set-primary", Some(m)) => userid_set_primary(config, m),
+        _ => unreachable!(),
+    }
+}
+
+fn userid_add(config: Config, m: &ArgMatches) -> Result<()> {
+    let key = read_key(m)?;
+    let vc = key.with_policy(&config.policy, None)?;
+
+    // Use the primary User ID's binding signature as template, so
+    // that the new User ID gets the same preferences and expiration.
+    let template = vc.primary_userid()
+        .map(|ua| ua.binding_signature().clone())
+        .or_else(|_| vc.direct_key_signature().map(Clone::clone))
+        .context("The key has no valid self signature")?;
+
+    let passwords = &mut Vec::new();
+    let mut pk_signer = primary_signer(&key, passwords)?;
+
+    let mut packets: Vec<Packet> = Vec::new();
+    for userid in m.values_of("userid").expect("required") {
+        let userid = UserID::from(userid);
+        if key.userids().any(|ua| ua.userid() == &userid) {
+            return Err(anyhow::anyhow!(
+                "The key already has the User ID {:?}",
+                String::from_utf8_lossy(userid.value())));
+        }
+
+        let builder = SignatureBuilder::from(template.clone())
+            .set_type(SignatureType::PositiveCertification)
+            .set_signature_creation_time(SystemTime::now())?
+            .modify_hashed_area(|mut a| {
+                a.remove_all(SubpacketTag::PrimaryUserID);
+                Ok(a)
+            })?;
+        let sig = userid.bind(&mut pk_signer, &key, builder)?;
+        packets.push(userid.into());
+        packets.push(sig.into());
+    }
+
+    let key = key.insert_packets(packets)?;
+    write_key(config, m, &key)
+}
+
+fn userid_strip(config: Config, m: &ArgMatches) -> Result<()> {
+    let key = read_key(m)?;
+
+    let strip = m.values_of("userid").expect("required")
+        .map(UserID::from)
+        .collect::<Vec<_>>();
+    for userid in &strip {
+        if ! key.userids().any(|ua| ua.userid() == userid) {
+            return Err(anyhow::anyhow!(
+                "The key does not have the User ID {:?}",
+                String::from_utf8_lossy(userid.value())));
+        }
+    }
+
+    let key = key.retain_userids(|ua| ! strip.contains(ua.userid()));
+    key.with_policy(&config.policy, None)
+        .context("The key would not be valid without these User IDs")?;
+
+    eprintln!("Note: Stripping User IDs only removes them from this copy \
+               of the key.  To retract a User ID that has been \
+               published, revoke it using \"sq revoke userid\".");
+
+    write_key(config, m, &key)
+}
+
+fn userid_set_primary(config: Config, m: &ArgMatches) -> Result<()> {
+    let key = read_key(m)?;
+    let primary = UserID::from(m.value_of("userid").expect("required"));
+
+    let vc = key.with_policy(&config.policy, None)?;
+    if ! vc.userids().revoked(false).any(|ua| ua.userid() == &primary) {
+        return Err(anyhow::anyhow!(
+            "The key does not have a valid User ID {:?}",
+            String::from_utf8_lossy(primary.value())));
+    }
+
+    let passwords = &mut Vec::new();
+    let mut pk_signer = primary_signer(&key, passwords)?;
+    let pk = key.primary_key().key();
+
+    // Re-sign all User IDs, so that only the new primary User ID is
+    // marked as such.
+    let mut sigs = Vec::new();
+    for ua in vc.userids().revoked(false) {
+        let builder = SignatureBuilder::from(ua.binding_signature().clone());
+        let builder = if ua.userid() == &primary {
+            builder.set_primary_userid(true)?
+        } else {
+            builder.modify_hashed_area(|mut a| {
+                a.remove_all(SubpacketTag::PrimaryUserID);
+                Ok(a)
+            })?
+        };
+        sigs.push(builder.sign_userid_binding(&mut pk_signer, pk,
+                                              ua.userid())?);
+    }
+
+    let key = key.insert_packets(sigs)?;
+    write_key(config, m, &key)
+}
+
+fn expire(config: Config, m: &ArgMatches) -> Result<()> {
+    let key = read_key(m)?;
+    let expiration = expiration(m)?;
+
+    let passwords = &mut Vec::new();
+    let mut pk_signer = primary_signer(&key, passwords)?;
+    let sigs = key.set_expiration_time(&config.policy, None, &mut pk_signer,
+                                       expiration)?;
+
+    let key = key.insert_packets(sigs)?;
+    write_key(config, m, &key)
+}
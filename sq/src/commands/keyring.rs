@@ -29,6 +29,7 @@ use crate::{
     Config,
     open_or_stdin,
 };
+use crate::output::{self, OutputFormat};
 
 pub fn dispatch(config: Config, m: &clap::ArgMatches) -> Result<()> {
     match m.subcommand() {
@@ -156,8 +157,11 @@ pub fn dispatch(config: Config, m: &clap::ArgMatches) -> Result<()> {
         },
         ("list",  Some(m)) => {
             let mut input = open_or_stdin(m.value_of("input"))?;
-            list(config, &mut input, m.is_present("all-userids"))
+            list(config, CertParser::from_reader(&mut input)?,
+                 m.is_present("all-userids"))
         },
+        ("lint",  Some(m)) => super::lint::lint(config, m),
+        ("diff",  Some(m)) => super::diff::diff(config, m),
         ("split",  Some(m)) => {
             let mut input = open_or_stdin(m.value_of("input"))?;
             let prefix =
@@ -217,16 +221,13 @@ fn filter<F>(inputs: Option<clap::Values>, output: &mut dyn io::Write,
 }
 
 /// Lists certs in a keyring.
-fn list(config: Config,
-        input: &mut (dyn io::Read + Sync + Send),
-        list_all_uids: bool)
-        -> Result<()>
+pub fn list<I>(config: Config, certs: I, list_all_uids: bool) -> Result<()>
+    where I: IntoIterator<Item = Result<Cert>>
 {
-    for (i, cert) in CertParser::from_reader(input)?.enumerate() {
+    let mut listing = output::KeyringListing { certificates: Vec::new() };
+
+    for (i, cert) in certs.into_iter().enumerate() {
         let cert = cert.context("Malformed certificate in keyring")?;
-        let line = format!("{}. {:X}", i, cert.fingerprint());
-        let indent = line.chars().map(|_| ' ').collect::<String>();
-        print!("{}", line);
 
         // Try to be more helpful by including a User ID in the
         // listing.  We'd like it to be the primary one.  Use
@@ -236,7 +237,6 @@ fn list(config: Config,
         // First, apply our policy.
         if let Ok(vcert) = cert.with_policy(&config.policy, None) {
             if let Ok(primary) = vcert.primary_userid() {
-                println!(" {}", String::from_utf8_lossy(primary.value()));
                 primary_uid = Some(primary.value().to_vec());
             }
         }
@@ -246,7 +246,6 @@ fn list(config: Config,
             let null = openpgp::policy::NullPolicy::new();
             if let Ok(vcert) = cert.with_policy(&null, None) {
                 if let Ok(primary) = vcert.primary_userid() {
-                    println!(" {}", String::from_utf8_lossy(primary.value()));
                     primary_uid = Some(primary.value().to_vec());
                 }
             }
@@ -255,12 +254,29 @@ fn list(config: Config,
         // As a last resort, pick the first user id.
         if primary_uid.is_none() {
             if let Some(primary) = cert.userids().next() {
-                println!(" {}", String::from_utf8_lossy(primary.value()));
                 primary_uid = Some(primary.value().to_vec());
             }
         }
 
-        if primary_uid.is_none() {
+        if config.output_format == OutputFormat::Json {
+            listing.certificates.push(output::KeyringEntry {
+                index: i,
+                fingerprint: cert.fingerprint().to_hex(),
+                primary_userid: primary_uid.as_ref()
+                    .map(|u| String::from_utf8_lossy(u).into()),
+                userids: cert.userids()
+                    .map(|u| String::from_utf8_lossy(u.value()).into())
+                    .collect(),
+            });
+            continue;
+        }
+
+        let line = format!("{}. {:X}", i, cert.fingerprint());
+        let indent = line.chars().map(|_| ' ').collect::<String>();
+        print!("{}", line);
+        if let Some(primary) = &primary_uid {
+            println!(" {}", String::from_utf8_lossy(primary));
+        } else {
             // No dice.
             println!();
         }
@@ -280,6 +296,10 @@ fn list(config: Config,
             }
         }
     }
+
+    if config.output_format == OutputFormat::Json {
+        output::emit(&mut io::stdout(), &listing)?;
+    }
     Ok(())
 }
 
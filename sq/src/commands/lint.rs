@@ -0,0 +1,441 @@
+//! Detects and repairs weak self-signatures.
+//!
+//! Certificates created by older OpenPGP implementations often have
+//! self-signatures using SHA-1, or signing-capable subkeys without a
+//! primary key binding signature ("backsig").  The standard policy
+//! rejects these, rendering the affected components, or even the
+//! whole certificate, unusable.
+//!
+//! `sq keyring lint` reports such components, and, if requested,
+//! re-issues the self-signatures using SHA-512, keeping all other
+//! subpackets.
+
+use std::fmt;
+use std::time::{Duration, SystemTime};
+
+use anyhow::Context as _;
+
+use sequoia_openpgp as openpgp;
+use openpgp::{Cert, Packet, Result};
+use openpgp::armor;
+use openpgp::cert::CertParser;
+use openpgp::crypto::Signer;
+use openpgp::crypto::hash::Digest;
+use openpgp::packet::{Key, Signature, UserAttribute, UserID, key};
+use openpgp::packet::signature::SignatureBuilder;
+use openpgp::parse::Parse;
+use openpgp::policy::{HashAlgoSecurity, Policy};
+use openpgp::serialize::Serialize;
+use openpgp::types::{HashAlgorithm, SignatureType};
+
+use sequoia_ipc::gnupg;
+use openpgp_dsm::DsmAgent;
+
+use crate::{
+    Config,
+    open_or_stdin,
+};
+use crate::secrets::{Credentials, dsm_auth};
+
+use super::unlock_keypair;
+
+/// A component with a self-signature that must be re-issued.
+enum Component {
+    DirectKey,
+    UserID(UserID),
+    UserAttribute(UserAttribute),
+    Subkey(Key<key::PublicParts, key::SubordinateRole>),
+}
+
+impl fmt::Display for Component {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Component::DirectKey => write!(f, "Direct key signature"),
+            Component::UserID(u) =>
+                write!(f, "User ID {:?}", String::from_utf8_lossy(u.value())),
+            Component::UserAttribute(_) => write!(f, "User attribute"),
+            Component::Subkey(k) => write!(f, "Subkey {}", k.fingerprint()),
+        }
+    }
+}
+
+/// A weak self-signature.
+struct Weak {
+    component: Component,
+    /// The active self-signature, the template for the new one.
+    sig: Signature,
+    /// Whether the subkey must make a new primary key binding
+    /// signature.
+    backsig: bool,
+    /// Why the policy rejects the component.
+    reason: String,
+}
+
+/// Where to look for secret key material.
+struct Secrets {
+    /// Keys given using --secret-key-file.
+    tsks: Vec<Cert>,
+    /// Keys stored in Fortanix DSM.
+    dsm: Vec<DsmAgent>,
+    /// The gpg-agent, if enabled.
+    gpg: Option<gnupg::Context>,
+}
+
+impl Secrets {
+    fn new(m: &clap::ArgMatches) -> Result<Self> {
+        let mut tsks = Vec::new();
+        for f in m.values_of("secret-key-file").into_iter().flatten() {
+            let tsk = Cert::from_file(f)
+                .context(format!("Failed to load key from file {:?}", f))?;
+            if ! tsk.is_tsk() {
+                return Err(anyhow::anyhow!(
+                    "Cert in file {:?} does not contain secret keys", f));
+            }
+            tsks.push(tsk);
+        }
+
+        let mut dsm = Vec::new();
+        if m.is_present("dsm-key") {
+            let credentials = Credentials::new(dsm_auth(m)?)?;
+            for name in m.values_of("dsm-key").into_iter().flatten() {
+                dsm.push(DsmAgent::new_certifier(credentials.clone(), name)?);
+                // Signing subkeys are only needed to make backsigs.
+                if let Ok(agent) =
+                    DsmAgent::new_signer(credentials.clone(), name)
+                {
+                    dsm.push(agent);
+                }
+            }
+        }
+
+        let gpg = crate::gpg_context(m)?;
+
+        Ok(Secrets { tsks, dsm, gpg })
+    }
+
+    /// Returns a signer for `key` of `cert`.
+    ///
+    /// Prefers secret key material in `cert` itself, then that given
+    /// using --secret-key-file, then DSM, and finally the gpg-agent.
+    fn signer<'a>(&self, cert: &Cert,
+                  key: &'a Key<key::PublicParts, key::UnspecifiedRole>)
+                  -> Result<Box<dyn Signer + 'a>>
+    {
+        let fp = key.fingerprint();
+
+        for tsk in std::iter::once(cert).chain(self.tsks.iter()) {
+            if let Some(ka) = tsk.keys().key_handle(fp.clone())
+                .find(|ka| ka.key().has_secret())
+            {
+                let secret = ka.key().optional_secret().expect("has secret");
+                return Ok(Box::new(unlock_keypair(tsk, ka.key(), secret)?));
+            }
+        }
+
+        if let Some(agent) = self.dsm.iter()
+            .find(|a| Signer::public(*a).fingerprint() == fp)
+        {
+            return Ok(Box::new(agent.clone()));
+        }
+
+        if let Some(ctx) = &self.gpg {
+            return Ok(Box::new(gnupg::KeyPair::new(ctx, key)?));
+        }
+
+        Err(anyhow::anyhow!("No secret key material for {} found", fp))
+    }
+}
+
+pub fn lint(config: Config, m: &clap::ArgMatches) -> Result<()> {
+    let fix = m.is_present("fix");
+    let list_keys = m.is_present("list-keys");
+    let secrets = if fix { Some(Secrets::new(m)?) } else { None };
+
+    let inputs: Vec<Option<&str>> = match m.values_of("input") {
+        Some(files) => files.map(Some).collect(),
+        None => vec![None],
+    };
+
+    let (mut examined, mut weak_certs, mut unfixed) = (0, 0, 0);
+    let mut fixed = Vec::new();
+    for input in inputs {
+        let mut input = open_or_stdin(input)?;
+        for cert in CertParser::from_reader(&mut input)? {
+            let cert = cert.context("Malformed certificate")?;
+            examined += 1;
+
+            let weak = weak_components(&config.policy, &cert);
+            if weak.is_empty() {
+                continue;
+            }
+            weak_certs += 1;
+
+            if list_keys {
+                println!("{}", cert.fingerprint().to_hex());
+            } else {
+                for w in &weak {
+                    eprintln!("{}: {}: {}", cert.fingerprint(), w.component,
+                              w.reason);
+                }
+            }
+
+            if let Some(secrets) = &secrets {
+                match repair(&config.policy, &cert, &weak, secrets) {
+                    Ok(cert) => fixed.push(cert),
+                    Err(e) => {
+                        eprintln!("{}: Failed to fix: {:#}",
+                                  cert.fingerprint(), e);
+                        unfixed += 1;
+                    },
+                }
+            } else {
+                unfixed += 1;
+            }
+        }
+    }
+
+    eprintln!("Examined {} certificates, {} have weak self-signatures.",
+              examined, weak_certs);
+    if fix {
+        eprintln!("Fixed {} certificates.", fixed.len());
+
+        let kind = if fixed.iter().any(|c| c.is_tsk()) {
+            armor::Kind::SecretKey
+        } else {
+            armor::Kind::PublicKey
+        };
+        let mut output = config.create_or_stdout_pgp(
+            m.value_of("output"), m.is_present("binary"), kind)?;
+        for cert in &fixed {
+            if cert.is_tsk() {
+                cert.as_tsk().serialize(&mut output)?;
+            } else {
+                cert.serialize(&mut output)?;
+            }
+        }
+        output.finalize()?;
+    }
+
+    if unfixed > 0 {
+        return Err(anyhow::anyhow!(
+            "{} certificates have weak self-signatures{}", unfixed,
+            if fix { "" } else { " (try: \"sq keyring lint --fix\")" }));
+    }
+
+    Ok(())
+}
+
+/// Returns the newest self-signature that is alive now.
+fn active<'a, I>(sigs: I) -> Option<&'a Signature>
+    where I: Iterator<Item=&'a Signature>
+{
+    let now = SystemTime::now();
+    sigs.filter(|s| s.signature_alive(now, Duration::new(0, 0)).is_ok())
+        .max_by_key(|s| s.signature_creation_time())
+}
+
+/// Returns why the policy rejects `sig`, if it does.
+fn rejected(policy: &dyn Policy, sig: &Signature, sec: HashAlgoSecurity)
+            -> Option<String>
+{
+    policy.signature(sig, sec).err().map(|e| format!("{:#}", e))
+}
+
+/// Returns why the subkey binding `sig` lacks a valid primary key
+/// binding signature, if it needs one.
+fn backsig_rejected(policy: &dyn Policy, sig: &Signature,
+                    sec: HashAlgoSecurity)
+                    -> Option<String>
+{
+    if ! sig.key_flags().map(|kf| kf.for_signing()).unwrap_or(false) {
+        return None;
+    }
+
+    let mut reason = None;
+    for backsig in sig.embedded_signatures() {
+        match backsig.signature_alive(SystemTime::now(), Duration::new(0, 0))
+            .and_then(|_| policy.signature(backsig, sec))
+        {
+            Ok(()) => return None,
+            Err(e) => if reason.is_none() {
+                reason = Some(format!("Primary key binding signature: {:#}",
+                                      e));
+            },
+        }
+    }
+    Some(reason.unwrap_or_else(
+        || "Signing-capable subkey has no primary key binding signature"
+            .into()))
+}
+
+/// Returns whether `sig` is a binding signature of `subkey`,
+/// ignoring the primary key binding signature.
+fn binds_subkey(sig: &Signature, cert: &Cert,
+                subkey: &Key<key::PublicParts, key::SubordinateRole>)
+                -> bool
+{
+    if sig.typ() != SignatureType::SubkeyBinding {
+        return false;
+    }
+    let primary = cert.primary_key().key();
+    let mut sig = sig.clone();
+    let mut hash = match sig.hash_algo().context() {
+        Ok(hash) => hash,
+        Err(_) => return false,
+    };
+    sig.hash_subkey_binding(&mut hash, primary, subkey);
+    match hash.into_digest() {
+        Ok(digest) => sig.verify_digest(primary, &digest[..]).is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// Returns the components of `cert` with self-signatures that the
+/// policy rejects.
+///
+/// Revoked components, and components without a live
+/// self-signature, are ignored.
+fn weak_components(policy: &dyn Policy, cert: &Cert) -> Vec<Weak> {
+    let mut weak = Vec::new();
+
+    let primary = cert.primary_key();
+    if primary.self_revocations().next().is_none() {
+        if let Some(sig) = active(primary.self_signatures()) {
+            if let Some(reason) = rejected(
+                policy, sig, primary.key().hash_algo_security())
+            {
+                weak.push(Weak {
+                    component: Component::DirectKey,
+                    sig: sig.clone(),
+                    backsig: false,
+                    reason,
+                });
+            }
+        }
+    }
+
+    for ua in cert.userids() {
+        if ua.self_revocations().next().is_some() {
+            continue;
+        }
+        if let Some(sig) = active(ua.self_signatures()) {
+            if let Some(reason) = rejected(
+                policy, sig, ua.userid().hash_algo_security())
+            {
+                weak.push(Weak {
+                    component: Component::UserID(ua.userid().clone()),
+                    sig: sig.clone(),
+                    backsig: false,
+                    reason,
+                });
+            }
+        }
+    }
+
+    for ua in cert.user_attributes() {
+        if ua.self_revocations().next().is_some() {
+            continue;
+        }
+        if let Some(sig) = active(ua.self_signatures()) {
+            if let Some(reason) = rejected(
+                policy, sig, ua.user_attribute().hash_algo_security())
+            {
+                weak.push(Weak {
+                    component: Component::UserAttribute(
+                        ua.user_attribute().clone()),
+                    sig: sig.clone(),
+                    backsig: false,
+                    reason,
+                });
+            }
+        }
+    }
+
+    for ka in cert.keys().subkeys() {
+        if ka.self_revocations().next().is_some() {
+            continue;
+        }
+        let subkey = ka.key();
+        let sec = subkey.hash_algo_security();
+
+        // Binding signatures of signing-capable subkeys without a
+        // valid backsig are not even considered self-signatures.
+        let sig = match active(ka.self_signatures()) {
+            Some(sig) => sig,
+            None => match active(cert.bad_signatures()
+                                 .filter(|s| binds_subkey(s, cert, subkey)))
+            {
+                Some(sig) => {
+                    weak.push(Weak {
+                        component: Component::Subkey(subkey.clone()),
+                        sig: sig.clone(),
+                        backsig: true,
+                        reason: "Signing-capable subkey has no valid \
+                                 primary key binding signature".into(),
+                    });
+                    continue;
+                },
+                None => continue,
+            },
+        };
+
+        let backsig = backsig_rejected(policy, sig, sec);
+        let reason = match (rejected(policy, sig, sec), &backsig) {
+            (Some(reason), _) => reason,
+            (None, Some(reason)) => reason.clone(),
+            (None, None) => continue,
+        };
+        weak.push(Weak {
+            component: Component::Subkey(subkey.clone()),
+            sig: sig.clone(),
+            backsig: backsig.is_some(),
+            reason,
+        });
+    }
+
+    weak
+}
+
+/// Re-issues the weak self-signatures of `cert`.
+fn repair(policy: &dyn Policy, cert: &Cert, weak: &[Weak], secrets: &Secrets)
+          -> Result<Cert>
+{
+    let primary = cert.primary_key().key();
+    let mut signer = secrets.signer(cert, primary.role_as_unspecified())?;
+
+    let mut packets: Vec<Packet> = Vec::new();
+    for w in weak {
+        let builder = SignatureBuilder::from(w.sig.clone())
+            .set_hash_algo(HashAlgorithm::SHA512);
+        let sig = match &w.component {
+            Component::DirectKey =>
+                builder.sign_direct_key(&mut *signer, primary)?,
+            Component::UserID(u) => u.bind(&mut *signer, cert, builder)?,
+            Component::UserAttribute(u) =>
+                u.bind(&mut *signer, cert, builder)?,
+            Component::Subkey(subkey) => {
+                let builder = if w.backsig {
+                    let mut subkey_signer =
+                        secrets.signer(cert, subkey.role_as_unspecified())?;
+                    let backsig =
+                        SignatureBuilder::new(SignatureType::PrimaryKeyBinding)
+                        .set_hash_algo(HashAlgorithm::SHA512)
+                        .sign_primary_key_binding(&mut *subkey_signer,
+                                                  primary, subkey)?;
+                    builder.set_embedded_signature(backsig)?
+                } else {
+                    builder
+                };
+                subkey.bind(&mut *signer, cert, builder)?
+            },
+        };
+        packets.push(sig.into());
+    }
+
+    let repaired = cert.clone().insert_packets(packets)?;
+    for w in weak_components(policy, &repaired) {
+        eprintln!("{}: {}: Still weak: {}", cert.fingerprint(), w.component,
+                  w.reason);
+    }
+    Ok(repaired)
+}
@@ -0,0 +1,240 @@
+//! Manifests of directory trees.
+//!
+//! A manifest lists every file in a directory tree together with its
+//! hash.  Signing the manifest protects the whole file set with a
+//! single signature: files that are added, removed, or modified are
+//! detected when the manifest is checked.
+//!
+//! The manifest is a canonical text document:
+//!
+//! ```text
+//! sq-manifest 1
+//! hash SHA256
+//! 0c15e883dee85bb2f3540a47ec58f617a2547117f9096417ba5422268029f501  README
+//! 9fa4b7ab7cbbc8c6e43e9b6d38bd5eb6b1dd1b5cbd1c1c77e7b1a1b4d4c55e87  bin/tool
+//! symlink:4b2e4ea8e4ec2b2e0f1d39ad1fd8cc3b9ae0a0e5b2a4e6c1e9e5b3d0d3c2a1f0  tool
+//! ```
+//!
+//! Paths are relative to the directory, use `/` as separator, and are
+//! sorted bytewise.  Symbolic links are not followed.  Instead, the hash
+//! of their target, prefixed with `symlink:`, is recorded, so that
+//! changing a link, or replacing a file with a link, is detected.  Other
+//! special files, like sockets or devices, are refused.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use anyhow::Context as _;
+
+use sequoia_openpgp as openpgp;
+use openpgp::Result;
+use openpgp::crypto::hash::Digest;
+use openpgp::fmt::hex;
+use openpgp::policy::{HashAlgoSecurity, StandardPolicy};
+use openpgp::types::HashAlgorithm;
+
+/// The first line of a manifest.
+const MAGIC: &str = "sq-manifest 1";
+
+/// The hashes of the files in a directory tree, by relative path.
+pub struct Manifest {
+    algo: HashAlgorithm,
+    files: BTreeMap<String, String>,
+}
+
+impl Manifest {
+    /// Hashes every file and symbolic link in `dir` using `algo`.
+    ///
+    /// The files in `exclude`, if they are in the directory tree, are
+    /// skipped.
+    pub fn from_dir(dir: &Path, algo: HashAlgorithm, exclude: &[PathBuf])
+                    -> Result<Self>
+    {
+        let exclude = exclude.iter()
+            .filter_map(|p| p.canonicalize().ok())
+            .collect::<Vec<_>>();
+        let mut manifest = Manifest { algo, files: BTreeMap::new() };
+        manifest.walk(dir, "", &exclude)?;
+        Ok(manifest)
+    }
+
+    fn walk(&mut self, dir: &Path, prefix: &str, exclude: &[PathBuf])
+            -> Result<()>
+    {
+        for entry in fs::read_dir(dir)
+            .context(format!("Failed to read {}", dir.display()))?
+        {
+            let path = entry?.path();
+            let name = path.file_name().expect("entries have names")
+                .to_str()
+                .ok_or_else(|| anyhow::anyhow!(
+                    "File name {:?} is not valid UTF-8", path))?;
+            if name.contains(|c| c == '\n' || c == '\r') {
+                return Err(anyhow::anyhow!(
+                    "File name {:?} contains a line break", path));
+            }
+            let relative = format!("{}{}", prefix, name);
+
+            let metadata = fs::symlink_metadata(&path)?;
+            if metadata.is_dir() {
+                self.walk(&path, &format!("{}/", relative), exclude)?;
+            } else if metadata.is_file() {
+                if exclude.contains(&path.canonicalize()?) {
+                    continue;
+                }
+                let hash = hash_file(&path, self.algo)?;
+                self.files.insert(relative, hash);
+            } else if metadata.file_type().is_symlink() {
+                let target = fs::read_link(&path)
+                    .context(format!("Failed to read {}", path.display()))?;
+                let target = target.to_str()
+                    .ok_or_else(|| anyhow::anyhow!(
+                        "Target of {:?} is not valid UTF-8", path))?;
+                let mut hash = self.algo.context()?;
+                hash.update(target.as_bytes());
+                self.files.insert(relative, format!(
+                    "symlink:{}",
+                    hex::encode(hash.into_digest()?).to_lowercase()));
+            } else {
+                return Err(anyhow::anyhow!(
+                    "{} is neither a regular file, a directory, nor a \
+                     symbolic link", path.display()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the hash algorithm.
+    pub fn algo(&self) -> HashAlgorithm {
+        self.algo
+    }
+
+    /// Serializes the manifest.
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut s = format!("{}\nhash {}\n", MAGIC, self.algo);
+        for (path, hash) in &self.files {
+            s.push_str(&format!("{}  {}\n", hash, path));
+        }
+        s.into_bytes()
+    }
+
+    /// Parses a manifest.
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let data = std::str::from_utf8(data)
+            .context("Malformed manifest: not valid UTF-8")?;
+        let mut lines = data.lines();
+        if lines.next() != Some(MAGIC) {
+            return Err(anyhow::anyhow!("Not a manifest"));
+        }
+        let algo = lines.next()
+            .and_then(|l| l.strip_prefix("hash "))
+            .and_then(|a| a.parse::<HashAlgorithm>().ok())
+            .ok_or_else(|| anyhow::anyhow!(
+                "Malformed manifest: unknown hash algorithm"))?;
+
+        let mut files = BTreeMap::new();
+        for line in lines {
+            let (hash, path) = line.split_once("  ")
+                .ok_or_else(|| anyhow::anyhow!(
+                    "Malformed manifest: {:?}", line))?;
+            if files.insert(path.to_string(), hash.to_lowercase()).is_some() {
+                return Err(anyhow::anyhow!(
+                    "Malformed manifest: {:?} is listed twice", path));
+            }
+        }
+        Ok(Manifest { algo, files })
+    }
+}
+
+/// Returns an error if `algo` is not suitable for manifests.
+pub fn check_algo(policy: &StandardPolicy, algo: HashAlgorithm) -> Result<()> {
+    if ! algo.is_supported() {
+        return Err(anyhow::anyhow!("Hash algorithm {} is not supported", algo));
+    }
+    // An attacker may control the content of the files, so we need
+    // collision resistance.
+    match policy.hash_cutoff(algo, HashAlgoSecurity::CollisionResistance) {
+        Some(cutoff) if cutoff <= SystemTime::now() =>
+            Err(anyhow::anyhow!(
+                "Hash algorithm {} is not considered secure", algo)),
+        _ => Ok(()),
+    }
+}
+
+/// Hashes the file at `path`.
+fn hash_file(path: &Path, algo: HashAlgorithm) -> Result<String> {
+    let mut file = File::open(path)
+        .context(format!("Failed to open {}", path.display()))?;
+    let mut hash = algo.context()?;
+    io::copy(&mut file, &mut hash)
+        .context(format!("Failed to read {}", path.display()))?;
+    Ok(hex::encode(hash.into_digest()?).to_lowercase())
+}
+
+/// The differences between a manifest and a directory tree.
+#[derive(Default)]
+pub struct Changes {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub modified: Vec<String>,
+}
+
+impl Changes {
+    /// Compares the `expected` manifest with the `actual` one.
+    pub fn new(expected: &Manifest, actual: &Manifest) -> Self {
+        let mut changes = Changes::default();
+        for (path, hash) in &expected.files {
+            match actual.files.get(path) {
+                None => changes.removed.push(path.clone()),
+                Some(h) if h != hash => changes.modified.push(path.clone()),
+                Some(_) => (),
+            }
+        }
+        for path in actual.files.keys() {
+            if ! expected.files.contains_key(path) {
+                changes.added.push(path.clone());
+            }
+        }
+        changes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+            && self.modified.is_empty()
+    }
+}
+
+/// Checks the directory tree `dir` against the signed manifest in
+/// `manifest`, and reports the differences.
+pub fn check(policy: &StandardPolicy, dir: &Path, manifest: &[u8],
+             exclude: &[PathBuf])
+             -> Result<()>
+{
+    let expected = Manifest::from_bytes(manifest)?;
+    check_algo(policy, expected.algo())?;
+    let actual = Manifest::from_dir(dir, expected.algo(), exclude)?;
+
+    let changes = Changes::new(&expected, &actual);
+    for path in &changes.added {
+        eprintln!("Added: {}", path);
+    }
+    for path in &changes.removed {
+        eprintln!("Removed: {}", path);
+    }
+    for path in &changes.modified {
+        eprintln!("Modified: {}", path);
+    }
+
+    if changes.is_empty() {
+        eprintln!("{} files match the manifest.", expected.files.len());
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!(
+            "{} does not match the manifest: {} added, {} removed, \
+             {} modified", dir.display(), changes.added.len(),
+            changes.removed.len(), changes.modified.len()))
+    }
+}
@@ -12,6 +12,9 @@ use crate::openpgp::{
 };
 use crate::openpgp::types::{
     CompressionAlgorithm,
+    DataFormat,
+    SignatureType,
+    SymmetricAlgorithm,
 };
 use crate::openpgp::cert::prelude::*;
 use crate::openpgp::crypto;
@@ -22,20 +25,22 @@ use crate::openpgp::parse::{
     PacketParserResult,
 };
 use crate::openpgp::parse::stream::*;
+use crate::openpgp::serialize::Serialize;
 use crate::openpgp::serialize::stream::{
     Message, Signer, LiteralWriter, Encryptor, Recipient,
     Compressor,
     padding::Padder,
 };
 use crate::openpgp::policy::Policy;
-use openpgp_dsm::DsmAgent;
+use openpgp_dsm::{self as dsm, DsmAgent};
 
 use crate::{
     Config,
     parse_armor_kind,
 };
+use crate::output::{self, OutputFormat};
 
-use crate::secrets::{PreSecret, Secret};
+use crate::secrets::{GpgAgentKey, PreSecret, Secret};
 
 #[cfg(feature = "autocrypt")]
 pub mod autocrypt;
@@ -44,16 +49,28 @@ pub use self::decrypt::decrypt;
 pub mod sign;
 pub use self::sign::sign;
 pub mod dump;
-pub use self::dump::dump;
+pub use self::dump::{dump, DumpOpts};
+mod explain;
 mod inspect;
 pub use self::inspect::inspect;
 pub mod key;
 pub mod merge_signatures;
 pub use self::merge_signatures::merge_signatures;
 pub mod keyring;
+pub mod lint;
+pub mod diff;
+pub mod manifest;
 #[cfg(feature = "net")]
 pub mod net;
 pub mod certify;
+pub mod revoke;
+pub mod gpg;
+pub mod recipients;
+pub mod sop;
+pub mod store;
+pub mod wot;
+#[cfg(unix)]
+pub mod agent;
 
 /// Returns suitable signing keys from a given list of Certs.
 #[allow(clippy::never_loop)]
@@ -68,6 +85,16 @@ fn get_signing_keys(presecrets: &[PreSecret], p: &dyn Policy,
             PreSecret::Dsm(credentials, name) => {
                 keys.push(Box::new(Secret::Dsm(DsmAgent::new_signer(credentials.clone(), name)?)));
             }
+            PreSecret::Gpg(context, cert) => {
+                let key = cert.keys().with_policy(p, timestamp).alive()
+                    .revoked(false).for_signing().supported()
+                    .map(|ka| ka.key().clone())
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!(
+                        "Found no suitable signing key on {}", cert))?;
+                keys.push(Box::new(Secret::Gpg(
+                    GpgAgentKey::new(context.clone(), key))));
+            }
             PreSecret::InMemory(tsk) => {
                 for key in tsk.keys().with_policy(p, timestamp).alive().revoked(false)
                     .for_signing()
@@ -75,21 +102,8 @@ fn get_signing_keys(presecrets: &[PreSecret], p: &dyn Policy,
                         .map(|ka| ka.key())
                         {
                             if let Some(secret) = key.optional_secret() {
-                                let unencrypted = match secret {
-                                    SecretKeyMaterial::Encrypted(ref e) => {
-                                        let password = rpassword::read_password_from_tty(Some(
-                                                &format!("Please enter password to decrypt {}/{}: ",
-                                                    tsk, key)))
-                                            .context("Reading password from tty")?;
-                                        e.decrypt(key.pk_algo(), &password.into())
-                                            .expect("decryption failed")
-                                    },
-                                    SecretKeyMaterial::Unencrypted(ref u) => u.clone(),
-                                };
-
                                 keys.push(Box::new(Secret::InMemory(
-                                        crypto::KeyPair::new(key.clone(), unencrypted)
-                                            .unwrap())));
+                                        unlock_keypair(tsk, key, secret)?)));
                                             break 'next_cert;
                             } else if let Some(private_key_store) = private_key_store {
                                 let password = rpassword::read_password_from_tty(
@@ -112,22 +126,51 @@ fn get_signing_keys(presecrets: &[PreSecret], p: &dyn Policy,
     Ok(keys)
 }
 
+/// Returns a keypair for `key`, asking for the password if the secret
+/// key material is encrypted.
+fn unlock_keypair(tsk: &Cert, key: &Key<key::PublicParts, key::UnspecifiedRole>,
+                  secret: &SecretKeyMaterial)
+    -> Result<crypto::KeyPair>
+{
+    let unencrypted = match secret {
+        SecretKeyMaterial::Encrypted(ref e) => {
+            let password = rpassword::read_password_from_tty(Some(
+                    &format!("Please enter password to decrypt {}/{}: ",
+                        tsk, key)))
+                .context("Reading password from tty")?;
+            e.decrypt(key.pk_algo(), &password.into())
+                .with_context(|| format!("Failed to decrypt {}/{}",
+                                         tsk, key))?
+        },
+        SecretKeyMaterial::Unencrypted(ref u) => u.clone(),
+    };
+
+    crypto::KeyPair::new(key.clone(), unencrypted)
+}
+
 pub struct EncryptOpts<'a> {
     pub policy: &'a dyn Policy,
     pub private_key_store: Option<&'a str>,
     pub input: &'a mut dyn io::Read,
     pub message: Message<'a>,
     pub npasswords: usize,
+    /// Passwords to encrypt with in addition to those prompted for.
+    pub passwords: Vec<crypto::Password>,
     pub recipients: &'a [openpgp::Cert],
+    pub dsm_wrapping_keys: Vec<(dsm::Credentials, String)>,
     pub signers: Vec<PreSecret>,
     pub mode: openpgp::types::KeyFlags,
     pub compression: &'a str,
     pub time: Option<SystemTime>,
     pub use_expired_subkey: bool,
+    /// Encrypt for expired certificates.
+    pub use_expired_recipient: bool,
+    /// Marks the data as text, and makes text signatures.
+    pub text: bool,
 }
 
 pub fn encrypt(opts: EncryptOpts) -> Result<()> {
-    let mut passwords: Vec<crypto::Password> = Vec::with_capacity(opts.npasswords);
+    let mut passwords = opts.passwords;
     for n in 0..opts.npasswords {
         let nprompt = format!("Enter password {}: ", n + 1);
         passwords.push(rpassword::read_password_from_tty(Some(
@@ -138,9 +181,11 @@ pub fn encrypt(opts: EncryptOpts) -> Result<()> {
             }))?.into());
     }
 
-    if opts.recipients.len() + passwords.len() == 0 {
+    if opts.recipients.len() + passwords.len()
+        + opts.dsm_wrapping_keys.len() == 0
+    {
         return Err(anyhow::anyhow!(
-            "Neither recipient nor password given"));
+            "Neither recipient, password, nor DSM key given"));
     }
 
     let mut signers = get_signing_keys(&opts.signers, opts.policy,
@@ -162,15 +207,19 @@ pub fn encrypt(opts: EncryptOpts) -> Result<()> {
                 .key_flags(&opts.mode).supported()
             {
                 let key = ka.key();
+                // If the certificate expired, the subkeys may not
+                // have an expiration time of their own.
                 expired_keys.push(
                     (ka.binding_signature().key_expiration_time(key)
-                         .expect("Key must have an expiration time"),
+                         .or_else(|| ka.cert().primary_key()
+                                  .key_expiration_time())
+                         .unwrap_or(std::time::UNIX_EPOCH),
                      key));
             }
             expired_keys.sort_by_key(|(expiration_time, _)| *expiration_time);
 
             if let Some((expiration_time, key)) = expired_keys.last() {
-                if opts.use_expired_subkey {
+                if opts.use_expired_subkey || opts.use_expired_recipient {
                     recipient_subkeys.push((*key).into());
                 } else {
                     use chrono::{DateTime, offset::Utc};
@@ -189,9 +238,22 @@ pub fn encrypt(opts: EncryptOpts) -> Result<()> {
     }
 
     // We want to encrypt a literal data packet.
-    let encryptor =
+    let encryptor = if opts.dsm_wrapping_keys.is_empty() {
         Encryptor::for_recipients(opts.message, recipient_subkeys)
-        .add_passwords(passwords);
+    } else {
+        // The session key is wrapped by DSM, and the resulting PKESKs
+        // precede those written by the Encryptor.
+        let mut message = opts.message;
+        let sym_algo = SymmetricAlgorithm::default();
+        let sk = crypto::SessionKey::new(sym_algo.key_size()?);
+        for (credentials, name) in &opts.dsm_wrapping_keys {
+            let pkesk = dsm::wrap_session_key(credentials, name,
+                                              sym_algo, &sk)?;
+            openpgp::Packet::PKESK(pkesk.into()).serialize(&mut message)?;
+        }
+        Encryptor::with_session_key(message, sym_algo, sk)?
+            .add_recipients(recipient_subkeys)
+    }.add_passwords(passwords);
 
     let mut sink = encryptor.build()
         .context("Failed to create encryptor")?;
@@ -210,7 +272,13 @@ pub fn encrypt(opts: EncryptOpts) -> Result<()> {
 
     // Optionally sign message.
     if ! signers.is_empty() {
-        let mut signer = Signer::new(sink, signers.pop().unwrap());
+        let typ = if opts.text {
+            SignatureType::Text
+        } else {
+            SignatureType::Binary
+        };
+        let mut signer = Signer::with_template(
+            sink, signers.pop().unwrap(), SignatureBuilder::new(typ));
         for s in signers {
             signer = signer.add_signer(s);
             if let Some(time) = opts.time {
@@ -223,7 +291,9 @@ pub fn encrypt(opts: EncryptOpts) -> Result<()> {
         sink = signer.build()?;
     }
 
-    let mut literal_writer = LiteralWriter::new(sink).build()
+    let mut literal_writer = LiteralWriter::new(sink)
+        .format(if opts.text { DataFormat::Unicode } else { DataFormat::Binary })
+        .build()
         .context("Failed to create literal writer")?;
 
     // Finally, copy stdin to our writer stack to encrypt the data.
@@ -237,7 +307,6 @@ pub fn encrypt(opts: EncryptOpts) -> Result<()> {
 }
 
 struct VHelper<'a> {
-    #[allow(dead_code)]
     config: Config<'a>,
     signatures: usize,
     certs: Option<Vec<Cert>>,
@@ -249,6 +318,12 @@ struct VHelper<'a> {
     bad_signatures: usize,
     bad_checksums: usize,
     broken_signatures: usize,
+    /// Where to write gpg-style `[GNUPG:]` status lines, if anywhere.
+    status: Option<Box<dyn io::Write + 'a>>,
+    /// Whether to explain why signatures failed to verify.
+    explain: bool,
+    /// The results for machine-readable output.
+    report: output::Decryption,
 }
 
 impl<'a> VHelper<'a> {
@@ -267,10 +342,183 @@ impl<'a> VHelper<'a> {
             bad_signatures: 0,
             bad_checksums: 0,
             broken_signatures: 0,
+            status: None,
+            explain: false,
+            report: Default::default(),
+        }
+    }
+
+    /// Returns whether to emit machine-readable output.
+    fn json(&self) -> bool {
+        self.config.output_format == OutputFormat::Json
+    }
+
+    /// Records the result of a signature for machine-readable output.
+    fn record_sig(&mut self, result: &VerificationResult) {
+        use self::VerificationError::*;
+        let (status, sig, key, signer, errors) = match result {
+            Ok(GoodChecksum { sig, ka, .. }) => {
+                let status = if self.trusted.contains(&ka.key().keyid()) {
+                    "good"
+                } else {
+                    "good-checksum"
+                };
+                (status, *sig, Some(ka.key().fingerprint()),
+                 Some(ka.cert().fingerprint()), Vec::new())
+            },
+            Err(MalformedSignature { sig, error }) =>
+                ("malformed", *sig, None, None, output::error_chain(error)),
+            Err(MissingKey { sig }) =>
+                ("missing-key", *sig, None, None, Vec::new()),
+            Err(UnboundKey { sig, cert, error }) =>
+                ("unbound-key", *sig, None, Some(cert.fingerprint()),
+                 output::error_chain(error)),
+            Err(BadKey { sig, ka, error }) =>
+                ("bad-key", *sig, Some(ka.key().fingerprint()),
+                 Some(ka.cert().fingerprint()), output::error_chain(error)),
+            Err(BadSignature { sig, ka, error }) =>
+                ("bad-signature", *sig, Some(ka.key().fingerprint()),
+                 Some(ka.cert().fingerprint()), output::error_chain(error)),
+        };
+
+        self.report.verification.signatures.push(output::SignatureResult {
+            status,
+            level: sig.level(),
+            key: key.map(|fp| fp.to_hex()),
+            signer: signer.map(|fp| fp.to_hex()),
+            alleged_issuers: output::alleged_issuers(sig),
+            creation_time: sig.signature_creation_time().map(output::time),
+            errors,
+        });
+    }
+
+    /// Completes the machine-readable results with the outcome of the
+    /// verification.
+    fn finish_report(&mut self, valid: bool) {
+        self.report.verification.valid = valid;
+        self.report.verification.summary = output::Summary {
+            good_signatures: self.good_signatures,
+            good_checksums: self.good_checksums,
+            unknown_checksums: self.unknown_checksums,
+            bad_signatures: self.bad_signatures,
+            bad_checksums: self.bad_checksums,
+            broken_signatures: self.broken_signatures,
+        };
+    }
+
+    /// Emits a gpg-style status line, if a status sink is set.
+    fn status(&mut self, keyword: &str, args: &[String]) {
+        if let Some(status) = self.status.as_mut() {
+            let mut line = format!("[GNUPG:] {}", keyword);
+            for arg in args {
+                line.push(' ');
+                line.push_str(arg);
+            }
+            // Failing to write status lines is not fatal, the result
+            // is reported via the exit status anyway.
+            let _ = writeln!(status, "{}", line);
+        }
+    }
+
+    /// Emits the status lines gpg emits for a signature.
+    fn status_for_sig(&mut self, result: &VerificationResult) {
+        use chrono::{DateTime, offset::Utc};
+        use self::VerificationError::*;
+        if self.status.is_none() {
+            return;
+        }
+
+        fn timestamp(sig: &Signature) -> u64 {
+            sig.signature_creation_time()
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|d| d.as_secs())
+                .unwrap_or(0)
+        }
+        fn errsig(sig: &Signature, keyid: String, rc: u8) -> Vec<String> {
+            vec![
+                keyid,
+                u8::from(sig.pk_algo()).to_string(),
+                u8::from(sig.hash_algo()).to_string(),
+                format!("{:02x}", u8::from(sig.typ())),
+                timestamp(sig).to_string(),
+                rc.to_string(),
+            ]
+        }
+        fn primary_uid(cert: &ValidCert) -> String {
+            cert.primary_userid()
+                .map(|u| String::from_utf8_lossy(u.value()).into_owned())
+                .unwrap_or_else(|_| "[?]".into())
+        }
+
+        self.status("NEWSIG", &[]);
+        match result {
+            Ok(GoodChecksum { sig, ka, .. }) => {
+                let keyid = ka.key().keyid();
+                let trusted = self.trusted.contains(&keyid);
+                let creation = sig.signature_creation_time()
+                    .map(|t| DateTime::<Utc>::from(t).format("%Y-%m-%d")
+                         .to_string())
+                    .unwrap_or_else(|| "0".into());
+                self.status("GOODSIG", &[
+                    keyid.to_hex(),
+                    primary_uid(ka.cert()),
+                ]);
+                self.status("VALIDSIG", &[
+                    ka.key().fingerprint().to_hex(),
+                    creation,
+                    timestamp(sig).to_string(),
+                    sig.signature_expiration_time()
+                        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH)
+                                  .ok())
+                        .map(|d| d.as_secs().to_string())
+                        .unwrap_or_else(|| "0".into()),
+                    sig.version().to_string(),
+                    "0".into(), // Reserved.
+                    u8::from(sig.pk_algo()).to_string(),
+                    u8::from(sig.hash_algo()).to_string(),
+                    format!("{:02x}", u8::from(sig.typ())),
+                    ka.cert().fingerprint().to_hex(),
+                ]);
+                if trusted {
+                    self.status("TRUST_FULLY", &["0".into(), "pgp".into()]);
+                } else {
+                    self.status("TRUST_UNDEFINED", &["0".into(), "pgp".into()]);
+                }
+            },
+            Err(MissingKey { sig, .. }) => {
+                let keyid = sig.get_issuers().get(0)
+                    .map(|i| KeyID::from(i).to_hex())
+                    .unwrap_or_else(|| "0000000000000000".into());
+                self.status("ERRSIG", &errsig(sig, keyid.clone(), 9));
+                self.status("NO_PUBKEY", &[keyid]);
+            },
+            Err(BadSignature { ka, .. }) => {
+                self.status("BADSIG", &[
+                    ka.key().keyid().to_hex(),
+                    primary_uid(ka.cert()),
+                ]);
+            },
+            Err(UnboundKey { sig, cert, .. }) => {
+                self.status("ERRSIG", &errsig(sig, cert.keyid().to_hex(), 4));
+            },
+            Err(BadKey { sig, ka, .. }) => {
+                self.status("ERRSIG",
+                            &errsig(sig, ka.key().keyid().to_hex(), 4));
+            },
+            Err(MalformedSignature { sig, .. }) => {
+                let keyid = sig.get_issuers().get(0)
+                    .map(|i| KeyID::from(i).to_hex())
+                    .unwrap_or_else(|| "0000000000000000".into());
+                self.status("ERRSIG", &errsig(sig, keyid, 4));
+            },
         }
     }
 
     fn print_status(&self) {
+        if self.json() {
+            return;
+        }
+
         fn p(dirty: &mut bool, what: &str, quantity: usize) {
             if quantity > 0 {
                 eprint!("{}{} {}{}",
@@ -293,16 +541,31 @@ impl<'a> VHelper<'a> {
         }
     }
 
+    /// Explains why a signature failed to verify, if requested.
+    fn explain(&self, result: &VerificationResult) {
+        if self.explain {
+            explain::explain(result);
+        }
+    }
+
     fn print_sigs(&mut self, results: &[VerificationResult]) {
         use crate::print_error_chain;
         use self::VerificationError::*;
+        let json = self.json();
         for result in results {
+            self.status_for_sig(result);
+            if json {
+                self.record_sig(result);
+            }
             let (issuer, level) = match result {
                 Ok(GoodChecksum { sig, ka, .. }) =>
                     (ka.key().keyid(), sig.level()),
                 Err(MalformedSignature { error, .. }) => {
-                    eprintln!("Malformed signature:");
-                    print_error_chain(error);
+                    if ! json {
+                        eprintln!("Malformed signature:");
+                        print_error_chain(error);
+                        self.explain(result);
+                    }
                     self.broken_signatures += 1;
                     continue;
                 },
@@ -314,21 +577,30 @@ impl<'a> VHelper<'a> {
                         0 => "checksum".into(),
                         n => format!("level {} notarizing checksum", n),
                     };
-                    eprintln!("No key to check {} from {}", what, issuer);
+                    if ! json {
+                        eprintln!("No key to check {} from {}", what, issuer);
+                        self.explain(result);
+                    }
                     self.unknown_checksums += 1;
                     continue;
                 },
                 Err(UnboundKey { cert, error, .. }) => {
-                    eprintln!("Signing key on {} is not bound:",
-                              cert.fingerprint());
-                    print_error_chain(error);
+                    if ! json {
+                        eprintln!("Signing key on {} is not bound:",
+                                  cert.fingerprint());
+                        print_error_chain(error);
+                        self.explain(result);
+                    }
                     self.bad_checksums += 1;
                     continue;
                 },
                 Err(BadKey { ka, error, .. }) => {
-                    eprintln!("Signing key on {} is bad:",
-                              ka.cert().fingerprint());
-                    print_error_chain(error);
+                    if ! json {
+                        eprintln!("Signing key on {} is bad:",
+                                  ka.cert().fingerprint());
+                        print_error_chain(error);
+                        self.explain(result);
+                    }
                     self.bad_checksums += 1;
                     continue;
                 },
@@ -338,9 +610,12 @@ impl<'a> VHelper<'a> {
                         0 => "checksum".into(),
                         n => format!("level {} notarizing checksum", n),
                     };
-                    eprintln!("Error verifying {} from {}:",
-                              what, issuer);
-                    print_error_chain(error);
+                    if ! json {
+                        eprintln!("Error verifying {} from {}:",
+                                  what, issuer);
+                        print_error_chain(error);
+                        self.explain(result);
+                    }
                     self.bad_checksums += 1;
                     continue;
                 }
@@ -357,7 +632,9 @@ impl<'a> VHelper<'a> {
 
             let issuer_str = issuer.to_string();
             let label = self.labels.get(&issuer).unwrap_or(&issuer_str);
-            eprintln!("Good {} from {}", what, label);
+            if ! json {
+                eprintln!("Good {} from {}", what, label);
+            }
             if trusted {
                 self.good_signatures += 1;
             } else {
@@ -368,8 +645,8 @@ impl<'a> VHelper<'a> {
 }
 
 impl<'a> VerificationHelper for VHelper<'a> {
-    fn get_certs(&mut self, _ids: &[openpgp::KeyHandle]) -> Result<Vec<Cert>> {
-        let certs = self.certs.take().unwrap();
+    fn get_certs(&mut self, ids: &[openpgp::KeyHandle]) -> Result<Vec<Cert>> {
+        let mut certs = self.certs.take().unwrap();
         // Get all keys.
         let seen: HashSet<_> = certs.iter()
             .flat_map(|cert| {
@@ -379,28 +656,70 @@ impl<'a> VerificationHelper for VHelper<'a> {
         // Explicitly provided keys are trusted.
         self.trusted = seen;
 
+        // Look up the remaining issuers in the certificate store.
+        // Certificates from the store are not authenticated, hence
+        // they are not trusted.
+        let missing: Vec<_> = ids.iter()
+            .filter(|id| ! self.trusted.contains(&KeyID::from(*id)))
+            .collect();
+        if ! missing.is_empty() {
+            if let Some(store) = self.config.cert_store() {
+                let index = store.index(&self.config.policy)?;
+                for id in missing {
+                    for cert in index.lookup_by_key(id) {
+                        if ! certs.iter()
+                            .any(|c| c.fingerprint() == cert.fingerprint())
+                        {
+                            certs.push(cert.clone());
+                        }
+                    }
+                }
+            }
+        }
+
         Ok(certs)
     }
 
     fn check(&mut self, structure: MessageStructure) -> Result<()> {
+        let result = self.check_structure(structure);
+        if self.json() {
+            output::emit(&mut io::stderr(), &self.report.verification)?;
+        }
+        result
+    }
+}
+
+impl<'a> VHelper<'a> {
+    /// Checks the message structure, without emitting machine-readable
+    /// output.
+    fn check_structure(&mut self, structure: MessageStructure) -> Result<()> {
+        let json = self.json();
         for layer in structure {
             match layer {
-                MessageLayer::Compression { algo } =>
-                    eprintln!("Compressed using {}", algo),
-                MessageLayer::Encryption { sym_algo, aead_algo } =>
-                    if let Some(aead_algo) = aead_algo {
-                        eprintln!("Encrypted and protected using {}/{}",
-                                  sym_algo, aead_algo);
-                    } else {
-                        eprintln!("Encrypted using {}", sym_algo);
-                    },
+                MessageLayer::Compression { algo } => if json {
+                    self.report.compression_algorithm = Some(algo.to_string());
+                } else {
+                    eprintln!("Compressed using {}", algo)
+                },
+                MessageLayer::Encryption { sym_algo, aead_algo } => if json {
+                    self.report.symmetric_algorithm = Some(sym_algo.to_string());
+                    self.report.aead_algorithm =
+                        aead_algo.map(|algo| algo.to_string());
+                } else if let Some(aead_algo) = aead_algo {
+                    eprintln!("Encrypted and protected using {}/{}",
+                              sym_algo, aead_algo);
+                } else {
+                    eprintln!("Encrypted using {}", sym_algo);
+                },
                 MessageLayer::SignatureGroup { ref results } =>
                     self.print_sigs(results),
             }
         }
 
-        if self.good_signatures >= self.signatures
-            && self.bad_signatures + self.bad_checksums == 0 {
+        let valid = self.good_signatures >= self.signatures
+            && self.bad_signatures + self.bad_checksums == 0;
+        self.finish_report(valid);
+        if valid {
             Ok(())
         } else {
             self.print_status();
@@ -413,9 +732,36 @@ pub fn verify(config: Config,
               input: &mut (dyn io::Read + Sync + Send),
               detached: Option<&mut (dyn io::Read + Sync + Send)>,
               output: &mut dyn io::Write,
-              signatures: usize, certs: Vec<Cert>)
+              signatures: usize, certs: Vec<Cert>, explain: bool)
               -> Result<()> {
-    let helper = VHelper::new(&config, signatures, certs);
+    verify_(config, input, detached, output, signatures, certs, None,
+            explain)
+}
+
+/// Like [`verify`], but additionally writes gpg-style `[GNUPG:]`
+/// status lines to `status`.
+pub fn verify_with_status<'a>(config: Config<'a>,
+                              input: &mut (dyn io::Read + Sync + Send),
+                              detached: Option<&mut (dyn io::Read + Sync + Send)>,
+                              output: &mut dyn io::Write,
+                              signatures: usize, certs: Vec<Cert>,
+                              status: Box<dyn io::Write + 'a>)
+                              -> Result<()> {
+    verify_(config, input, detached, output, signatures, certs, Some(status),
+            false)
+}
+
+fn verify_<'a>(config: Config<'a>,
+               input: &mut (dyn io::Read + Sync + Send),
+               detached: Option<&mut (dyn io::Read + Sync + Send)>,
+               output: &mut dyn io::Write,
+               signatures: usize, certs: Vec<Cert>,
+               status: Option<Box<dyn io::Write + 'a>>,
+               explain: bool)
+               -> Result<()> {
+    let mut helper = VHelper::new(&config, signatures, certs);
+    helper.status = status;
+    helper.explain = explain;
     let helper = if let Some(dsig) = detached {
         let mut v = DetachedVerifierBuilder::from_reader(dsig)?
             .with_policy(&config.policy, None, helper)?;
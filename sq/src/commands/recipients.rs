@@ -0,0 +1,229 @@
+//! Selects recipients for `sq encrypt`.
+//!
+//! Recipients can be named by email address, User ID, or
+//! fingerprint.  They are looked up in the keyrings given on the
+//! command line and in the certificate store, and, if enabled, using
+//! WKD and a keyserver.
+
+use anyhow::Context as _;
+
+use sequoia_openpgp as openpgp;
+use openpgp::{Cert, Fingerprint, KeyHandle, Result};
+use openpgp::packet::UserID;
+use openpgp::policy::Policy;
+use openpgp::types::RevocationStatus;
+
+#[cfg(feature = "net")]
+use sequoia_net as net;
+
+use crate::cert_store::{CertStore, Index, Query, merge};
+
+/// Where to look for recipients.
+pub struct Lookup<'a> {
+    policy: &'a dyn Policy,
+    local: Index,
+    /// Whether to look up email addresses using WKD.
+    #[cfg(feature = "net")]
+    wkd: bool,
+    /// The keyserver to query, if any.
+    #[cfg(feature = "net")]
+    keyserver: Option<net::KeyServer>,
+}
+
+impl<'a> Lookup<'a> {
+    /// Looks up recipients in `keyrings` and in `cert_store`.
+    pub fn new(policy: &'a dyn Policy, keyrings: Vec<Cert>,
+               cert_store: Option<CertStore>)
+               -> Result<Self>
+    {
+        let mut certs = keyrings;
+        if let Some(store) = cert_store {
+            certs.append(&mut store.certs()?);
+        }
+        Ok(Lookup {
+            policy,
+            local: Index::new(policy, merge(certs)?),
+            #[cfg(feature = "net")]
+            wkd: false,
+            #[cfg(feature = "net")]
+            keyserver: None,
+        })
+    }
+
+    /// Additionally looks up email addresses using WKD.
+    #[cfg(feature = "net")]
+    pub fn wkd(mut self, enable: bool) -> Self {
+        self.wkd = enable;
+        self
+    }
+
+    /// Additionally looks up recipients on the keyserver at `uri`.
+    #[cfg(feature = "net")]
+    pub fn keyserver(mut self, uri: &str) -> Result<Self> {
+        self.keyserver = Some(
+            net::KeyServer::new(net::Policy::Encrypted, uri)
+                .context("Malformed keyserver URI")?);
+        Ok(self)
+    }
+
+    /// Returns the certificate with the given fingerprint.
+    ///
+    /// The fingerprint may also be that of a subkey.
+    pub fn by_fingerprint(&mut self, fingerprint: &str) -> Result<Cert> {
+        let fp: Fingerprint = fingerprint.parse()?;
+        if let Fingerprint::Invalid(_) = fp {
+            return Err(anyhow::anyhow!(
+                "{:?} is not a fingerprint", fingerprint));
+        }
+        let handle = KeyHandle::from(fp);
+
+        let certs: Vec<Cert> = self.local.lookup_by_key(&handle)
+            .into_iter().cloned().collect();
+
+        #[cfg(feature = "net")]
+        let certs = match (certs.is_empty(), self.keyserver.as_mut()) {
+            (true, Some(ks)) => {
+                let rt = runtime()?;
+                let cert = rt.block_on(ks.get(handle.clone()))
+                    .context("Failed to retrieve cert from the keyserver")?;
+                // Don't trust the keyserver to return the right cert.
+                if cert.keys().any(|ka| ka.key().key_handle().aliases(&handle)) {
+                    vec![cert]
+                } else {
+                    vec![]
+                }
+            },
+            _ => certs,
+        };
+
+        // Fingerprints don't collide, so there is at most one match.
+        let cert = certs.into_iter().next().ok_or_else(|| anyhow::anyhow!(
+            "No certificate with the fingerprint {} found", fingerprint))?;
+        cert.with_policy(self.policy, None)
+            .with_context(|| format!("Cert {} is not valid under the policy",
+                                     cert))?;
+        Ok(cert)
+    }
+
+    /// Returns the certificate with a valid User ID containing the
+    /// given email address.
+    pub fn by_email(&mut self, email: &str) -> Result<Cert> {
+        let email = UserID::from(format!("<{}>", email)).email_normalized()
+            .ok().flatten()
+            .ok_or_else(|| anyhow::anyhow!(
+                "{:?} is not a valid email address", email))?;
+        let matches = |uid: &UserID| {
+            uid.email_normalized().ok().flatten().as_ref() == Some(&email)
+        };
+
+        // Prefer local certificates.
+        let certs: Vec<Cert> = self.local.lookup(&Query::Email(email.clone()))
+            .into_iter().cloned().collect();
+        if ! certs.is_empty() {
+            return self.one(certs, &email, matches);
+        }
+
+        #[cfg(feature = "net")]
+        {
+            let mut certs = Vec::new();
+            if self.wkd {
+                let rt = runtime()?;
+                certs.append(&mut rt.block_on(net::wkd::get(&email))
+                             .context("Failed to retrieve certs using WKD")?);
+            }
+            if let Some(ks) = self.keyserver.as_mut() {
+                let rt = runtime()?;
+                certs.append(&mut rt.block_on(ks.search(email.as_str()))
+                             .context("Failed to retrieve certs from the \
+                                       keyserver")?);
+            }
+            if ! certs.is_empty() {
+                return self.one(merge(certs)?, &email, matches);
+            }
+        }
+
+        Err(anyhow::anyhow!("No certificate found for {:?}", email))
+    }
+
+    /// Returns the certificate with the given valid User ID.
+    pub fn by_userid(&mut self, userid: &str) -> Result<Cert> {
+        let certs: Vec<Cert> =
+            self.local.lookup(&Query::UserID(userid.into()))
+            .into_iter().cloned().collect();
+        self.one(certs, userid, |uid| uid.value() == userid.as_bytes())
+    }
+
+    /// Returns the only certificate in `certs` with a valid User ID
+    /// satisfying `matches`.
+    ///
+    /// It is an error if there is no such certificate, or if there
+    /// are several.
+    fn one<F>(&self, certs: Vec<Cert>, query: &str, matches: F) -> Result<Cert>
+        where F: Fn(&UserID) -> bool
+    {
+        let certs: Vec<Cert> = certs.into_iter()
+            .filter(|cert| {
+                let vc = match cert.with_policy(self.policy, None) {
+                    Ok(vc) => vc,
+                    Err(_) => return false,
+                };
+                vc.userids().any(|ua| {
+                    ! matches!(ua.revocation_status(),
+                               RevocationStatus::Revoked(_))
+                        && matches(ua.userid())
+                })
+            })
+            .collect();
+
+        match certs.len() {
+            0 => Err(anyhow::anyhow!(
+                "No certificate with a valid User ID matching {:?} found",
+                query)),
+            1 => Ok(certs.into_iter().next().expect("have one")),
+            _ => Err(anyhow::anyhow!(
+                "Several certificates match {:?}: {}\n\
+                 Hint: Use --recipient-fingerprint to select one.",
+                query,
+                certs.iter().map(|c| c.fingerprint().to_hex())
+                    .collect::<Vec<_>>().join(", "))),
+        }
+    }
+}
+
+#[cfg(feature = "net")]
+fn runtime() -> Result<tokio::runtime::Runtime> {
+    Ok(tokio::runtime::Builder::new_current_thread()
+       .enable_io()
+       .enable_time()
+       .build()?)
+}
+
+/// Checks that `cert` is neither revoked nor expired.
+pub fn check(cert: &Cert, policy: &dyn Policy,
+             use_expired: bool, use_revoked: bool)
+             -> Result<()>
+{
+    let vc = cert.with_policy(policy, None)
+        .with_context(|| format!("Cert {} is not valid under the policy",
+                                 cert))?;
+
+    if let RevocationStatus::Revoked(_) = vc.revocation_status() {
+        if ! use_revoked {
+            return Err(anyhow::anyhow!(
+                "Cert {} is revoked\n\
+                 Hint: Use --use-revoked-recipient to use it anyway.",
+                cert));
+        }
+    }
+
+    if let Err(err) = vc.alive() {
+        if ! use_expired {
+            return Err(anyhow::anyhow!(
+                "Cert {} is not alive: {}\n\
+                 Hint: Use --use-expired-recipient to use it anyway.",
+                cert, err));
+        }
+    }
+
+    Ok(())
+}
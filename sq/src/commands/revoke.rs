@@ -0,0 +1,201 @@
+use std::time::SystemTime;
+
+use anyhow::Context;
+
+use sequoia_openpgp as openpgp;
+use openpgp::{Cert, KeyHandle, Packet, Result};
+use openpgp::armor;
+use openpgp::cert::prelude::*;
+use openpgp::crypto;
+use openpgp::packet::signature::subpacket::NotationDataFlags;
+use openpgp::parse::Parse;
+use openpgp::serialize::Serialize;
+use openpgp::types::ReasonForRevocation;
+
+use crate::{
+    Config,
+    open_or_stdin,
+};
+
+use super::unlock_keypair;
+
+pub fn dispatch(config: Config, m: &clap::ArgMatches) -> Result<()> {
+    match m.subcommand() {
+        ("certificate",  Some(m)) => revoke(config, m, Target::Certificate),
+        ("subkey",  Some(m)) =>
+            revoke(config, m, Target::Subkey(m.value_of("subkey").unwrap())),
+        ("userid",  Some(m)) =>
+            revoke(config, m, Target::UserID(m.value_of("userid").unwrap())),
+        _ => unreachable!(),
+    }
+}
+
+/// What to revoke.
+enum Target<'a> {
+    Certificate,
+    Subkey(&'a str),
+    UserID(&'a str),
+}
+
+fn revoke(config: Config, m: &clap::ArgMatches, target: Target) -> Result<()> {
+    let input = open_or_stdin(m.value_of("certificate"))?;
+    let cert = Cert::from_reader(input)?;
+
+    let revoker = match m.value_of("revocation-key") {
+        Some(file) => Cert::from_file(file)
+            .context(format!("Failed to read revocation key {:?}", file))?,
+        None => cert.clone(),
+    };
+    let mut signer = revocation_signer(&revoker)?;
+
+    let reason = parse_reason(&target, m.value_of("reason").unwrap());
+    let message = m.value_of("message").unwrap().as_bytes();
+    let time: Option<SystemTime> = if let Some(time) = m.value_of("time") {
+        Some(crate::parse_iso8601(time, chrono::NaiveTime::from_hms(0, 0, 0))
+                 .context(format!("Bad value passed to --time: {:?}",
+                                  time))?.into())
+    } else {
+        None
+    };
+
+    // Each --notation takes two values.  The iterator returns them
+    // one at a time, however.
+    let mut notations = Vec::new();
+    if let Some(mut n) = m.values_of("notation") {
+        while let Some(name) = n.next() {
+            let value = n.next().unwrap();
+
+            let (critical, name) = if !name.is_empty()
+                && name.starts_with('!')
+            {
+                (true, &name[1..])
+            } else {
+                (false, name)
+            };
+            notations.push((critical, name, value));
+        }
+    }
+
+    // The packets accompanying the revocation: the primary key, and the
+    // revoked component.
+    let mut packets: Vec<Packet> =
+        vec![cert.primary_key().key().clone().into()];
+
+    let revocation = match target {
+        Target::Certificate => {
+            let mut builder = CertRevocationBuilder::new()
+                .set_reason_for_revocation(reason, message)?;
+            if let Some(time) = time {
+                builder = builder.set_signature_creation_time(time)?;
+            }
+            for (critical, name, value) in notations {
+                builder = builder.add_notation(
+                    name, value,
+                    NotationDataFlags::empty().set_human_readable(),
+                    critical)?;
+            }
+            builder.build(&mut signer, &cert, None)?
+        },
+
+        Target::Subkey(handle) => {
+            let handle: KeyHandle = handle.parse()
+                .context(format!("Bad fingerprint or key ID: {:?}", handle))?;
+            let subkey = match cert.keys().subkeys()
+                .find(|ka| ka.key_handle().aliases(&handle))
+            {
+                Some(ka) => ka.key().clone(),
+                None => {
+                    eprintln!("Subkey {} not found.\nSubkeys:", handle);
+                    for ka in cert.keys().subkeys() {
+                        eprintln!("  - {}", ka.fingerprint());
+                    }
+                    return Err(anyhow::anyhow!("No matching subkey found"));
+                },
+            };
+
+            let mut builder = SubkeyRevocationBuilder::new()
+                .set_reason_for_revocation(reason, message)?;
+            if let Some(time) = time {
+                builder = builder.set_signature_creation_time(time)?;
+            }
+            for (critical, name, value) in notations {
+                builder = builder.add_notation(
+                    name, value,
+                    NotationDataFlags::empty().set_human_readable(),
+                    critical)?;
+            }
+            let revocation = builder.build(&mut signer, &cert, &subkey, None)?;
+            packets.push(subkey.into());
+            revocation
+        },
+
+        Target::UserID(userid) => {
+            let userid = match cert.userids()
+                .find(|ua| ua.userid().value() == userid.as_bytes())
+            {
+                Some(ua) => ua.userid().clone(),
+                None => {
+                    eprintln!("User ID: '{}' not found.\nUser IDs:", userid);
+                    for ua in cert.userids() {
+                        eprintln!("  - {}",
+                                  String::from_utf8_lossy(ua.userid().value()));
+                    }
+                    return Err(anyhow::anyhow!("No matching User ID found"));
+                },
+            };
+
+            let mut builder = UserIDRevocationBuilder::new()
+                .set_reason_for_revocation(reason, message)?;
+            if let Some(time) = time {
+                builder = builder.set_signature_creation_time(time)?;
+            }
+            for (critical, name, value) in notations {
+                builder = builder.add_notation(
+                    name, value,
+                    NotationDataFlags::empty().set_human_readable(),
+                    critical)?;
+            }
+            let revocation = builder.build(&mut signer, &cert, &userid, None)?;
+            packets.push(userid.into());
+            revocation
+        },
+    };
+
+    let mut output = config.create_or_stdout_pgp(
+        m.value_of("output"),
+        m.is_present("binary"), armor::Kind::PublicKey)?;
+    if m.is_present("full-certificate") {
+        let cert = cert.insert_packets(revocation)?;
+        cert.serialize(&mut output)?;
+    } else {
+        packets.push(revocation.into());
+        for packet in packets {
+            packet.serialize(&mut output)?;
+        }
+    }
+    output.finalize()?;
+
+    Ok(())
+}
+
+/// Returns a signer for the primary key of the given key.
+fn revocation_signer(tsk: &Cert) -> Result<crypto::KeyPair> {
+    let key = tsk.primary_key().key();
+    let secret = key.optional_secret().ok_or_else(|| anyhow::anyhow!(
+        "No secret key material for the primary key of {}, \
+         see --revocation-key", tsk.fingerprint()))?;
+    unlock_keypair(tsk, key.role_as_unspecified(), secret)
+}
+
+fn parse_reason(target: &Target, reason: &str) -> ReasonForRevocation {
+    match reason {
+        "compromised" => ReasonForRevocation::KeyCompromised,
+        "superseded" => ReasonForRevocation::KeySuperseded,
+        "retired" => match target {
+            Target::UserID(_) => ReasonForRevocation::UIDRetired,
+            _ => ReasonForRevocation::KeyRetired,
+        },
+        "unspecified" => ReasonForRevocation::Unspecified,
+        _ => unreachable!("checked by clap"),
+    }
+}
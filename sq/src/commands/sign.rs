@@ -1,7 +1,9 @@
 use anyhow::Context as _;
-use std::fs;
-use std::io;
-use std::path::PathBuf;
+use std::fs::{self, File};
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
 use std::time::SystemTime;
 use tempfile::NamedTempFile;
 
@@ -10,6 +12,7 @@ use crate::openpgp::armor;
 use crate::openpgp::{Packet, Result};
 use crate::openpgp::packet::prelude::*;
 use crate::openpgp::packet::signature::subpacket::NotationData;
+use crate::openpgp::policy::Policy;
 use crate::openpgp::parse::{
     Parse,
     PacketParserResult,
@@ -20,7 +23,9 @@ use crate::openpgp::serialize::stream::{
 };
 use crate::openpgp::types::SignatureType;
 
-use crate::secrets::PreSecret;
+use openpgp_dsm::DsmAgent;
+
+use crate::secrets::{GpgAgentKey, PreSecret, Secret};
 
 use crate::Config;
 
@@ -452,3 +457,270 @@ pub fn clearsign(config: Config,
 
     Ok(())
 }
+
+pub struct BatchSignOpts<'a> {
+    pub config: Config<'a>,
+    /// Files or directories to sign.  If empty, the file names are read
+    /// from stdin.
+    pub inputs: Vec<PathBuf>,
+    pub presecrets: Vec<PreSecret>,
+    pub binary: bool,
+    /// Maximum number of concurrent signing operations.
+    pub jobs: usize,
+    pub time: Option<SystemTime>,
+    pub notations: &'a [(bool, NotationData)]
+}
+
+/// Writes detached signatures next to many files.
+///
+/// The signing keys are resolved once, and DSM keys share a single
+/// session.  Up to `jobs` files are signed concurrently.  Failures are
+/// reported per file, and do not stop the other files from being
+/// signed.
+pub fn sign_batch(opts: BatchSignOpts) -> Result<()> {
+    let BatchSignOpts {
+        config,
+        inputs,
+        presecrets,
+        binary,
+        jobs,
+        time,
+        notations,
+    } = opts;
+    let extension = if binary { "sig" } else { "asc" };
+    let force = config.force;
+
+    let files = batch_files(&inputs)?;
+    if files.is_empty() {
+        return Err(anyhow::anyhow!("No files to sign"));
+    }
+
+    let signers = batch_signing_keys(&presecrets, &config.policy, time)?;
+    if signers.is_empty() {
+        return Err(anyhow::anyhow!("No signing keys found"));
+    }
+
+    let mut template = SignatureBuilder::new(SignatureType::Binary);
+    for (critical, n) in notations.iter() {
+        template = template.add_notation(
+            n.name(),
+            n.value(),
+            Some(n.flags().clone()),
+            *critical)?;
+    }
+
+    let total = files.len();
+    let queue = Arc::new(Mutex::new(files.into_iter()));
+    let (sender, receiver) = mpsc::channel();
+    for _ in 0..jobs.max(1).min(total) {
+        let queue = queue.clone();
+        let sender = sender.clone();
+        let signers = signers.clone();
+        let template = template.clone();
+        thread::spawn(move || loop {
+            let path = match queue.lock().unwrap().next() {
+                Some(path) => path,
+                None => break,
+            };
+            let result = sign_detached(&path, extension, signers.clone(),
+                                       template.clone(), time, binary, force);
+            if sender.send((path, result)).is_err() {
+                break;
+            }
+        });
+    }
+    drop(sender);
+
+    let mut failed = 0;
+    for (path, result) in receiver {
+        if let Err(e) = result {
+            eprintln!("{}: {:#}", path.display(), e);
+            failed += 1;
+        }
+    }
+
+    eprintln!("Signed {} of {} files", total - failed, total);
+    if failed > 0 {
+        return Err(anyhow::anyhow!("Failed to sign {} files", failed));
+    }
+    Ok(())
+}
+
+/// Returns the files to sign in batch mode.
+///
+/// Directories are searched recursively, skipping existing detached
+/// signatures, binary or armored.  Symbolic links to directories are
+/// not followed.  Without inputs, the file names are read from stdin,
+/// one per line.
+fn batch_files(inputs: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut inputs = inputs.to_vec();
+    if inputs.is_empty() {
+        for line in io::stdin().lock().lines() {
+            let line = line?;
+            if ! line.is_empty() {
+                inputs.push(line.into());
+            }
+        }
+    }
+
+    let mut files = Vec::new();
+    for input in inputs {
+        if input.is_dir() {
+            walk_dir(&input, &mut files)?;
+        } else {
+            files.push(input);
+        }
+    }
+    Ok(files)
+}
+
+fn walk_dir(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let mut entries = fs::read_dir(dir)
+        .context(format!("Failed to read {}", dir.display()))?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+
+    for path in entries {
+        let metadata = fs::symlink_metadata(&path)
+            .context(format!("Failed to read {}", path.display()))?;
+        if metadata.is_dir() {
+            walk_dir(&path, files)?;
+        } else if metadata.file_type().is_symlink() && path.is_dir() {
+            // Following links to directories may loop, or leave the
+            // tree.
+            eprintln!("Skipping {}, it is a symbolic link to a directory",
+                      path.display());
+        } else if is_detached_signature(&path) {
+            eprintln!("Skipping {}, it is a detached signature",
+                      path.display());
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Returns whether the file at `path` is a detached signature.
+///
+/// Only files named like signatures are inspected.  They are
+/// considered detached signatures if they contain signature packets,
+/// and nothing else.
+fn is_detached_signature(path: &Path) -> bool {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("sig") | Some("asc") => (),
+        _ => return false,
+    }
+
+    let mut ppr = match openpgp::parse::PacketParser::from_file(path) {
+        Ok(ppr) => ppr,
+        Err(_) => return false,
+    };
+    let mut signatures = 0;
+    while let PacketParserResult::Some(pp) = ppr {
+        if ! matches!(pp.packet, Packet::Signature(_)) {
+            return false;
+        }
+        signatures += 1;
+        ppr = match pp.next() {
+            Ok((_, ppr)) => ppr,
+            Err(_) => return false,
+        };
+    }
+    signatures > 0
+}
+
+/// Returns the signing keys for batch mode.
+///
+/// Unlike [`super::get_signing_keys`], the keys can be cloned to sign
+/// concurrently.  DSM keys are resolved once, and share a DSM session.
+fn batch_signing_keys(presecrets: &[PreSecret], p: &dyn Policy,
+                      timestamp: Option<SystemTime>)
+    -> Result<Vec<Secret>>
+{
+    let mut keys = Vec::new();
+    for presecret in presecrets {
+        match presecret {
+            PreSecret::Dsm(credentials, name) => {
+                let agent = DsmAgent::new_signer(credentials.clone(), name)?
+                    .with_session()?;
+                keys.push(Secret::Dsm(agent));
+            }
+            PreSecret::Gpg(context, cert) => {
+                let key = cert.keys().with_policy(p, timestamp).alive()
+                    .revoked(false).for_signing().supported()
+                    .map(|ka| ka.key().clone())
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!(
+                        "Found no suitable signing key on {}", cert))?;
+                keys.push(Secret::Gpg(GpgAgentKey::new(context.clone(), key)));
+            }
+            PreSecret::InMemory(tsk) => {
+                let key = tsk.keys().with_policy(p, timestamp).alive()
+                    .revoked(false).for_signing().supported()
+                    .map(|ka| ka.key())
+                    .next();
+                match key.and_then(|k| k.optional_secret().map(|s| (k, s))) {
+                    Some((key, secret)) => keys.push(Secret::InMemory(
+                        super::unlock_keypair(tsk, key, secret)?)),
+                    None => return Err(anyhow::anyhow!(
+                        "Found no suitable signing key on {}", tsk)),
+                }
+            }
+        }
+    }
+    Ok(keys)
+}
+
+/// Writes a detached signature of `path` to `path.extension`.
+fn sign_detached(path: &Path, extension: &str, mut signers: Vec<Secret>,
+                 template: SignatureBuilder, time: Option<SystemTime>,
+                 binary: bool, force: bool)
+                 -> Result<PathBuf>
+{
+    let mut sig_path = path.as_os_str().to_owned();
+    sig_path.push(".");
+    sig_path.push(extension);
+    let sig_path = PathBuf::from(sig_path);
+    if sig_path.exists() && ! force {
+        return Err(anyhow::anyhow!(
+            "File {:?} exists, use \"sq --force ...\" to overwrite",
+            sig_path));
+    }
+
+    let mut input = File::open(path).context("Failed to open input")?;
+
+    // Write to a temporary file, so that no partial signature is left
+    // behind on failure.
+    let mut output = NamedTempFile::new_in(
+        sig_path.parent().unwrap_or(&PathBuf::from(".")))?;
+    {
+        let mut message = Message::new(&mut output);
+        if ! binary {
+            message = Armorer::new(message)
+                .kind(armor::Kind::Signature)
+                .build()?;
+        }
+
+        let crypto_signer = signers.pop().expect("at least one signer");
+        let mut signer = Signer::with_template(message, crypto_signer,
+                                               template);
+        for s in signers {
+            signer = signer.add_signer(s);
+        }
+        if let Some(time) = time {
+            signer = signer.creation_time(time);
+        }
+        let mut writer = signer.detached().build()
+            .context("Failed to create signer")?;
+
+        io::copy(&mut input, &mut writer)
+            .context("Failed to sign")?;
+        writer.finalize()
+            .context("Failed to sign")?;
+    }
+
+    output.persist(&sig_path)
+        .context(format!("Failed to write {:?}", sig_path))?;
+    Ok(sig_path)
+}
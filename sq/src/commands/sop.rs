@@ -0,0 +1,1195 @@
+//! A Stateless OpenPGP (SOP) command-line interface.
+//!
+//! The [Stateless OpenPGP Command-Line Interface] is a common
+//! interface to OpenPGP implementations, used for instance by the
+//! OpenPGP interoperability test suite.  When `sq` is invoked as
+//! `sq-sop` (e.g. via a symlink), it implements this interface using
+//! sq's signing, encryption, and armoring code:
+//!
+//! ```text
+//! $ ln -s $(which sq) ~/bin/sq-sop
+//! $ sq-sop generate-key "Alice <alice@example.org>" > alice.key
+//! $ sq-sop extract-cert < alice.key > alice.cert
+//! $ sq-sop sign alice.key < message.txt > message.sig
+//! $ sq-sop verify message.sig alice.cert < message.txt
+//! ```
+//!
+//! Arguments naming keys, certificates, signatures, passwords, and
+//! session keys are file names, or one of the special designators
+//! `@ENV:VARIABLE` (the value of an environment variable) and `@FD:N`
+//! (the data read from file descriptor N).  Outputs like
+//! `--session-key-out` accept `@FD:N` as well.
+//!
+//! Keys stored in Fortanix DSM are referred to as `@DSM:KEY-NAME`
+//! wherever a key is expected, and their certificates likewise
+//! wherever a certificate is expected.  The DSM credentials are taken
+//! from the environment (see `sq key generate --help`).
+//!
+//! Failures are reported using the exit codes defined by the
+//! specification.
+//!
+//! [Stateless OpenPGP Command-Line Interface]: https://datatracker.ietf.org/doc/draft-dkg-openpgp-stateless-cli/
+
+use std::env;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Context as _;
+use chrono::{DateTime, NaiveTime, offset::Utc};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+
+use sequoia_openpgp as openpgp;
+use crate::openpgp::{armor, Cert, Fingerprint, KeyHandle, Packet, Result};
+use crate::openpgp::cert::prelude::*;
+use crate::openpgp::crypto::{self, Password, SessionKey};
+use crate::openpgp::fmt::hex;
+use crate::openpgp::packet::prelude::*;
+use crate::openpgp::parse::{Parse, PacketParser, PacketParserResult};
+use crate::openpgp::parse::stream::*;
+use crate::openpgp::serialize::Serialize;
+use crate::openpgp::serialize::stream::{
+    Armorer, LiteralWriter, Message, Signer,
+};
+use crate::openpgp::types::{
+    DataFormat,
+    HashAlgorithm,
+    KeyFlags,
+    SignatureType,
+    SymmetricAlgorithm,
+};
+use openpgp_dsm::{self as dsm, DsmAgent};
+
+use crate::{
+    Config,
+    open_or_stdin,
+};
+use crate::secrets::{Auth, Credentials, GpgAgentKey, PreSecret};
+
+/// The name `sq` must be invoked as to implement SOP.
+const PERSONALITY: &str = "sq-sop";
+
+/// The prefix designating keys and certificates stored in DSM.
+const DSM_PREFIX: &str = "@DSM:";
+
+/// Returns whether we have been invoked as `sq-sop`.
+pub fn invoked_as_sop() -> bool {
+    env::args_os().next()
+        .and_then(|arg0| Path::new(&arg0).file_stem()
+                  .map(|s| s == PERSONALITY))
+        .unwrap_or(false)
+}
+
+/// The failures defined by the specification.
+///
+/// The discriminant is the exit code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Error {
+    NoSignature = 3,
+    UnsupportedAsymmetricAlgo = 13,
+    CertCannotEncrypt = 17,
+    MissingArg = 19,
+    IncompleteVerification = 23,
+    CannotDecrypt = 29,
+    PasswordNotHumanReadable = 31,
+    UnsupportedOption = 37,
+    BadData = 41,
+    ExpectedText = 53,
+    OutputExists = 59,
+    MissingInput = 61,
+    KeyIsProtected = 67,
+    UnsupportedSubcommand = 69,
+    UnsupportedSpecialPrefix = 71,
+    AmbiguousInput = 73,
+    KeyCannotSign = 79,
+    IncompatibleOptions = 83,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::Error::*;
+        f.write_str(match self {
+            NoSignature => "No acceptable signatures found",
+            UnsupportedAsymmetricAlgo => "Asymmetric algorithm unsupported",
+            CertCannotEncrypt => "Certificate not encryption-capable",
+            MissingArg => "Missing required argument",
+            IncompleteVerification =>
+                "Verification requested without certificates",
+            CannotDecrypt => "Unable to decrypt",
+            PasswordNotHumanReadable => "Password is not human-readable",
+            UnsupportedOption => "Unsupported option",
+            BadData => "Invalid data type",
+            ExpectedText => "Non-text input where text expected",
+            OutputExists => "Output file already exists",
+            MissingInput => "Input file does not exist",
+            KeyIsProtected => "Key is password protected",
+            UnsupportedSubcommand => "Unsupported subcommand",
+            UnsupportedSpecialPrefix => "Unsupported special prefix",
+            AmbiguousInput => "Special designator is also a file name",
+            KeyCannotSign => "Key not signature-capable",
+            IncompatibleOptions => "Incompatible options",
+        })
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl Error {
+    /// Returns this failure, explained by `context`.
+    fn because<C>(self, context: C) -> anyhow::Error
+    where
+        C: fmt::Display + Send + Sync + 'static,
+    {
+        anyhow::Error::from(self).context(context)
+    }
+}
+
+/// Marks an error as the given failure.
+fn tag(failure: Error) -> impl FnOnce(anyhow::Error) -> anyhow::Error {
+    move |err| err.context(failure)
+}
+
+/// Returns the exit code for an error.
+fn exit_code(err: &anyhow::Error) -> i32 {
+    if let Some(failure) = err.downcast_ref::<Error>() {
+        return *failure as i32;
+    }
+
+    for cause in err.chain() {
+        // Errors raised while streaming are wrapped in io::Errors.
+        let inner = cause.downcast_ref::<io::Error>()
+            .and_then(|e| e.get_ref())
+            .map(|e| e as &(dyn std::error::Error + 'static));
+        for cause in std::iter::once(cause).chain(inner) {
+            if let Some(failure) = cause.downcast_ref::<Error>() {
+                return *failure as i32;
+            }
+            if let Some(e) = cause.downcast_ref::<openpgp::Error>() {
+                use openpgp::Error::*;
+                match e {
+                    UnsupportedPublicKeyAlgorithm(_) =>
+                        return Error::UnsupportedAsymmetricAlgo as i32,
+                    MalformedPacket(_) | MalformedMessage(_)
+                        | MalformedCert(_) | MalformedMPI(_)
+                        | UnsupportedPacketType(_) | ManipulatedMessage =>
+                        return Error::BadData as i32,
+                    _ => (),
+                }
+            }
+        }
+    }
+    1
+}
+
+fn build() -> App<'static, 'static> {
+    let no_armor = Arg::with_name("no-armor")
+        .long("no-armor")
+        .help("Emits binary data");
+    let with_key_password = Arg::with_name("with-key-password")
+        .long("with-key-password").value_name("PASSWORD")
+        .multiple(true).number_of_values(1)
+        .help("Unlocks the keys with the password read from PASSWORD");
+    let not_before = |name| Arg::with_name(name)
+        .long(name).value_name("DATE")
+        .help("Ignores signatures made before DATE \
+               (\"-\" for no limit, the default)");
+    let not_after = |name| Arg::with_name(name)
+        .long(name).value_name("DATE")
+        .help("Ignores signatures made after DATE \
+               (\"-\" for no limit, default: now)");
+    let verifications_out = Arg::with_name("verifications-out")
+        .long("verifications-out").alias("verify-out").value_name("VERIFICATIONS")
+        .help("Writes the verification results to VERIFICATIONS");
+
+    App::new(PERSONALITY)
+        .about("Stateless OpenPGP command-line interface for sq")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .setting(AppSettings::VersionlessSubcommands)
+        .setting(AppSettings::DisableVersion)
+        .subcommand(SubCommand::with_name("version")
+                    .about("Prints version information")
+                    .arg(Arg::with_name("backend")
+                         .long("backend")
+                         .help("Prints the version of the OpenPGP library"))
+                    .arg(Arg::with_name("extended")
+                         .long("extended")
+                         .help("Prints detailed version information")))
+        .subcommand(SubCommand::with_name("generate-key")
+                    .about("Generates a secret key")
+                    .arg(no_armor.clone())
+                    .arg(Arg::with_name("with-key-password")
+                         .long("with-key-password").value_name("PASSWORD")
+                         .help("Protects the key with the password read \
+                                from PASSWORD"))
+                    .arg(Arg::with_name("signing-only")
+                         .long("signing-only")
+                         .help("Generates a key that cannot encrypt"))
+                    .arg(Arg::with_name("userids")
+                         .value_name("USERID")
+                         .multiple(true)
+                         .help("Adds USERID to the key")))
+        .subcommand(SubCommand::with_name("extract-cert")
+                    .about("Extracts the certificates from secret keys")
+                    .arg(no_armor.clone()))
+        .subcommand(SubCommand::with_name("sign")
+                    .about("Creates detached signatures")
+                    .arg(no_armor.clone())
+                    .arg(Arg::with_name("as")
+                         .long("as").value_name("TYPE")
+                         .possible_values(&["binary", "text"])
+                         .default_value("binary")
+                         .help("Makes signatures over binary data or text"))
+                    .arg(Arg::with_name("micalg-out")
+                         .long("micalg-out").value_name("MICALG")
+                         .help("Writes the digest algorithm used to MICALG"))
+                    .arg(with_key_password.clone())
+                    .arg(Arg::with_name("keys")
+                         .value_name("KEYS")
+                         .required(true).multiple(true)
+                         .help("Signs using the keys in KEYS")))
+        .subcommand(SubCommand::with_name("verify")
+                    .about("Verifies detached signatures")
+                    .arg(not_before("not-before"))
+                    .arg(not_after("not-after"))
+                    .arg(Arg::with_name("signatures")
+                         .value_name("SIGNATURES")
+                         .required(true)
+                         .help("Reads the signatures from SIGNATURES"))
+                    .arg(Arg::with_name("certs")
+                         .value_name("CERTS")
+                         .required(true).multiple(true)
+                         .help("Accepts signatures by the certificates \
+                                in CERTS")))
+        .subcommand(SubCommand::with_name("encrypt")
+                    .about("Encrypts a message")
+                    .arg(no_armor.clone())
+                    .arg(Arg::with_name("as")
+                         .long("as").value_name("TYPE")
+                         .possible_values(&["binary", "text"])
+                         .default_value("binary")
+                         .help("Encrypts binary data or text"))
+                    .arg(Arg::with_name("with-password")
+                         .long("with-password").value_name("PASSWORD")
+                         .multiple(true).number_of_values(1)
+                         .help("Encrypts with the password read from \
+                                PASSWORD"))
+                    .arg(Arg::with_name("sign-with")
+                         .long("sign-with").value_name("KEYS")
+                         .multiple(true).number_of_values(1)
+                         .help("Signs using the keys in KEYS"))
+                    .arg(with_key_password.clone())
+                    .arg(Arg::with_name("certs")
+                         .value_name("CERTS")
+                         .multiple(true)
+                         .help("Encrypts for the certificates in CERTS")))
+        .subcommand(SubCommand::with_name("decrypt")
+                    .about("Decrypts a message")
+                    .arg(Arg::with_name("session-key-out")
+                         .long("session-key-out").value_name("SESSIONKEY")
+                         .help("Writes the session key to SESSIONKEY"))
+                    .arg(Arg::with_name("with-session-key")
+                         .long("with-session-key").value_name("SESSIONKEY")
+                         .multiple(true).number_of_values(1)
+                         .help("Decrypts with the session key read from \
+                                SESSIONKEY"))
+                    .arg(Arg::with_name("with-password")
+                         .long("with-password").value_name("PASSWORD")
+                         .multiple(true).number_of_values(1)
+                         .help("Decrypts with the password read from \
+                                PASSWORD"))
+                    .arg(with_key_password.clone())
+                    .arg(verifications_out.clone())
+                    .arg(Arg::with_name("verify-with")
+                         .long("verify-with").value_name("CERTS")
+                         .multiple(true).number_of_values(1)
+                         .help("Verifies signatures by the certificates \
+                                in CERTS"))
+                    .arg(not_before("verify-not-before"))
+                    .arg(not_after("verify-not-after"))
+                    .arg(Arg::with_name("keys")
+                         .value_name("KEYS")
+                         .multiple(true)
+                         .help("Decrypts using the keys in KEYS")))
+        .subcommand(SubCommand::with_name("armor")
+                    .about("Adds ASCII armor"))
+        .subcommand(SubCommand::with_name("dearmor")
+                    .about("Removes ASCII armor"))
+        .subcommand(SubCommand::with_name("inline-sign")
+                    .about("Creates an inline-signed message")
+                    .arg(no_armor.clone())
+                    .arg(Arg::with_name("as")
+                         .long("as").value_name("TYPE")
+                         .possible_values(&["binary", "text", "clearsigned"])
+                         .default_value("binary")
+                         .help("Signs binary data or text, or makes a \
+                                cleartext signature"))
+                    .arg(with_key_password)
+                    .arg(Arg::with_name("keys")
+                         .value_name("KEYS")
+                         .required(true).multiple(true)
+                         .help("Signs using the keys in KEYS")))
+        .subcommand(SubCommand::with_name("inline-verify")
+                    .about("Verifies an inline-signed message")
+                    .arg(not_before("not-before"))
+                    .arg(not_after("not-after"))
+                    .arg(verifications_out)
+                    .arg(Arg::with_name("certs")
+                         .value_name("CERTS")
+                         .required(true).multiple(true)
+                         .help("Accepts signatures by the certificates \
+                                in CERTS")))
+        .subcommand(SubCommand::with_name("inline-detach")
+                    .about("Splits the signatures from an inline-signed \
+                            message")
+                    .arg(no_armor)
+                    .arg(Arg::with_name("signatures-out")
+                         .long("signatures-out").value_name("SIGNATURES")
+                         .required(true)
+                         .help("Writes the signatures to SIGNATURES")))
+}
+
+/// Implements SOP, exiting with the code of the failure, if any.
+pub fn dispatch(config: Config) -> Result<()> {
+    if let Err(err) = dispatch_from(config, env::args_os()) {
+        eprintln!("{}: {}", PERSONALITY, err);
+        err.chain().skip(1)
+            .for_each(|cause| eprintln!("  because: {}", cause));
+        process::exit(exit_code(&err));
+    }
+    Ok(())
+}
+
+fn dispatch_from<I>(config: Config, args: I) -> Result<()>
+where
+    I: IntoIterator<Item = OsString>,
+{
+    let args: Vec<OsString> = args.into_iter().collect();
+    let m = match build().get_matches_from_safe(args.clone()) {
+        Ok(m) => m,
+        Err(e) => {
+            use clap::ErrorKind::*;
+            let failure = match e.kind {
+                HelpDisplayed | VersionDisplayed => e.exit(),
+                // clap reports unknown subcommands as unexpected
+                // arguments.
+                UnknownArgument if e.info.as_ref()
+                    .and_then(|info| info.get(0))
+                    .zip(args.get(1))
+                    .map(|(arg, first)| first.as_os_str() == arg.as_str())
+                    .unwrap_or(false) =>
+                    Error::UnsupportedSubcommand,
+                UnrecognizedSubcommand | InvalidSubcommand
+                    | MissingSubcommand | MissingArgumentOrSubcommand =>
+                    Error::UnsupportedSubcommand,
+                MissingRequiredArgument | EmptyValue | TooFewValues =>
+                    Error::MissingArg,
+                ArgumentConflict => Error::IncompatibleOptions,
+                _ => Error::UnsupportedOption,
+            };
+            eprintln!("{}", e.message);
+            process::exit(failure as i32);
+        },
+    };
+
+    match m.subcommand() {
+        ("version", Some(m)) => version(m),
+        ("generate-key", Some(m)) => generate_key(config, m),
+        ("extract-cert", Some(m)) => extract_cert(config, m),
+        ("sign", Some(m)) => sign(config, m),
+        ("verify", Some(m)) => verify(config, m),
+        ("encrypt", Some(m)) => encrypt(config, m),
+        ("decrypt", Some(m)) => decrypt(config, m),
+        ("armor", Some(_)) =>
+            crate::armor(&config, open_or_stdin(None)?, None, None),
+        ("dearmor", Some(_)) => dearmor(),
+        ("inline-sign", Some(m)) => inline_sign(config, m),
+        ("inline-verify", Some(m)) => inline_verify(config, m),
+        ("inline-detach", Some(m)) => inline_detach(m),
+        (name, _) => Err(Error::UnsupportedSubcommand.because(
+            format!("{:?} is not supported", name))),
+    }
+}
+
+/// Opens an input given as file name or special designator.
+fn open_input(name: &str) -> Result<Box<dyn io::Read + Send + Sync>> {
+    if name.starts_with('@') && Path::new(name).exists() {
+        return Err(Error::AmbiguousInput.because(
+            format!("{:?} is a file", name)));
+    }
+
+    if let Some(var) = name.strip_prefix("@ENV:") {
+        let value = env::var(var).map_err(|_| Error::MissingInput.because(
+            format!("Environment variable {} is not set", var)))?;
+        Ok(Box::new(io::Cursor::new(value.into_bytes())))
+    } else if let Some(fd) = name.strip_prefix("@FD:") {
+        Ok(Box::new(fd_file(fd)?))
+    } else if name.starts_with('@') {
+        Err(Error::UnsupportedSpecialPrefix.because(
+            format!("Cannot read from {:?}", name)))
+    } else {
+        Ok(Box::new(File::open(name).map_err(|e| Error::MissingInput.because(
+            format!("Failed to open {:?}: {}", name, e)))?))
+    }
+}
+
+/// Reads an input given as file name or special designator.
+fn read_input(name: &str) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    open_input(name)?.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// Creates an output given as file name or special designator.
+///
+/// Existing files are not overwritten.
+fn create_output(name: &str) -> Result<Box<dyn io::Write + Send + Sync>> {
+    if let Some(fd) = name.strip_prefix("@FD:") {
+        Ok(Box::new(fd_file(fd)?))
+    } else if name.starts_with('@') {
+        Err(Error::UnsupportedSpecialPrefix.because(
+            format!("Cannot write to {:?}", name)))
+    } else {
+        match OpenOptions::new().write(true).create_new(true).open(name) {
+            Ok(f) => Ok(Box::new(f)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists =>
+                Err(Error::OutputExists.because(format!("{:?} exists", name))),
+            Err(e) => Err(anyhow::Error::from(e)
+                          .context(format!("Failed to create {:?}", name))),
+        }
+    }
+}
+
+/// Returns the file with the given descriptor number.
+///
+/// The descriptor is duplicated, so that closing the file neither closes
+/// stdin or stdout, nor a descriptor that is named more than once.
+#[cfg(unix)]
+fn fd_file(fd: &str) -> Result<File> {
+    use std::os::unix::io::FromRawFd;
+    let fd: u16 = fd.parse().map_err(|_| Error::UnsupportedSpecialPrefix
+                                     .because(format!("Bad descriptor {:?}", fd)))?;
+    // Safety: dup(2) does not affect the original descriptor.
+    let dup = unsafe { libc::dup(fd.into()) };
+    if dup < 0 {
+        return Err(Error::UnsupportedSpecialPrefix.because(format!(
+            "Bad descriptor {}: {}", fd, io::Error::last_os_error())));
+    }
+    // Safety: the duplicate is new, we are its only user.
+    Ok(unsafe { File::from_raw_fd(dup) })
+}
+
+/// Returns the file with the given descriptor number.
+#[cfg(not(unix))]
+fn fd_file(fd: &str) -> Result<File> {
+    Err(Error::UnsupportedSpecialPrefix.because(
+        format!("@FD:{} is not supported on this platform", fd)))
+}
+
+/// Reads a password to protect data with.
+///
+/// The password must be UTF-8, trailing whitespace is stripped.
+fn read_new_password(name: &str) -> Result<Password> {
+    let password = String::from_utf8(read_input(name)?)
+        .map_err(|_| Error::PasswordNotHumanReadable.because(
+            format!("Password {:?} is not UTF-8", name)))?;
+    Ok(password.trim_end().into())
+}
+
+/// Reads passwords to unlock data with.
+///
+/// The password may have been used with or without trailing
+/// whitespace, so both variants are returned.
+fn read_passwords<'a>(names: impl Iterator<Item = &'a str>)
+                      -> Result<Vec<Password>> {
+    let mut passwords = Vec::new();
+    for name in names {
+        let password = read_input(name)?;
+        let trimmed = password.iter()
+            .rposition(|b| ! b.is_ascii_whitespace())
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        if trimmed < password.len() {
+            passwords.push(password[..trimmed].to_vec().into());
+        }
+        passwords.push(password.into());
+    }
+    Ok(passwords)
+}
+
+/// Reads a session key of the form `ALGORITHM:HEX`.
+fn read_session_key(name: &str) -> Result<(SymmetricAlgorithm, SessionKey)> {
+    let bad = || Error::BadData.because(
+        format!("{:?} is not a session key", name));
+    let session_key = String::from_utf8(read_input(name)?)
+        .map_err(|_| bad())?;
+    let (algo, key) = session_key.trim().split_once(':').ok_or_else(bad)?;
+    let algo: u8 = algo.parse().map_err(|_| bad())?;
+    let key = hex::decode(key).map_err(|_| bad())?;
+    Ok((algo.into(), key.into()))
+}
+
+/// Returns the DSM credentials from the environment.
+fn dsm_credentials() -> Result<Credentials> {
+    Credentials::new(Auth::from_options_or_env(None, None, None, None)?)
+}
+
+/// Loads the keys named by the given arguments, unlocking them using
+/// `passwords`.
+fn load_keys<'a>(names: impl Iterator<Item = &'a str>,
+                 passwords: &[Password])
+                 -> Result<Vec<PreSecret>> {
+    let mut keys = Vec::new();
+    for name in names {
+        if let Some(key_name) = name.strip_prefix(DSM_PREFIX) {
+            keys.push(PreSecret::Dsm(dsm_credentials()?, key_name.into()));
+            continue;
+        }
+
+        for key in CertParser::from_reader(open_input(name)?)
+            .map_err(tag(Error::BadData))?
+        {
+            let key = key.map_err(tag(Error::BadData))?;
+            if ! key.is_tsk() {
+                return Err(Error::BadData.because(
+                    format!("{} in {:?} is not a secret key",
+                            key.fingerprint(), name)));
+            }
+            keys.push(PreSecret::InMemory(unlock(key, passwords)?));
+        }
+    }
+    Ok(keys)
+}
+
+/// Unlocks the encrypted secret keys of `key` using one of
+/// `passwords`.
+fn unlock(key: Cert, passwords: &[Password]) -> Result<Cert> {
+    let mut unlocked: Vec<Packet> = Vec::new();
+    for ka in key.keys().secret() {
+        if ! ka.key().secret().is_encrypted() {
+            continue;
+        }
+
+        let k = passwords.iter()
+            .find_map(|p| ka.key().clone().decrypt_secret(p).ok())
+            .ok_or_else(|| Error::KeyIsProtected.because(
+                format!("Cannot unlock {}", ka.fingerprint())))?;
+        unlocked.push(if ka.primary() {
+            k.role_into_primary().into()
+        } else {
+            k.role_into_subkey().into()
+        });
+    }
+    key.insert_packets(unlocked)
+}
+
+/// Loads the certificates named by the given arguments.
+fn load_certs<'a>(names: impl Iterator<Item = &'a str>) -> Result<Vec<Cert>> {
+    let mut certs = Vec::new();
+    for name in names {
+        if let Some(key_name) = name.strip_prefix(DSM_PREFIX) {
+            certs.push(dsm::extract_cert(key_name, dsm_credentials()?)?);
+            continue;
+        }
+
+        for cert in CertParser::from_reader(open_input(name)?)
+            .map_err(tag(Error::BadData))?
+        {
+            certs.push(cert.map_err(tag(Error::BadData))?
+                       .strip_secret_key_material());
+        }
+    }
+    Ok(certs)
+}
+
+/// Checks that each of the keys can make signatures.
+fn check_signing_keys(config: &Config, keys: &[PreSecret]) -> Result<()> {
+    for key in keys {
+        if let PreSecret::InMemory(tsk) = key {
+            if tsk.keys().with_policy(&config.policy, None)
+                .alive().revoked(false).for_signing().secret()
+                .next().is_none()
+            {
+                return Err(Error::KeyCannotSign.because(
+                    format!("{} has no usable signing key",
+                            tsk.fingerprint())));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks that each of the certificates can be encrypted for.
+fn check_recipients(config: &Config, certs: &[Cert]) -> Result<()> {
+    for cert in certs {
+        if cert.keys().with_policy(&config.policy, None)
+            .alive().revoked(false)
+            .for_transport_encryption().for_storage_encryption()
+            .supported().next().is_none()
+        {
+            return Err(Error::CertCannotEncrypt.because(
+                format!("{} has no usable encryption key",
+                        cert.fingerprint())));
+        }
+    }
+    Ok(())
+}
+
+/// Parses a `--not-before` or `--not-after` argument.
+///
+/// `-` means no limit, and `now` the current time.
+fn parse_time(time: Option<&str>, default: Option<SystemTime>,
+              pad_date_with: NaiveTime)
+              -> Result<Option<SystemTime>> {
+    match time {
+        None => Ok(default),
+        Some("-") => Ok(None),
+        Some("now") => Ok(Some(SystemTime::now())),
+        Some(time) => Ok(Some(crate::parse_iso8601(time, pad_date_with)
+                              .map_err(tag(Error::UnsupportedOption))?
+                              .into())),
+    }
+}
+
+/// Returns the signature type requested using `--as`.
+fn signature_type(m: &ArgMatches) -> SignatureType {
+    match m.value_of("as") {
+        Some("text") => SignatureType::Text,
+        _ => SignatureType::Binary,
+    }
+}
+
+/// Reads the data to sign from stdin.
+fn read_data(typ: SignatureType) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    io::stdin().read_to_end(&mut data)?;
+    if typ == SignatureType::Text && std::str::from_utf8(&data).is_err() {
+        return Err(Error::ExpectedText.because("The input is not UTF-8"));
+    }
+    Ok(data)
+}
+
+/// Signs `data`, writing a detached signature or an inline-signed
+/// message to `sink`.
+///
+/// Returns the hash algorithm used.
+fn sign_data(config: &Config, keys: &[PreSecret], data: &[u8],
+             sink: Message, typ: SignatureType, detached: bool)
+             -> Result<HashAlgorithm> {
+    let mut signers =
+        super::get_signing_keys(keys, &config.policy, None, None)?;
+    let first = signers.pop().ok_or_else(|| Error::MissingArg.because(
+        "No signing keys given"))?;
+
+    let hash_algo = HashAlgorithm::default();
+    let mut signer =
+        Signer::with_template(sink, first, SignatureBuilder::new(typ))
+        .hash_algo(hash_algo)?;
+    for s in signers {
+        signer = signer.add_signer(s);
+    }
+    if detached {
+        signer = signer.detached();
+    }
+    let signer = signer.build().context("Failed to create signer")?;
+
+    let mut writer = if detached {
+        signer
+    } else {
+        LiteralWriter::new(signer)
+            .format(if typ == SignatureType::Text {
+                DataFormat::Unicode
+            } else {
+                DataFormat::Binary
+            })
+            .build()
+            .context("Failed to create literal writer")?
+    };
+    writer.write_all(data).context("Failed to sign")?;
+    writer.finalize().context("Failed to sign")?;
+    Ok(hash_algo)
+}
+
+/// A good signature.
+struct Verification {
+    time: SystemTime,
+    key: Fingerprint,
+    cert: Fingerprint,
+    typ: SignatureType,
+}
+
+impl fmt::Display for Verification {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {} mode:{}",
+               DateTime::<Utc>::from(self.time).format("%Y-%m-%dT%H:%M:%SZ"),
+               self.key.to_hex(),
+               self.cert.to_hex(),
+               if self.typ == SignatureType::Text { "text" } else { "binary" })
+    }
+}
+
+/// Writes the verification results.
+fn write_verifications(output: &mut dyn io::Write,
+                       verifications: &[Verification])
+                       -> Result<()> {
+    for v in verifications {
+        writeln!(output, "{}", v)?;
+    }
+    Ok(())
+}
+
+/// Collects the good signatures made in a time window.
+struct VHelper {
+    certs: Vec<Cert>,
+    not_before: Option<SystemTime>,
+    not_after: Option<SystemTime>,
+    /// Whether to fail if there is no good signature.
+    require_signature: bool,
+    verifications: Vec<Verification>,
+}
+
+impl VHelper {
+    fn new(certs: Vec<Cert>, not_before: Option<&str>,
+           not_after: Option<&str>, require_signature: bool)
+           -> Result<Self> {
+        Ok(VHelper {
+            certs,
+            not_before: parse_time(not_before, None,
+                                   NaiveTime::from_hms(0, 0, 0))?,
+            not_after: parse_time(not_after, Some(SystemTime::now()),
+                                  NaiveTime::from_hms(23, 59, 59))?,
+            require_signature,
+            verifications: Vec::new(),
+        })
+    }
+}
+
+impl VerificationHelper for VHelper {
+    fn get_certs(&mut self, _ids: &[KeyHandle]) -> Result<Vec<Cert>> {
+        Ok(self.certs.clone())
+    }
+
+    fn check(&mut self, structure: MessageStructure) -> Result<()> {
+        for layer in structure {
+            if let MessageLayer::SignatureGroup { results } = layer {
+                for result in results {
+                    if let Ok(GoodChecksum { sig, ka, .. }) = result {
+                        let time = sig.signature_creation_time()
+                            .unwrap_or(UNIX_EPOCH);
+                        if self.not_before.map(|t| time < t).unwrap_or(false)
+                            || self.not_after.map(|t| time > t).unwrap_or(false)
+                        {
+                            continue;
+                        }
+                        self.verifications.push(Verification {
+                            time,
+                            key: ka.key().fingerprint(),
+                            cert: ka.cert().fingerprint(),
+                            typ: sig.typ(),
+                        });
+                    }
+                }
+            }
+        }
+
+        if self.require_signature && self.verifications.is_empty() {
+            // Not explained, so that it survives being wrapped in an
+            // io::Error by the streaming verifier.
+            return Err(Error::NoSignature.into());
+        }
+        Ok(())
+    }
+}
+
+/// Decrypts messages using keys, passwords, and session keys.
+struct DHelper {
+    vhelper: VHelper,
+    /// Decryptors, and the certificates they belong to, if known.
+    decryptors: Vec<(Box<dyn crypto::Decryptor>, Option<Fingerprint>)>,
+    passwords: Vec<Password>,
+    session_keys: Vec<(SymmetricAlgorithm, SessionKey)>,
+    /// The session key that decrypted the message.
+    session_key: Option<(SymmetricAlgorithm, SessionKey)>,
+}
+
+impl DHelper {
+    fn new(config: &Config, vhelper: VHelper, keys: Vec<PreSecret>,
+           passwords: Vec<Password>,
+           session_keys: Vec<(SymmetricAlgorithm, SessionKey)>)
+           -> Result<Self> {
+        let mut decryptors = Vec::new();
+        for key in keys {
+            match key {
+                PreSecret::Dsm(credentials, name) => {
+                    for d in DsmAgent::new_decryptors(credentials, &name)? {
+                        decryptors.push(
+                            (Box::new(d) as Box<dyn crypto::Decryptor>, None));
+                    }
+                },
+                PreSecret::InMemory(tsk) => {
+                    for ka in tsk.keys().with_policy(&config.policy, None)
+                        .for_transport_encryption().for_storage_encryption()
+                        .secret()
+                    {
+                        decryptors.push((
+                            Box::new(ka.key().clone().into_keypair()?)
+                                as Box<dyn crypto::Decryptor>,
+                            Some(tsk.fingerprint())));
+                    }
+                },
+                PreSecret::Gpg(context, cert) => {
+                    for ka in cert.keys().with_policy(&config.policy, None)
+                        .for_transport_encryption().for_storage_encryption()
+                    {
+                        decryptors.push((
+                            Box::new(GpgAgentKey::new(context.clone(),
+                                                      ka.key().clone()))
+                                as Box<dyn crypto::Decryptor>,
+                            Some(cert.fingerprint())));
+                    }
+                },
+            }
+        }
+
+        Ok(DHelper {
+            vhelper,
+            decryptors,
+            passwords,
+            session_keys,
+            session_key: None,
+        })
+    }
+}
+
+impl VerificationHelper for DHelper {
+    fn get_certs(&mut self, ids: &[KeyHandle]) -> Result<Vec<Cert>> {
+        self.vhelper.get_certs(ids)
+    }
+
+    fn check(&mut self, structure: MessageStructure) -> Result<()> {
+        self.vhelper.check(structure)
+    }
+}
+
+impl DecryptionHelper for DHelper {
+    fn decrypt<D>(&mut self, pkesks: &[PKESK], skesks: &[SKESK],
+                  sym_algo: Option<SymmetricAlgorithm>,
+                  mut decrypt: D) -> Result<Option<Fingerprint>>
+        where D: FnMut(SymmetricAlgorithm, &SessionKey) -> bool
+    {
+        for (algo, sk) in &self.session_keys {
+            if decrypt(*algo, sk) {
+                self.session_key = Some((*algo, sk.clone()));
+                return Ok(None);
+            }
+        }
+
+        for pkesk in pkesks {
+            for (decryptor, cert) in self.decryptors.iter_mut() {
+                let recipient = pkesk.recipient();
+                if ! recipient.is_wildcard()
+                    && *recipient != decryptor.public().keyid()
+                {
+                    continue;
+                }
+                if let Some((algo, sk)) =
+                    pkesk.decrypt(&mut **decryptor, sym_algo)
+                {
+                    if decrypt(algo, &sk) {
+                        self.session_key = Some((algo, sk));
+                        return Ok(cert.clone());
+                    }
+                }
+            }
+        }
+
+        for skesk in skesks {
+            for password in &self.passwords {
+                if let Ok((algo, sk)) = skesk.decrypt(password) {
+                    if decrypt(algo, &sk) {
+                        self.session_key = Some((algo, sk));
+                        return Ok(None);
+                    }
+                }
+            }
+        }
+
+        Err(Error::CannotDecrypt.into())
+    }
+}
+
+fn version(m: &ArgMatches) -> Result<()> {
+    if m.is_present("backend") {
+        println!("sequoia-openpgp {}", openpgp::VERSION);
+        return Ok(());
+    }
+
+    println!("{} {}", PERSONALITY, env!("CARGO_PKG_VERSION"));
+    if m.is_present("extended") {
+        println!("sequoia-openpgp {}", openpgp::VERSION);
+        println!("Keys stored in Fortanix DSM: {}KEY-NAME", DSM_PREFIX);
+    }
+    Ok(())
+}
+
+fn generate_key(config: Config, m: &ArgMatches) -> Result<()> {
+    let mut builder = CertBuilder::new()
+        .add_signing_subkey();
+    if ! m.is_present("signing-only") {
+        builder = builder.add_subkey(KeyFlags::empty()
+                                     .set_transport_encryption()
+                                     .set_storage_encryption(), None, None);
+    }
+    for userid in m.values_of("userids").unwrap_or_default() {
+        builder = builder.add_userid(userid);
+    }
+    if let Some(name) = m.value_of("with-key-password") {
+        builder = builder.set_password(Some(read_new_password(name)?));
+    }
+    let (key, _) = builder.generate()?;
+
+    let mut output = config.create_or_stdout_pgp(
+        None, m.is_present("no-armor"), armor::Kind::SecretKey)?;
+    key.as_tsk().serialize(&mut output)?;
+    output.finalize()?;
+    Ok(())
+}
+
+fn extract_cert(config: Config, m: &ArgMatches) -> Result<()> {
+    let mut output = config.create_or_stdout_pgp(
+        None, m.is_present("no-armor"), armor::Kind::PublicKey)?;
+    for key in CertParser::from_reader(io::stdin())
+        .map_err(tag(Error::BadData))?
+    {
+        let key = key.map_err(tag(Error::BadData))?;
+        if ! key.is_tsk() {
+            return Err(Error::BadData.because(
+                format!("{} is not a secret key", key.fingerprint())));
+        }
+        key.serialize(&mut output)?;
+    }
+    output.finalize()?;
+    Ok(())
+}
+
+fn sign(config: Config, m: &ArgMatches) -> Result<()> {
+    let passwords =
+        read_passwords(m.values_of("with-key-password").unwrap_or_default())?;
+    let keys = load_keys(m.values_of("keys").expect("required"), &passwords)?;
+    check_signing_keys(&config, &keys)?;
+    let mut micalg_out = m.value_of("micalg-out").map(create_output)
+        .transpose()?;
+
+    let typ = signature_type(m);
+    let data = read_data(typ)?;
+    let output = config.create_or_stdout_pgp(
+        None, m.is_present("no-armor"), armor::Kind::Signature)?;
+    let hash_algo = sign_data(&config, &keys, &data, output, typ, true)?;
+
+    if let Some(micalg_out) = micalg_out.as_mut() {
+        write!(micalg_out, "pgp-{}", hash_algo.to_string().to_lowercase())?;
+    }
+    Ok(())
+}
+
+fn verify(config: Config, m: &ArgMatches) -> Result<()> {
+    let signatures = open_input(m.value_of("signatures").expect("required"))?;
+    let certs = load_certs(m.values_of("certs").expect("required"))?;
+    let helper = VHelper::new(certs, m.value_of("not-before"),
+                              m.value_of("not-after"), true)?;
+
+    let mut v = DetachedVerifierBuilder::from_reader(signatures)
+        .map_err(tag(Error::BadData))?
+        .with_policy(&config.policy, None, helper)?;
+    v.verify_reader(io::stdin())?;
+    write_verifications(&mut io::stdout(), &v.into_helper().verifications)
+}
+
+fn encrypt(config: Config, m: &ArgMatches) -> Result<()> {
+    let certs = load_certs(m.values_of("certs").unwrap_or_default())?;
+    check_recipients(&config, &certs)?;
+    let passwords = m.values_of("with-password").unwrap_or_default()
+        .map(read_new_password)
+        .collect::<Result<Vec<_>>>()?;
+    if certs.is_empty() && passwords.is_empty() {
+        return Err(Error::MissingArg.because(
+            "Neither certificates nor passwords given"));
+    }
+    let key_passwords =
+        read_passwords(m.values_of("with-key-password").unwrap_or_default())?;
+    let signers = load_keys(m.values_of("sign-with").unwrap_or_default(),
+                            &key_passwords)?;
+    check_signing_keys(&config, &signers)?;
+
+    // Text must be checked before it is encrypted.
+    let typ = signature_type(m);
+    let mut input: Box<dyn io::Read> = if typ == SignatureType::Text {
+        Box::new(io::Cursor::new(read_data(typ)?))
+    } else {
+        Box::new(io::stdin())
+    };
+
+    let message = config.create_or_stdout_pgp(
+        None, m.is_present("no-armor"), armor::Kind::Message)?;
+    super::encrypt(super::EncryptOpts {
+        policy: &config.policy,
+        private_key_store: None,
+        input: &mut input,
+        message,
+        npasswords: 0,
+        passwords,
+        recipients: &certs,
+        dsm_wrapping_keys: Vec::new(),
+        signers,
+        mode: KeyFlags::empty()
+            .set_storage_encryption().set_transport_encryption(),
+        compression: "none",
+        time: None,
+        use_expired_subkey: false,
+        use_expired_recipient: false,
+        text: typ == SignatureType::Text,
+    })
+}
+
+fn decrypt(config: Config, m: &ArgMatches) -> Result<()> {
+    if m.is_present("verifications-out") && ! m.is_present("verify-with") {
+        return Err(Error::IncompleteVerification.because(
+            "--verifications-out requires --verify-with"));
+    }
+
+    let key_passwords =
+        read_passwords(m.values_of("with-key-password").unwrap_or_default())?;
+    let keys = load_keys(m.values_of("keys").unwrap_or_default(),
+                         &key_passwords)?;
+    let passwords =
+        read_passwords(m.values_of("with-password").unwrap_or_default())?;
+    let session_keys = m.values_of("with-session-key").unwrap_or_default()
+        .map(read_session_key)
+        .collect::<Result<Vec<_>>>()?;
+    if keys.is_empty() && passwords.is_empty() && session_keys.is_empty() {
+        return Err(Error::MissingArg.because(
+            "Neither keys, passwords, nor session keys given"));
+    }
+
+    let certs = load_certs(m.values_of("verify-with").unwrap_or_default())?;
+
+    // Create the outputs first, so that we fail early.
+    let mut session_key_out = m.value_of("session-key-out")
+        .map(create_output).transpose()?;
+    let mut verifications_out = m.value_of("verifications-out")
+        .map(create_output).transpose()?;
+
+    let vhelper = VHelper::new(certs, m.value_of("verify-not-before"),
+                               m.value_of("verify-not-after"), false)?;
+    let helper = DHelper::new(&config, vhelper, keys, passwords,
+                              session_keys)?;
+    let mut decryptor = DecryptorBuilder::from_reader(io::stdin())
+        .map_err(tag(Error::BadData))?
+        .with_policy(&config.policy, None, helper)?;
+    io::copy(&mut decryptor, &mut io::stdout())?;
+
+    let helper = decryptor.into_helper();
+    if let (Some(output), Some((algo, sk))) =
+        (session_key_out.as_mut(), helper.session_key.as_ref())
+    {
+        writeln!(output, "{}:{}", u8::from(*algo), hex::encode(sk))?;
+    }
+    if let Some(output) = verifications_out.as_mut() {
+        write_verifications(output, &helper.vhelper.verifications)?;
+    }
+    Ok(())
+}
+
+fn dearmor() -> Result<()> {
+    let mut input = open_or_stdin(None)?;
+    let mut reader = armor::Reader::new(&mut input, None);
+    io::copy(&mut reader, &mut io::stdout())
+        .map_err(|e| anyhow::Error::from(e).context(Error::BadData))?;
+    Ok(())
+}
+
+fn inline_sign(config: Config, m: &ArgMatches) -> Result<()> {
+    let passwords =
+        read_passwords(m.values_of("with-key-password").unwrap_or_default())?;
+    let keys = load_keys(m.values_of("keys").expect("required"), &passwords)?;
+    check_signing_keys(&config, &keys)?;
+
+    if m.value_of("as") == Some("clearsigned") {
+        if m.is_present("no-armor") {
+            return Err(Error::IncompatibleOptions.because(
+                "Cleartext signatures cannot be emitted without armor"));
+        }
+        return super::sign::clearsign(config, None, io::stdin(), io::stdout(),
+                                      keys, None, &[]);
+    }
+
+    let typ = signature_type(m);
+    let data = read_data(typ)?;
+    let output = config.create_or_stdout_pgp(
+        None, m.is_present("no-armor"), armor::Kind::Message)?;
+    sign_data(&config, &keys, &data, output, typ, false)?;
+    Ok(())
+}
+
+fn inline_verify(config: Config, m: &ArgMatches) -> Result<()> {
+    let certs = load_certs(m.values_of("certs").expect("required"))?;
+    let mut verifications_out = m.value_of("verifications-out")
+        .map(create_output).transpose()?;
+    let helper = VHelper::new(certs, m.value_of("not-before"),
+                              m.value_of("not-after"), true)?;
+
+    let mut v = VerifierBuilder::from_reader(io::stdin())
+        .map_err(tag(Error::BadData))?
+        .with_policy(&config.policy, None, helper)?;
+    io::copy(&mut v, &mut io::stdout())?;
+
+    if let Some(output) = verifications_out.as_mut() {
+        write_verifications(output, &v.into_helper().verifications)?;
+    }
+    Ok(())
+}
+
+fn inline_detach(m: &ArgMatches) -> Result<()> {
+    let signatures_out =
+        create_output(m.value_of("signatures-out").expect("required"))?;
+
+    let mut sigs = Vec::new();
+    let mut literal = false;
+    let mut ppr = PacketParser::from_reader(io::stdin())
+        .map_err(tag(Error::BadData))?;
+    while let PacketParserResult::Some(mut pp) = ppr {
+        if let Packet::Literal(_) = pp.packet {
+            io::copy(&mut pp, &mut io::stdout())?;
+            literal = true;
+        }
+
+        let (packet, ppr_) = pp.recurse()?;
+        ppr = ppr_;
+        match packet {
+            Packet::Signature(sig) => sigs.push(sig),
+            Packet::OnePassSig(_) | Packet::Literal(_)
+                | Packet::CompressedData(_) | Packet::Marker(_) => (),
+            p => return Err(Error::BadData.because(
+                format!("Unexpected {} packet", p.tag()))),
+        }
+    }
+    if ! literal || sigs.is_empty() {
+        return Err(Error::BadData.because(
+            "The input is not an inline-signed message"));
+    }
+
+    let mut output = Message::new(signatures_out);
+    if ! m.is_present("no-armor") {
+        output = Armorer::new(output)
+            .kind(armor::Kind::Signature)
+            .build()?;
+    }
+    for sig in sigs {
+        Packet::Signature(sig).serialize(&mut output)?;
+    }
+    output.finalize()?;
+    Ok(())
+}
@@ -0,0 +1,113 @@
+//! Commands operating on the certificate store.
+
+use anyhow::Context as _;
+
+use sequoia_openpgp as openpgp;
+use openpgp::{Cert, Result};
+use openpgp::armor;
+use openpgp::cert::CertParser;
+use openpgp::parse::Parse;
+use openpgp::serialize::Serialize;
+
+use crate::{
+    Config,
+    open_or_stdin,
+};
+use crate::cert_store::{CertStore, Insertion, Query};
+
+/// Returns the certificate store, or an error if it is disabled.
+fn store(config: &Config) -> Result<CertStore> {
+    config.cert_store().ok_or_else(|| anyhow::anyhow!(
+        "The certificate store is disabled, or its location is unknown \
+         (try: \"sq --cert-store DIR ...\")"))
+}
+
+pub fn import(config: Config, m: &clap::ArgMatches) -> Result<()> {
+    let store = store(&config)?;
+
+    let inputs: Vec<Option<&str>> = match m.values_of("input") {
+        Some(files) => files.map(Some).collect(),
+        None => vec![None],
+    };
+
+    let (mut new, mut updated, mut unchanged) = (0, 0, 0);
+    for input in inputs {
+        let mut input = open_or_stdin(input)?;
+        for cert in CertParser::from_reader(&mut input)? {
+            let cert = cert.context("Malformed certificate")?;
+            let fp = cert.fingerprint();
+            let (_, insertion) = store.insert(cert)
+                .with_context(|| format!("Failed to import {}", fp))?;
+            match insertion {
+                Insertion::New => new += 1,
+                Insertion::Updated => updated += 1,
+                Insertion::Unchanged => unchanged += 1,
+            }
+            eprintln!("{:X}: {}", fp, match insertion {
+                Insertion::New => "imported",
+                Insertion::Updated => "updated",
+                Insertion::Unchanged => "unchanged",
+            });
+        }
+    }
+
+    eprintln!("Imported {} new, updated {}, and left {} unchanged \
+               certificates in {:?}.",
+              new, updated, unchanged, store.base());
+    Ok(())
+}
+
+pub fn export(config: Config, m: &clap::ArgMatches) -> Result<()> {
+    let store = store(&config)?;
+
+    let certs: Vec<Cert> = match m.values_of("query") {
+        None => store.certs()?,
+        Some(queries) => {
+            let index = store.index(&config.policy)?;
+            let mut certs: Vec<Cert> = Vec::new();
+            for query in queries {
+                let query: Query = query.parse()?;
+                for cert in index.lookup(&query) {
+                    if ! certs.iter().any(|c| c.fingerprint() == cert.fingerprint()) {
+                        certs.push(cert.clone());
+                    }
+                }
+            }
+            if certs.is_empty() {
+                return Err(anyhow::anyhow!("No matching certificates found"));
+            }
+            certs
+        },
+    };
+
+    let mut output =
+        config.create_or_stdout_pgp(m.value_of("output"),
+                                    m.is_present("binary"),
+                                    armor::Kind::PublicKey)?;
+    for cert in certs {
+        cert.serialize(&mut output)?;
+    }
+    output.finalize()
+}
+
+pub fn lookup(config: Config, m: &clap::ArgMatches) -> Result<()> {
+    let store = store(&config)?;
+
+    let query = m.value_of("query").expect("required");
+    let query = if m.is_present("email") {
+        Query::Email(query.to_lowercase())
+    } else if m.is_present("userid") {
+        Query::UserID(query.into())
+    } else {
+        query.parse()?
+    };
+
+    let certs = store.lookup(&config.policy, &query)?;
+    if certs.is_empty() {
+        return Err(anyhow::anyhow!("No certificate matches {:?}",
+                                   m.value_of("query").expect("required")));
+    }
+
+    super::keyring::list(config, certs.into_iter().map(Ok),
+                         m.is_present("all-userids"))
+}
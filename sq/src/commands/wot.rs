@@ -0,0 +1,235 @@
+//! Authenticates bindings using the web of trust.
+
+use std::time::SystemTime;
+
+use anyhow::Context as _;
+
+use sequoia_openpgp as openpgp;
+use openpgp::{Cert, Fingerprint, Result};
+use openpgp::packet::UserID;
+
+use crate::{
+    Config,
+    load_certs,
+};
+use crate::cert_store::merge;
+use crate::wot::{FULLY_TRUSTED, Network, Path};
+
+pub fn dispatch(config: Config, m: &clap::ArgMatches) -> Result<()> {
+    match m.subcommand() {
+        ("authenticate",  Some(m)) => authenticate(config, m),
+        ("lookup",  Some(m)) => lookup(config, m),
+        ("path",  Some(m)) => path(config, m),
+        _ => unreachable!(),
+    }
+}
+
+/// Returns the reference time.
+fn time(m: &clap::ArgMatches) -> Result<SystemTime> {
+    Ok(match m.value_of("time") {
+        Some(t) => SystemTime::from(
+            crate::parse_iso8601(t, chrono::NaiveTime::from_hms(0, 0, 0))?),
+        None => SystemTime::now(),
+    })
+}
+
+/// Returns the required trust amount.
+fn required_amount(m: &clap::ArgMatches) -> Result<usize> {
+    match m.value_of("amount") {
+        Some(a) => {
+            let a: usize = a.parse()
+                .context("The trust amount must be a number")?;
+            if a == 0 || a > FULLY_TRUSTED {
+                return Err(anyhow::anyhow!(
+                    "The trust amount must be between 1 and {}",
+                    FULLY_TRUSTED));
+            }
+            Ok(a)
+        },
+        None => Ok(FULLY_TRUSTED),
+    }
+}
+
+/// Parses a fingerprint given on the command line.
+fn fingerprint(s: &str) -> Result<Fingerprint> {
+    let fp: Fingerprint = s.parse()?;
+    if let Fingerprint::Invalid(_) = fp {
+        return Err(anyhow::anyhow!("{:?} is not a fingerprint", s));
+    }
+    Ok(fp)
+}
+
+/// Builds the network from the keyrings and the certificate store.
+fn network(config: &Config, m: &clap::ArgMatches, roots: &[Fingerprint])
+           -> Result<Network>
+{
+    let mut certs: Vec<Cert> =
+        load_certs(m.values_of("keyring").into_iter().flatten())?;
+    if let Some(store) = config.cert_store() {
+        certs.append(&mut store.certs()?);
+    } else if m.values_of("keyring").is_none() {
+        return Err(anyhow::anyhow!(
+            "No certificates to work with (try: \"--keyring FILE\")"));
+    }
+    let certs = merge(certs)?;
+
+    for root in roots {
+        if ! certs.iter().any(|c| c.fingerprint() == *root) {
+            eprintln!("Warning: The trust root {} was not found.", root);
+        }
+    }
+
+    Ok(Network::new(&certs, &config.policy, time(m)?, roots))
+}
+
+/// Returns the trust roots given on the command line.
+fn roots(m: &clap::ArgMatches) -> Result<Vec<Fingerprint>> {
+    m.values_of("trust-root").expect("required").map(fingerprint).collect()
+}
+
+/// Returns a predicate matching the User IDs given on the command
+/// line.
+fn userid_matcher(m: &clap::ArgMatches) -> Result<Box<dyn Fn(&UserID) -> bool>>
+{
+    let userid = m.value_of("userid").expect("required").to_string();
+    if m.is_present("email") {
+        let email = UserID::from(format!("<{}>", userid)).email_normalized()
+            .ok().flatten()
+            .ok_or_else(|| anyhow::anyhow!(
+                "{:?} is not a valid email address", userid))?;
+        Ok(Box::new(move |u: &UserID| {
+            u.email_normalized().ok().flatten().as_ref() == Some(&email)
+        }))
+    } else {
+        Ok(Box::new(move |u: &UserID| u.value() == userid.as_bytes()))
+    }
+}
+
+fn authenticate(config: Config, m: &clap::ArgMatches) -> Result<()> {
+    let roots = roots(m)?;
+    let network = network(&config, m, &roots)?;
+    let required = required_amount(m)?;
+    let target = fingerprint(m.value_of("fingerprint").expect("required"))?;
+    let matches = userid_matcher(m)?;
+
+    let bindings: Vec<UserID> = network.userids(&target).iter()
+        .filter(|u| matches(u)).cloned().collect();
+    if bindings.is_empty() {
+        return Err(anyhow::anyhow!(
+            "{} has no valid User ID matching {:?} at the reference time",
+            target, m.value_of("userid").expect("required")));
+    }
+
+    let mut authenticated = false;
+    for userid in bindings {
+        authenticated |= report(&network, &target, &userid, required);
+    }
+
+    if authenticated {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!("Could not authenticate any binding"))
+    }
+}
+
+fn lookup(config: Config, m: &clap::ArgMatches) -> Result<()> {
+    let roots = roots(m)?;
+    let network = network(&config, m, &roots)?;
+    let required = required_amount(m)?;
+    let matches = userid_matcher(m)?;
+
+    let mut authenticated = false;
+    for (target, userid) in network.certs_with_userid(matches) {
+        authenticated |= report(&network, &target, &userid, required);
+    }
+
+    if authenticated {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!("Could not authenticate any binding"))
+    }
+}
+
+fn path(config: Config, m: &clap::ArgMatches) -> Result<()> {
+    let path = m.values_of("fingerprint").expect("required")
+        .map(fingerprint)
+        .collect::<Result<Vec<_>>>()?;
+    let userid = UserID::from(m.value_of("userid").expect("required"));
+    let network = network(&config, m, &path[..1])?;
+    let required = required_amount(m)?;
+
+    let path = network.check_path(&path, &userid)
+        .context("The path is not valid")?;
+    print_path(&network, &path, &userid);
+
+    if path.amount >= required {
+        println!("The path authenticates the binding.");
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!(
+            "The path's trust amount is insufficient ({}/{})",
+            path.amount, required))
+    }
+}
+
+/// Authenticates the binding, and prints the result.
+///
+/// Returns whether the binding could be authenticated.
+fn report(network: &Network, target: &Fingerprint, userid: &UserID,
+          required: usize)
+          -> bool
+{
+    let auth = network.authenticate(target, userid, required);
+    let authenticated = auth.amount >= required;
+    println!("[{}] {:X} {}: {} ({}/{})",
+             if authenticated { "✓" } else { " " },
+             target, String::from_utf8_lossy(userid.value()),
+             if authenticated {
+                 "authenticated"
+             } else if auth.amount > 0 {
+                 "partially authenticated"
+             } else {
+                 "not authenticated"
+             },
+             auth.amount, required);
+
+    let n = auth.paths.len();
+    for (i, path) in auth.paths.iter().enumerate() {
+        println!("  Path #{} of {}, trust amount {}:", i + 1, n, path.amount);
+        print_path(network, path, userid);
+    }
+    println!();
+
+    authenticated
+}
+
+/// Prints the certifications making up `path`.
+fn print_path(network: &Network, path: &Path, userid: &UserID) {
+    let uid = |u: &UserID| String::from_utf8_lossy(u.value()).into_owned();
+
+    let root_uid = if path.certifications.is_empty() {
+        Some(uid(userid))
+    } else {
+        network.userids(&path.root).first().map(uid)
+    };
+    println!("    ◯ {:X} (trust root){}", path.root,
+             root_uid.map(|u| format!(" {:?}", u)).unwrap_or_default());
+
+    let n = path.certifications.len();
+    for (i, c) in path.certifications.iter().enumerate() {
+        let date = chrono::DateTime::<chrono::offset::Utc>::from(
+            c.creation_time).format("%Y-%m-%d");
+        if c.depth > 0 {
+            println!("    │   certified the following certificate on {} \
+                      as a trusted introducer (depth {}, amount {})",
+                     date, c.depth, c.amount);
+        } else {
+            println!("    │   certified the following binding on {} \
+                      (amount {})",
+                     date, c.amount);
+        }
+        println!("    {} {:X} {:?}",
+                 if i + 1 == n { "└" } else { "├" },
+                 c.target, uid(&c.userid));
+    }
+}
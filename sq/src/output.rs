@@ -0,0 +1,433 @@
+//! Machine-readable output.
+//!
+//! With `--output-format json`, `sq inspect`, `sq keyring list`, `sq
+//! verify`, `sq decrypt` and `sq packet dump` emit the structures
+//! defined here instead of human-readable text.  Every document is a
+//! JSON object carrying the version of this schema in
+//! `sq_output_version`.  The version follows semantic versioning:
+//! adding fields bumps the minor version, removing or changing fields
+//! bumps the major version.
+
+use std::io;
+use std::str::FromStr;
+use std::time::SystemTime;
+
+use chrono::{DateTime, SecondsFormat, offset::Utc};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use sequoia_openpgp as openpgp;
+use openpgp::{KeyHandle, Result};
+use openpgp::packet::Signature;
+use openpgp::types::{KeyFlags, RevocationStatus};
+
+/// The version of the JSON schema.
+pub const OUTPUT_VERSION: &str = "1.2.0";
+
+/// The format of the output of informational commands.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    HumanReadable,
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "human-readable" => Ok(OutputFormat::HumanReadable),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(anyhow::anyhow!("Unknown output format {:?}", s)),
+        }
+    }
+}
+
+/// Writes `data` as a JSON document of the current schema version.
+pub fn emit<T: Serialize>(output: &mut dyn io::Write, data: &T) -> Result<()> {
+    #[derive(Serialize)]
+    struct Versioned<'a, T> {
+        sq_output_version: &'static str,
+        #[serde(flatten)]
+        data: &'a T,
+    }
+
+    serde_json::to_writer_pretty(&mut *output, &Versioned {
+        sq_output_version: OUTPUT_VERSION,
+        data,
+    })?;
+    writeln!(output)?;
+    Ok(())
+}
+
+/// The result of `sq inspect`.
+#[derive(Serialize)]
+pub struct Inspection {
+    pub input: String,
+    /// One of `certificate`, `transferable-secret-key`, `keyring`,
+    /// `message`, `detached-signatures`, `no-data`, `unknown`.
+    #[serde(rename = "type")]
+    pub typ: &'static str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub certificates: Vec<CertInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<MessageInfo>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub signatures: Vec<SignatureInfo>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+}
+
+/// The structure of a certificate.
+#[derive(Serialize)]
+pub struct CertInfo {
+    pub fingerprint: String,
+    pub secret: bool,
+    pub revocation: RevocationInfo,
+    pub primary_key: KeyInfo,
+    pub subkeys: Vec<KeyInfo>,
+    pub userids: Vec<UserIDInfo>,
+    pub user_attributes: usize,
+    pub bad_signatures: usize,
+}
+
+/// A primary key or subkey.
+#[derive(Serialize)]
+pub struct KeyInfo {
+    pub fingerprint: String,
+    pub algorithm: String,
+    pub bits: Option<usize>,
+    /// `encrypted` or `unencrypted`, if secret key material is present.
+    pub secret: Option<&'static str>,
+    pub creation_time: String,
+    pub expiration_time: Option<String>,
+    pub flags: Vec<&'static str>,
+    pub valid: bool,
+    pub errors: Vec<String>,
+    pub revocation: RevocationInfo,
+    pub certifications: Vec<SignatureInfo>,
+}
+
+/// A User ID.
+#[derive(Serialize)]
+pub struct UserIDInfo {
+    pub userid: String,
+    pub valid: bool,
+    pub errors: Vec<String>,
+    pub revocation: RevocationInfo,
+    pub certifications: Vec<SignatureInfo>,
+}
+
+/// The revocation status of a certificate or component.
+#[derive(Serialize)]
+pub struct RevocationInfo {
+    /// One of `not-revoked`, `revoked`, `possibly-revoked`.
+    pub status: &'static str,
+    pub reasons: Vec<ReasonInfo>,
+}
+
+#[derive(Serialize)]
+pub struct ReasonInfo {
+    pub code: Option<u8>,
+    pub reason: String,
+    pub message: String,
+}
+
+/// An unverified signature.
+#[derive(Serialize)]
+pub struct SignatureInfo {
+    #[serde(rename = "type")]
+    pub typ: String,
+    pub alleged_issuers: Vec<String>,
+}
+
+/// The structure of a message, as far as it can be seen without
+/// decrypting it.
+#[derive(Serialize)]
+pub struct MessageInfo {
+    pub encrypted: bool,
+    pub signed: bool,
+    pub recipients: Vec<String>,
+    pub passwords: usize,
+}
+
+/// An entry of `sq keyring list`.
+#[derive(Serialize)]
+pub struct KeyringEntry {
+    pub index: usize,
+    pub fingerprint: String,
+    pub primary_userid: Option<String>,
+    pub userids: Vec<String>,
+}
+
+/// The result of `sq keyring list`.
+#[derive(Serialize)]
+pub struct KeyringListing {
+    pub certificates: Vec<KeyringEntry>,
+}
+
+/// The result of `sq keyring diff`.
+#[derive(Serialize)]
+pub struct KeyringDiff {
+    /// The certificates that differ.
+    pub certificates: Vec<CertDiff>,
+}
+
+/// The differences between two versions of a certificate.
+#[derive(Serialize)]
+pub struct CertDiff {
+    pub fingerprint: String,
+    /// One of `added`, `removed`, `changed`.
+    pub status: &'static str,
+    /// The components that differ.
+    pub components: Vec<ComponentDiff>,
+}
+
+/// The differences between two versions of a component.
+#[derive(Serialize)]
+pub struct ComponentDiff {
+    /// One of `primary-key`, `subkey`, `userid`, `user-attribute`.
+    #[serde(rename = "type")]
+    pub typ: &'static str,
+    /// The key's fingerprint, the User ID, or the SHA-256 digest of
+    /// the user attribute.
+    pub id: String,
+    /// One of `added`, `removed`, `changed`.
+    pub status: &'static str,
+    /// The signatures that were added or removed.
+    pub signatures: Vec<SignatureDiff>,
+}
+
+/// A signature that was added or removed.
+#[derive(Serialize)]
+pub struct SignatureDiff {
+    /// `added` or `removed`.
+    pub status: &'static str,
+    /// One of `self-signature`, `certification`, `self-revocation`,
+    /// `third-party-revocation`.
+    pub kind: &'static str,
+    #[serde(rename = "type")]
+    pub typ: String,
+    pub creation_time: Option<String>,
+    pub issuers: Vec<String>,
+    /// Whether the signature was cryptographically verified.
+    /// Third-party signatures are not.
+    pub verified: bool,
+    /// Whether the signature is alive and acceptable under the
+    /// policy.
+    pub valid: bool,
+    pub error: Option<String>,
+}
+
+/// The verification result of a signature.
+#[derive(Serialize)]
+pub struct SignatureResult {
+    /// One of `good`, `good-checksum`, `missing-key`, `unbound-key`,
+    /// `bad-key`, `bad-signature`, `malformed`.
+    pub status: &'static str,
+    pub level: usize,
+    /// The fingerprint of the signing key, if it was found.
+    pub key: Option<String>,
+    /// The fingerprint of the signer's certificate, if it was found.
+    pub signer: Option<String>,
+    /// The issuers named by the signature.
+    pub alleged_issuers: Vec<String>,
+    pub creation_time: Option<String>,
+    pub errors: Vec<String>,
+}
+
+/// Counts of signature results by status.
+#[derive(Default, Serialize)]
+pub struct Summary {
+    pub good_signatures: usize,
+    pub good_checksums: usize,
+    pub unknown_checksums: usize,
+    pub bad_signatures: usize,
+    pub bad_checksums: usize,
+    pub broken_signatures: usize,
+}
+
+/// The result of `sq verify`.
+#[derive(Default, Serialize)]
+pub struct Verification {
+    pub valid: bool,
+    pub signatures: Vec<SignatureResult>,
+    pub summary: Summary,
+}
+
+/// The result of `sq decrypt`.
+#[derive(Default, Serialize)]
+pub struct Decryption {
+    pub symmetric_algorithm: Option<String>,
+    pub aead_algorithm: Option<String>,
+    pub compression_algorithm: Option<String>,
+    /// The recipients of the PKESK packets, wildcards as
+    /// `0000000000000000`.
+    pub recipients: Vec<String>,
+    /// The number of SKESK packets.
+    pub passwords: usize,
+    /// The fingerprint of the key that decrypted the session key, if it
+    /// was decrypted with a key.
+    pub decryption_key: Option<String>,
+    pub verification: Verification,
+}
+
+/// The result of `sq packet dump`.
+#[derive(Serialize)]
+pub struct PacketDump {
+    pub packets: Vec<PacketInfo>,
+}
+
+/// A packet, and the packets it contains.
+#[derive(Serialize)]
+pub struct PacketInfo {
+    /// The position in the packet tree, e.g. `1.0` for the first
+    /// packet in the container that is the second top-level packet.
+    pub path: String,
+    /// The offset of the packet in the input or, for packets in a
+    /// container, in the container's decrypted or decompressed body.
+    /// `None` if a packet of unknown length precedes it.
+    pub offset: Option<u64>,
+    pub header_length: usize,
+    /// `old` or `new`.
+    pub ctb: &'static str,
+    /// One of `full`, `partial`, `indeterminate`.
+    pub length_type: &'static str,
+    /// The length of the body, unless the length is partial or
+    /// indeterminate.
+    pub body_length: Option<u32>,
+    pub tag: u8,
+    #[serde(rename = "type")]
+    pub typ: String,
+    /// The packet's body fields, depending on the type.
+    pub fields: Map<String, Value>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub mpis: Vec<MpiInfo>,
+    /// The raw fields, with `--hex`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub map: Vec<FieldInfo>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<PacketInfo>,
+}
+
+/// A signature subpacket.
+#[derive(Serialize)]
+pub struct SubpacketInfo {
+    pub tag: u8,
+    #[serde(rename = "type")]
+    pub typ: String,
+    pub critical: bool,
+    pub value: Value,
+}
+
+/// A cryptographic artifact.
+#[derive(Serialize)]
+pub struct MpiInfo {
+    pub name: String,
+    /// The value, hex-encoded.
+    pub value: String,
+}
+
+/// A raw field of a packet.
+#[derive(Serialize)]
+pub struct FieldInfo {
+    pub name: String,
+    /// The offset relative to the start of the packet.
+    pub offset: usize,
+    pub length: usize,
+    /// The data, hex-encoded.
+    pub data: String,
+}
+
+/// Formats a time as RFC 3339 in UTC.
+pub fn time(t: SystemTime) -> String {
+    DateTime::<Utc>::from(t).to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Lists the messages of an error and its causes.
+pub fn error_chain(err: &anyhow::Error) -> Vec<String> {
+    err.chain().map(|cause| cause.to_string()).collect()
+}
+
+/// Returns the names of the given key flags.
+pub fn key_flags(flags: &KeyFlags) -> Vec<&'static str> {
+    let mut names = Vec::new();
+    if flags.for_certification() {
+        names.push("certification");
+    }
+    if flags.for_signing() {
+        names.push("signing");
+    }
+    if flags.for_authentication() {
+        names.push("authentication");
+    }
+    if flags.for_transport_encryption() {
+        names.push("transport-encryption");
+    }
+    if flags.for_storage_encryption() {
+        names.push("storage-encryption");
+    }
+    if flags.is_group_key() {
+        names.push("group-key");
+    }
+    if flags.is_split_key() {
+        names.push("split-key");
+    }
+    names
+}
+
+impl From<RevocationStatus<'_>> for RevocationInfo {
+    fn from(status: RevocationStatus) -> Self {
+        let (status, sigs) = match status {
+            RevocationStatus::Revoked(sigs) => ("revoked", sigs),
+            RevocationStatus::CouldBe(sigs) => ("possibly-revoked", sigs),
+            RevocationStatus::NotAsFarAsWeKnow => ("not-revoked", Vec::new()),
+        };
+        RevocationInfo {
+            status,
+            reasons: sigs.into_iter().map(|sig| {
+                match sig.reason_for_revocation() {
+                    Some((r, message)) => ReasonInfo {
+                        code: Some(r.into()),
+                        reason: r.to_string(),
+                        message: String::from_utf8_lossy(message).into(),
+                    },
+                    None => ReasonInfo {
+                        code: None,
+                        reason: "No reason specified".into(),
+                        message: String::new(),
+                    },
+                }
+            }).collect(),
+        }
+    }
+}
+
+impl From<&Signature> for SignatureInfo {
+    fn from(sig: &Signature) -> Self {
+        SignatureInfo {
+            typ: sig.typ().to_string(),
+            alleged_issuers: alleged_issuers(sig),
+        }
+    }
+}
+
+/// Returns the issuers of a signature, fingerprints first, without key
+/// IDs aliasing them.
+pub fn alleged_issuers(sig: &Signature) -> Vec<String> {
+    let mut fps: Vec<_> = sig.issuer_fingerprints().collect();
+    fps.sort();
+    fps.dedup();
+    let fps: Vec<KeyHandle> = fps.into_iter().map(|fp| fp.into()).collect();
+    let mut keyids: Vec<_> = sig.issuers().collect();
+    keyids.sort();
+    keyids.dedup();
+
+    let mut issuers: Vec<String> = fps.iter().map(|fp| fp.to_hex()).collect();
+    for keyid in keyids {
+        if ! fps.iter().any(|fp| fp.aliases(&keyid.into())) {
+            issuers.push(keyid.to_hex());
+        }
+    }
+    issuers
+}
@@ -1,28 +1,52 @@
 //! This module abstracts over in-memory or external secrets for low-level
 //! cryptographic operations.
-use anyhow::Error;
+use std::path::PathBuf;
+use std::sync::Arc;
 
+use anyhow::{Context as _, Error};
+
+use sequoia_ipc::gnupg;
+use sequoia_ipc::keybox::{Keybox, KeyboxRecord};
+use sequoia_openpgp::Cert;
 use sequoia_openpgp::crypto::mpi::{Ciphertext, Signature};
 use sequoia_openpgp::crypto::{Decryptor, KeyPair, SessionKey, Signer};
 use sequoia_openpgp::packet::key::{PublicParts, UnspecifiedRole};
 use sequoia_openpgp::packet::Key;
+use sequoia_openpgp::parse::Parse;
 use sequoia_openpgp::types::HashAlgorithm;
 
 pub use openpgp_dsm::Credentials;
 pub use openpgp_dsm::Auth;
-use openpgp_dsm::DsmAgent;
+use openpgp_dsm::{AuthOptions, DsmAgent};
+
+/// Returns the DSM authentication method given using the
+/// authentication options in `m`, or in the environment.
+pub fn dsm_auth(m: &clap::ArgMatches) -> anyhow::Result<Auth> {
+    Auth::from_options(&AuthOptions {
+        api_key:           m.value_of("api-key"),
+        client_cert:       m.value_of("client-cert"),
+        app_uuid:          m.value_of("app-uuid"),
+        p12_pass:          m.value_of("pkcs12-passphrase"),
+        bearer_token_file: m.value_of("dsm-bearer-token-file"),
+        jwt_file:          m.value_of("dsm-jwt-file"),
+        username:          m.value_of("dsm-username"),
+        account_id:        m.value_of("dsm-account-id"),
+    })
+}
 
 /// A Secret can be a private key loaded from memory, or stored externally. It
 /// implements the [Decryptor] and [Signer] traits.
 ///
 ///   [Decryptor]: ../../crypto/trait.Decryptor.html
 ///   [Signer]: ../../crypto/trait.Signer.html
+#[derive(Clone)]
 pub enum Secret {
     /// A [KeyPair] stored in local memory
     ///
     ///   [KeyPair]: ../../crypto/struct.KeyPair.html
     InMemory(KeyPair),
     Dsm(DsmAgent),
+    Gpg(GpgAgentKey),
 }
 
 impl Signer for Secret {
@@ -30,6 +54,7 @@ impl Signer for Secret {
         match self {
             Secret::InMemory(signer) => signer.public(),
             Secret::Dsm(signer) => <DsmAgent as Signer>::public(signer),
+            Secret::Gpg(signer) => <GpgAgentKey as Signer>::public(signer),
         }
     }
 
@@ -37,6 +62,7 @@ impl Signer for Secret {
         match self {
             Secret::InMemory(signer) => signer.sign(hash, digest),
             Secret::Dsm(signer) => signer.sign(hash, digest),
+            Secret::Gpg(signer) => signer.sign(hash, digest),
         }
     }
 }
@@ -46,6 +72,7 @@ impl Decryptor for Secret {
         match self {
             Secret::InMemory(decryptor) => decryptor.public(),
             Secret::Dsm(decryptor) => <DsmAgent as Decryptor>::public(decryptor),
+            Secret::Gpg(decryptor) => <GpgAgentKey as Decryptor>::public(decryptor),
         }
     }
 
@@ -53,6 +80,7 @@ impl Decryptor for Secret {
         match self {
             Secret::InMemory(decryptor) => decryptor.decrypt(ciphertext, plaintext_len),
             Secret::Dsm(decryptor) => decryptor.decrypt(ciphertext, plaintext_len),
+            Secret::Gpg(decryptor) => decryptor.decrypt(ciphertext, plaintext_len),
         }
     }
 }
@@ -60,4 +88,69 @@ impl Decryptor for Secret {
 pub enum PreSecret {
     InMemory(sequoia_openpgp::Cert),
     Dsm(Credentials, String),
+    /// A certificate whose secret key material is managed by
+    /// gpg-agent.
+    Gpg(Arc<gnupg::Context>, sequoia_openpgp::Cert),
+}
+
+/// A key whose secret key material is managed by gpg-agent.
+///
+/// Unlike [gnupg::KeyPair], this owns the public key, so that it can
+/// be used wherever a [KeyPair] can.
+#[derive(Clone)]
+pub struct GpgAgentKey {
+    context: Arc<gnupg::Context>,
+    key: Key<PublicParts, UnspecifiedRole>,
+}
+
+impl GpgAgentKey {
+    pub fn new(context: Arc<gnupg::Context>,
+               key: Key<PublicParts, UnspecifiedRole>)
+               -> Self
+    {
+        GpgAgentKey { context, key }
+    }
+}
+
+impl Signer for GpgAgentKey {
+    fn public(&self) -> &Key<PublicParts, UnspecifiedRole> {
+        &self.key
+    }
+
+    fn sign(&mut self, hash: HashAlgorithm, digest: &[u8]) -> Result<Signature, Error> {
+        gnupg::KeyPair::new(&self.context, &self.key)?.sign(hash, digest)
+    }
+}
+
+impl Decryptor for GpgAgentKey {
+    fn public(&self) -> &Key<PublicParts, UnspecifiedRole> {
+        &self.key
+    }
+
+    fn decrypt(&mut self, ciphertext: &Ciphertext, plaintext_len: Option<usize>) -> Result<SessionKey, Error> {
+        gnupg::KeyPair::new(&self.context, &self.key)?
+            .decrypt(ciphertext, plaintext_len)
+    }
+}
+
+/// Returns the certificates in the GnuPG keybox.
+pub fn gpg_keybox(context: &gnupg::Context) -> Result<Vec<Cert>, Error> {
+    let homedir = match context.homedir() {
+        Some(homedir) => homedir.to_path_buf(),
+        None => std::env::var_os("GNUPGHOME").map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME")
+                     .map(|home| PathBuf::from(home).join(".gnupg")))
+            .ok_or_else(|| anyhow::anyhow!(
+                "Cannot determine the GnuPG home directory"))?,
+    };
+    let path = homedir.join("pubring.kbx");
+    let keybox = Keybox::from_file(&path)
+        .context(format!("Failed to open the GnuPG keybox {:?}", path))?;
+    Ok(keybox
+       // Skip records we do not understand, like GnuPG does.
+       .filter_map(|record| match record {
+           Ok(KeyboxRecord::OpenPGP(r)) => r.cert().ok(),
+           _ => None,
+       })
+       .collect())
 }
@@ -5,9 +5,17 @@
 //! ```text
 //! A command-line frontend for Sequoia, an implementation of OpenPGP
 //!
-//! Functionality is grouped and available using subcommands.  Currently,
-//! this interface is completely stateless.  Therefore, you need to supply
-//! all configuration and certificates explicitly on each invocation.
+//! Functionality is grouped and available using subcommands.  Apart from
+//! the certificate store, this interface is stateless.  Therefore, you
+//! need to supply all configuration and keys explicitly on each
+//! invocation.
+//!
+//! Certificates can be imported into a local certificate store using
+//! "sq import".  The store uses the shared OpenPGP certificate directory
+//! format.  When verifying signatures, certificates are looked up in the
+//! store.  Certificates that are named on the command line, but that are
+//! not files, are looked up in the store by their fingerprint.  Use "sq
+//! lookup" to find the fingerprint of a stored certificate.
 //!
 //! OpenPGP data can be provided in binary or ASCII armored form.  This
 //! will be handled automatically.  Emitted OpenPGP data is ASCII armored
@@ -27,15 +35,29 @@
 //!     -h, --help
 //!             Prints help information
 //!
+//!         --no-cert-store
+//!             Disables the certificate store
+//!
 //!     -V, --version
 //!             Prints version information
 //!
 //!
 //! OPTIONS:
+//!         --cert-store <DIR>
+//!             Uses the certificate store in DIR.  By default, the certificate
+//!             store is located in the platform's data directory, e.g.
+//!             "$HOME/.local/share/pgp.cert.d". [env: PGP_CERT_D=]
 //!         --known-notation <NOTATION>...
 //!             Adds NOTATION to the list of known notations. This is used when
 //!             validating signatures. Signatures that have unknown notations with
 //!             the critical bit set are considered invalid.
+//!         --output-format <FORMAT>
+//!             Produces output in FORMAT.  With "json", inspect, keyring list,
+//!             keyring diff, verify, decrypt and packet dump emit JSON documents
+//!             whose schema is versioned by their "sq_output_version" field.  The
+//!             results of verify and decrypt are written to stderr, so that the
+//!             verified or decrypted data can still be written to stdout. [default:
+//!             human-readable]  [possible values: human-readable, json]
 //!
 //! SUBCOMMANDS:
 //!     encrypt      Encrypts a message
@@ -44,7 +66,12 @@
 //!     verify       Verifies signed messages or detached signatures
 //!     key          Manages keys
 //!     keyring      Manages collections of keys or certs
+//!     import       Imports certificates into the certificate store
+//!     export       Exports certificates from the certificate store
+//!     lookup       Looks up certificates in the certificate store
 //!     certify      Certifies a User ID for a Certificate
+//!     wot          Authenticates bindings using the web of trust
+//!     revoke       Revokes a certificate, subkey or User ID
 //!     autocrypt    Communicates certificates using Autocrypt
 //!     keyserver    Interacts with keyservers
 //!     wkd          Interacts with Web Key Directories
@@ -63,6 +90,17 @@
 //! Encrypts a message for any number of recipients and with any number of
 //! passwords, optionally signing the message in the process.
 //!
+//! Recipients can be selected by email address, User ID, or fingerprint.
+//! Their certificates are looked up in the keyrings given using
+//! "--keyring", and in the certificate store.  If no certificate is
+//! found for an email address or fingerprint, they can also be looked up
+//! using WKD or a keyserver.  A recipient is only selected if the
+//! matching User ID is valid under the policy and not revoked.  If
+//! several certificates match, none is selected.
+//!
+//! Certificates that are revoked or expired are refused, unless
+//! "--use-revoked-recipient" or "--use-expired-recipient" is given.
+//!
 //! The converse operation is "sq decrypt".
 //!
 //! USAGE:
@@ -72,15 +110,28 @@
 //!     -B, --binary
 //!             Emits binary data
 //!
+//!         --gpg-agent
+//!             Signs using the secret keys in the gpg-agent.  The keys given using
+//!             --signer-key may then be certificates without secret key material,
+//!             or the fingerprints or Key IDs of certificates in the GnuPG keybox.
 //!     -h, --help
 //!             Prints help information
 //!
 //!     -s, --symmetric
 //!             Adds a password to encrypt with.  The message can be decrypted with
 //!             either one of the recipient's keys, or any password.
+//!         --use-expired-recipient
+//!             Encrypts for expired certificates
+//!
 //!         --use-expired-subkey
 //!             If a certificate has only expired encryption-capable subkeys, falls
 //!             back to using the one that expired last
+//!         --use-revoked-recipient
+//!             Encrypts for revoked certificates
+//!
+//!         --wkd
+//!             Looks up recipients by email address using WKD
+//!
 //!
 //! OPTIONS:
 //!         --api-key <API-KEY>
@@ -95,6 +146,27 @@
 //!         --compression <KIND>
 //!             Selects compression scheme to use [default: pad]  [possible values:
 //!             none, pad, zip, zlib, bzip2]
+//!         --dsm-account-id <ACCOUNT-UUID>
+//!             Selects the Fortanix DSM account after logging in
+//!
+//!         --dsm-bearer-token-file <FILE>
+//!             Authenticates to Fortanix DSM with the access token in FILE
+//!
+//!         --dsm-jwt-file <FILE>
+//!             Authenticates to Fortanix DSM with the JWT in FILE (requires the App
+//!             UUID)
+//!         --dsm-username <USERNAME>
+//!             Logs in to Fortanix DSM as USERNAME.  The password is taken from
+//!             FORTANIX_PASSWORD, or asked for.
+//!         --gpg-homedir <DIR>
+//!             Uses the gpg-agent of the GnuPG home directory DIR
+//!
+//!         --keyring <FILE>...
+//!             Looks up recipients in FILE
+//!
+//!         --keyserver <URI>
+//!             Looks up recipients on the keyserver at URI, e.g.
+//!             "hkps://keys.openpgp.org".
 //!         --mode <MODE>
 //!             Selects what kind of keys are considered for encryption.  Transport
 //!             select subkeys marked as suitable for transport encryption, rest
@@ -110,15 +182,32 @@
 //!         --private-key-store <KEY_STORE>
 //!             Provides parameters for private key store
 //!
-//!         --recipient-cert <CERT-RING>...
-//!             Encrypts for all recipients in CERT-RING
+//!         --recipient-dsm-key <DSM-KEY-NAME>...
+//!             Encrypts for the certificate of a key stored in Fortanix DSM
+//!
+//!         --recipient-email <EMAIL>...
+//!             Encrypts for the certificate with a User ID containing EMAIL
 //!
+//!         --recipient-fingerprint <FINGERPRINT>...
+//!             Encrypts for the certificate with the primary key or subkey
+//!             FINGERPRINT
+//!         --recipient-userid <USERID>...
+//!             Encrypts for the certificate with the User ID USERID
+//!
+//!         --recipient-cert <CERT-RING>...
+//!             Encrypts for all recipients in CERT-RING.  If CERT-RING is not a
+//!             file, it is the fingerprint of a certificate in the certificate
+//!             store.
 //!         --signer-dsm-key <DSM-KEY-NAME>
 //!             Signs the message with a key stored in Fortanix DSM
 //!
 //!         --signer-key <KEY>...
 //!             Signs the message with KEY
 //!
+//!         --symmetric-dsm-key <DSM-KEY-NAME>...
+//!             Wraps the session key with an AES key stored in Fortanix DSM.  The
+//!             message can be decrypted by anyone allowed to decrypt with that key,
+//!             using "sq decrypt --dsm-key".
 //!     -t, --time <TIME>
 //!             Chooses keys valid at the specified time and sets the signature's
 //!             creation time
@@ -136,8 +225,18 @@
 //! # Encrypt a file creating a signature in the process
 //! $ sq encrypt --recipient-cert romeo.pgp --signer-key juliet.pgp message.txt
 //!
+//! # Encrypt a file for a recipient selected by email address
+//! $ sq encrypt --recipient-email romeo@example.org --keyring certs.pgp \
+//!     message.txt
+//!
+//! # Encrypt a file using the certificate of a key stored in DSM
+//! $ sq encrypt --recipient-dsm-key romeo message.txt
+//!
 //! # Encrypt a file using a password
 //! $ sq encrypt --symmetric message.txt
+//!
+//! # Encrypt a file for the holders of an AES key stored in Fortanix DSM
+//! $ sq encrypt --symmetric-dsm-key shared-aes-key message.txt
 //! ```
 //!
 //! ## Subcommand decrypt
@@ -167,12 +266,23 @@
 //!     sq decrypt [FLAGS] [OPTIONS] [--] [FILE]
 //!
 //! FLAGS:
+//!         --dsm-auto
+//!             Decrypts with any accessible key stored in Fortanix DSM, found by
+//!             matching the recipients of the message against the fingerprints of
+//!             the DSM keys.  The mapping is cached in $XDG_CACHE_HOME/sq-
+//!             dsm/recipients.json, and rebuilt when a recipient
+//!             is not found.
 //!         --dump
 //!             Prints a packet dump to stderr
 //!
 //!         --dump-session-key
 //!             Prints the session key to stderr
 //!
+//!         --gpg-agent
+//!             Decrypts using the secret keys in the gpg-agent.  The keys given
+//!             using --recipient-key may then be certificates without secret key
+//!             material, or the fingerprints or Key IDs of certificates in the
+//!             GnuPG keybox.
 //!     -h, --help
 //!             Prints help information
 //!
@@ -190,9 +300,26 @@
 //!         --client-cert <P12-FILE>
 //!             Authenticates to Fortanix DSM with the given client certificate
 //!
-//!         --dsm-key <DSM-KEY-NAME>
+//!         --dsm-account-id <ACCOUNT-UUID>
+//!             Selects the Fortanix DSM account after logging in
+//!
+//!         --dsm-bearer-token-file <FILE>
+//!             Authenticates to Fortanix DSM with the access token in FILE
+//!
+//!         --dsm-jwt-file <FILE>
+//!             Authenticates to Fortanix DSM with the JWT in FILE (requires the App
+//!             UUID)
+//!         --dsm-key <DSM-KEY-NAME>...
 //!             Decrypts with secrets stored inside the Fortanix Self-Defending Key-
-//!             Management System
+//!             Management System.  Session keys wrapped with a DSM AES key (see
+//!             "sq encrypt --symmetric-dsm-key") are unwrapped with the same
+//!             credentials.
+//!         --dsm-username <USERNAME>
+//!             Logs in to Fortanix DSM as USERNAME.  The password is taken from
+//!             FORTANIX_PASSWORD, or asked for.
+//!         --gpg-homedir <DIR>
+//!             Uses the gpg-agent of the GnuPG home directory DIR
+//!
 //!     -o, --output <FILE>
 //!             Writes to FILE or stdout if omitted
 //!
@@ -206,8 +333,8 @@
 //!             Decrypts with KEY
 //!
 //!         --signer-cert <CERT>...
-//!             Verifies signatures with CERT
-//!
+//!             Verifies signatures with CERT.  If CERT is not a file, it is the
+//!             fingerprint of a certificate in the certificate store.
 //!     -n, --signatures <N>
 //!             Sets the threshold of valid signatures to N. The message will only
 //!             be considered verified if this threshold is reached. [default: 1 if
@@ -228,6 +355,9 @@
 //!
 //! # Decrypt a file using a password
 //! $ sq decrypt ciphertext.pgp
+//!
+//! # Decrypt a file using a secret key in the gpg-agent
+//! $ sq decrypt --gpg-agent --recipient-key juliet.cert.pgp ciphertext.pgp
 //! ```
 //!
 //! ## Subcommand sign
@@ -241,12 +371,18 @@
 //! The converse operation is "sq verify".
 //!
 //! USAGE:
-//!     sq sign [FLAGS] [OPTIONS] [--] [FILE]
+//!     sq sign [FLAGS] [OPTIONS] [--] [FILE]...
 //!
 //! FLAGS:
 //!     -a, --append
 //!             Appends a signature to existing signature
 //!
+//!         --batch
+//!             Creates detached signatures next to each input file.  Directories
+//!             are searched recursively.  If no input is given, the names of the
+//!             files are read from stdin, one per line.  The signatures are written
+//!             to FILE.asc, or FILE.sig with --binary.  The signing keys are
+//!             resolved once, and failures are reported for each file.
 //!     -B, --binary
 //!             Emits binary data
 //!
@@ -256,6 +392,10 @@
 //!         --detached
 //!             Creates a detached signature
 //!
+//!         --gpg-agent
+//!             Signs using the secret keys in the gpg-agent.  The keys given using
+//!             --signer-key may then be certificates without secret key material,
+//!             or the fingerprints or Key IDs of certificates in the GnuPG keybox.
 //!     -h, --help
 //!             Prints help information
 //!
@@ -273,9 +413,36 @@
 //!         --client-cert <P12-FILE>
 //!             Authenticates to Fortanix DSM with the given client certificate
 //!
+//!         --dsm-account-id <ACCOUNT-UUID>
+//!             Selects the Fortanix DSM account after logging in
+//!
+//!         --dsm-bearer-token-file <FILE>
+//!             Authenticates to Fortanix DSM with the access token in FILE
+//!
+//!         --dsm-jwt-file <FILE>
+//!             Authenticates to Fortanix DSM with the JWT in FILE (requires the App
+//!             UUID)
 //!         --dsm-key <DSM-KEY-NAME>
 //!             Signs the message with the Fortanix DSM key
 //!
+//!         --dsm-username <USERNAME>
+//!             Logs in to Fortanix DSM as USERNAME.  The password is taken from
+//!             FORTANIX_PASSWORD, or asked for.
+//!         --gpg-homedir <DIR>
+//!             Uses the gpg-agent of the GnuPG home directory DIR
+//!
+//!         --hash <ALGO>
+//!             Hashes the files in the manifest using ALGO [default: SHA256]
+//!             [possible values: SHA256, SHA384, SHA512, SHA224]
+//!         --jobs <N>
+//!             Signs up to N files concurrently with --batch [default: 8]
+//!
+//!         --manifest <DIR>
+//!             Signs a manifest of the files in DIR.  The manifest lists the hash
+//!             of every regular file in the directory tree.  Symbolic links are not
+//!             followed, but their targets are recorded.  If the output file is in
+//!             DIR, it is not included in the manifest.  Use "sq verify --manifest"
+//!             to check the directory tree against the signed manifest.
 //!         --merge <SIGNED-MESSAGE>
 //!             Merges signatures from the input and SIGNED-MESSAGE
 //!
@@ -303,9 +470,9 @@
 //!             creation time
 //!
 //! ARGS:
-//!     <FILE>
-//!             Reads from FILE or stdin if omitted
-//!
+//!     <FILE>...
+//!             Reads from FILE or stdin if omitted.  With --batch, signs the given
+//!             files, and the files in the given directories.
 //!
 //! EXAMPLES:
 //!
@@ -314,6 +481,18 @@
 //!
 //! # Create a detached signature
 //! $ sq sign --detached --signer-key juliet.pgp message.txt
+//!
+//! # Create a signed message using a key in the gpg-agent
+//! $ sq sign --gpg-agent --signer-key 0123456789ABCDEF message.txt
+//!
+//! # Create detached signatures for all files in a directory
+//! $ sq sign --batch --dsm-key="My key" dist/
+//!
+//! # Sign the files listed on stdin, at most 16 at a time
+//! $ find dist -name '*.tar.gz' | sq sign --batch --jobs=16 --dsm-key="My key"
+//!
+//! # Sign a manifest of all files in a directory tree
+//! $ sq sign --manifest dist/ --signer-key juliet.pgp -o dist.manifest
 //! ```
 //!
 //! ## Subcommand verify
@@ -335,30 +514,79 @@
 //! i.e. if the message is smaller than 25 MiB, no output is produced, and
 //! if it is larger, then the output will be truncated.
 //!
+//! Signatures by keys that are not given using "--signer-cert" are
+//! checked using certificates from the certificate store.  Because these
+//! certificates are not authenticated, such signatures are only reported
+//! as good checksums, and do not count towards the threshold.
+//!
 //! The converse operation is "sq sign".
 //!
 //! USAGE:
-//!     sq verify [OPTIONS] [--] [FILE]
+//!     sq verify [FLAGS] [OPTIONS] [--] [FILE]
 //!
 //! FLAGS:
+//!         --explain
+//!             Explains why signatures failed to verify.  For every signature that
+//!             does not check out, prints which component failed (the signature,
+//!             the signing key, its binding, or the certificate), and why, e.g.
+//!             because the policy rejects an algorithm, something expired or was
+//!             revoked, a backsig is missing, or the signature does not match the
+//!             data.
 //!     -h, --help
 //!             Prints help information
 //!
 //!
 //! OPTIONS:
+//!         --api-key <API-KEY>
+//!             Authenticates to Fortanix DSM using the given API key
+//!
+//!         --app-uuid <APP-UUID>
+//!             Authenticates to Fortanix DSM with the given App  (cert-based
+//!             authentication)
+//!         --client-cert <P12-FILE>
+//!             Authenticates to Fortanix DSM with the given client certificate
+//!
 //!         --detached <SIG>
 //!             Verifies a detached signature
 //!
+//!         --dsm-account-id <ACCOUNT-UUID>
+//!             Selects the Fortanix DSM account after logging in
+//!
+//!         --dsm-bearer-token-file <FILE>
+//!             Authenticates to Fortanix DSM with the access token in FILE
+//!
+//!         --dsm-jwt-file <FILE>
+//!             Authenticates to Fortanix DSM with the JWT in FILE (requires the App
+//!             UUID)
+//!         --dsm-username <USERNAME>
+//!             Logs in to Fortanix DSM as USERNAME.  The password is taken from
+//!             FORTANIX_PASSWORD, or asked for.
+//!         --manifest <DIR>
+//!             Checks DIR against the signed manifest created by "sq sign
+//!             --manifest".  Files that were added, removed, or modified are
+//!             reported, and the verification fails if there are any.
 //!     -o, --output <FILE>
 //!             Writes to FILE or stdout if omitted
 //!
+//!         --pkcs12-passphrase <PKCS12-PASSPHRASE>
+//!             Passphrase for unlocking the PKCS12 identity file (cert-based
+//!             authentication)
+//!         --policy-as-of <TIME>
+//!             Evaluates signatures under the policy in effect at TIME.  Algorithms
+//!             are phased out over time, e.g. SHA-1 is rejected for most purposes
+//!             since 2013.  This option can be used to check old signatures using
+//!             the algorithms considered secure back then.  TIME is interpreted as
+//!             an ISO 8601 timestamp.  To use a date in UTC, use "YYYY-MM-DD".
 //!         --signer-cert <CERT>...
-//!             Verifies signatures with CERT
-//!
+//!             Verifies signatures with CERT.  If CERT is not a file, it is the
+//!             fingerprint of a certificate in the certificate store.
 //!     -n, --signatures <N>
 //!             Sets the threshold of valid signatures to N. If this threshold is
 //!             not reached, the message will not be considered verified. [default:
 //!             1]
+//!         --signer-dsm-key <DSM-KEY-NAME>...
+//!             Verifies signatures with the certificate of a key stored in Fortanix
+//!             DSM
 //!
 //! ARGS:
 //!     <FILE>
@@ -373,6 +601,18 @@
 //! # Verify a detached message
 //! $ sq verify --signer-cert juliet.pgp --detached message.sig message.txt
 //!
+//! # Verify a signed message with the certificate of a key stored in DSM
+//! $ sq verify --signer-dsm-key juliet signed-message.pgp
+//!
+//! # Verify a directory tree against a signed manifest
+//! $ sq verify --signer-cert juliet.pgp --manifest dist/ dist.manifest
+//!
+//! # Explain why a signature made in 2010 using SHA-1 is rejected, and
+//! # check it under the policy in effect back then
+//! $ sq verify --signer-cert juliet.pgp --explain signed-message.pgp
+//! $ sq verify --signer-cert juliet.pgp --policy-as-of 2010-01-01 \
+//!      signed-message.pgp
+//!
 //! SEE ALSO:
 //!
 //! If you are looking for a standalone program to verify detached
@@ -408,9 +648,25 @@
 //!     dsm-import
 //!             Imports a Transferable Secret Key (TSK) or a Transferable Public Key
 //!             (TPK) into Fortanix DSM
+//!     export-ssh               Exports authentication keys in SSH format
+//!     dsm-disable
+//!             Disables or deactivates a key stored in Fortanix DSM
+//!
+//!     dsm-delete               Deletes a key stored in Fortanix DSM
+//!     dsm-check
+//!             Checks the consistency of keys stored in Fortanix DSM
+//!
+//!     dsm-audit-verify         Verifies the hash chain of a DSM audit log
 //!     attest-certifications    Attests to third-party certifications
+//!     subkey                   Manages subkeys
+//!     userid                   Manages User IDs
+//!     expire                   Changes the expiration time of a key
 //!     info                     List details on DSM key
 //!     list-dsm-keys            List all accessible keys for the App
+//!     gpg-agent                Serves DSM keys via the gpg-agent protocol
+//!     ssh-agent
+//!             Serves DSM authentication keys via the ssh-agent protocol
+//!
 //!     adopt                    Binds keys from one certificate to another
 //!     help
 //!             Prints this message or the help of the given subcommand(s)
@@ -479,9 +735,21 @@
 //!         --client-cert <P12-FILE>
 //!             Authenticates to Fortanix DSM with the given client certificate
 //!
+//!         --dsm-account-id <ACCOUNT-UUID>
+//!             Selects the Fortanix DSM account after logging in
+//!
+//!         --dsm-bearer-token-file <FILE>
+//!             Authenticates to Fortanix DSM with the access token in FILE
+//!
+//!         --dsm-jwt-file <FILE>
+//!             Authenticates to Fortanix DSM with the JWT in FILE (requires the App
+//!             UUID)
 //!         --dsm-key <DSM-KEY-NAME>
 //!             Generate secrets inside Fortanix DSM with the given name
 //!
+//!         --dsm-username <USERNAME>
+//!             Logs in to Fortanix DSM as USERNAME.  The password is taken from
+//!             FORTANIX_PASSWORD, or asked for.
 //!         --expires <TIME>
 //!             Makes the key expire at TIME (as ISO 8601). Use "never" to create
 //!             keys that do not expire.
@@ -603,9 +871,21 @@
 //!         --client-cert <P12-FILE>
 //!             Authenticates to Fortanix DSM with the given client certificate
 //!
+//!         --dsm-account-id <ACCOUNT-UUID>
+//!             Selects the Fortanix DSM account after logging in
+//!
+//!         --dsm-bearer-token-file <FILE>
+//!             Authenticates to Fortanix DSM with the access token in FILE
+//!
+//!         --dsm-jwt-file <FILE>
+//!             Authenticates to Fortanix DSM with the JWT in FILE (requires the App
+//!             UUID)
 //!         --dsm-key <DSM-KEY-NAME>
 //!             Extracts the certificate from Fortanix DSM
 //!
+//!         --dsm-username <USERNAME>
+//!             Logs in to Fortanix DSM as USERNAME.  The password is taken from
+//!             FORTANIX_PASSWORD, or asked for.
 //!     -o, --output <FILE>
 //!             Writes to FILE or stdout if omitted
 //!
@@ -659,9 +939,21 @@
 //!         --client-cert <P12-FILE>
 //!             Authenticates to Fortanix DSM with the given client certificate
 //!
+//!         --dsm-account-id <ACCOUNT-UUID>
+//!             Selects the Fortanix DSM account after logging in
+//!
+//!         --dsm-bearer-token-file <FILE>
+//!             Authenticates to Fortanix DSM with the access token in FILE
+//!
+//!         --dsm-jwt-file <FILE>
+//!             Authenticates to Fortanix DSM with the JWT in FILE (requires the App
+//!             UUID)
 //!         --dsm-key <DSM-KEY-NAME>
 //!             Name of the DSM key
 //!
+//!         --dsm-username <USERNAME>
+//!             Logs in to Fortanix DSM as USERNAME.  The password is taken from
+//!             FORTANIX_PASSWORD, or asked for.
 //!     -o, --output <FILE>
 //!             Writes to FILE or stdout if omitted
 //!
@@ -703,9 +995,21 @@
 //!         --client-cert <P12-FILE>
 //!             Authenticates to Fortanix DSM with the given client certificate
 //!
+//!         --dsm-account-id <ACCOUNT-UUID>
+//!             Selects the Fortanix DSM account after logging in
+//!
+//!         --dsm-bearer-token-file <FILE>
+//!             Authenticates to Fortanix DSM with the access token in FILE
+//!
+//!         --dsm-jwt-file <FILE>
+//!             Authenticates to Fortanix DSM with the JWT in FILE (requires the App
+//!             UUID)
 //!         --dsm-key <DSM-KEY-NAME>
 //!             Name of the DSM key
 //!
+//!         --dsm-username <USERNAME>
+//!             Logs in to Fortanix DSM as USERNAME.  The password is taken from
+//!             FORTANIX_PASSWORD, or asked for.
 //!         --input <FILE>
 //!             Reads from FILE or stdin if omitted
 //!
@@ -719,217 +1023,293 @@
 //! $ sq-dsm key dsm-import --dsm-key="Imported by sq-dsm" < my_priv_key.asc
 //! ```
 //!
-//! ### Subcommand key attest-certifications
+//! ### Subcommand key export-ssh
 //!
 //! ```text
+//! Exports authentication keys in SSH format
 //!
-//! Attests to third-party certifications allowing for their distribution
-//!
-//! To prevent certificate flooding attacks, modern key servers prevent
-//! uncontrolled distribution of third-party certifications on
-//! certificates.  To make the key holder the sovereign over the
-//! information over what information is distributed with the certificate,
-//! the key holder needs to explicitly attest to third-party
-//! certifications.
+//! Prints a line in the format of OpenSSH's authorized_keys file for every
+//! valid key of the certificate that is flagged for authentication.  The
+//! comment is the certificate's primary user ID.
 //!
-//! After the attestation has been created, the certificate has to be
-//! distributed, e.g. by uploading it to a keyserver.
+//! The certificate is read from a file, or from Fortanix DSM if --dsm-key
+//! is given.
 //!
 //! USAGE:
-//!     sq key attest-certifications [FLAGS] [OPTIONS] [KEY]
+//!     sq key export-ssh [OPTIONS] [FILE]
 //!
 //! FLAGS:
-//!         --all
-//!             Attests to all certifications [default]
-//!
-//!     -B, --binary
-//!             Emits binary data
-//!
 //!     -h, --help
 //!             Prints help information
 //!
-//!         --none
-//!             Removes all prior attestations
-//!
 //!     -V, --version
 //!             Prints version information
 //!
 //!
 //! OPTIONS:
+//!         --api-key <API-KEY>
+//!             Authenticates to Fortanix DSM using the given API key
+//!
+//!         --app-uuid <APP-UUID>
+//!             Authenticates to Fortanix DSM with the given App (cert-based
+//!             authentication)
+//!         --client-cert <P12-FILE>
+//!             Authenticates to Fortanix DSM with the given client certificate
+//!
+//!         --dsm-account-id <ACCOUNT-UUID>
+//!             Selects the Fortanix DSM account after logging in
+//!
+//!         --dsm-bearer-token-file <FILE>
+//!             Authenticates to Fortanix DSM with the access token in FILE
+//!
+//!         --dsm-jwt-file <FILE>
+//!             Authenticates to Fortanix DSM with the JWT in FILE (requires the App
+//!             UUID)
+//!         --dsm-key <DSM-KEY-NAME>
+//!             Reads the certificate from Fortanix DSM
+//!
+//!         --dsm-username <USERNAME>
+//!             Logs in to Fortanix DSM as USERNAME.  The password is taken from
+//!             FORTANIX_PASSWORD, or asked for.
 //!     -o, --output <FILE>
 //!             Writes to FILE or stdout if omitted
 //!
+//!         --pkcs12-passphrase <PKCS12-PASSPHRASE>
+//!             Passphrase for unlocking the PKCS12 identity file (cert-based
+//!             authentication)
 //!
 //! ARGS:
-//!     <KEY>
-//!             Changes attestations on KEY
+//!     <FILE>
+//!             Reads from FILE or stdin if omitted
 //!
 //!
 //! EXAMPLES:
 //!
-//! # Attest to all certifications present on the key
-//! $ sq key attest-certifications juliet.pgp
+//! # Allow logging in using the authentication key of "My key"
+//! $ sq key export-ssh --dsm-key="My key" >> ~/.ssh/authorized_keys
 //!
-//! # Retract prior attestations on the key
-//! $ sq key attest-certifications --none juliet.pgp
+//! # Export the authentication keys of a certificate
+//! $ sq key export-ssh juliet.cert.pgp
 //! ```
 //!
-//! ### Subcommand key info
+//! ### Subcommand key dsm-disable
 //!
 //! ```text
+//! Disables or deactivates a key stored in Fortanix DSM
 //!
-//! This command prints data on a given DSM key name, if the key is present.
+//! This command disables the Security Object of the primary key, and the
+//! Security Objects of all subkeys linked to it.  Disabled keys can be
+//! enabled again in DSM.  With `--deactivate`, the deactivation date of
+//! the Security Objects is set to now instead.
+//!
+//! A summary of the affected Security Objects is printed, and confirmation
+//! is requested, before any change is made.
 //!
 //! USAGE:
-//!     sq key info --dsm-key <DSM-KEY-NAME>
+//!     sq key dsm-disable [FLAGS] [OPTIONS] --dsm-key <DSM-KEY-NAME>
 //!
 //! FLAGS:
+//!         --deactivate
+//!             Deactivates the key instead of disabling it
+//!
 //!     -h, --help
 //!             Prints help information
 //!
 //!     -V, --version
 //!             Prints version information
 //!
+//!         --yes
+//!             Does not ask for confirmation
+//!
 //!
 //! OPTIONS:
+//!         --api-key <API-KEY>
+//!             Authenticates to Fortanix DSM using the given API key
+//!
+//!         --app-uuid <APP-UUID>
+//!             Authenticates to Fortanix DSM with the given App  (cert-based
+//!             authentication)
+//!         --client-cert <P12-FILE>
+//!             Authenticates to Fortanix DSM with the given client certificate
+//!
+//!         --dsm-account-id <ACCOUNT-UUID>
+//!             Selects the Fortanix DSM account after logging in
+//!
+//!         --dsm-bearer-token-file <FILE>
+//!             Authenticates to Fortanix DSM with the access token in FILE
+//!
+//!         --dsm-jwt-file <FILE>
+//!             Authenticates to Fortanix DSM with the JWT in FILE (requires the App
+//!             UUID)
 //!         --dsm-key <DSM-KEY-NAME>
 //!             Name of the DSM key
 //!
+//!         --dsm-username <USERNAME>
+//!             Logs in to Fortanix DSM as USERNAME.  The password is taken from
+//!             FORTANIX_PASSWORD, or asked for.
+//!         --pkcs12-passphrase <PKCS12-PASSPHRASE>
+//!             Passphrase for unlocking the PKCS12 identity file (cert-based
+//!             authentication)
 //!
 //! EXAMPLES:
 //!
-//! # Prints details on given key
-//! $ sq key info --dsm-key 0123456789A
+//! # Disable the key and all its subkeys
+//! $ sq key dsm-disable --dsm-key="My key"
+//!
+//! # Deactivate the key and all its subkeys
+//! $ sq key dsm-disable --deactivate --dsm-key="My key"
 //! ```
 //!
-//! ### Subcommand key list-dsm-keys
+//! ### Subcommand key dsm-delete
 //!
 //! ```text
+//! Deletes a key stored in Fortanix DSM
 //!
-//! This command prints details about all the keys accessible to the app.
-//! Command will query DSM list keys API for each group, and club the outputs
-//! to print on STDOUT.
+//! This command destroys the Security Object of the primary key, and the
+//! Security Objects of all subkeys linked to it.  This cannot be undone:
+//! messages encrypted to the key can no longer be decrypted.
+//!
+//! A summary of the affected Security Objects is printed, and confirmation
+//! is requested, before any change is made.
 //!
 //! USAGE:
-//!     sq key list-dsm-keys [FLAGS]
+//!     sq key dsm-delete [FLAGS] [OPTIONS] --dsm-key <DSM-KEY-NAME>
 //!
 //! FLAGS:
 //!     -h, --help
 //!             Prints help information
 //!
-//!     -l, --long
-//!             prints long details of key
-//!
 //!     -V, --version
 //!             Prints version information
 //!
+//!         --yes
+//!             Does not ask for confirmation
 //!
-//! EXAMPLES:
 //!
-//! # Print list of keys which app can access
-//! $ sq key list-dsm-keys
+//! OPTIONS:
+//!         --api-key <API-KEY>
+//!             Authenticates to Fortanix DSM using the given API key
 //!
-//! # Print detailed list of keys which app can access
-//! $ sq key list-dsm-keys -l
+//!         --app-uuid <APP-UUID>
+//!             Authenticates to Fortanix DSM with the given App  (cert-based
+//!             authentication)
+//!         --client-cert <P12-FILE>
+//!             Authenticates to Fortanix DSM with the given client certificate
+//!
+//!         --dsm-account-id <ACCOUNT-UUID>
+//!             Selects the Fortanix DSM account after logging in
+//!
+//!         --dsm-bearer-token-file <FILE>
+//!             Authenticates to Fortanix DSM with the access token in FILE
+//!
+//!         --dsm-jwt-file <FILE>
+//!             Authenticates to Fortanix DSM with the JWT in FILE (requires the App
+//!             UUID)
+//!         --dsm-key <DSM-KEY-NAME>
+//!             Name of the DSM key
+//!
+//!         --dsm-username <USERNAME>
+//!             Logs in to Fortanix DSM as USERNAME.  The password is taken from
+//!             FORTANIX_PASSWORD, or asked for.
+//!         --pkcs12-passphrase <PKCS12-PASSPHRASE>
+//!             Passphrase for unlocking the PKCS12 identity file (cert-based
+//!             authentication)
+//!
+//! EXAMPLES:
+//!
+//! # Delete the key and all its subkeys
+//! $ sq key dsm-delete --dsm-key="My key"
 //! ```
 //!
-//! ### Subcommand key adopt
+//! ### Subcommand key dsm-check
 //!
 //! ```text
+//! Checks the consistency of keys stored in Fortanix DSM
 //!
-//! Binds keys from one certificate to another
+//! The certificate of a PGP key is stored in the metadata of the Security
+//! Object of its primary key.  This command checks that the Security
+//! Objects still match it: fingerprints are recomputed from the key
+//! material and compared with the metadata and the certificate, links
+//! between the primary key and the subkeys are checked in both directions,
+//! and binding signatures are validated.
 //!
-//! This command allows one to transfer primary keys and subkeys into an
-//! existing certificate.  Say you want to transition to a new
-//! certificate, but have an authentication subkey on your current
-//! certificate.  You want to keep the authentication subkey because it
-//! allows access to SSH servers and updating their configuration is not
-//! feasible.
+//! Without --dsm-key, all accessible PGP keys are checked.
 //!
-//! USAGE:
-//!     sq key adopt [FLAGS] [OPTIONS] --key <KEY>... [--] [TARGET-KEY]
+//! With --repair, wrong fingerprints and key flags in the metadata, and
+//! missing links from subkeys to the primary key, are fixed.  Other
+//! problems are only reported.
 //!
-//! FLAGS:
-//!         --allow-broken-crypto
-//!             Allows adopting keys from certificates using broken cryptography
+//! The exit status is non-zero if unrepaired problems are found.
 //!
-//!     -B, --binary
-//!             Emits binary data
+//! USAGE:
+//!     sq key dsm-check [FLAGS] [OPTIONS]
 //!
+//! FLAGS:
 //!     -h, --help
 //!             Prints help information
 //!
+//!         --repair
+//!             Repairs the problems that can be repaired
+//!
 //!     -V, --version
 //!             Prints version information
 //!
 //!
 //! OPTIONS:
-//!     -k, --key <KEY>...
-//!             Adds the key or subkey KEY to the TARGET-KEY
+//!         --api-key <API-KEY>
+//!             Authenticates to Fortanix DSM using the given API key
 //!
-//!     -r, --keyring <KEY-RING>...
-//!             Supplies keys for use in --key.
+//!         --app-uuid <APP-UUID>
+//!             Authenticates to Fortanix DSM with the given App  (cert-based
+//!             authentication)
+//!         --client-cert <P12-FILE>
+//!             Authenticates to Fortanix DSM with the given client certificate
 //!
-//!     -o, --output <FILE>
-//!             Writes to FILE or stdout if omitted
+//!         --dsm-account-id <ACCOUNT-UUID>
+//!             Selects the Fortanix DSM account after logging in
 //!
+//!         --dsm-bearer-token-file <FILE>
+//!             Authenticates to Fortanix DSM with the access token in FILE
 //!
-//! ARGS:
-//!     <TARGET-KEY>
-//!             Adds keys to TARGET-KEY
+//!         --dsm-jwt-file <FILE>
+//!             Authenticates to Fortanix DSM with the JWT in FILE (requires the App
+//!             UUID)
+//!         --dsm-key <DSM-KEY-NAME>
+//!             Checks only the given DSM key
 //!
+//!         --dsm-username <USERNAME>
+//!             Logs in to Fortanix DSM as USERNAME.  The password is taken from
+//!             FORTANIX_PASSWORD, or asked for.
+//!         --pkcs12-passphrase <PKCS12-PASSPHRASE>
+//!             Passphrase for unlocking the PKCS12 identity file (cert-based
+//!             authentication)
 //!
 //! EXAMPLES:
 //!
-//! # Adopt an subkey into the new cert
-//! $ sq key adopt --keyring juliet-old.pgp --key 0123456789ABCDEF -- juliet-new.pgp
-//! ```
-//!
-//! ## Subcommand keyring
-//!
-//! ```text
-//! Manages collections of keys or certs
-//!
-//! Collections of keys or certficicates (also known as "keyrings" when
-//! they contain secret key material, and "certrings" when they don't) are
-//! any number of concatenated certificates.  This subcommand provides
-//! tools to list, split, join, merge, and filter keyrings.
-//!
-//! Note: In the documentation of this subcommand, we sometimes use the
-//! terms keys and certs interchangeably.
-//!
-//! USAGE:
-//!     sq keyring <SUBCOMMAND>
-//!
-//! FLAGS:
-//!     -h, --help
-//!             Prints help information
-//!
+//! # Check all keys
+//! $ sq key dsm-check
 //!
-//! SUBCOMMANDS:
-//!     list      Lists keys in a keyring
-//!     split     Splits a keyring into individual keys
-//!     join      Joins keys or keyrings into a single keyring
-//!     merge     Merges keys or keyrings into a single keyring
-//!     filter    Joins keys into a keyring applying a filter
-//!     help      Prints this message or the help of the given subcommand(s)
+//! # Check a key, and repair it
+//! $ sq key dsm-check --repair --dsm-key="My key"
 //! ```
 //!
-//! ### Subcommand keyring list
+//! ### Subcommand key dsm-audit-verify
 //!
 //! ```text
-//! Lists keys in a keyring
+//! Verifies the hash chain of a DSM audit log
 //!
-//! Prints the fingerprint as well as the primary userid for every
-//! certificate encountered in the keyring.
+//! If the SQ_DSM_AUDIT_LOG environment variable is set, every operation
+//! performed in Fortanix DSM is recorded in the file it names.  Each record
+//! contains the SHA-256 hash of the previous one.  This command checks
+//! that the chain is intact, i.e., that no record has been modified,
+//! inserted, or removed, except at the end of the log.
+//!
+//! The exit status is non-zero if the chain is broken.
 //!
 //! USAGE:
-//!     sq keyring list [FLAGS] [FILE]
+//!     sq key dsm-audit-verify [FILE]
 //!
 //! FLAGS:
-//!         --all-userids
-//!             Lists all user ids, even those that are expired, revoked, or not
-//!             valid under the standard policy.
 //!     -h, --help
 //!             Prints help information
 //!
@@ -939,34 +1319,1416 @@
 //!
 //! ARGS:
 //!     <FILE>
-//!             Reads from FILE or stdin if omitted
-//!
+//!             Reads the audit log from FILE, or the file named by SQ_DSM_AUDIT_LOG
+//!             if omitted
 //!
 //! EXAMPLES:
 //!
-//! # List all certs
-//! $ sq keyring list certs.pgp
+//! # Verify the audit log named by SQ_DSM_AUDIT_LOG
+//! $ sq key dsm-audit-verify
 //!
-//! # List all certs with a userid on example.org
-//! $ sq keyring filter --domain example.org certs.pgp | sq keyring list
+//! # Verify a given audit log
+//! $ sq key dsm-audit-verify audit.log
 //! ```
 //!
-//! ### Subcommand keyring split
+//! ### Subcommand key attest-certifications
 //!
 //! ```text
-//! Splits a keyring into individual keys
 //!
-//! Splitting up a keyring into individual keys helps with curating a
-//! keyring.
+//! Attests to third-party certifications allowing for their distribution
 //!
-//! The converse operation is "sq keyring join".
+//! To prevent certificate flooding attacks, modern key servers prevent
+//! uncontrolled distribution of third-party certifications on
+//! certificates.  To make the key holder the sovereign over the
+//! information over what information is distributed with the certificate,
+//! the key holder needs to explicitly attest to third-party
+//! certifications.
+//!
+//! After the attestation has been created, the certificate has to be
+//! distributed, e.g. by uploading it to a keyserver.
+//!
+//! USAGE:
+//!     sq key attest-certifications [FLAGS] [OPTIONS] [KEY]
+//!
+//! FLAGS:
+//!         --all
+//!             Attests to all certifications [default]
+//!
+//!     -B, --binary
+//!             Emits binary data
+//!
+//!     -h, --help
+//!             Prints help information
+//!
+//!         --none
+//!             Removes all prior attestations
+//!
+//!     -V, --version
+//!             Prints version information
+//!
+//!
+//! OPTIONS:
+//!     -o, --output <FILE>
+//!             Writes to FILE or stdout if omitted
+//!
+//!
+//! ARGS:
+//!     <KEY>
+//!             Changes attestations on KEY
+//!
+//!
+//! EXAMPLES:
+//!
+//! # Attest to all certifications present on the key
+//! $ sq key attest-certifications juliet.pgp
+//!
+//! # Retract prior attestations on the key
+//! $ sq key attest-certifications --none juliet.pgp
+//! ```
+//!
+//! ### Subcommand key subkey
+//!
+//! ```text
+//! Manages subkeys
+//!
+//! Adds new subkeys to a key, and changes the expiration time of
+//! existing subkeys.  Password-protected keys are unlocked as needed,
+//! and the secret key material in the resulting key stays protected.
+//!
+//! USAGE:
+//!     sq key subkey <SUBCOMMAND>
+//!
+//! FLAGS:
+//!     -h, --help
+//!             Prints help information
+//!
+//!     -V, --version
+//!             Prints version information
+//!
+//!
+//! SUBCOMMANDS:
+//!     add       Adds a newly generated subkey
+//!     expire    Changes the expiration time of subkeys
+//!     help      Prints this message or the help of the given subcommand(s)
+//! ```
+//!
+//! #### Subcommand key subkey add
+//!
+//! ```text
+//! Adds a newly generated subkey
+//!
+//! A new subkey with the given capability is generated and bound to the
+//! key.  If the key is protected by a password, the new subkey is
+//! protected by the same password.
+//!
+//! USAGE:
+//!     sq key subkey add [FLAGS] [OPTIONS] <--can-sign|--can-authenticate|--can-encrypt <PURPOSE>> [KEY]
+//!
+//! FLAGS:
+//!     -B, --binary
+//!             Emits binary data
+//!
+//!         --can-authenticate
+//!             Adds an authentication-capable subkey
+//!
+//!         --can-sign
+//!             Adds a signing-capable subkey
+//!
+//!     -h, --help
+//!             Prints help information
+//!
+//!     -V, --version
+//!             Prints version information
+//!
+//!
+//! OPTIONS:
+//!         --can-encrypt <PURPOSE>
+//!             Adds an encryption-capable subkey [possible values: transport,
+//!             storage, universal]
+//!     -c, --cipher-suite <CIPHER-SUITE>
+//!             Selects the cryptographic algorithms for the subkey [default:
+//!             cv25519]  [possible values: rsa2k, rsa3k, rsa4k, cv25519, nistp256,
+//!             nistp384, nistp521]
+//!         --expires <TIME>
+//!             Makes the subkey expire at TIME (as ISO 8601).  Use "never" to
+//!             create subkeys that do not expire on their own. [default: never]
+//!         --expires-in <DURATION>
+//!             Makes the subkey expire after DURATION. Either "N[ymwd]", for N
+//!             years, months, weeks, or days, or "never".
+//!     -o, --output <FILE>
+//!             Writes to FILE or stdout if omitted
+//!
+//!
+//! ARGS:
+//!     <KEY>
+//!             Reads from KEY or stdin if omitted
+//!
+//!
+//! EXAMPLES:
+//!
+//! # Add a new encryption subkey
+//! $ sq key subkey add --can-encrypt=universal juliet.key.pgp
+//!
+//! # Add a new signing subkey that expires in a year
+//! $ sq key subkey add --can-sign --expires-in 1y juliet.key.pgp
+//! ```
+//!
+//! #### Subcommand key subkey expire
+//!
+//! ```text
+//! Changes the expiration time of subkeys
+//!
+//! The subkeys are re-bound with the new expiration time.  A subkey can
+//! be retired by letting it expire now.  Note that a subkey does not
+//! outlive the primary key, see "sq key expire".
+//!
+//! USAGE:
+//!     sq key subkey expire [FLAGS] [OPTIONS] --subkey <SUBKEY>... <--expires <TIME>|--expires-in <DURATION>> [--] [KEY]
+//!
+//! FLAGS:
+//!     -B, --binary
+//!             Emits binary data
+//!
+//!     -h, --help
+//!             Prints help information
+//!
+//!     -V, --version
+//!             Prints version information
+//!
+//!
+//! OPTIONS:
+//!         --expires <TIME>
+//!             Makes the subkeys expire at TIME (as ISO 8601).  Use "never" to
+//!             remove the expiration time.
+//!         --expires-in <DURATION>
+//!             Makes the subkeys expire after DURATION. Either "N[ymwd]", for N
+//!             years, months, weeks, or days, or "never".
+//!     -o, --output <FILE>
+//!             Writes to FILE or stdout if omitted
+//!
+//!         --subkey <SUBKEY>...
+//!             Changes the expiration time of the subkey with this fingerprint or
+//!             Key ID
+//!
+//! ARGS:
+//!     <KEY>
+//!             Reads from KEY or stdin if omitted
+//!
+//!
+//! EXAMPLES:
+//!
+//! # Extend the validity of a subkey by two years
+//! $ sq key subkey expire --subkey 0123456789ABCDEF --expires-in 2y \
+//!      juliet.key.pgp
+//! ```
+//!
+//! ### Subcommand key userid
+//!
+//! ```text
+//! Manages User IDs
+//!
+//! Adds User IDs to a key, removes them, or changes the primary User ID.
+//! Password-protected keys are unlocked as needed.
+//!
+//! USAGE:
+//!     sq key userid <SUBCOMMAND>
+//!
+//! FLAGS:
+//!     -h, --help
+//!             Prints help information
+//!
+//!     -V, --version
+//!             Prints version information
+//!
+//!
+//! SUBCOMMANDS:
+//!     add            Adds User IDs
+//!     strip          Removes User IDs
+//!     set-primary    Changes the primary User ID
+//!     help           Prints this message or the help of the given
+//!                    subcommand(s)
+//! ```
+//!
+//! #### Subcommand key userid add
+//!
+//! ```text
+//! Adds User IDs
+//!
+//! The new User IDs are bound to the key using the preferences and the
+//! expiration time of the primary User ID.
+//!
+//! USAGE:
+//!     sq key userid add [FLAGS] [OPTIONS] --userid <USERID>... [--] [KEY]
+//!
+//! FLAGS:
+//!     -B, --binary
+//!             Emits binary data
+//!
+//!     -h, --help
+//!             Prints help information
+//!
+//!     -V, --version
+//!             Prints version information
+//!
+//!
+//! OPTIONS:
+//!     -o, --output <FILE>
+//!             Writes to FILE or stdout if omitted
+//!
+//!     -u, --userid <USERID>...
+//!             Adds USERID to the key
+//!
+//!
+//! ARGS:
+//!     <KEY>
+//!             Reads from KEY or stdin if omitted
+//!
+//!
+//! EXAMPLES:
+//!
+//! # Add a new User ID
+//! $ sq key userid add --userid "Juliet <juliet@example.org>" juliet.key.pgp
+//! ```
+//!
+//! #### Subcommand key userid strip
+//!
+//! ```text
+//! Removes User IDs
+//!
+//! The User IDs and their signatures are removed from the key.  This
+//! only changes the local copy of the key: anybody who already has a
+//! copy of the certificate still sees the User IDs.  To retract a User
+//! ID that has been published, use "sq revoke userid" instead.
+//!
+//! USAGE:
+//!     sq key userid strip [FLAGS] [OPTIONS] --userid <USERID>... [--] [KEY]
+//!
+//! FLAGS:
+//!     -B, --binary
+//!             Emits binary data
+//!
+//!     -h, --help
+//!             Prints help information
+//!
+//!     -V, --version
+//!             Prints version information
+//!
+//!
+//! OPTIONS:
+//!     -o, --output <FILE>
+//!             Writes to FILE or stdout if omitted
+//!
+//!     -u, --userid <USERID>...
+//!             Removes USERID from the key
+//!
+//!
+//! ARGS:
+//!     <KEY>
+//!             Reads from KEY or stdin if omitted
+//!
+//!
+//! EXAMPLES:
+//!
+//! # Remove a User ID before publishing the certificate
+//! $ sq key userid strip --userid "Juliet <juliet@work.example>" \
+//!      juliet.key.pgp
+//! ```
+//!
+//! #### Subcommand key userid set-primary
+//!
+//! ```text
+//! Changes the primary User ID
+//!
+//! USAGE:
+//!     sq key userid set-primary [FLAGS] [OPTIONS] --userid <USERID> [KEY]
+//!
+//! FLAGS:
+//!     -B, --binary     Emits binary data
+//!     -h, --help       Prints help information
+//!     -V, --version    Prints version information
+//!
+//! OPTIONS:
+//!     -o, --output <FILE>      Writes to FILE or stdout if omitted
+//!     -u, --userid <USERID>    Marks USERID as primary User ID
+//!
+//! ARGS:
+//!     <KEY>    Reads from KEY or stdin if omitted
+//!
+//! EXAMPLES:
+//!
+//! # Make the work address the primary User ID
+//! $ sq key userid set-primary --userid "Juliet <juliet@work.example>" \
+//!      juliet.key.pgp
+//! ```
+//!
+//! ### Subcommand key expire
+//!
+//! ```text
+//! Changes the expiration time of a key
+//!
+//! The primary key's direct key signature and the binding signatures of
+//! all User IDs are updated with the new expiration time.  When the
+//! primary key expires, the whole certificate expires, including the
+//! subkeys.  Use "sq key subkey expire" to change the expiration time
+//! of individual subkeys.
+//!
+//! USAGE:
+//!     sq key expire [FLAGS] [OPTIONS] <--expires <TIME>|--expires-in <DURATION>> [KEY]
+//!
+//! FLAGS:
+//!     -B, --binary
+//!             Emits binary data
+//!
+//!     -h, --help
+//!             Prints help information
+//!
+//!     -V, --version
+//!             Prints version information
+//!
+//!
+//! OPTIONS:
+//!         --expires <TIME>
+//!             Makes the key expire at TIME (as ISO 8601). Use "never" to remove
+//!             the expiration time.
+//!         --expires-in <DURATION>
+//!             Makes the key expire after DURATION. Either "N[ymwd]", for N years,
+//!             months, weeks, or days, or "never".
+//!     -o, --output <FILE>
+//!             Writes to FILE or stdout if omitted
+//!
+//!
+//! ARGS:
+//!     <KEY>
+//!             Reads from KEY or stdin if omitted
+//!
+//!
+//! EXAMPLES:
+//!
+//! # Extend the validity of the key by two years
+//! $ sq key expire --expires-in 2y juliet.key.pgp
+//!
+//! # Make the key never expire
+//! $ sq key expire --expires never juliet.key.pgp
+//! ```
+//!
+//! ### Subcommand key info
+//!
+//! ```text
+//!
+//! This command prints data on a given DSM key name, if the key is present.
+//!
+//! USAGE:
+//!     sq key info --dsm-key <DSM-KEY-NAME>
+//!
+//! FLAGS:
+//!     -h, --help
+//!             Prints help information
+//!
+//!     -V, --version
+//!             Prints version information
+//!
+//!
+//! OPTIONS:
+//!         --dsm-key <DSM-KEY-NAME>
+//!             Name of the DSM key
+//!
+//!
+//! EXAMPLES:
+//!
+//! # Prints details on given key
+//! $ sq key info --dsm-key 0123456789A
+//! ```
+//!
+//! ### Subcommand key list-dsm-keys
+//!
+//! ```text
+//!
+//! This command prints details about all the keys accessible to the app.
+//! Command will query DSM list keys API for each group, and club the outputs
+//! to print on STDOUT.
+//!
+//! USAGE:
+//!     sq key list-dsm-keys [FLAGS]
+//!
+//! FLAGS:
+//!     -h, --help
+//!             Prints help information
+//!
+//!     -l, --long
+//!             prints long details of key
+//!
+//!     -V, --version
+//!             Prints version information
+//!
+//!
+//! EXAMPLES:
+//!
+//! # Print list of keys which app can access
+//! $ sq key list-dsm-keys
+//!
+//! # Print detailed list of keys which app can access
+//! $ sq key list-dsm-keys -l
+//! ```
+//!
+//! ### Subcommand key gpg-agent
+//!
+//! ```text
+//!
+//! Serves DSM keys via the gpg-agent protocol
+//!
+//! This command listens on the given socket, and serves the primary key
+//! and the subkeys of the given DSM keys to clients speaking gpg-agent's
+//! Assuan protocol, like GnuPG.  The secret key operations are carried out
+//! in Fortanix DSM.  Keys are identified by their keygrip.
+//!
+//! For GnuPG to use the keys, the certificates must be imported into its
+//! keyring, and GnuPG must be pointed to the socket, e.g. by placing it at
+//! the location reported by `gpgconf --list-dirs agent-socket`.
+//!
+//! The command runs until interrupted.
+//!
+//! USAGE:
+//!     sq key gpg-agent [OPTIONS] --dsm-key <DSM-KEY-NAME>... --socket <SOCKET>
+//!
+//! FLAGS:
+//!     -h, --help
+//!             Prints help information
+//!
+//!     -V, --version
+//!             Prints version information
+//!
+//!
+//! OPTIONS:
+//!         --api-key <API-KEY>
+//!             Authenticates to Fortanix DSM using the given API key
+//!
+//!         --app-uuid <APP-UUID>
+//!             Authenticates to Fortanix DSM with the given App  (cert-based
+//!             authentication)
+//!         --client-cert <P12-FILE>
+//!             Authenticates to Fortanix DSM with the given client certificate
+//!
+//!         --dsm-account-id <ACCOUNT-UUID>
+//!             Selects the Fortanix DSM account after logging in
+//!
+//!         --dsm-bearer-token-file <FILE>
+//!             Authenticates to Fortanix DSM with the access token in FILE
+//!
+//!         --dsm-jwt-file <FILE>
+//!             Authenticates to Fortanix DSM with the JWT in FILE (requires the App
+//!             UUID)
+//!         --dsm-key <DSM-KEY-NAME>...
+//!             Serves the DSM key with this name (may be given multiple times)
+//!
+//!         --dsm-username <USERNAME>
+//!             Logs in to Fortanix DSM as USERNAME.  The password is taken from
+//!             FORTANIX_PASSWORD, or asked for.
+//!         --pkcs12-passphrase <PKCS12-PASSPHRASE>
+//!             Passphrase for unlocking the PKCS12 identity file (cert-based
+//!             authentication)
+//!         --socket <SOCKET>
+//!             Listens on the Unix domain socket SOCKET
+//!
+//!
+//! EXAMPLES:
+//!
+//! # Serve the key "My key" to GnuPG
+//! $ sq key extract-cert --dsm-key="My key" | gpg --import
+//! $ gpgconf --kill gpg-agent
+//! $ sq --force key gpg-agent --dsm-key="My key" \
+//!      --socket=$(gpgconf --list-dirs agent-socket)
+//! ```
+//!
+//! ### Subcommand key ssh-agent
+//!
+//! ```text
+//!
+//! Serves DSM authentication keys via the ssh-agent protocol
+//!
+//! This command listens on the given socket, and serves the keys flagged
+//! for authentication of the given DSM keys to SSH clients.  The
+//! signatures are made in Fortanix DSM.  Ed25519, ECDSA (NIST curves), and
+//! RSA keys are supported.
+//!
+//! SSH clients use the keys if SSH_AUTH_SOCK points to the socket.  Use
+//! `sq key export-ssh` to get the lines to add to the server's
+//! authorized_keys file.
+//!
+//! The command runs until interrupted.
+//!
+//! USAGE:
+//!     sq key ssh-agent [OPTIONS] --dsm-key <DSM-KEY-NAME>... --socket <SOCKET>
+//!
+//! FLAGS:
+//!     -h, --help
+//!             Prints help information
+//!
+//!     -V, --version
+//!             Prints version information
+//!
+//!
+//! OPTIONS:
+//!         --api-key <API-KEY>
+//!             Authenticates to Fortanix DSM using the given API key
+//!
+//!         --app-uuid <APP-UUID>
+//!             Authenticates to Fortanix DSM with the given App  (cert-based
+//!             authentication)
+//!         --client-cert <P12-FILE>
+//!             Authenticates to Fortanix DSM with the given client certificate
+//!
+//!         --dsm-account-id <ACCOUNT-UUID>
+//!             Selects the Fortanix DSM account after logging in
+//!
+//!         --dsm-bearer-token-file <FILE>
+//!             Authenticates to Fortanix DSM with the access token in FILE
+//!
+//!         --dsm-jwt-file <FILE>
+//!             Authenticates to Fortanix DSM with the JWT in FILE (requires the App
+//!             UUID)
+//!         --dsm-key <DSM-KEY-NAME>...
+//!             Serves the DSM key with this name (may be given multiple times)
+//!
+//!         --dsm-username <USERNAME>
+//!             Logs in to Fortanix DSM as USERNAME.  The password is taken from
+//!             FORTANIX_PASSWORD, or asked for.
+//!         --pkcs12-passphrase <PKCS12-PASSPHRASE>
+//!             Passphrase for unlocking the PKCS12 identity file (cert-based
+//!             authentication)
+//!         --socket <SOCKET>
+//!             Listens on the Unix domain socket SOCKET
+//!
+//!
+//! EXAMPLES:
+//!
+//! # Serve the authentication keys of "My key" to ssh
+//! $ sq key ssh-agent --dsm-key="My key" --socket=/tmp/sq-ssh.sock &
+//! $ SSH_AUTH_SOCK=/tmp/sq-ssh.sock ssh user@example.org
+//! ```
+//!
+//! ### Subcommand key adopt
+//!
+//! ```text
+//!
+//! Binds keys from one certificate to another
+//!
+//! This command allows one to transfer primary keys and subkeys into an
+//! existing certificate.  Say you want to transition to a new
+//! certificate, but have an authentication subkey on your current
+//! certificate.  You want to keep the authentication subkey because it
+//! allows access to SSH servers and updating their configuration is not
+//! feasible.
+//!
+//! USAGE:
+//!     sq key adopt [FLAGS] [OPTIONS] --key <KEY>... [--] [TARGET-KEY]
+//!
+//! FLAGS:
+//!         --allow-broken-crypto
+//!             Allows adopting keys from certificates using broken cryptography
+//!
+//!     -B, --binary
+//!             Emits binary data
+//!
+//!     -h, --help
+//!             Prints help information
+//!
+//!     -V, --version
+//!             Prints version information
+//!
+//!
+//! OPTIONS:
+//!     -k, --key <KEY>...
+//!             Adds the key or subkey KEY to the TARGET-KEY
+//!
+//!     -r, --keyring <KEY-RING>...
+//!             Supplies keys for use in --key.
+//!
+//!     -o, --output <FILE>
+//!             Writes to FILE or stdout if omitted
+//!
+//!
+//! ARGS:
+//!     <TARGET-KEY>
+//!             Adds keys to TARGET-KEY
+//!
+//!
+//! EXAMPLES:
+//!
+//! # Adopt an subkey into the new cert
+//! $ sq key adopt --keyring juliet-old.pgp --key 0123456789ABCDEF -- juliet-new.pgp
+//! ```
+//!
+//! ## Subcommand keyring
+//!
+//! ```text
+//! Manages collections of keys or certs
+//!
+//! Collections of keys or certficicates (also known as "keyrings" when
+//! they contain secret key material, and "certrings" when they don't) are
+//! any number of concatenated certificates.  This subcommand provides
+//! tools to list, split, join, merge, and filter keyrings.
+//!
+//! Note: In the documentation of this subcommand, we sometimes use the
+//! terms keys and certs interchangeably.
+//!
+//! USAGE:
+//!     sq keyring <SUBCOMMAND>
+//!
+//! FLAGS:
+//!     -h, --help
+//!             Prints help information
+//!
+//!
+//! SUBCOMMANDS:
+//!     list      Lists keys in a keyring
+//!     split     Splits a keyring into individual keys
+//!     join      Joins keys or keyrings into a single keyring
+//!     merge     Merges keys or keyrings into a single keyring
+//!     lint      Detects and repairs weak self-signatures
+//!     diff      Compares certificates
+//!     filter    Joins keys into a keyring applying a filter
+//!     help      Prints this message or the help of the given subcommand(s)
+//! ```
+//!
+//! ### Subcommand keyring list
+//!
+//! ```text
+//! Lists keys in a keyring
+//!
+//! Prints the fingerprint as well as the primary userid for every
+//! certificate encountered in the keyring.
+//!
+//! USAGE:
+//!     sq keyring list [FLAGS] [FILE]
+//!
+//! FLAGS:
+//!         --all-userids
+//!             Lists all user ids, even those that are expired, revoked, or not
+//!             valid under the standard policy.
+//!     -h, --help
+//!             Prints help information
+//!
+//!     -V, --version
+//!             Prints version information
+//!
+//!
+//! ARGS:
+//!     <FILE>
+//!             Reads from FILE or stdin if omitted
+//!
+//!
+//! EXAMPLES:
+//!
+//! # List all certs
+//! $ sq keyring list certs.pgp
+//!
+//! # List all certs with a userid on example.org
+//! $ sq keyring filter --domain example.org certs.pgp | sq keyring list
+//! ```
+//!
+//! ### Subcommand keyring split
+//!
+//! ```text
+//! Splits a keyring into individual keys
+//!
+//! Splitting up a keyring into individual keys helps with curating a
+//! keyring.
+//!
+//! The converse operation is "sq keyring join".
+//!
+//! USAGE:
+//!     sq keyring split [FLAGS] [OPTIONS] [FILE]
+//!
+//! FLAGS:
+//!     -B, --binary
+//!             Emits binary data
+//!
+//!     -h, --help
+//!             Prints help information
+//!
+//!     -V, --version
+//!             Prints version information
+//!
+//!
+//! OPTIONS:
+//!     -p, --prefix <FILE>
+//!             Writes to files with prefix FILE [defaults to the input filename
+//!             with a dash, or "output" if keyring is read from stdin]
+//!
+//! ARGS:
+//!     <FILE>
+//!             Reads from FILE or stdin if omitted
+//!
+//!
+//! EXAMPLES:
+//!
+//! # Split all certs
+//! $ sq keyring split certs.pgp
+//!
+//! # Split all certs, merging them first to avoid duplicates
+//! $ sq keyring merge certs.pgp | sq keyring split
+//! ```
+//!
+//! ### Subcommand keyring join
+//!
+//! ```text
+//! Joins keys or keyrings into a single keyring
+//!
+//! Unlike "sq keyring merge", multiple versions of the same key are not
+//! merged together.
+//!
+//! The converse operation is "sq keyring split".
+//!
+//! USAGE:
+//!     sq keyring join [FLAGS] [OPTIONS] [FILE]...
+//!
+//! FLAGS:
+//!     -B, --binary
+//!             Don't ASCII-armor the keyring
+//!
+//!     -h, --help
+//!             Prints help information
+//!
+//!     -V, --version
+//!             Prints version information
+//!
+//!
+//! OPTIONS:
+//!     -o, --output <FILE>
+//!             Sets the output file to use
+//!
+//!
+//! ARGS:
+//!     <FILE>...
+//!             Sets the input files to use
+//!
+//!
+//! EXAMPLES:
+//!
+//! # Collect certs for an email conversation
+//! $ sq keyring join juliet.pgp romeo.pgp alice.pgp
+//! ```
+//!
+//! ### Subcommand keyring merge
+//!
+//! ```text
+//! Merges keys or keyrings into a single keyring
+//!
+//! Unlike "sq keyring join", the certificates are buffered and multiple
+//! versions of the same certificate are merged together.  Where data is
+//! replaced (e.g., secret key material), data from the later certificate
+//! is preferred.
+//!
+//! USAGE:
+//!     sq keyring merge [FLAGS] [OPTIONS] [FILE]...
+//!
+//! FLAGS:
+//!     -B, --binary
+//!             Emits binary data
+//!
+//!     -h, --help
+//!             Prints help information
+//!
+//!     -V, --version
+//!             Prints version information
+//!
+//!
+//! OPTIONS:
+//!     -o, --output <FILE>
+//!             Writes to FILE or stdout if omitted
+//!
+//!
+//! ARGS:
+//!     <FILE>...
+//!             Reads from FILE
+//!
+//!
+//! EXAMPLES:
+//!
+//! # Merge certificate updates
+//! $ sq keyring merge certs.pgp romeo-updates.pgp
+//! ```
+//!
+//! ### Subcommand keyring lint
+//!
+//! ```text
+//! Detects and repairs weak self-signatures
+//!
+//! Older certificates often have self-signatures using SHA-1, or
+//! signing-capable subkeys without a primary key binding signature
+//! ("backsig").  The standard policy rejects these, which renders the
+//! affected User IDs and subkeys, or even the whole certificate,
+//! unusable.
+//!
+//! This subcommand reports every component with such a self-signature,
+//! and the reason why the policy rejects it.  Revoked components are
+//! ignored.  It fails if any certificate has weak self-signatures that
+//! are not fixed.
+//!
+//! With --fix, the affected self-signatures are re-issued using SHA-512,
+//! keeping all other subpackets, and the fixed certificates are written
+//! out.  This requires the primary key's secret key material, and, to
+//! make a new primary key binding signature, that of the subkey.  It is
+//! looked for in the certificates themselves, in the keys given using
+//! --secret-key-file, in Fortanix DSM (see --dsm-key), and, finally, in
+//! the gpg-agent (see --gpg-agent), in that order.
+//!
+//! USAGE:
+//!     sq keyring lint [FLAGS] [OPTIONS] [--] [FILE]...
+//!
+//! FLAGS:
+//!     -B, --binary
+//!             Emits binary data
+//!
+//!         --fix
+//!             Re-issues weak self-signatures, and writes out the fixed
+//!             certificates
+//!         --gpg-agent
+//!             Signs using the secret keys in the gpg-agent
+//!
+//!     -h, --help
+//!             Prints help information
+//!
+//!     -k, --list-keys
+//!             Lists the fingerprints of the certificates with weak self-signatures
+//!             instead of the details
+//!     -V, --version
+//!             Prints version information
+//!
+//!
+//! OPTIONS:
+//!         --api-key <API-KEY>
+//!             Authenticates to Fortanix DSM using the given API key
+//!
+//!         --app-uuid <APP-UUID>
+//!             Authenticates to Fortanix DSM with the given App  (cert-based
+//!             authentication)
+//!         --client-cert <P12-FILE>
+//!             Authenticates to Fortanix DSM with the given client certificate
+//!
+//!         --dsm-account-id <ACCOUNT-UUID>
+//!             Selects the Fortanix DSM account after logging in
+//!
+//!         --dsm-bearer-token-file <FILE>
+//!             Authenticates to Fortanix DSM with the access token in FILE
+//!
+//!         --dsm-jwt-file <FILE>
+//!             Authenticates to Fortanix DSM with the JWT in FILE (requires the App
+//!             UUID)
+//!         --dsm-key <DSM-KEY-NAME>...
+//!             Signs using the key DSM-KEY-NAME stored inside the Fortanix Self-
+//!             Defending Key-Management System
+//!         --dsm-username <USERNAME>
+//!             Logs in to Fortanix DSM as USERNAME.  The password is taken from
+//!             FORTANIX_PASSWORD, or asked for.
+//!         --gpg-homedir <DIR>
+//!             Uses the gpg-agent of the GnuPG home directory DIR
+//!
+//!     -o, --output <FILE>
+//!             Writes to FILE or stdout if omitted
+//!
+//!         --pkcs12-passphrase <PKCS12-PASSPHRASE>
+//!             Passphrase for unlocking the PKCS12 identity file (cert-based
+//!             authentication)
+//!         --secret-key-file <TSK-FILE>...
+//!             Signs using the secret keys in TSK-FILE
+//!
+//!
+//! ARGS:
+//!     <FILE>...
+//!             Reads from FILE or stdin if omitted
+//!
+//!
+//! EXAMPLES:
+//!
+//! # Reports weak self-signatures
+//! $ sq keyring lint certs.pgp
+//!
+//! # Lists the certificates with weak self-signatures
+//! $ sq keyring lint --list-keys certs.pgp
+//!
+//! # Fixes a key
+//! $ sq keyring lint --fix juliet.pgp > juliet-fixed.pgp
+//!
+//! # Fixes a certificate using the secrets in the gpg-agent
+//! $ gpg --export juliet@example.org | sq keyring lint --fix --gpg-agent | gpg
+//! --import
+//! ```
+//!
+//! ### Subcommand keyring diff
+//!
+//! ```text
+//! Compares certificates
+//!
+//! Compares two versions of certificates, e.g. a certificate before and
+//! after it was updated from a keyserver or merged using "sq keyring
+//! merge".  If A and B are keyrings, certificates are matched by their
+//! fingerprints.
+//!
+//! For every certificate that differs, lists the subkeys, User IDs, and
+//! user attributes that were added or removed, and the self-signatures,
+//! third-party certifications, and revocations that were added or
+//! removed.  Signatures are compared ignoring their unhashed subpacket
+//! areas.  For every signature, its creation time and whether it is
+//! valid under the policy are shown.  Third-party signatures are only
+//! checked against the policy, they are not cryptographically verified.
+//!
+//! With "--output-format json", emits a JSON document instead.
+//!
+//! USAGE:
+//!     sq keyring diff <A> <B>
+//!
+//! FLAGS:
+//!     -h, --help
+//!             Prints help information
+//!
+//!     -V, --version
+//!             Prints version information
+//!
+//!
+//! ARGS:
+//!     <A>
+//!             Reads the first version from A
+//!
+//!     <B>
+//!             Reads the second version from B
+//!
+//!
+//! EXAMPLES:
+//!
+//! # Shows what changed when updating a certificate
+//! $ sq keyring diff juliet.pgp juliet-updated.pgp
+//!
+//! # Shows what merging two keyrings changes in the first one
+//! $ sq keyring merge certs.pgp new.pgp > merged.pgp
+//! $ sq keyring diff certs.pgp merged.pgp
+//!
+//! # Emits the differences as JSON
+//! $ sq --output-format json keyring diff juliet.pgp juliet-updated.pgp
+//! ```
+//!
+//! ### Subcommand keyring filter
+//!
+//! ```text
+//! Joins keys into a keyring applying a filter
+//!
+//! This can be used to filter keys based on given predicates,
+//! e.g. whether they have a user id containing an email address with a
+//! certain domain.  Additionally, the keys can be pruned to only include
+//! components matching the predicates.
+//!
+//! If no filters are supplied, everything matches.
+//!
+//! If multiple predicates are given, they are or'ed, i.e. a key matches
+//! if any of the predicates match.  To require all predicates to match,
+//! chain multiple invocations of this command.  See EXAMPLES for
+//! inspiration.
+//!
+//! USAGE:
+//!     sq keyring filter [FLAGS] [OPTIONS] [--] [FILE]...
+//!
+//! FLAGS:
+//!     -B, --binary
+//!             Emits binary data
+//!
+//!     -h, --help
+//!             Prints help information
+//!
+//!     -P, --prune-certs
+//!             Removes certificate components not matching the filter
+//!
+//!         --to-cert
+//!             Converts any keys in the input to certificates.  Converting a key to
+//!             a certificate removes secret key material from the key thereby
+//!             turning it into a certificate.
+//!     -V, --version
+//!             Prints version information
+//!
+//!
+//! OPTIONS:
+//!         --domain <FQDN>...
+//!             Parses user ids into name and email address and case-sensitively
+//!             matches on the domain of the email address, requiring an exact
+//!             match.
+//!         --email <ADDRESS>...
+//!             Parses user ids into name and email address and case-sensitively
+//!             matches on the email address, requiring an exact match.
+//!         --name <NAME>...
+//!             Parses user ids into name and email and case-sensitively matches on
+//!             the name, requiring an exact match.
+//!     -o, --output <FILE>
+//!             Writes to FILE or stdout if omitted
+//!
+//!         --userid <USERID>...
+//!             Case-sensitively matches on the user id, requiring an exact match.
+//!
+//!
+//! ARGS:
+//!     <FILE>...
+//!             Reads from FILE or stdin if omitted
+//!
+//!
+//! EXAMPLES:
+//!
+//! # Converts a key to a cert (i.e., remove any secret key material)
+//! $ sq keyring filter --to-cert cat juliet.pgp
+//!
+//! # Gets the keys with a user id on example.org
+//! $ sq keyring filter --domain example.org keys.pgp
+//!
+//! # Gets the keys with a user id on example.org or example.net
+//! $ sq keyring filter --domain example.org --domain example.net keys.pgp
+//!
+//! # Gets the keys with a user id with the name Juliet
+//! $ sq keyring filter --name Juliet keys.pgp
+//!
+//! # Gets the keys with a user id with the name Juliet on example.org
+//! $ sq keyring filter --domain example.org keys.pgp | \
+//!   sq keyring filter --name Juliet
+//!
+//! # Gets the keys with a user id on example.org, pruning other userids
+//! $ sq keyring filter --domain example.org --prune-certs certs.pgp
+//! ```
+//!
+//! ## Subcommand import
+//!
+//! ```text
+//! Imports certificates into the certificate store
+//!
+//! Certificates that are already in the store are merged with the
+//! imported version.  Secret key material is not imported.
+//!
+//! The converse operation is "sq export".
+//!
+//! USAGE:
+//!     sq import [FILE]...
+//!
+//! FLAGS:
+//!     -h, --help
+//!             Prints help information
+//!
+//!
+//! ARGS:
+//!     <FILE>...
+//!             Reads from FILE or stdin if omitted
+//!
+//!
+//! EXAMPLES:
+//!
+//! # Import a certificate
+//! $ sq import juliet.pgp
+//!
+//! # Import certificates from a keyserver
+//! $ sq keyserver get romeo@example.org | sq import
+//! ```
+//!
+//! ## Subcommand export
+//!
+//! ```text
+//! Exports certificates from the certificate store
+//!
+//! Exports the certificates matching any of the given queries, or all
+//! certificates if no query is given.  See "sq lookup" for the
+//! supported queries.
+//!
+//! The converse operation is "sq import".
+//!
+//! USAGE:
+//!     sq export [FLAGS] [OPTIONS] [QUERY]...
+//!
+//! FLAGS:
+//!     -B, --binary
+//!             Emits binary data
+//!
+//!     -h, --help
+//!             Prints help information
+//!
+//!
+//! OPTIONS:
+//!     -o, --output <FILE>
+//!             Writes to FILE or stdout if omitted
+//!
+//!
+//! ARGS:
+//!     <QUERY>...
+//!             Exports certificates matching QUERY
+//!
+//!
+//! EXAMPLES:
+//!
+//! # Export all certificates
+//! $ sq export > certs.pgp
+//!
+//! # Export the certificates with a user ID containing an email address
+//! $ sq export juliet@example.org
+//! ```
+//!
+//! ## Subcommand lookup
+//!
+//! ```text
+//! Looks up certificates in the certificate store
+//!
+//! Lists the certificates matching QUERY, like "sq keyring list".
+//! QUERY may be a fingerprint or key ID of a primary key or subkey, an
+//! email address, or a User ID.  Fingerprints and key IDs may be prefixed
+//! with "0x".  Email addresses are enclosed in angle brackets, or
+//! contain an "@" and no whitespace.  Everything else is matched
+//! exactly against the User IDs.
+//!
+//! The options can be used to force the interpretation of QUERY.
+//!
+//! USAGE:
+//!     sq lookup [FLAGS] <QUERY>
+//!
+//! FLAGS:
+//!         --all-userids
+//!             Lists all user ids
+//!
+//!         --email
+//!             Interprets QUERY as an email address
+//!
+//!     -h, --help
+//!             Prints help information
+//!
+//!         --userid
+//!             Interprets QUERY as a User ID
+//!
+//!
+//! ARGS:
+//!     <QUERY>
+//!             Looks up certificates matching QUERY
+//!
+//!
+//! EXAMPLES:
+//!
+//! # Look up a certificate by subkey
+//! $ sq lookup 0xC2B819056C652598
+//!
+//! # Look up certificates by email address
+//! $ sq lookup juliet@example.org
+//!
+//! # Look up certificates by User ID
+//! $ sq lookup 'Juliet Capulet <juliet@example.org>'
+//! ```
+//!
+//! ## Subcommand certify
+//!
+//! ```text
+//!
+//! Certifies a User ID for a Certificate
+//!
+//! Using a certification a keyholder may vouch for the fact that another
+//! certificate legitimately belongs to a user id.  In the context of
+//! emails this means that the same entity controls the key and the email
+//! address.  These kind of certifications form the basis for the Web Of
+//! Trust.
+//!
+//! This command emits the certificate with the new certification.  The
+//! updated certificate has to be distributed, preferably by sending it to
+//! the certificate holder for attestation.  See also "sq key
+//! attest-certification".
+//!
+//! USAGE:
+//!     sq certify [FLAGS] [OPTIONS] <CERTIFIER-KEY> <CERTIFICATE> <USERID>
+//!
+//! FLAGS:
+//!     -B, --binary
+//!             Emits binary data
+//!
+//!         --gpg-agent
+//!             Certifies using the secret key in the gpg-agent.  CERTIFIER-KEY may
+//!             then be a certificate without secret key material, or the
+//!             fingerprint or Key ID of a certificate in the GnuPG keybox.
+//!     -h, --help
+//!             Prints help information
+//!
+//!     -l, --local
+//!             Makes the certification a local certification.  Normally, local
+//!             certifications are not exported.
+//!         --non-revocable
+//!             Marks the certification as being non-revocable. That is, you cannot
+//!             later revoke this certification.  This should normally only be used
+//!             with an expiration.
+//!
+//! OPTIONS:
+//!     -a, --amount <TRUST_AMOUNT>
+//!             Sets the amount of trust.  Values between 1 and 120 are meaningful.
+//!             120 means fully trusted.  Values less than 120 indicate the degree
+//!             of trust.  60 is usually used for partially trusted.  The default is
+//!             120.
+//!     -d, --depth <TRUST_DEPTH>
+//!             Sets the trust depth (sometimes referred to as the trust level).  0
+//!             means a normal certification of <CERTIFICATE, USERID>.  1 means
+//!             CERTIFICATE is also a trusted introducer, 2 means CERTIFICATE is a
+//!             meta-trusted introducer, etc.  The default is 0.
+//!         --expires <TIME>
+//!             Makes the certification expire at TIME (as ISO 8601). Use "never" to
+//!             create certifications that do not expire.
+//!         --expires-in <DURATION>
+//!             Makes the certification expire after DURATION. Either "N[ymwd]", for
+//!             N years, months, weeks, or days, or "never".  [default: 5y]
+//!         --gpg-homedir <DIR>
+//!             Uses the gpg-agent of the GnuPG home directory DIR
+//!
+//!         --notation <NAME> <VALUE>
+//!             Adds a notation to the certification.  A user-defined notation's
+//!             name must be of the form "name@a.domain.you.control.org". If the
+//!             notation's name starts with a !, then the notation is marked as
+//!             being critical.  If a consumer of a signature doesn't understand a
+//!             critical notation, then it will ignore the signature.  The notation
+//!             is marked as being human readable.
+//!     -o, --output <FILE>
+//!             Writes to FILE or stdout if omitted
+//!
+//!     -r, --regex <REGEX>...
+//!             Adds a regular expression to constrain what a trusted introducer can
+//!             certify.  The regular expression must match the certified User ID in
+//!             all intermediate introducers, and the certified certificate.
+//!             Multiple regular expressions may be specified.  In that case, at
+//!             least one must match.
+//!
+//! ARGS:
+//!     <CERTIFIER-KEY>
+//!             Creates the certificate using CERTIFIER-KEY.
+//!
+//!     <CERTIFICATE>
+//!             Certifies CERTIFICATE.
+//!
+//!     <USERID>
+//!             Certifies USERID for CERTIFICATE.
+//!
+//!
+//! EXAMPLES:
+//!
+//! # Juliet certifies that Romeo controls romeo.pgp and romeo@example.org
+//! $ sq certify juliet.pgp romeo.pgp "<romeo@example.org>"
+//!
+//! # Juliet certifies using her key in the gpg-agent
+//! $ sq certify --gpg-agent 0123456789ABCDEF romeo.pgp "<romeo@example.org>"
+//! ```
+//!
+//! ## Subcommand wot
+//!
+//! ```text
+//! Authenticates bindings using the web of trust
+//!
+//! Certifications, see "sq certify", vouch for bindings between
+//! certificates and User IDs.  Starting from the certificates that you
+//! trust, the trust roots, this subcommand follows the certifications
+//! to authenticate bindings.
+//!
+//! A trust root authenticates its own User IDs, and vouches for the
+//! bindings that it certifies.  A certification that is a trust
+//! signature (see "sq certify --depth") also makes the certified
+//! certificate a trusted introducer, that may in turn vouch for
+//! bindings, possibly constrained by regular expressions
+//! ("sq certify --regex").
+//!
+//! Every certification has a trust amount, and a binding is only fully
+//! authenticated if the certifications vouching for it add up to a
+//! trust amount of 120.  The trust amount of a path is the minimum of
+//! the trust amounts of its certifications, and the trust amounts of
+//! several independent paths are added.
+//!
+//! Only certifications, certificates and User IDs that are valid at
+//! the reference time are considered.  Expired and revoked ones are
+//! ignored.
+//!
+//! The certificates are read from the keyrings given using --keyring
+//! and from the certificate store (see "sq import").
+//!
+//! USAGE:
+//!     sq wot <SUBCOMMAND>
+//!
+//! FLAGS:
+//!     -h, --help
+//!             Prints help information
+//!
+//!
+//! SUBCOMMANDS:
+//!     authenticate    Authenticates a binding
+//!     lookup          Looks up authenticated certificates by User ID
+//!     path            Checks a path
+//!     help            Prints this message or the help of the given
+//!                     subcommand(s)
+//!
+//! EXAMPLES:
+//!
+//! # Authenticate the binding between a certificate and a User ID
+//! $ sq wot authenticate --trust-root 8F17777118A33DDA9BA48E62AACB3243630052D9 \
+//!      --keyring keyring.pgp \
+//!      C2B819056C652598A0D8C1FEE5C7D0D86B00AD4F 'Juliet <juliet@example.org>'
+//!
+//! # Find certificates with an authenticated email address
+//! $ sq wot lookup --trust-root 8F17777118A33DDA9BA48E62AACB3243630052D9 \
+//!      --keyring keyring.pgp --email juliet@example.org
+//!
+//! # Check a given path
+//! $ sq wot path --keyring keyring.pgp \
+//!      8F17777118A33DDA9BA48E62AACB3243630052D9 \
+//!      C2B819056C652598A0D8C1FEE5C7D0D86B00AD4F 'Juliet <juliet@example.org>'
+//! ```
+//!
+//! ### Subcommand wot authenticate
+//!
+//! ```text
+//! Authenticates a binding
+//!
+//! Authenticates the binding between the certificate FINGERPRINT and
+//! USERID, and prints the paths that were found.  Fails if the binding
+//! cannot be authenticated.
+//!
+//! USAGE:
+//!     sq wot authenticate [FLAGS] [OPTIONS] <FINGERPRINT> <USERID> --trust-root <FINGERPRINT>...
+//!
+//! FLAGS:
+//!         --email
+//!             Authenticates all User IDs with the email address USERID
+//!
+//!     -h, --help
+//!             Prints help information
+//!
+//!     -V, --version
+//!             Prints version information
+//!
+//!
+//! OPTIONS:
+//!         --amount <TRUST_AMOUNT>
+//!             Requires a trust amount of TRUST_AMOUNT.  120 means fully
+//!             authenticated.  [default: 120]
+//!         --keyring <FILE>...
+//!             Uses the certificates in FILE
+//!
+//!         --time <TIME>
+//!             Uses TIME (as ISO 8601) as the reference time [default: now]
+//!
+//!         --trust-root <FINGERPRINT>...
+//!             Trusts the certificate FINGERPRINT
+//!
+//!
+//! ARGS:
+//!     <FINGERPRINT>
+//!             Authenticates the certificate FINGERPRINT
+//!
+//!     <USERID>
+//!             Authenticates USERID
+//! ```
+//!
+//! ### Subcommand wot lookup
+//!
+//! ```text
+//! Looks up authenticated certificates by User ID
+//!
+//! Authenticates the bindings of all certificates with USERID, and
+//! prints the paths that were found.  Fails if no binding can be
+//! authenticated.
 //!
 //! USAGE:
-//!     sq keyring split [FLAGS] [OPTIONS] [FILE]
+//!     sq wot lookup [FLAGS] [OPTIONS] <USERID> --trust-root <FINGERPRINT>...
 //!
 //! FLAGS:
-//!     -B, --binary
-//!             Emits binary data
+//!         --email
+//!             Looks up certificates with User IDs with the email address USERID
 //!
 //!     -h, --help
 //!             Prints help information
@@ -976,41 +2738,38 @@
 //!
 //!
 //! OPTIONS:
-//!     -p, --prefix <FILE>
-//!             Writes to files with prefix FILE [defaults to the input filename
-//!             with a dash, or "output" if keyring is read from stdin]
-//!
-//! ARGS:
-//!     <FILE>
-//!             Reads from FILE or stdin if omitted
+//!         --amount <TRUST_AMOUNT>
+//!             Requires a trust amount of TRUST_AMOUNT.  120 means fully
+//!             authenticated.  [default: 120]
+//!         --keyring <FILE>...
+//!             Uses the certificates in FILE
 //!
+//!         --time <TIME>
+//!             Uses TIME (as ISO 8601) as the reference time [default: now]
 //!
-//! EXAMPLES:
+//!         --trust-root <FINGERPRINT>...
+//!             Trusts the certificate FINGERPRINT
 //!
-//! # Split all certs
-//! $ sq keyring split certs.pgp
 //!
-//! # Split all certs, merging them first to avoid duplicates
-//! $ sq keyring merge certs.pgp | sq keyring split
+//! ARGS:
+//!     <USERID>
+//!             Looks up USERID
 //! ```
 //!
-//! ### Subcommand keyring join
+//! ### Subcommand wot path
 //!
 //! ```text
-//! Joins keys or keyrings into a single keyring
+//! Checks a path
 //!
-//! Unlike "sq keyring merge", multiple versions of the same key are not
-//! merged together.
-//!
-//! The converse operation is "sq keyring split".
+//! Checks that the certificates FINGERPRINT... form a path from the
+//! first one, which is considered to be the trust root, to the binding
+//! between the last one and USERID.  If the path is not valid, explains
+//! why.
 //!
 //! USAGE:
-//!     sq keyring join [FLAGS] [OPTIONS] [FILE]...
+//!     sq wot path [OPTIONS] <FINGERPRINT>... <USERID>
 //!
 //! FLAGS:
-//!     -B, --binary
-//!             Don't ASCII-armor the keyring
-//!
 //!     -h, --help
 //!             Prints help information
 //!
@@ -1019,38 +2778,111 @@
 //!
 //!
 //! OPTIONS:
-//!     -o, --output <FILE>
-//!             Sets the output file to use
+//!         --amount <TRUST_AMOUNT>
+//!             Requires a trust amount of TRUST_AMOUNT.  120 means fully
+//!             authenticated.  [default: 120]
+//!         --keyring <FILE>...
+//!             Uses the certificates in FILE
+//!
+//!         --time <TIME>
+//!             Uses TIME (as ISO 8601) as the reference time [default: now]
 //!
 //!
 //! ARGS:
-//!     <FILE>...
-//!             Sets the input files to use
+//!     <FINGERPRINT>...
+//!             Follows the path FINGERPRINT...
 //!
+//!     <USERID>
+//!             Checks the binding to USERID
+//! ```
 //!
-//! EXAMPLES:
+//! ### Subcommand wot EXAMPLES:
 //!
-//! # Collect certs for an email conversation
-//! $ sq keyring join juliet.pgp romeo.pgp alice.pgp
+//! ```text
+//!
+//! USAGE:
+//!     sq wot <SUBCOMMAND>
+//!
+//! For more information try --help
 //! ```
 //!
-//! ### Subcommand keyring merge
+//! ### Subcommand wot #
 //!
 //! ```text
-//! Merges keys or keyrings into a single keyring
 //!
-//! Unlike "sq keyring join", the certificates are buffered and multiple
-//! versions of the same certificate are merged together.  Where data is
-//! replaced (e.g., secret key material), data from the later certificate
-//! is preferred.
+//! USAGE:
+//!     sq wot <SUBCOMMAND>
+//!
+//! For more information try --help
+//! ```
+//!
+//! ### Subcommand wot #
+//!
+//! ```text
 //!
 //! USAGE:
-//!     sq keyring merge [FLAGS] [OPTIONS] [FILE]...
+//!     sq wot <SUBCOMMAND>
+//!
+//! For more information try --help
+//! ```
+//!
+//! ### Subcommand wot #
+//!
+//! ```text
+//!
+//! USAGE:
+//!     sq wot <SUBCOMMAND>
+//!
+//! For more information try --help
+//! ```
+//!
+//! ## Subcommand revoke
+//!
+//! ```text
+//!
+//! Revokes a certificate, subkey or User ID
+//!
+//! A revocation states that a certificate, one of its subkeys, or one of
+//! its User IDs should no longer be used, and why.  It is signed by the
+//! certificate's primary key, which must be available, or by a designated
+//! revoker (see "--revocation-key").
+//!
+//! By default, the revocation is emitted together with the primary key and,
+//! for subkeys and User IDs, the revoked component, so that it can be merged
+//! into the certificate by anyone holding it.  Use "--full-certificate"
+//! to emit the revoked certificate instead.
+//!
+//! USAGE:
+//!     sq revoke <SUBCOMMAND>
+//!
+//! FLAGS:
+//!     -h, --help
+//!             Prints help information
+//!
+//!
+//! SUBCOMMANDS:
+//!     certificate    Revokes a certificate
+//!     subkey         Revokes a subkey
+//!     userid         Revokes a User ID
+//!     help           Prints this message or the help of the given
+//!                    subcommand(s)
+//! ```
+//!
+//! ### Subcommand revoke certificate
+//!
+//! ```text
+//! Revokes a certificate
+//!
+//! USAGE:
+//!     sq revoke certificate [FLAGS] [OPTIONS] <REASON> <MESSAGE>
 //!
 //! FLAGS:
 //!     -B, --binary
 //!             Emits binary data
 //!
+//!         --full-certificate
+//!             Emits the certificate with the revocation instead of the revocation
+//!             alone.  Secret key material is not emitted.
 //!     -h, --help
 //!             Prints help information
 //!
@@ -1059,159 +2891,137 @@
 //!
 //!
 //! OPTIONS:
+//!         --certificate <FILE>
+//!             Reads the certificate to revoke from FILE or stdin if omitted
+//!
+//!         --notation <NAME> <VALUE>
+//!             Adds a notation to the revocation.  A user-defined notation's name
+//!             must be of the form "name@a.domain.you.control.org". If the
+//!             notation's name starts with a !, then the notation is marked as
+//!             being critical.  If a consumer of a signature doesn't understand a
+//!             critical notation, then it will ignore the signature.  The notation
+//!             is marked as being human readable.
 //!     -o, --output <FILE>
 //!             Writes to FILE or stdout if omitted
 //!
+//!         --revocation-key <KEY>
+//!             Signs the revocation with the primary key of KEY instead of the
+//!             certificate's own primary key.  Such a third-party revocation is
+//!             only honored if KEY is a designated revoker of the certificate.
+//!     -t, --time <TIME>
+//!             Sets the revocation's creation time (as ISO 8601)
+//!
 //!
 //! ARGS:
-//!     <FILE>...
-//!             Reads from FILE
+//!     <REASON>
+//!             Gives the reason for the revocation.  "compromised" means that the
+//!             secret key material may have been exposed; signatures made at any
+//!             time are then considered suspicious.  "superseded" means that a new
+//!             certificate replaces this one, and "retired" that it is no longer
+//!             used. [possible values: compromised, superseded, retired,
+//!             unspecified]
+//!     <MESSAGE>
+//!             Explains the revocation in a human-readable message
 //!
 //!
 //! EXAMPLES:
 //!
-//! # Merge certificate updates
-//! $ sq keyring merge certs.pgp romeo-updates.pgp
+//! # Revoke Juliet's certificate, whose key was compromised
+//! $ sq revoke certificate --certificate juliet.pgp compromised \
+//!     "My laptop was stolen"
 //! ```
 //!
-//! ### Subcommand keyring filter
+//! ### Subcommand revoke subkey
 //!
 //! ```text
-//! Joins keys into a keyring applying a filter
-//!
-//! This can be used to filter keys based on given predicates,
-//! e.g. whether they have a user id containing an email address with a
-//! certain domain.  Additionally, the keys can be pruned to only include
-//! components matching the predicates.
-//!
-//! If no filters are supplied, everything matches.
-//!
-//! If multiple predicates are given, they are or'ed, i.e. a key matches
-//! if any of the predicates match.  To require all predicates to match,
-//! chain multiple invocations of this command.  See EXAMPLES for
-//! inspiration.
+//! Revokes a subkey
 //!
 //! USAGE:
-//!     sq keyring filter [FLAGS] [OPTIONS] [--] [FILE]...
+//!     sq revoke subkey [FLAGS] [OPTIONS] <SUBKEY> <REASON> <MESSAGE>
 //!
 //! FLAGS:
 //!     -B, --binary
 //!             Emits binary data
 //!
+//!         --full-certificate
+//!             Emits the certificate with the revocation instead of the revocation
+//!             alone.  Secret key material is not emitted.
 //!     -h, --help
 //!             Prints help information
 //!
-//!     -P, --prune-certs
-//!             Removes certificate components not matching the filter
-//!
-//!         --to-cert
-//!             Converts any keys in the input to certificates.  Converting a key to
-//!             a certificate removes secret key material from the key thereby
-//!             turning it into a certificate.
 //!     -V, --version
 //!             Prints version information
 //!
 //!
 //! OPTIONS:
-//!         --domain <FQDN>...
-//!             Parses user ids into name and email address and case-sensitively
-//!             matches on the domain of the email address, requiring an exact
-//!             match.
-//!         --email <ADDRESS>...
-//!             Parses user ids into name and email address and case-sensitively
-//!             matches on the email address, requiring an exact match.
-//!         --name <NAME>...
-//!             Parses user ids into name and email and case-sensitively matches on
-//!             the name, requiring an exact match.
+//!         --certificate <FILE>
+//!             Reads the certificate to revoke from FILE or stdin if omitted
+//!
+//!         --notation <NAME> <VALUE>
+//!             Adds a notation to the revocation.  A user-defined notation's name
+//!             must be of the form "name@a.domain.you.control.org". If the
+//!             notation's name starts with a !, then the notation is marked as
+//!             being critical.  If a consumer of a signature doesn't understand a
+//!             critical notation, then it will ignore the signature.  The notation
+//!             is marked as being human readable.
 //!     -o, --output <FILE>
 //!             Writes to FILE or stdout if omitted
 //!
-//!         --userid <USERID>...
-//!             Case-sensitively matches on the user id, requiring an exact match.
+//!         --revocation-key <KEY>
+//!             Signs the revocation with the primary key of KEY instead of the
+//!             certificate's own primary key.  Such a third-party revocation is
+//!             only honored if KEY is a designated revoker of the certificate.
+//!     -t, --time <TIME>
+//!             Sets the revocation's creation time (as ISO 8601)
 //!
 //!
 //! ARGS:
-//!     <FILE>...
-//!             Reads from FILE or stdin if omitted
-//!
-//!
-//! EXAMPLES:
-//!
-//! # Converts a key to a cert (i.e., remove any secret key material)
-//! $ sq keyring filter --to-cert cat juliet.pgp
-//!
-//! # Gets the keys with a user id on example.org
-//! $ sq keyring filter --domain example.org keys.pgp
+//!     <SUBKEY>
+//!             Revokes the subkey with this fingerprint or key ID
 //!
-//! # Gets the keys with a user id on example.org or example.net
-//! $ sq keyring filter --domain example.org --domain example.net keys.pgp
+//!     <REASON>
+//!             Gives the reason for the revocation [possible values: compromised,
+//!             superseded, retired, unspecified]
+//!     <MESSAGE>
+//!             Explains the revocation in a human-readable message
 //!
-//! # Gets the keys with a user id with the name Juliet
-//! $ sq keyring filter --name Juliet keys.pgp
 //!
-//! # Gets the keys with a user id with the name Juliet on example.org
-//! $ sq keyring filter --domain example.org keys.pgp | \
-//!   sq keyring filter --name Juliet
+//! EXAMPLES:
 //!
-//! # Gets the keys with a user id on example.org, pruning other userids
-//! $ sq keyring filter --domain example.org --prune-certs certs.pgp
+//! # Revoke a subkey of Juliet's certificate that is no longer used
+//! $ sq revoke subkey --certificate juliet.pgp \
+//!     8C6C0A7E0A5F5C5D retired "Replaced by a new encryption subkey"
 //! ```
 //!
-//! ## Subcommand certify
+//! ### Subcommand revoke userid
 //!
 //! ```text
-//!
-//! Certifies a User ID for a Certificate
-//!
-//! Using a certification a keyholder may vouch for the fact that another
-//! certificate legitimately belongs to a user id.  In the context of
-//! emails this means that the same entity controls the key and the email
-//! address.  These kind of certifications form the basis for the Web Of
-//! Trust.
-//!
-//! This command emits the certificate with the new certification.  The
-//! updated certificate has to be distributed, preferably by sending it to
-//! the certificate holder for attestation.  See also "sq key
-//! attest-certification".
+//! Revokes a User ID
 //!
 //! USAGE:
-//!     sq certify [FLAGS] [OPTIONS] <CERTIFIER-KEY> <CERTIFICATE> <USERID>
+//!     sq revoke userid [FLAGS] [OPTIONS] <USERID> <REASON> <MESSAGE>
 //!
 //! FLAGS:
 //!     -B, --binary
 //!             Emits binary data
 //!
+//!         --full-certificate
+//!             Emits the certificate with the revocation instead of the revocation
+//!             alone.  Secret key material is not emitted.
 //!     -h, --help
 //!             Prints help information
 //!
-//!     -l, --local
-//!             Makes the certification a local certification.  Normally, local
-//!             certifications are not exported.
-//!         --non-revocable
-//!             Marks the certification as being non-revocable. That is, you cannot
-//!             later revoke this certification.  This should normally only be used
-//!             with an expiration.
+//!     -V, --version
+//!             Prints version information
+//!
 //!
 //! OPTIONS:
-//!     -a, --amount <TRUST_AMOUNT>
-//!             Sets the amount of trust.  Values between 1 and 120 are meaningful.
-//!             120 means fully trusted.  Values less than 120 indicate the degree
-//!             of trust.  60 is usually used for partially trusted.  The default is
-//!             120.
-//!     -d, --depth <TRUST_DEPTH>
-//!             Sets the trust depth (sometimes referred to as the trust level).  0
-//!             means a normal certification of <CERTIFICATE, USERID>.  1 means
-//!             CERTIFICATE is also a trusted introducer, 2 means CERTIFICATE is a
-//!             meta-trusted introducer, etc.  The default is 0.
-//!         --expires <TIME>
-//!             Makes the certification expire at TIME (as ISO 8601). Use "never" to
-//!             create certifications that do not expire.
-//!         --expires-in <DURATION>
-//!             Makes the certification expire after DURATION. Either "N[ymwd]", for
-//!             N years, months, weeks, or days, or "never".  [default: 5y]
+//!         --certificate <FILE>
+//!             Reads the certificate to revoke from FILE or stdin if omitted
+//!
 //!         --notation <NAME> <VALUE>
-//!             Adds a notation to the certification.  A user-defined notation's
-//!             name must be of the form "name@a.domain.you.control.org". If the
+//!             Adds a notation to the revocation.  A user-defined notation's name
+//!             must be of the form "name@a.domain.you.control.org". If the
 //!             notation's name starts with a !, then the notation is marked as
 //!             being critical.  If a consumer of a signature doesn't understand a
 //!             critical notation, then it will ignore the signature.  The notation
@@ -1219,28 +3029,30 @@
 //!     -o, --output <FILE>
 //!             Writes to FILE or stdout if omitted
 //!
-//!     -r, --regex <REGEX>...
-//!             Adds a regular expression to constrain what a trusted introducer can
-//!             certify.  The regular expression must match the certified User ID in
-//!             all intermediate introducers, and the certified certificate.
-//!             Multiple regular expressions may be specified.  In that case, at
-//!             least one must match.
-//!
-//! ARGS:
-//!     <CERTIFIER-KEY>
-//!             Creates the certificate using CERTIFIER-KEY.
+//!         --revocation-key <KEY>
+//!             Signs the revocation with the primary key of KEY instead of the
+//!             certificate's own primary key.  Such a third-party revocation is
+//!             only honored if KEY is a designated revoker of the certificate.
+//!     -t, --time <TIME>
+//!             Sets the revocation's creation time (as ISO 8601)
 //!
-//!     <CERTIFICATE>
-//!             Certifies CERTIFICATE.
 //!
+//! ARGS:
 //!     <USERID>
-//!             Certifies USERID for CERTIFICATE.
+//!             Revokes this User ID
+//!
+//!     <REASON>
+//!             Gives the reason for the revocation [possible values: retired,
+//!             unspecified]
+//!     <MESSAGE>
+//!             Explains the revocation in a human-readable message
 //!
 //!
 //! EXAMPLES:
 //!
-//! # Juliet certifies that Romeo controls romeo.pgp and romeo@example.org
-//! $ sq certify juliet.pgp romeo.pgp "<romeo@example.org>"
+//! # Juliet no longer uses her work address
+//! $ sq revoke userid --certificate juliet.pgp \
+//!     "Juliet <juliet@work.example.org>" retired "I left this job"
 //! ```
 //!
 //! ## Subcommand autocrypt
@@ -1674,11 +3486,21 @@
 //! octet stream similar to hexdump(1), annotating specifically which
 //! bytes are parsed into OpenPGP values.
 //!
-//! To inspect encrypted messages, either supply the session key, or see
-//! "sq decrypt --dump" or "sq packet decrypt".
+//! To inspect encrypted messages, either supply the session key or the
+//! recipient's key, or see "sq decrypt --dump" or "sq packet decrypt".
+//!
+//! With "--output-format json", emits a JSON document describing the
+//! packet tree, including the offset and header length of every packet,
+//! its fields, signature subpackets, and MPIs.  Secret key material is
+//! only included with "--mpis".
+//!
+//! Packets are addressed by their path in the packet tree, which starts
+//! at 0 for the first top-level packet.  For example, "1.0" is the first
+//! packet inside the second top-level packet.  These are the same numbers
+//! used by "sq packet split".
 //!
 //! USAGE:
-//!     sq packet dump [FLAGS] [OPTIONS] [FILE]
+//!     sq packet dump [FLAGS] [OPTIONS] [--] [FILE]
 //!
 //! FLAGS:
 //!     -h, --help
@@ -1690,14 +3512,50 @@
 //!         --mpis
 //!             Prints cryptographic artifacts
 //!
+//!         --skip-literal-bodies
+//!             Omits the content of literal data packets
+//!
 //!     -V, --version
 //!             Prints version information
 //!
 //!
 //! OPTIONS:
+//!         --api-key <API-KEY>
+//!             Authenticates to Fortanix DSM using the given API key
+//!
+//!         --app-uuid <APP-UUID>
+//!             Authenticates to Fortanix DSM with the given App (cert-based
+//!             authentication)
+//!         --client-cert <P12-FILE>
+//!             Authenticates to Fortanix DSM with the given client certificate
+//!
+//!         --dsm-account-id <ACCOUNT-UUID>
+//!             Selects the Fortanix DSM account after logging in
+//!
+//!         --dsm-bearer-token-file <FILE>
+//!             Authenticates to Fortanix DSM with the access token in FILE
+//!
+//!         --dsm-jwt-file <FILE>
+//!             Authenticates to Fortanix DSM with the JWT in FILE (requires the App
+//!             UUID)
+//!         --dsm-key <DSM-KEY-NAME>
+//!             Decrypts an encrypted message with the secret stored inside the
+//!             Fortanix Self-Defending Key-Management System
+//!         --dsm-username <USERNAME>
+//!             Logs in to Fortanix DSM as USERNAME.  The password is taken from
+//!             FORTANIX_PASSWORD, or asked for.
 //!     -o, --output <FILE>
 //!             Writes to FILE or stdout if omitted
 //!
+//!         --packet <PATH>...
+//!             Dumps only the packet at PATH, e.g. "1.0", and the packets it
+//!             contains
+//!         --pkcs12-passphrase <PKCS12-PASSPHRASE>
+//!             Passphrase for unlocking the PKCS12 identity file (cert-based
+//!             authentication)
+//!         --recipient-key <KEY>...
+//!             Decrypts an encrypted message with KEY
+//!
 //!         --session-key <SESSION-KEY>
 //!             Decrypts an encrypted message using SESSION-KEY
 //!
@@ -1712,6 +3570,15 @@
 //! # Prints the packets of a certificate
 //! $ sq packet dump juliet.pgp
 //!
+//! # Emits a JSON description of a certificate's packets
+//! $ sq --output-format json packet dump juliet.pgp
+//!
+//! # Prints only the packets inside the encryption container
+//! $ sq packet dump --recipient-key juliet.pgp --packet 1 ciphertext.pgp
+//!
+//! # Prints the packets of a large message, omitting the literal data
+//! $ sq packet dump --skip-literal-bodies --hex message.pgp
+//!
 //! # Prints cryptographic artifacts of a certificate
 //! $ sq packet dump --mpis juliet.pgp
 //!
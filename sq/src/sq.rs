@@ -4,18 +4,20 @@ use anyhow::Context as _;
 use std::fs::OpenOptions;
 use std::io;
 use std::path::{Path, PathBuf};
-use std::time::Duration;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 use chrono::{DateTime, offset::Utc};
 use itertools::Itertools;
 
 use buffered_reader::{BufferedReader, Dup, File, Generic, Limitor};
+use sequoia_ipc::gnupg;
 use sequoia_openpgp as openpgp;
 
 use crate::openpgp::Result;
-use crate::openpgp::{armor, Cert};
+use crate::openpgp::{armor, Cert, KeyHandle};
 use crate::openpgp::crypto::Password;
 use crate::openpgp::fmt::hex;
-use crate::openpgp::types::KeyFlags;
+use crate::openpgp::types::{HashAlgorithm, KeyFlags};
 use crate::openpgp::packet::prelude::*;
 use crate::openpgp::parse::{Parse, PacketParser, PacketParserResult};
 use crate::openpgp::packet::signature::subpacket::NotationData;
@@ -25,10 +27,15 @@ use crate::openpgp::cert::prelude::*;
 use crate::openpgp::policy::StandardPolicy as P;
 
 mod sq_cli;
+mod cert_store;
 mod commands;
+mod output;
 mod secrets;
+mod wot;
 
-use secrets::{Auth, Credentials, PreSecret};
+use cert_store::{CertStore, Query};
+use output::OutputFormat;
+use secrets::{Credentials, PreSecret, dsm_auth};
 
 fn open_or_stdin(f: Option<&str>)
                  -> Result<Box<dyn BufferedReader<()>>> {
@@ -146,6 +153,62 @@ fn load_keys<'a, I>(files: I) -> openpgp::Result<Vec<PreSecret>>
     Ok(certs)
 }
 
+/// Returns the GnuPG context to use if --gpg-agent is given.
+fn gpg_context(m: &clap::ArgMatches) -> Result<Option<gnupg::Context>> {
+    if ! m.is_present("gpg-agent") {
+        return Ok(None);
+    }
+    Ok(Some(match m.value_of("gpg-homedir") {
+        Some(homedir) => gnupg::Context::with_homedir(homedir)?,
+        None => gnupg::Context::new()?,
+    }))
+}
+
+/// Loads the keys given as `arg`.
+///
+/// With --gpg-agent, the keys may also be certificates without
+/// secret key material, given as files, or as fingerprints or Key
+/// IDs of certificates in the GnuPG keybox.  Their secret key
+/// operations are carried out by gpg-agent.
+fn load_secrets(m: &clap::ArgMatches, arg: &str) -> Result<Vec<PreSecret>> {
+    let names = m.values_of(arg).into_iter().flatten();
+    let context = match gpg_context(m)? {
+        Some(context) => Arc::new(context),
+        None => return load_keys(names),
+    };
+
+    let mut keybox = None;
+    let mut keys = vec![];
+    for name in names {
+        let cert = if Path::new(name).exists() {
+            Cert::from_file(name)
+                .context(format!("Failed to load key from file {:?}", name))?
+        } else {
+            let handle = name.parse::<KeyHandle>()
+                .ok().filter(|h| ! h.is_invalid())
+                .ok_or_else(|| anyhow::anyhow!(
+                    "{:?} is neither a file, nor a fingerprint or Key ID",
+                    name))?;
+            if keybox.is_none() {
+                keybox = Some(secrets::gpg_keybox(&context)?);
+            }
+            keybox.as_ref().expect("loaded above").iter()
+                .find(|c| c.keys().any(|ka| handle.aliases(ka.key_handle())))
+                .cloned()
+                .ok_or_else(|| anyhow::anyhow!(
+                    "No certificate for {} found in the GnuPG keybox",
+                    handle))?
+        };
+
+        if cert.is_tsk() {
+            keys.push(PreSecret::InMemory(cert));
+        } else {
+            keys.push(PreSecret::Gpg(context.clone(), cert));
+        }
+    }
+    Ok(keys)
+}
+
 /// Loads one or more certs from every given file.
 fn load_certs<'a, I>(files: I) -> openpgp::Result<Vec<Cert>>
     where I: Iterator<Item=&'a str>
@@ -163,6 +226,41 @@ fn load_certs<'a, I>(files: I) -> openpgp::Result<Vec<Cert>>
     Ok(certs)
 }
 
+/// Loads one or more certs from every given file.
+///
+/// Arguments that do not name files are looked up in the certificate
+/// store, if any.  Because the certificates in the store are not
+/// authenticated, they are only looked up by the fingerprint of their
+/// primary key.
+fn resolve_certs<'a, I>(config: &Config, names: I) -> openpgp::Result<Vec<Cert>>
+    where I: Iterator<Item=&'a str>
+{
+    let store = config.cert_store();
+    let mut certs = vec![];
+    for name in names {
+        match &store {
+            Some(store) if ! Path::new(name).exists() => {
+                let fp = match name.parse() {
+                    Ok(Query::Key(KeyHandle::Fingerprint(fp))) => fp,
+                    _ => return Err(anyhow::anyhow!(
+                        "{:?} is neither a file nor a fingerprint\n\
+                         Hint: Use \"sq lookup {}\" to find the \
+                         fingerprint of a stored certificate.",
+                        name, name)),
+                };
+                match store.get(&fp)? {
+                    Some(cert) => certs.push(cert),
+                    None => return Err(anyhow::anyhow!(
+                        "{:?} is neither a file nor in the certificate store",
+                        name)),
+                }
+            },
+            _ => certs.append(&mut load_certs(std::iter::once(name))?),
+        }
+    }
+    Ok(certs)
+}
+
 /// Serializes a keyring, adding descriptive headers if armored.
 #[allow(dead_code)]
 fn serialize_keyring(mut output: &mut dyn io::Write, certs: &[Cert], binary: bool)
@@ -246,6 +344,58 @@ fn detect_armor_kind(input: Box<dyn BufferedReader<()>>)
     (dup.into_inner().unwrap().into_inner().unwrap(), kind)
 }
 
+/// Armors the input, unless it is already armored with the wanted
+/// kind.
+///
+/// If `want_kind` is `None`, the kind is detected from the data.
+fn armor(config: &Config, input: Box<dyn BufferedReader<()>>,
+         output: Option<&str>, mut want_kind: Option<armor::Kind>)
+         -> Result<()> {
+    // Peek at the data.  If it looks like it is armored
+    // data, avoid armoring it again.
+    let mut dup = Limitor::new(Dup::new(input), ARMOR_DETECTION_LIMIT);
+    let (already_armored, have_kind) = {
+        let mut reader =
+            armor::Reader::new(&mut dup,
+                               armor::ReaderMode::Tolerant(None));
+        (reader.data(8).is_ok(), reader.kind())
+    };
+    let mut input =
+        dup.as_boxed().into_inner().unwrap().into_inner().unwrap();
+
+    if already_armored
+        && (want_kind.is_none() || want_kind == have_kind)
+    {
+        // It is already armored and has the correct kind.
+        let mut output = config.create_or_stdout_safe(output)?;
+        io::copy(&mut input, &mut output)?;
+        return Ok(());
+    }
+
+    if want_kind.is_none() {
+        let (tmp, kind) = detect_armor_kind(input);
+        input = tmp;
+        want_kind = Some(kind);
+    }
+
+    // At this point, want_kind is determined.
+    let want_kind = want_kind.expect("given or detected");
+
+    let mut output = config.create_or_stdout_pgp(output, false, want_kind)?;
+
+    if already_armored {
+        // Dearmor and copy to change the type.
+        let mut reader =
+            armor::Reader::new(input,
+                               armor::ReaderMode::Tolerant(None));
+        io::copy(&mut reader, &mut output)?;
+    } else {
+        io::copy(&mut input, &mut output)?;
+    }
+    output.finalize()?;
+    Ok(())
+}
+
 // Decrypts a key, if possible.
 //
 // The passwords in `passwords` are tried first.  If the key can't be
@@ -345,11 +495,19 @@ fn emit_unstable_cli_warning() {
 pub struct Config<'a> {
     force: bool,
     policy: P<'a>,
+    output_format: OutputFormat,
+    /// The location of the certificate store, if enabled.
+    cert_store: Option<PathBuf>,
     /// Have we emitted the warning yet?
     unstable_cli_warning_emitted: bool,
 }
 
 impl Config<'_> {
+    /// Returns the certificate store, if enabled.
+    fn cert_store(&self) -> Option<CertStore> {
+        self.cert_store.as_ref().map(CertStore::open)
+    }
+
     /// Opens the file (or stdout) for writing data that is safe for
     /// non-interactive use.
     ///
@@ -394,6 +552,29 @@ fn main() -> Result<()> {
     let policy = &mut P::new();
 
     env_logger::init();
+
+    // Invoked as `sq-gpg`, act like the subset of gpg that git uses.
+    if commands::gpg::invoked_as_gpg() {
+        return commands::gpg::dispatch(Config {
+            force: false,
+            policy: policy.clone(),
+            output_format: OutputFormat::HumanReadable,
+            cert_store: None,
+            unstable_cli_warning_emitted: false,
+        });
+    }
+
+    // Invoked as `sq-sop`, implement the Stateless OpenPGP CLI.
+    if commands::sop::invoked_as_sop() {
+        return commands::sop::dispatch(Config {
+            force: false,
+            policy: policy.clone(),
+            output_format: OutputFormat::HumanReadable,
+            cert_store: None,
+            unstable_cli_warning_emitted: false,
+        });
+    }
+
     let matches = sq_cli::build().get_matches();
 
     let known_notations: Vec<&str> = matches.values_of("known-notation")
@@ -402,10 +583,21 @@ fn main() -> Result<()> {
     policy.good_critical_notations(&known_notations);
 
     let force = matches.is_present("force");
+    let output_format = matches.value_of("output-format")
+        .expect("has default").parse()?;
+
+    let cert_store = if matches.is_present("no-cert-store") {
+        None
+    } else {
+        matches.value_of("cert-store").map(PathBuf::from)
+            .or_else(CertStore::default_location)
+    };
 
     let mut config = Config {
         force,
         policy: policy.clone(),
+        output_format,
+        cert_store,
         unstable_cli_warning_emitted: false,
     };
 
@@ -415,7 +607,7 @@ fn main() -> Result<()> {
             let mut output =
                 config.create_or_stdout_safe(m.value_of("output"))?;
             let certs = m.values_of("sender-cert-file")
-                .map(load_certs)
+                .map(|names| resolve_certs(&config, names))
                 .unwrap_or_else(|| Ok(vec![]))?;
             // Fancy default for --signatures.  If you change this,
             // also change the description in the CLI definition.
@@ -432,39 +624,78 @@ fn main() -> Result<()> {
                     // valid signature.
                     1
                 };
-            let mut secrets = m.values_of("secret-key-file")
-                .map(load_keys)
-                .unwrap_or_else(|| Ok(vec![]))?;
-            if let Some(name) = m.value_of("dsm-key") {
+            let mut secrets = load_secrets(m, "secret-key-file")?;
+            let mut dsm_auto = None;
+            if m.is_present("dsm-key") || m.is_present("dsm-auto") {
                 // Fortanix DSM
-                let dsm_secret = Auth::from_options_or_env(
-                    m.value_of("api-key"),
-                    m.value_of("client-cert"),
-                    m.value_of("app-uuid"),
-                    m.value_of("pkcs12-passphrase"),
-                )?;
+                let dsm_secret = dsm_auth(m)?;
                 let dsm_auth = Credentials::new(dsm_secret)?;
-                secrets.push(PreSecret::Dsm(dsm_auth, name.to_string()));
+                for name in m.values_of("dsm-key").into_iter().flatten() {
+                    secrets.push(PreSecret::Dsm(dsm_auth.clone(),
+                                                name.to_string()));
+                }
+                if m.is_present("dsm-auto") {
+                    dsm_auto = Some(dsm_auth);
+                }
             }
             let private_key_store = m.value_of("private-key-store");
             commands::decrypt(config, private_key_store,
                               &mut input, &mut output,
-                              signatures, certs, secrets,
+                              signatures, certs, secrets, dsm_auto,
                               m.is_present("dump-session-key"),
                               m.is_present("dump"), m.is_present("hex"))?;
         },
         ("encrypt",  Some(m)) => {
-            let recipients = m.values_of("recipients-cert-file")
-                .map(load_certs)
+            let mut recipients = m.values_of("recipients-cert-file")
+                .map(|names| resolve_certs(&config, names))
                 .unwrap_or_else(|| Ok(vec![]))?;
+            if m.is_present("recipient-email")
+                || m.is_present("recipient-userid")
+                || m.is_present("recipient-fingerprint")
+            {
+                let keyrings = m.values_of("keyring")
+                    .map(load_certs)
+                    .unwrap_or_else(|| Ok(vec![]))?;
+                let lookup = commands::recipients::Lookup::new(
+                    &config.policy, keyrings, config.cert_store())?;
+                #[cfg(feature = "net")]
+                let lookup = lookup.wkd(m.is_present("wkd"));
+                #[cfg(feature = "net")]
+                let lookup = match m.value_of("keyserver") {
+                    Some(uri) => lookup.keyserver(uri)?,
+                    None => lookup,
+                };
+                let mut lookup = lookup;
+
+                let mut found = Vec::new();
+                for email in m.values_of("recipient-email").into_iter().flatten() {
+                    found.push(lookup.by_email(email)?);
+                }
+                for userid in m.values_of("recipient-userid").into_iter().flatten() {
+                    found.push(lookup.by_userid(userid)?);
+                }
+                for fp in m.values_of("recipient-fingerprint")
+                    .into_iter().flatten()
+                {
+                    found.push(lookup.by_fingerprint(fp)?);
+                }
+
+                // Certificates given by file are used as is, those that
+                // were looked up must be usable.
+                for cert in &found {
+                    commands::recipients::check(
+                        cert, &config.policy,
+                        m.is_present("use-expired-recipient"),
+                        m.is_present("use-revoked-recipient"))?;
+                }
+                recipients.append(&mut found);
+            }
             let mut input = open_or_stdin(m.value_of("input"))?;
             let output =
                 config.create_or_stdout_pgp(m.value_of("output"),
                                             m.is_present("binary"),
                                             armor::Kind::Message)?;
-            let mut additional_secrets = m.values_of("signer-key-file")
-                .map(load_keys)
-                .unwrap_or_else(|| Ok(vec![]))?;
+            let mut additional_secrets = load_secrets(m, "signer-key-file")?;
             let mode = match m.value_of("mode").expect("has default") {
                 "rest" => KeyFlags::empty()
                     .set_storage_encryption(),
@@ -483,17 +714,29 @@ fn main() -> Result<()> {
                 None
             };
             let private_key_store = m.value_of("private-key-store");
-            if let Some(name) = m.value_of("signer-dsm-key") {
+            let mut dsm_wrapping_keys = Vec::new();
+            if m.is_present("signer-dsm-key")
+                || m.is_present("symmetric-dsm-key")
+                || m.is_present("recipient-dsm-key")
+            {
                 // Fortanix DSM
-                let dsm_secret = Auth::from_options_or_env(
-                    m.value_of("api-key"),
-                    m.value_of("client-cert"),
-                    m.value_of("app-uuid"),
-                    m.value_of("pkcs12-passphrase"),
-                )?;
+                let dsm_secret = dsm_auth(m)?;
                 let dsm_auth = Credentials::new(dsm_secret)?;
-                additional_secrets
-                    .push(secrets::PreSecret::Dsm(dsm_auth, name.to_string()));
+                if let Some(name) = m.value_of("signer-dsm-key") {
+                    additional_secrets.push(secrets::PreSecret::Dsm(
+                        dsm_auth.clone(), name.to_string()));
+                }
+                for name in m.values_of("symmetric-dsm-key")
+                    .into_iter().flatten()
+                {
+                    dsm_wrapping_keys.push((dsm_auth.clone(), name.to_string()));
+                }
+                for name in m.values_of("recipient-dsm-key")
+                    .into_iter().flatten()
+                {
+                    recipients.push(
+                        openpgp_dsm::extract_cert(name, dsm_auth.clone())?);
+                }
             }
             commands::encrypt(commands::EncryptOpts {
                 policy,
@@ -501,25 +744,31 @@ fn main() -> Result<()> {
                 input: &mut input,
                 message: output,
                 npasswords: m.occurrences_of("symmetric") as usize,
+                passwords: Vec::new(),
                 recipients: &recipients,
+                dsm_wrapping_keys,
                 signers: additional_secrets,
                 mode,
                 compression: m.value_of("compression").expect("has default"),
                 time,
                 use_expired_subkey: m.is_present("use-expired-subkey"),
+                use_expired_recipient: m.is_present("use-expired-recipient"),
+                text: false,
             })?;
         },
         ("sign",  Some(m)) => {
-            let mut input = open_or_stdin(m.value_of("input"))?;
+            let batch = m.is_present("batch");
+            if ! batch && m.occurrences_of("input") > 1 {
+                return Err(anyhow::anyhow!(
+                    "Multiple inputs are only supported with --batch"));
+            }
             let output = m.value_of("output");
             let detached = m.is_present("detached");
             let binary = m.is_present("binary");
             let append = m.is_present("append");
             let notarize = m.is_present("notarize");
             let private_key_store = m.value_of("private-key-store");
-            let mut secrets = m.values_of("secret-key-file")
-                .map(load_keys)
-                .unwrap_or_else(|| Ok(vec![]))?;
+            let mut secrets = load_secrets(m, "secret-key-file")?;
             let time = if let Some(time) = m.value_of("time") {
                 Some(parse_iso8601(time, chrono::NaiveTime::from_hms(0, 0, 0))
                          .context(format!("Bad value passed to --time: {:?}",
@@ -552,25 +801,63 @@ fn main() -> Result<()> {
 
             if let Some(name) = m.value_of("dsm-key") {
                 // Fortanix DSM
-                let dsm_secret = Auth::from_options_or_env(
-                    m.value_of("api-key"),
-                    m.value_of("client-cert"),
-                    m.value_of("app-uuid"),
-                    m.value_of("pkcs12-passphrase"),
-                )?;
+                let dsm_secret = dsm_auth(m)?;
                 let dsm_auth = Credentials::new(dsm_secret)?;
                 secrets.push(secrets::PreSecret::Dsm(dsm_auth, name.to_string()));
             }
-            if let Some(merge) = m.value_of("merge") {
+            if let Some(dir) = m.value_of("manifest") {
+                // Not a clap default, which would make --hash
+                // present, and hence require --manifest.
+                let algo = m.value_of("hash").unwrap_or("SHA256");
+                let algo: HashAlgorithm = algo.parse().map_err(
+                    |_| anyhow::anyhow!("Unknown hash algorithm {:?}", algo))?;
+                commands::manifest::check_algo(&config.policy, algo)?;
+                // Don't include the signed manifest itself.
+                let exclude: Vec<PathBuf> =
+                    output.map(PathBuf::from).into_iter().collect();
+                let manifest = commands::manifest::Manifest::from_dir(
+                    Path::new(dir), algo, &exclude)?;
+                let mut input = io::Cursor::new(manifest.to_vec());
+                commands::sign(commands::sign::SignOpts {
+                    config,
+                    private_key_store,
+                    input: &mut input,
+                    output_path: output,
+                    presecrets: secrets,
+                    detached: false,
+                    binary,
+                    append: false,
+                    notarize: false,
+                    time,
+                    notations: &notations
+                })?;
+            } else if batch {
+                let jobs = m.value_of("jobs").expect("has a default");
+                commands::sign::sign_batch(commands::sign::BatchSignOpts {
+                    config,
+                    inputs: m.values_of("input")
+                        .map(|v| v.map(PathBuf::from).collect())
+                        .unwrap_or_default(),
+                    presecrets: secrets,
+                    binary,
+                    jobs: jobs.parse().context(
+                        format!("Bad value passed to --jobs: {:?}", jobs))?,
+                    time,
+                    notations: &notations,
+                })?;
+            } else if let Some(merge) = m.value_of("merge") {
+                let mut input = open_or_stdin(m.value_of("input"))?;
                 let output = config.create_or_stdout_pgp(output, binary,
                                                          armor::Kind::Message)?;
                 let mut input2 = open_or_stdin(Some(merge))?;
                 commands::merge_signatures(&mut input, &mut input2, output)?;
             } else if m.is_present("clearsign") {
+                let input = open_or_stdin(m.value_of("input"))?;
                 let output = config.create_or_stdout_safe(output)?;
                 commands::sign::clearsign(config, private_key_store, input, output, secrets,
                                           time, &notations)?;
             } else {
+                let mut input = open_or_stdin(m.value_of("input"))?;
                 commands::sign(commands::sign::SignOpts {
                     config,
                     private_key_store,
@@ -588,8 +875,6 @@ fn main() -> Result<()> {
         },
         ("verify",  Some(m)) => {
             let mut input = open_or_stdin(m.value_of("input"))?;
-            let mut output =
-                config.create_or_stdout_safe(m.value_of("output"))?;
             let mut detached = if let Some(f) = m.value_of("detached") {
                 Some(File::open(f)?)
             } else {
@@ -597,63 +882,50 @@ fn main() -> Result<()> {
             };
             let signatures: usize =
                 m.value_of("signatures").expect("has a default").parse()?;
-            let certs = m.values_of("sender-cert-file")
-                .map(load_certs)
+            let explain = m.is_present("explain");
+            if let Some(time) = m.value_of("policy-as-of") {
+                let time: SystemTime =
+                    parse_iso8601(time, chrono::NaiveTime::from_hms(0, 0, 0))
+                    .context(format!("Bad value passed to --policy-as-of: {:?}",
+                                     time))?.into();
+                let mut policy = P::at(time);
+                policy.good_critical_notations(&known_notations);
+                config.policy = policy;
+            }
+            let mut certs = m.values_of("sender-cert-file")
+                .map(|names| resolve_certs(&config, names))
                 .unwrap_or_else(|| Ok(vec![]))?;
-            commands::verify(config, &mut input,
-                             detached.as_mut().map(|r| r as &mut (dyn io::Read + Sync + Send)),
-                             &mut output, signatures, certs)?;
-        },
-
-        ("armor",  Some(m)) => {
-            let input = open_or_stdin(m.value_of("input"))?;
-            let mut want_kind = parse_armor_kind(m.value_of("kind"));
-
-            // Peek at the data.  If it looks like it is armored
-            // data, avoid armoring it again.
-            let mut dup = Limitor::new(Dup::new(input), ARMOR_DETECTION_LIMIT);
-            let (already_armored, have_kind) = {
-                let mut reader =
-                    armor::Reader::new(&mut dup,
-                                       armor::ReaderMode::Tolerant(None));
-                (reader.data(8).is_ok(), reader.kind())
-            };
-            let mut input =
-                dup.as_boxed().into_inner().unwrap().into_inner().unwrap();
-
-            if already_armored
-                && (want_kind.is_none() || want_kind == have_kind)
-            {
-                // It is already armored and has the correct kind.
+            if let Some(names) = m.values_of("signer-dsm-key") {
+                // Fortanix DSM
+                let dsm_secret = dsm_auth(m)?;
+                let dsm_auth = Credentials::new(dsm_secret)?;
+                for name in names {
+                    certs.push(openpgp_dsm::extract_cert(name, dsm_auth.clone())?);
+                }
+            }
+            if let Some(dir) = m.value_of("manifest") {
+                let policy = config.policy.clone();
+                let mut manifest = Vec::new();
+                commands::verify(config, &mut input, None,
+                                 &mut manifest, signatures, certs, explain)?;
+                // Don't include the signed manifest itself.
+                let exclude: Vec<PathBuf> =
+                    m.value_of("input").map(PathBuf::from).into_iter().collect();
+                commands::manifest::check(&policy, Path::new(dir), &manifest,
+                                          &exclude)?;
+            } else {
                 let mut output =
                     config.create_or_stdout_safe(m.value_of("output"))?;
-                io::copy(&mut input, &mut output)?;
-                return Ok(());
+                commands::verify(config, &mut input,
+                                 detached.as_mut().map(|r| r as &mut (dyn io::Read + Sync + Send)),
+                                 &mut output, signatures, certs, explain)?;
             }
+        },
 
-            if want_kind.is_none() {
-                let (tmp, kind) = detect_armor_kind(input);
-                input = tmp;
-                want_kind = Some(kind);
-            }
-
-            // At this point, want_kind is determined.
-            let want_kind = want_kind.expect("given or detected");
-
-            let mut output =
-                config.create_or_stdout_pgp(m.value_of("output"),
-                                            false, want_kind)?;
-
-            if already_armored {
-                // Dearmor and copy to change the type.
-                let mut reader =
-                    armor::Reader::new(input,
-                                       armor::ReaderMode::Tolerant(None));
-                io::copy(&mut reader, &mut output)?;
-            } else {
-                io::copy(&mut input, &mut output)?;
-            }
-            output.finalize()?;
+        ("armor",  Some(m)) => {
+            let input = open_or_stdin(m.value_of("input"))?;
+            armor(&config, input, m.value_of("output"),
+                  parse_armor_kind(m.value_of("kind")))?;
         },
         ("dearmor",  Some(m)) => {
             let mut input = open_or_stdin(m.value_of("input"))?;
@@ -666,28 +938,68 @@ fn main() -> Result<()> {
         ("autocrypt", Some(m)) => commands::autocrypt::dispatch(config, m)?,
 
         ("inspect",  Some(m)) => {
-            let mut output =
-                config.create_or_stdout_unsafe(m.value_of("output"))?;
-            commands::inspect(m, policy, &mut output)?;
+            // The JSON schema is stable, the text is not.
+            let mut output = match config.output_format {
+                OutputFormat::Json =>
+                    config.create_or_stdout_safe(m.value_of("output"))?,
+                OutputFormat::HumanReadable =>
+                    config.create_or_stdout_unsafe(m.value_of("output"))?,
+            };
+            commands::inspect(m, policy, config.output_format, &mut output)?;
         },
 
         ("keyring", Some(m)) => commands::keyring::dispatch(config, m)?,
+        ("import", Some(m)) => commands::store::import(config, m)?,
+        ("export", Some(m)) => commands::store::export(config, m)?,
+        ("lookup", Some(m)) => commands::store::lookup(config, m)?,
 
         ("packet", Some(m)) => match m.subcommand() {
             ("dump",  Some(m)) => {
                 let mut input = open_or_stdin(m.value_of("input"))?;
-                let mut output =
-                    config.create_or_stdout_unsafe(m.value_of("output"))?;
+                // The JSON schema is stable, the text is not.
+                let json = config.output_format == OutputFormat::Json;
+                let mut output = if json {
+                    config.create_or_stdout_safe(m.value_of("output"))?
+                } else {
+                    config.create_or_stdout_unsafe(m.value_of("output"))?
+                };
                 let session_key: Option<openpgp::crypto::SessionKey> =
                     if let Some(sk) = m.value_of("session-key") {
                         Some(hex::decode_pretty(sk)?.into())
                     } else {
                         None
                     };
+                let paths = m.values_of("packet").into_iter().flatten()
+                    .map(commands::dump::parse_path)
+                    .collect::<Result<Vec<_>>>()?;
+
+                let mut secrets = m.values_of("recipient-key")
+                    .map(load_keys)
+                    .unwrap_or_else(|| Ok(vec![]))?;
+                if let Some(name) = m.value_of("dsm-key") {
+                    // Fortanix DSM
+                    let dsm_secret = dsm_auth(m)?;
+                    let dsm_auth = Credentials::new(dsm_secret)?;
+                    secrets.push(PreSecret::Dsm(dsm_auth, name.to_string()));
+                }
+                let decryptor = if secrets.is_empty() {
+                    None
+                } else {
+                    Some(commands::decrypt::ContainerDecryptor::new(
+                        &config, secrets, false))
+                };
+
                 let width = term_size::dimensions_stdout().map(|(w, _)| w);
-                commands::dump(&mut input, &mut output,
-                               m.is_present("mpis"), m.is_present("hex"),
-                               session_key.as_ref(), width)?;
+                commands::dump(&mut input, &mut output, commands::DumpOpts {
+                    mpis: m.is_present("mpis"),
+                    hex: m.is_present("hex"),
+                    json,
+                    session_key: session_key.as_ref(),
+                    decryptor,
+                    paths,
+                    skip_literal_bodies: m.is_present("skip-literal-bodies"),
+                    width,
+                })?;
             },
 
             ("decrypt",  Some(m)) => {
@@ -701,12 +1013,7 @@ fn main() -> Result<()> {
                     .unwrap_or_else(|| Ok(vec![]))?;
                 if let Some(name) = m.value_of("dsm-key") {
                     // Fortanix DSM
-                    let dsm_secret = Auth::from_options_or_env(
-                        m.value_of("api-key"),
-                        m.value_of("client-cert"),
-                        m.value_of("app-uuid"),
-                        m.value_of("pkcs12-passphrase"),
-                    )?;
+                    let dsm_secret = dsm_auth(m)?;
                     let dsm_auth = Credentials::new(dsm_secret)?;
                     secrets.push(PreSecret::Dsm(dsm_auth, name.to_string()));
                 }
@@ -753,6 +1060,10 @@ fn main() -> Result<()> {
             commands::certify::certify(config, m)?;
         },
 
+        ("wot",  Some(m)) => commands::wot::dispatch(config, m)?,
+
+        ("revoke",  Some(m)) => commands::revoke::dispatch(config, m)?,
+
         _ => unreachable!(),
     }
 
@@ -38,9 +38,17 @@ pub fn configure(
         .long_about(
 "A command-line frontend for Sequoia, an implementation of OpenPGP
 
-Functionality is grouped and available using subcommands.  Currently,
-this interface is completely stateless.  Therefore, you need to supply
-all configuration and certificates explicitly on each invocation.
+Functionality is grouped and available using subcommands.  Apart from
+the certificate store, this interface is stateless.  Therefore, you
+need to supply all configuration and keys explicitly on each
+invocation.
+
+Certificates can be imported into a local certificate store using
+\"sq import\".  The store uses the shared OpenPGP certificate directory
+format.  When verifying signatures, certificates are looked up in the
+store.  Certificates that are named on the command line, but that are
+not files, are looked up in the store by their fingerprint.  Use \"sq
+lookup\" to find the fingerprint of a stored certificate.
 
 OpenPGP data can be provided in binary or ASCII armored form.  This
 will be handled automatically.  Emitted OpenPGP data is ASCII armored
@@ -65,6 +73,28 @@ to refer to OpenPGP keys that do contain secrets.
                This is used when validating signatures. \
                Signatures that have unknown notations with the \
                critical bit set are considered invalid."))
+        .arg(Arg::with_name("cert-store")
+             .long("cert-store").value_name("DIR")
+             .env("PGP_CERT_D")
+             .help("Uses the certificate store in DIR")
+             .long_help("Uses the certificate store in DIR.  By default, \
+               the certificate store is located in the platform's data \
+               directory, e.g. \"$HOME/.local/share/pgp.cert.d\"."))
+        .arg(Arg::with_name("no-cert-store")
+             .long("no-cert-store")
+             .help("Disables the certificate store"))
+        .arg(Arg::with_name("output-format")
+             .long("output-format").value_name("FORMAT")
+             .possible_values(&["human-readable", "json"])
+             .default_value("human-readable")
+             .help("Produces output in FORMAT")
+             .long_help("Produces output in FORMAT.  With \"json\", \
+               inspect, keyring list, keyring diff, verify, decrypt \
+               and packet dump emit JSON documents whose schema is \
+               versioned by their \"sq_output_version\" field.  The \
+               results of verify and decrypt are written to stderr, \
+               so that the verified or decrypted data can still be \
+               written to stdout."))
 
         .subcommand(SubCommand::with_name("decrypt")
                     .display_order(110)
@@ -101,6 +131,9 @@ $ sq decrypt --recipient-key juliet.pgp --signer-cert romeo.pgp ciphertext.pgp
 
 # Decrypt a file using a password
 $ sq decrypt ciphertext.pgp
+
+# Decrypt a file using a secret key in the gpg-agent
+$ sq decrypt --gpg-agent --recipient-key juliet.cert.pgp ciphertext.pgp
 ")
                     .arg(Arg::with_name("input")
                          .value_name("FILE")
@@ -120,11 +153,30 @@ $ sq decrypt ciphertext.pgp
                     .arg(Arg::with_name("sender-cert-file")
                          .long("signer-cert").value_name("CERT")
                          .multiple(true).number_of_values(1)
-                         .help("Verifies signatures with CERT"))
+                         .help("Verifies signatures with CERT")
+                         .long_help("Verifies signatures with CERT.  If \
+                              CERT is not a file, it is the fingerprint of \
+                              a certificate in the certificate store."))
                     .arg(Arg::with_name("secret-key-file")
                          .long("recipient-key").value_name("KEY")
                          .multiple(true).number_of_values(1)
                          .help("Decrypts with KEY"))
+                    .arg(Arg::with_name("gpg-agent")
+                         .long("gpg-agent")
+                         .requires("secret-key-file")
+                         .help("Decrypts using the secret keys in the \
+                                gpg-agent")
+                         .long_help(
+                             "Decrypts using the secret keys in the \
+                              gpg-agent.  The keys given using --recipient-key \
+                              may then be certificates without secret \
+                              key material, or the fingerprints or Key \
+                              IDs of certificates in the GnuPG keybox."))
+                    .arg(Arg::with_name("gpg-homedir")
+                         .long("gpg-homedir").value_name("DIR")
+                         .requires("gpg-agent")
+                         .help("Uses the gpg-agent of the GnuPG home \
+                                directory DIR"))
                     .arg(Arg::with_name("private-key-store")
                          .long("private-key-store").value_name("KEY_STORE")
                          .help("Provides parameters for private key store"))
@@ -153,10 +205,27 @@ $ sq decrypt ciphertext.pgp
                         .long("pkcs12-passphrase").value_name("PKCS12-PASSPHRASE")
                         .help("Passphrase for unlocking the PKCS12 identity file \
                         (cert-based authentication)"))
+                    .args(&dsm_auth_args())
                     .arg(Arg::with_name("dsm-key")
                         .long("dsm-key").value_name("DSM-KEY-NAME")
+                        .multiple(true).number_of_values(1)
                         .help("Decrypts with secrets stored inside the \
-                        Fortanix Self-Defending Key-Management System"))
+                        Fortanix Self-Defending Key-Management System")
+                        .long_help("Decrypts with secrets stored inside the \
+                        Fortanix Self-Defending Key-Management System.  \
+                        Session keys wrapped with a DSM AES key (see \
+                        \"sq encrypt --symmetric-dsm-key\") are unwrapped \
+                        with the same credentials."))
+                    .arg(Arg::with_name("dsm-auto")
+                        .long("dsm-auto")
+                        .help("Decrypts with any accessible key stored in \
+                        Fortanix DSM")
+                        .long_help("Decrypts with any accessible key stored \
+                        in Fortanix DSM, found by matching the recipients \
+                        of the message against the fingerprints of the \
+                        DSM keys.  The mapping is cached in \
+                        $XDG_CACHE_HOME/sq-dsm/recipients.json, and rebuilt \
+                        when a recipient is not found."))
         )
 
         .subcommand(SubCommand::with_name("encrypt")
@@ -168,6 +237,17 @@ $ sq decrypt ciphertext.pgp
 Encrypts a message for any number of recipients and with any number of
 passwords, optionally signing the message in the process.
 
+Recipients can be selected by email address, User ID, or fingerprint.
+Their certificates are looked up in the keyrings given using
+\"--keyring\", and in the certificate store.  If no certificate is
+found for an email address or fingerprint, they can also be looked up
+using WKD or a keyserver.  A recipient is only selected if the
+matching User ID is valid under the policy and not revoked.  If
+several certificates match, none is selected.
+
+Certificates that are revoked or expired are refused, unless
+\"--use-revoked-recipient\" or \"--use-expired-recipient\" is given.
+
 The converse operation is \"sq decrypt\".
 ")
                     .after_help(
@@ -179,8 +259,18 @@ $ sq encrypt --recipient-cert romeo.pgp message.txt
 # Encrypt a file creating a signature in the process
 $ sq encrypt --recipient-cert romeo.pgp --signer-key juliet.pgp message.txt
 
+# Encrypt a file for a recipient selected by email address
+$ sq encrypt --recipient-email romeo@example.org --keyring certs.pgp \\
+    message.txt
+
+# Encrypt a file using the certificate of a key stored in DSM
+$ sq encrypt --recipient-dsm-key romeo message.txt
+
 # Encrypt a file using a password
 $ sq encrypt --symmetric message.txt
+
+# Encrypt a file for the holders of an AES key stored in Fortanix DSM
+$ sq encrypt --symmetric-dsm-key shared-aes-key message.txt
 ")
                     .arg(Arg::with_name("input")
                          .value_name("FILE")
@@ -194,11 +284,73 @@ $ sq encrypt --symmetric message.txt
                     .arg(Arg::with_name("recipients-cert-file")
                          .long("recipient-cert").value_name("CERT-RING")
                          .multiple(true).number_of_values(1)
-                         .help("Encrypts for all recipients in CERT-RING"))
+                         .help("Encrypts for all recipients in CERT-RING")
+                         .long_help("Encrypts for all recipients in \
+                              CERT-RING.  If CERT-RING is not a file, it \
+                              is the fingerprint of a certificate in the \
+                              certificate store."))
+                    .arg(Arg::with_name("recipient-email")
+                         .long("recipient-email").value_name("EMAIL")
+                         .multiple(true).number_of_values(1)
+                         .help("Encrypts for the certificate with a User ID \
+                                containing EMAIL"))
+                    .arg(Arg::with_name("recipient-userid")
+                         .long("recipient-userid").value_name("USERID")
+                         .multiple(true).number_of_values(1)
+                         .help("Encrypts for the certificate with the User \
+                                ID USERID"))
+                    .arg(Arg::with_name("recipient-fingerprint")
+                         .long("recipient-fingerprint")
+                         .value_name("FINGERPRINT")
+                         .multiple(true).number_of_values(1)
+                         .help("Encrypts for the certificate with the \
+                                primary key or subkey FINGERPRINT"))
+                    .arg(Arg::with_name("keyring")
+                         .long("keyring").value_name("FILE")
+                         .multiple(true).number_of_values(1)
+                         .help("Looks up recipients in FILE"))
+                    .args(&if feature_net {
+                        vec![
+                            Arg::with_name("wkd")
+                                .long("wkd")
+                                .help("Looks up recipients by email address \
+                                       using WKD"),
+                            Arg::with_name("keyserver")
+                                .long("keyserver").value_name("URI")
+                                .help("Looks up recipients on the keyserver \
+                                       at URI")
+                                .long_help("Looks up recipients on the \
+                                       keyserver at URI, e.g. \
+                                       \"hkps://keys.openpgp.org\"."),
+                        ]
+                    } else {
+                        vec![]
+                    })
+                    .arg(Arg::with_name("recipient-dsm-key")
+                         .long("recipient-dsm-key").value_name("DSM-KEY-NAME")
+                         .multiple(true).number_of_values(1)
+                         .help("Encrypts for the certificate of a key stored \
+                                in Fortanix DSM"))
                     .arg(Arg::with_name("signer-key-file")
                          .long("signer-key").value_name("KEY")
                          .multiple(true).number_of_values(1)
                          .help("Signs the message with KEY"))
+                    .arg(Arg::with_name("gpg-agent")
+                         .long("gpg-agent")
+                         .requires("signer-key-file")
+                         .help("Signs using the secret keys in the \
+                                gpg-agent")
+                         .long_help(
+                             "Signs using the secret keys in the \
+                              gpg-agent.  The keys given using --signer-key \
+                              may then be certificates without secret \
+                              key material, or the fingerprints or Key \
+                              IDs of certificates in the GnuPG keybox."))
+                    .arg(Arg::with_name("gpg-homedir")
+                         .long("gpg-homedir").value_name("DIR")
+                         .requires("gpg-agent")
+                         .help("Uses the gpg-agent of the GnuPG home \
+                                directory DIR"))
                     .arg(Arg::with_name("private-key-store")
                          .long("private-key-store").value_name("KEY_STORE")
                          .help("Provides parameters for private key store"))
@@ -218,6 +370,7 @@ $ sq encrypt --symmetric message.txt
                         .long("pkcs12-passphrase").value_name("PKCS12-PASSPHRASE")
                         .help("Passphrase for unlocking the PKCS12 identity file \
                         (cert-based authentication)"))
+                    .args(&dsm_auth_args())
                     .arg(Arg::with_name("signer-dsm-key")
                          .long("signer-dsm-key").value_name("DSM-KEY-NAME")
                          .help("Signs the message with a key stored in Fortanix \
@@ -230,6 +383,16 @@ $ sq encrypt --symmetric message.txt
                                      The message can be decrypted with \
                                      either one of the recipient's keys, \
                                      or any password."))
+                    .arg(Arg::with_name("symmetric-dsm-key")
+                         .long("symmetric-dsm-key").value_name("DSM-KEY-NAME")
+                         .multiple(true).number_of_values(1)
+                         .help("Wraps the session key with an AES key stored \
+                                in Fortanix DSM")
+                         .long_help("Wraps the session key with an AES key \
+                                     stored in Fortanix DSM.  The message \
+                                     can be decrypted by anyone allowed to \
+                                     decrypt with that key, using \
+                                     \"sq decrypt --dsm-key\"."))
                     .arg(Arg::with_name("mode")
                          .long("mode").value_name("MODE")
                          .possible_values(&["transport", "rest", "all"])
@@ -260,6 +423,12 @@ $ sq encrypt --symmetric message.txt
                              "If a certificate has only expired \
                               encryption-capable subkeys, falls back \
                               to using the one that expired last"))
+                    .arg(Arg::with_name("use-expired-recipient")
+                         .long("use-expired-recipient")
+                         .help("Encrypts for expired certificates"))
+                    .arg(Arg::with_name("use-revoked-recipient")
+                         .long("use-revoked-recipient")
+                         .help("Encrypts for revoked certificates"))
         )
 
         .subcommand(SubCommand::with_name("sign")
@@ -281,10 +450,27 @@ $ sq sign --signer-key juliet.pgp message.txt
 
 # Create a detached signature
 $ sq sign --detached --signer-key juliet.pgp message.txt
+
+# Create a signed message using a key in the gpg-agent
+$ sq sign --gpg-agent --signer-key 0123456789ABCDEF message.txt
+
+# Create detached signatures for all files in a directory
+$ sq sign --batch --dsm-key=\"My key\" dist/
+
+# Sign the files listed on stdin, at most 16 at a time
+$ find dist -name '*.tar.gz' | sq sign --batch --jobs=16 --dsm-key=\"My key\"
+
+# Sign a manifest of all files in a directory tree
+$ sq sign --manifest dist/ --signer-key juliet.pgp -o dist.manifest
 ")
                     .arg(Arg::with_name("input")
                          .value_name("FILE")
-                         .help("Reads from FILE or stdin if omitted"))
+                         .multiple(true)
+                         .help("Reads from FILE or stdin if omitted")
+                         .long_help(
+                             "Reads from FILE or stdin if omitted.  With \
+                              --batch, signs the given files, and the \
+                              files in the given directories."))
                     .arg(Arg::with_name("output")
                          .short("o").long("output").value_name("FILE")
                          .help("Writes to FILE or stdout if omitted"))
@@ -297,6 +483,61 @@ $ sq sign --detached --signer-key juliet.pgp message.txt
                     .arg(Arg::with_name("detached")
                          .long("detached")
                          .help("Creates a detached signature"))
+                    .arg(Arg::with_name("batch")
+                         .long("batch")
+                         .conflicts_with_all(&[
+                             "output",
+                             "clearsign",
+                             "append",
+                             "notarize",
+                             "merge",
+                             "private-key-store",
+                         ])
+                         .help("Creates detached signatures next to each \
+                                input file")
+                         .long_help(
+                             "Creates detached signatures next to each \
+                              input file.  Directories are searched \
+                              recursively.  If no input is given, the \
+                              names of the files are read from stdin, one \
+                              per line.  The signatures are written to \
+                              FILE.asc, or FILE.sig with --binary.  The \
+                              signing keys are resolved once, and \
+                              failures are reported for each file."))
+                    .arg(Arg::with_name("jobs")
+                         .long("jobs").value_name("N")
+                         .default_value("8")
+                         .help("Signs up to N files concurrently with \
+                                --batch"))
+                    .arg(Arg::with_name("manifest")
+                         .long("manifest").value_name("DIR")
+                         .conflicts_with_all(&[
+                             "input",
+                             "batch",
+                             "detached",
+                             "clearsign",
+                             "append",
+                             "notarize",
+                             "merge",
+                         ])
+                         .help("Signs a manifest of the files in DIR")
+                         .long_help(
+                             "Signs a manifest of the files in DIR.  The \
+                              manifest lists the hash of every regular \
+                              file in the directory tree.  Symbolic links \
+                              are not followed, but their targets are \
+                              recorded.  If the output file is in \
+                              DIR, it is not included in the manifest.  \
+                              Use \"sq verify --manifest\" to check the \
+                              directory tree against the signed \
+                              manifest."))
+                    .arg(Arg::with_name("hash")
+                         .long("hash").value_name("ALGO")
+                         .requires("manifest")
+                         .possible_values(&["SHA256", "SHA384", "SHA512",
+                                            "SHA224"])
+                         .help("Hashes the files in the manifest using \
+                                ALGO [default: SHA256]"))
                     .arg(Arg::with_name("clearsign")
                          .long("cleartext-signature")
                          .conflicts_with_all(&[
@@ -330,6 +571,22 @@ $ sq sign --detached --signer-key juliet.pgp message.txt
                          .long("signer-key").value_name("KEY")
                          .multiple(true).number_of_values(1)
                          .help("Signs using KEY"))
+                    .arg(Arg::with_name("gpg-agent")
+                         .long("gpg-agent")
+                         .requires("secret-key-file")
+                         .help("Signs using the secret keys in the \
+                                gpg-agent")
+                         .long_help(
+                             "Signs using the secret keys in the \
+                              gpg-agent.  The keys given using --signer-key \
+                              may then be certificates without secret \
+                              key material, or the fingerprints or Key \
+                              IDs of certificates in the GnuPG keybox."))
+                    .arg(Arg::with_name("gpg-homedir")
+                         .long("gpg-homedir").value_name("DIR")
+                         .requires("gpg-agent")
+                         .help("Uses the gpg-agent of the GnuPG home \
+                                directory DIR"))
                     .arg(Arg::with_name("api-key")
                         .long("api-key").value_name("API-KEY")
                         .help("Authenticates to Fortanix DSM using the given \
@@ -346,6 +603,7 @@ $ sq sign --detached --signer-key juliet.pgp message.txt
                         .long("pkcs12-passphrase").value_name("PKCS12-PASSPHRASE")
                         .help("Passphrase for unlocking the PKCS12 identity file \
                         (cert-based authentication)"))
+                    .args(&dsm_auth_args())
                     .arg(Arg::with_name("dsm-key")
                         .long("dsm-key").value_name("DSM-KEY-NAME")
                         .help("Signs the message with the Fortanix DSM key"))
@@ -391,6 +649,11 @@ In addition to that, the last 25 MiB of the message are withheld,
 i.e. if the message is smaller than 25 MiB, no output is produced, and
 if it is larger, then the output will be truncated.
 
+Signatures by keys that are not given using \"--signer-cert\" are
+checked using certificates from the certificate store.  Because these
+certificates are not authenticated, such signatures are only reported
+as good checksums, and do not count towards the threshold.
+
 The converse operation is \"sq sign\".
 ")
                     .after_help(
@@ -402,6 +665,18 @@ $ sq verify --signer-cert juliet.pgp signed-message.pgp
 # Verify a detached message
 $ sq verify --signer-cert juliet.pgp --detached message.sig message.txt
 
+# Verify a signed message with the certificate of a key stored in DSM
+$ sq verify --signer-dsm-key juliet signed-message.pgp
+
+# Verify a directory tree against a signed manifest
+$ sq verify --signer-cert juliet.pgp --manifest dist/ dist.manifest
+
+# Explain why a signature made in 2010 using SHA-1 is rejected, and
+# check it under the policy in effect back then
+$ sq verify --signer-cert juliet.pgp --explain signed-message.pgp
+$ sq verify --signer-cert juliet.pgp --policy-as-of 2010-01-01 \\
+     signed-message.pgp
+
 SEE ALSO:
 
 If you are looking for a standalone program to verify detached
@@ -416,6 +691,16 @@ signatures, consider using sequoia-sqv.
                     .arg(Arg::with_name("detached")
                          .long("detached").value_name("SIG")
                          .help("Verifies a detached signature"))
+                    .arg(Arg::with_name("manifest")
+                         .long("manifest").value_name("DIR")
+                         .conflicts_with_all(&["detached", "output"])
+                         .help("Checks DIR against the signed manifest")
+                         .long_help(
+                             "Checks DIR against the signed manifest \
+                              created by \"sq sign --manifest\".  Files \
+                              that were added, removed, or modified are \
+                              reported, and the verification fails if \
+                              there are any."))
                     .arg(Arg::with_name("signatures")
                          .short("n").long("signatures").value_name("N")
                          .default_value("1")
@@ -424,10 +709,62 @@ signatures, consider using sequoia-sqv.
                              "Sets the threshold of valid signatures to N. \
                               If this threshold is not reached, the message \
                               will not be considered verified."))
+                    .arg(Arg::with_name("explain")
+                         .long("explain")
+                         .help("Explains why signatures failed to verify")
+                         .long_help(
+                             "Explains why signatures failed to verify.  \
+                              For every signature that does not check \
+                              out, prints which component failed (the \
+                              signature, the signing key, its binding, \
+                              or the certificate), and why, e.g. because \
+                              the policy rejects an algorithm, something \
+                              expired or was revoked, a backsig is \
+                              missing, or the signature does not match \
+                              the data."))
+                    .arg(Arg::with_name("policy-as-of")
+                         .long("policy-as-of").value_name("TIME")
+                         .help("Evaluates signatures under the policy \
+                                in effect at TIME")
+                         .long_help(
+                             "Evaluates signatures under the policy in \
+                              effect at TIME.  Algorithms are phased out \
+                              over time, e.g. SHA-1 is rejected for most \
+                              purposes since 2013.  This option can be \
+                              used to check old signatures using the \
+                              algorithms considered secure back then.  \
+                              TIME is interpreted as an ISO 8601 \
+                              timestamp.  To use a date in UTC, use \
+                              \"YYYY-MM-DD\"."))
                     .arg(Arg::with_name("sender-cert-file")
                          .long("signer-cert").value_name("CERT")
                          .multiple(true).number_of_values(1)
-                         .help("Verifies signatures with CERT"))
+                         .help("Verifies signatures with CERT")
+                         .long_help("Verifies signatures with CERT.  If \
+                              CERT is not a file, it is the fingerprint of \
+                              a certificate in the certificate store."))
+                    .arg(Arg::with_name("signer-dsm-key")
+                         .long("signer-dsm-key").value_name("DSM-KEY-NAME")
+                         .multiple(true).number_of_values(1)
+                         .help("Verifies signatures with the certificate of \
+                                a key stored in Fortanix DSM"))
+                    .arg(Arg::with_name("api-key")
+                        .long("api-key").value_name("API-KEY")
+                        .help("Authenticates to Fortanix DSM using the given \
+                               API key"))
+                    .arg(Arg::with_name("client-cert")
+                        .long("client-cert").value_name("P12-FILE")
+                        .help("Authenticates to Fortanix DSM with the given client \
+                               certificate"))
+                    .arg(Arg::with_name("app-uuid")
+                        .long("app-uuid").value_name("APP-UUID")
+                        .help("Authenticates to Fortanix DSM with the given App  \
+                        (cert-based authentication)"))
+                    .arg(Arg::with_name("pkcs12-passphrase")
+                        .long("pkcs12-passphrase").value_name("PKCS12-PASSPHRASE")
+                        .help("Passphrase for unlocking the PKCS12 identity file \
+                        (cert-based authentication)"))
+                    .args(&dsm_auth_args())
         )
 
         .subcommand(SubCommand::with_name("armor")
@@ -622,6 +959,7 @@ $ sq key generate --userid \"<juliet@example.org>\" --userid \"Juliet Capulet\"
                             .long("pkcs12-passphrase").value_name("PKCS12-PASSPHRASE")
                             .help("Passphrase for unlocking the PKCS12 identity file \
                                    (cert-based authentication)"))
+                        .args(&dsm_auth_args())
                         .arg(Arg::with_name("dsm-exportable")
                             .long("dsm-exportable")
                             .help("(DANGER) Configure the key to be exportable from DSM"))
@@ -792,11 +1130,61 @@ $ sq key extract-cert --output juliet.cert.pgp juliet.key.pgp
                                 .long("pkcs12-passphrase").value_name("PKCS12-PASSPHRASE")
                                 .help("Passphrase for unlocking the PKCS12 identity file \
                                        (cert-based authentication)"))
+                            .args(&dsm_auth_args())
                             .arg(Arg::with_name("dsm-key")
                                 .long("dsm-key").value_name("DSM-KEY-NAME")
                                 .help("Extracts the certificate from Fortanix \
                                        DSM"))
                             )
+                .subcommand(SubCommand::with_name("export-ssh")
+                            .display_order(112)
+                            .about("Exports authentication keys in SSH format")
+                            .long_about(
+"Exports authentication keys in SSH format
+
+Prints a line in the format of OpenSSH's authorized_keys file for every
+valid key of the certificate that is flagged for authentication.  The
+comment is the certificate's primary user ID.
+
+The certificate is read from a file, or from Fortanix DSM if --dsm-key
+is given.
+")
+                            .after_help(
+                                "EXAMPLES:
+
+# Allow logging in using the authentication key of \"My key\"
+$ sq key export-ssh --dsm-key=\"My key\" >> ~/.ssh/authorized_keys
+
+# Export the authentication keys of a certificate
+$ sq key export-ssh juliet.cert.pgp
+")
+                            .arg(Arg::with_name("input")
+                                 .value_name("FILE")
+                                 .help("Reads from FILE or stdin if omitted"))
+                            .arg(Arg::with_name("output")
+                                 .short("o").long("output").value_name("FILE")
+                                 .help("Writes to FILE or stdout if omitted"))
+                            .arg(Arg::with_name("api-key")
+                                .long("api-key").value_name("API-KEY")
+                                .help("Authenticates to Fortanix DSM using the \
+                                       given API key"))
+                            .arg(Arg::with_name("client-cert")
+                                .long("client-cert").value_name("P12-FILE")
+                                .help("Authenticates to Fortanix DSM with the given \
+                                       client certificate"))
+                            .arg(Arg::with_name("app-uuid")
+                                .long("app-uuid").value_name("APP-UUID")
+                                .help("Authenticates to Fortanix DSM with the given App \
+                                       (cert-based authentication)"))
+                            .arg(Arg::with_name("pkcs12-passphrase")
+                                .long("pkcs12-passphrase").value_name("PKCS12-PASSPHRASE")
+                                .help("Passphrase for unlocking the PKCS12 identity file \
+                                       (cert-based authentication)"))
+                            .args(&dsm_auth_args())
+                            .arg(Arg::with_name("dsm-key")
+                                .long("dsm-key").value_name("DSM-KEY-NAME")
+                                .help("Reads the certificate from Fortanix DSM"))
+                            )
                 .subcommand(SubCommand::with_name("extract-dsm-secret")
                             .display_order(111)
                             .about("Extracts a secret key from Fortanix DSM")
@@ -822,6 +1210,7 @@ command exfiltrates secrets from DSM and outputs a Key.
                                 .long("pkcs12-passphrase").value_name("PKCS12-PASSPHRASE")
                                 .help("Passphrase for unlocking the PKCS12 identity file \
                                        (cert-based authentication)"))
+                            .args(&dsm_auth_args())
                             .arg(Arg::with_name("dsm-key")
                                 .long("dsm-key").value_name("DSM-KEY-NAME")
                                 .required(true)
@@ -863,6 +1252,7 @@ $ sq-dsm key dsm-import --dsm-key=\"Imported by sq-dsm\" < my_priv_key.asc
                                 .long("pkcs12-passphrase").value_name("PKCS12-PASSPHRASE")
                                 .help("Passphrase for unlocking the PKCS12 identity file \
                                        (cert-based authentication)"))
+                            .args(&dsm_auth_args())
                             .arg(Arg::with_name("dsm-key")
                                 .long("dsm-key").value_name("DSM-KEY-NAME")
                                 .required(true)
@@ -874,6 +1264,186 @@ $ sq-dsm key dsm-import --dsm-key=\"Imported by sq-dsm\" < my_priv_key.asc
                                  .long("input").value_name("FILE")
                                  .help("Reads from FILE or stdin if omitted"))
                             )
+                .subcommand(SubCommand::with_name("dsm-disable")
+                            .display_order(113)
+                            .about("Disables or deactivates a key stored in Fortanix DSM")
+                            .long_about(
+"Disables or deactivates a key stored in Fortanix DSM
+
+This command disables the Security Object of the primary key, and the
+Security Objects of all subkeys linked to it.  Disabled keys can be
+enabled again in DSM.  With `--deactivate`, the deactivation date of
+the Security Objects is set to now instead.
+
+A summary of the affected Security Objects is printed, and confirmation
+is requested, before any change is made.
+")
+                            .after_help(
+                                "EXAMPLES:
+
+# Disable the key and all its subkeys
+$ sq key dsm-disable --dsm-key=\"My key\"
+
+# Deactivate the key and all its subkeys
+$ sq key dsm-disable --deactivate --dsm-key=\"My key\"
+")
+                            .arg(Arg::with_name("api-key")
+                                .long("api-key").value_name("API-KEY")
+                                .help("Authenticates to Fortanix DSM using the \
+                                       given API key"))
+                            .arg(Arg::with_name("client-cert")
+                                .long("client-cert").value_name("P12-FILE")
+                                .help("Authenticates to Fortanix DSM with the given client \
+                                   certificate"))
+                            .arg(Arg::with_name("app-uuid")
+                                .long("app-uuid").value_name("APP-UUID")
+                                .help("Authenticates to Fortanix DSM with the given App  \
+                                       (cert-based authentication)"))
+                            .arg(Arg::with_name("pkcs12-passphrase")
+                                .long("pkcs12-passphrase").value_name("PKCS12-PASSPHRASE")
+                                .help("Passphrase for unlocking the PKCS12 identity file \
+                                       (cert-based authentication)"))
+                            .args(&dsm_auth_args())
+                            .arg(Arg::with_name("dsm-key")
+                                .long("dsm-key").value_name("DSM-KEY-NAME")
+                                .required(true)
+                                .help("Name of the DSM key"))
+                            .arg(Arg::with_name("deactivate")
+                                .long("deactivate")
+                                .help("Deactivates the key instead of disabling it"))
+                            .arg(Arg::with_name("yes")
+                                .long("yes")
+                                .help("Does not ask for confirmation"))
+                            )
+                .subcommand(SubCommand::with_name("dsm-delete")
+                            .display_order(114)
+                            .about("Deletes a key stored in Fortanix DSM")
+                            .long_about(
+"Deletes a key stored in Fortanix DSM
+
+This command destroys the Security Object of the primary key, and the
+Security Objects of all subkeys linked to it.  This cannot be undone:
+messages encrypted to the key can no longer be decrypted.
+
+A summary of the affected Security Objects is printed, and confirmation
+is requested, before any change is made.
+")
+                            .after_help(
+                                "EXAMPLES:
+
+# Delete the key and all its subkeys
+$ sq key dsm-delete --dsm-key=\"My key\"
+")
+                            .arg(Arg::with_name("api-key")
+                                .long("api-key").value_name("API-KEY")
+                                .help("Authenticates to Fortanix DSM using the \
+                                       given API key"))
+                            .arg(Arg::with_name("client-cert")
+                                .long("client-cert").value_name("P12-FILE")
+                                .help("Authenticates to Fortanix DSM with the given client \
+                                   certificate"))
+                            .arg(Arg::with_name("app-uuid")
+                                .long("app-uuid").value_name("APP-UUID")
+                                .help("Authenticates to Fortanix DSM with the given App  \
+                                       (cert-based authentication)"))
+                            .arg(Arg::with_name("pkcs12-passphrase")
+                                .long("pkcs12-passphrase").value_name("PKCS12-PASSPHRASE")
+                                .help("Passphrase for unlocking the PKCS12 identity file \
+                                       (cert-based authentication)"))
+                            .args(&dsm_auth_args())
+                            .arg(Arg::with_name("dsm-key")
+                                .long("dsm-key").value_name("DSM-KEY-NAME")
+                                .required(true)
+                                .help("Name of the DSM key"))
+                            .arg(Arg::with_name("yes")
+                                .long("yes")
+                                .help("Does not ask for confirmation"))
+                            )
+                .subcommand(SubCommand::with_name("dsm-check")
+                            .display_order(115)
+                            .about("Checks the consistency of keys stored in \
+                                    Fortanix DSM")
+                            .long_about(
+"Checks the consistency of keys stored in Fortanix DSM
+
+The certificate of a PGP key is stored in the metadata of the Security
+Object of its primary key.  This command checks that the Security
+Objects still match it: fingerprints are recomputed from the key
+material and compared with the metadata and the certificate, links
+between the primary key and the subkeys are checked in both directions,
+and binding signatures are validated.
+
+Without --dsm-key, all accessible PGP keys are checked.
+
+With --repair, wrong fingerprints and key flags in the metadata, and
+missing links from subkeys to the primary key, are fixed.  Other
+problems are only reported.
+
+The exit status is non-zero if unrepaired problems are found.
+")
+                            .after_help(
+                                "EXAMPLES:
+
+# Check all keys
+$ sq key dsm-check
+
+# Check a key, and repair it
+$ sq key dsm-check --repair --dsm-key=\"My key\"
+")
+                            .arg(Arg::with_name("api-key")
+                                .long("api-key").value_name("API-KEY")
+                                .help("Authenticates to Fortanix DSM using the \
+                                       given API key"))
+                            .arg(Arg::with_name("client-cert")
+                                .long("client-cert").value_name("P12-FILE")
+                                .help("Authenticates to Fortanix DSM with the given client \
+                                   certificate"))
+                            .arg(Arg::with_name("app-uuid")
+                                .long("app-uuid").value_name("APP-UUID")
+                                .help("Authenticates to Fortanix DSM with the given App  \
+                                       (cert-based authentication)"))
+                            .arg(Arg::with_name("pkcs12-passphrase")
+                                .long("pkcs12-passphrase").value_name("PKCS12-PASSPHRASE")
+                                .help("Passphrase for unlocking the PKCS12 identity file \
+                                       (cert-based authentication)"))
+                            .args(&dsm_auth_args())
+                            .arg(Arg::with_name("dsm-key")
+                                .long("dsm-key").value_name("DSM-KEY-NAME")
+                                .help("Checks only the given DSM key"))
+                            .arg(Arg::with_name("repair")
+                                .long("repair")
+                                .help("Repairs the problems that can be \
+                                       repaired"))
+                            )
+                .subcommand(SubCommand::with_name("dsm-audit-verify")
+                            .display_order(116)
+                            .about("Verifies the hash chain of a DSM audit log")
+                            .long_about(
+"Verifies the hash chain of a DSM audit log
+
+If the SQ_DSM_AUDIT_LOG environment variable is set, every operation
+performed in Fortanix DSM is recorded in the file it names.  Each record
+contains the SHA-256 hash of the previous one.  This command checks
+that the chain is intact, i.e., that no record has been modified,
+inserted, or removed, except at the end of the log.
+
+The exit status is non-zero if the chain is broken.
+")
+                            .after_help(
+                                "EXAMPLES:
+
+# Verify the audit log named by SQ_DSM_AUDIT_LOG
+$ sq key dsm-audit-verify
+
+# Verify a given audit log
+$ sq key dsm-audit-verify audit.log
+")
+                            .arg(Arg::with_name("log")
+                                .value_name("FILE")
+                                .help("Reads the audit log from FILE, or \
+                                       the file named by SQ_DSM_AUDIT_LOG \
+                                       if omitted"))
+                            )
                 .subcommand(
                     SubCommand::with_name("adopt")
                         .display_order(800)
@@ -962,6 +1532,117 @@ $ sq key list-dsm-keys -l
                              .help("prints long details of key")
                             )
                 )
+                .subcommand(
+                    SubCommand::with_name("gpg-agent")
+                        .display_order(410)
+                        .about("Serves DSM keys via the gpg-agent protocol")
+                        .long_about(
+"
+Serves DSM keys via the gpg-agent protocol
+
+This command listens on the given socket, and serves the primary key
+and the subkeys of the given DSM keys to clients speaking gpg-agent's
+Assuan protocol, like GnuPG.  The secret key operations are carried out
+in Fortanix DSM.  Keys are identified by their keygrip.
+
+For GnuPG to use the keys, the certificates must be imported into its
+keyring, and GnuPG must be pointed to the socket, e.g. by placing it at
+the location reported by `gpgconf --list-dirs agent-socket`.
+
+The command runs until interrupted.
+")
+                        .after_help(
+"EXAMPLES:
+
+# Serve the key \"My key\" to GnuPG
+$ sq key extract-cert --dsm-key=\"My key\" | gpg --import
+$ gpgconf --kill gpg-agent
+$ sq --force key gpg-agent --dsm-key=\"My key\" \\
+     --socket=$(gpgconf --list-dirs agent-socket)
+")
+                        .arg(Arg::with_name("api-key")
+                             .long("api-key").value_name("API-KEY")
+                             .help("Authenticates to Fortanix DSM using the \
+                                    given API key"))
+                        .arg(Arg::with_name("client-cert")
+                             .long("client-cert").value_name("P12-FILE")
+                             .help("Authenticates to Fortanix DSM with the given client \
+                                   certificate"))
+                        .arg(Arg::with_name("app-uuid")
+                             .long("app-uuid").value_name("APP-UUID")
+                             .help("Authenticates to Fortanix DSM with the given App  \
+                                    (cert-based authentication)"))
+                        .arg(Arg::with_name("pkcs12-passphrase")
+                             .long("pkcs12-passphrase").value_name("PKCS12-PASSPHRASE")
+                             .help("Passphrase for unlocking the PKCS12 identity file \
+                                    (cert-based authentication)"))
+                        .args(&dsm_auth_args())
+                        .arg(Arg::with_name("dsm-key")
+                             .long("dsm-key").value_name("DSM-KEY-NAME")
+                             .required(true)
+                             .multiple(true).number_of_values(1)
+                             .help("Serves the DSM key with this name \
+                                    (may be given multiple times)"))
+                        .arg(Arg::with_name("socket")
+                             .long("socket").value_name("SOCKET")
+                             .required(true)
+                             .help("Listens on the Unix domain socket SOCKET"))
+                )
+                .subcommand(
+                    SubCommand::with_name("ssh-agent")
+                        .display_order(411)
+                        .about("Serves DSM authentication keys via the \
+                                ssh-agent protocol")
+                        .long_about(
+"
+Serves DSM authentication keys via the ssh-agent protocol
+
+This command listens on the given socket, and serves the keys flagged
+for authentication of the given DSM keys to SSH clients.  The
+signatures are made in Fortanix DSM.  Ed25519, ECDSA (NIST curves), and
+RSA keys are supported.
+
+SSH clients use the keys if SSH_AUTH_SOCK points to the socket.  Use
+`sq key export-ssh` to get the lines to add to the server's
+authorized_keys file.
+
+The command runs until interrupted.
+")
+                        .after_help(
+"EXAMPLES:
+
+# Serve the authentication keys of \"My key\" to ssh
+$ sq key ssh-agent --dsm-key=\"My key\" --socket=/tmp/sq-ssh.sock &
+$ SSH_AUTH_SOCK=/tmp/sq-ssh.sock ssh user@example.org
+")
+                        .arg(Arg::with_name("api-key")
+                             .long("api-key").value_name("API-KEY")
+                             .help("Authenticates to Fortanix DSM using the \
+                                    given API key"))
+                        .arg(Arg::with_name("client-cert")
+                             .long("client-cert").value_name("P12-FILE")
+                             .help("Authenticates to Fortanix DSM with the given client \
+                                   certificate"))
+                        .arg(Arg::with_name("app-uuid")
+                             .long("app-uuid").value_name("APP-UUID")
+                             .help("Authenticates to Fortanix DSM with the given App  \
+                                    (cert-based authentication)"))
+                        .arg(Arg::with_name("pkcs12-passphrase")
+                             .long("pkcs12-passphrase").value_name("PKCS12-PASSPHRASE")
+                             .help("Passphrase for unlocking the PKCS12 identity file \
+                                    (cert-based authentication)"))
+                        .args(&dsm_auth_args())
+                        .arg(Arg::with_name("dsm-key")
+                             .long("dsm-key").value_name("DSM-KEY-NAME")
+                             .required(true)
+                             .multiple(true).number_of_values(1)
+                             .help("Serves the DSM key with this name \
+                                    (may be given multiple times)"))
+                        .arg(Arg::with_name("socket")
+                             .long("socket").value_name("SOCKET")
+                             .required(true)
+                             .help("Listens on the Unix domain socket SOCKET"))
+                )
                 .subcommand(
                     SubCommand::with_name("attest-certifications")
                         .display_order(200)
@@ -1007,22 +1688,332 @@ $ sq key attest-certifications --none juliet.pgp
                              .short("B").long("binary")
                              .help("Emits binary data"))
                 )
-        )
-
-        .subcommand(
-            SubCommand::with_name("keyring")
-                .display_order(310)
-                .about("Manages collections of keys or certs")
-                .long_about(
-"Manages collections of keys or certs
-
-Collections of keys or certficicates (also known as \"keyrings\" when
-they contain secret key material, and \"certrings\" when they don't) are
-any number of concatenated certificates.  This subcommand provides
-tools to list, split, join, merge, and filter keyrings.
+                .subcommand(
+                    SubCommand::with_name("subkey")
+                        .display_order(210)
+                        .about("Manages subkeys")
+                        .long_about(
+"Manages subkeys
 
-Note: In the documentation of this subcommand, we sometimes use the
-terms keys and certs interchangeably.
+Adds new subkeys to a key, and changes the expiration time of
+existing subkeys.  Password-protected keys are unlocked as needed,
+and the secret key material in the resulting key stays protected.
+")
+                        .setting(AppSettings::SubcommandRequiredElseHelp)
+                        .subcommand(
+                            SubCommand::with_name("add")
+                                .display_order(100)
+                                .about("Adds a newly generated subkey")
+                                .long_about(
+"Adds a newly generated subkey
+
+A new subkey with the given capability is generated and bound to the
+key.  If the key is protected by a password, the new subkey is
+protected by the same password.
+")
+                                .after_help(
+"EXAMPLES:
+
+# Add a new encryption subkey
+$ sq key subkey add --can-encrypt=universal juliet.key.pgp
+
+# Add a new signing subkey that expires in a year
+$ sq key subkey add --can-sign --expires-in 1y juliet.key.pgp
+")
+                                .group(ArgGroup::with_name("capability")
+                                       .args(&["can-sign", "can-authenticate",
+                                               "can-encrypt"])
+                                       .required(true))
+                                .arg(Arg::with_name("can-sign")
+                                     .long("can-sign")
+                                     .help("Adds a signing-capable subkey"))
+                                .arg(Arg::with_name("can-authenticate")
+                                     .long("can-authenticate")
+                                     .help("Adds an authentication-capable \
+                                            subkey"))
+                                .arg(Arg::with_name("can-encrypt")
+                                     .long("can-encrypt").value_name("PURPOSE")
+                                     .possible_values(&["transport", "storage",
+                                                        "universal"])
+                                     .help("Adds an encryption-capable subkey"))
+                                .arg(Arg::with_name("cipher-suite")
+                                     .short("c").long("cipher-suite")
+                                     .value_name("CIPHER-SUITE")
+                                     .possible_values(&[
+                                         "rsa2k",
+                                         "rsa3k",
+                                         "rsa4k",
+                                         "cv25519",
+                                         "nistp256",
+                                         "nistp384",
+                                         "nistp521",
+                                     ])
+                                     .default_value("cv25519")
+                                     .help("Selects the cryptographic \
+                                            algorithms for the subkey"))
+                                .group(ArgGroup::with_name("expiration-group")
+                                       .args(&["expires", "expires-in"]))
+                                .arg(Arg::with_name("expires")
+                                     .long("expires").value_name("TIME")
+                                     .help("Makes the subkey expire at TIME \
+                                            (as ISO 8601)")
+                                     .long_help(
+                                         "Makes the subkey expire at TIME \
+                                          (as ISO 8601).  Use \"never\" to \
+                                          create subkeys that do not expire \
+                                          on their own. [default: never]"))
+                                .arg(Arg::with_name("expires-in")
+                                     .long("expires-in").value_name("DURATION")
+                                     // Catch negative numbers.
+                                     .allow_hyphen_values(true)
+                                     .help("Makes the subkey expire after \
+                                            DURATION (as N[ymwd])")
+                                     .long_help(
+                                         "Makes the subkey expire after \
+                                          DURATION. Either \"N[ymwd]\", for \
+                                          N years, months, weeks, or days, \
+                                          or \"never\"."))
+                                .arg(Arg::with_name("key")
+                                     .value_name("KEY")
+                                     .help("Reads from KEY or stdin if omitted"))
+                                .arg(Arg::with_name("output")
+                                     .short("o").long("output").value_name("FILE")
+                                     .help("Writes to FILE or stdout if omitted"))
+                                .arg(Arg::with_name("binary")
+                                     .short("B").long("binary")
+                                     .help("Emits binary data"))
+                        )
+                        .subcommand(
+                            SubCommand::with_name("expire")
+                                .display_order(110)
+                                .about("Changes the expiration time of subkeys")
+                                .long_about(
+"Changes the expiration time of subkeys
+
+The subkeys are re-bound with the new expiration time.  A subkey can
+be retired by letting it expire now.  Note that a subkey does not
+outlive the primary key, see \"sq key expire\".
+")
+                                .after_help(
+"EXAMPLES:
+
+# Extend the validity of a subkey by two years
+$ sq key subkey expire --subkey 0123456789ABCDEF --expires-in 2y \\
+     juliet.key.pgp
+")
+                                .arg(Arg::with_name("subkey")
+                                     .long("subkey").value_name("SUBKEY")
+                                     .multiple(true).number_of_values(1)
+                                     .required(true)
+                                     .help("Changes the expiration time of \
+                                            the subkey with this fingerprint \
+                                            or Key ID"))
+                                .group(ArgGroup::with_name("expiration-group")
+                                       .args(&["expires", "expires-in"])
+                                       .required(true))
+                                .arg(Arg::with_name("expires")
+                                     .long("expires").value_name("TIME")
+                                     .help("Makes the subkeys expire at TIME \
+                                            (as ISO 8601)")
+                                     .long_help(
+                                         "Makes the subkeys expire at TIME \
+                                          (as ISO 8601).  Use \"never\" to \
+                                          remove the expiration time."))
+                                .arg(Arg::with_name("expires-in")
+                                     .long("expires-in").value_name("DURATION")
+                                     // Catch negative numbers.
+                                     .allow_hyphen_values(true)
+                                     .help("Makes the subkeys expire after \
+                                            DURATION (as N[ymwd])")
+                                     .long_help(
+                                         "Makes the subkeys expire after \
+                                          DURATION. Either \"N[ymwd]\", for \
+                                          N years, months, weeks, or days, \
+                                          or \"never\"."))
+                                .arg(Arg::with_name("key")
+                                     .value_name("KEY")
+                                     .help("Reads from KEY or stdin if omitted"))
+                                .arg(Arg::with_name("output")
+                                     .short("o").long("output").value_name("FILE")
+                                     .help("Writes to FILE or stdout if omitted"))
+                                .arg(Arg::with_name("binary")
+                                     .short("B").long("binary")
+                                     .help("Emits binary data"))
+                        )
+                )
+                .subcommand(
+                    SubCommand::with_name("userid")
+                        .display_order(220)
+                        .about("Manages User IDs")
+                        .long_about(
+"Manages User IDs
+
+Adds User IDs to a key, removes them, or changes the primary User ID.
+Password-protected keys are unlocked as needed.
+")
+                        .setting(AppSettings::SubcommandRequiredElseHelp)
+                        .subcommand(
+                            SubCommand::with_name("add")
+                                .display_order(100)
+                                .about("Adds User IDs")
+                                .long_about(
+"Adds User IDs
+
+The new User IDs are bound to the key using the preferences and the
+expiration time of the primary User ID.
+")
+                                .after_help(
+"EXAMPLES:
+
+# Add a new User ID
+$ sq key userid add --userid \"Juliet <juliet@example.org>\" juliet.key.pgp
+")
+                                .arg(Arg::with_name("userid")
+                                     .short("u").long("userid")
+                                     .value_name("USERID")
+                                     .multiple(true).number_of_values(1)
+                                     .required(true)
+                                     .help("Adds USERID to the key"))
+                                .arg(Arg::with_name("key")
+                                     .value_name("KEY")
+                                     .help("Reads from KEY or stdin if omitted"))
+                                .arg(Arg::with_name("output")
+                                     .short("o").long("output").value_name("FILE")
+                                     .help("Writes to FILE or stdout if omitted"))
+                                .arg(Arg::with_name("binary")
+                                     .short("B").long("binary")
+                                     .help("Emits binary data"))
+                        )
+                        .subcommand(
+                            SubCommand::with_name("strip")
+                                .display_order(110)
+                                .about("Removes User IDs")
+                                .long_about(
+"Removes User IDs
+
+The User IDs and their signatures are removed from the key.  This
+only changes the local copy of the key: anybody who already has a
+copy of the certificate still sees the User IDs.  To retract a User
+ID that has been published, use \"sq revoke userid\" instead.
+")
+                                .after_help(
+"EXAMPLES:
+
+# Remove a User ID before publishing the certificate
+$ sq key userid strip --userid \"Juliet <juliet@work.example>\" \\
+     juliet.key.pgp
+")
+                                .arg(Arg::with_name("userid")
+                                     .short("u").long("userid")
+                                     .value_name("USERID")
+                                     .multiple(true).number_of_values(1)
+                                     .required(true)
+                                     .help("Removes USERID from the key"))
+                                .arg(Arg::with_name("key")
+                                     .value_name("KEY")
+                                     .help("Reads from KEY or stdin if omitted"))
+                                .arg(Arg::with_name("output")
+                                     .short("o").long("output").value_name("FILE")
+                                     .help("Writes to FILE or stdout if omitted"))
+                                .arg(Arg::with_name("binary")
+                                     .short("B").long("binary")
+                                     .help("Emits binary data"))
+                        )
+                        .subcommand(
+                            SubCommand::with_name("set-primary")
+                                .display_order(120)
+                                .about("Changes the primary User ID")
+                                .after_help(
+"EXAMPLES:
+
+# Make the work address the primary User ID
+$ sq key userid set-primary --userid \"Juliet <juliet@work.example>\" \\
+     juliet.key.pgp
+")
+                                .arg(Arg::with_name("userid")
+                                     .short("u").long("userid")
+                                     .value_name("USERID")
+                                     .required(true)
+                                     .help("Marks USERID as primary User ID"))
+                                .arg(Arg::with_name("key")
+                                     .value_name("KEY")
+                                     .help("Reads from KEY or stdin if omitted"))
+                                .arg(Arg::with_name("output")
+                                     .short("o").long("output").value_name("FILE")
+                                     .help("Writes to FILE or stdout if omitted"))
+                                .arg(Arg::with_name("binary")
+                                     .short("B").long("binary")
+                                     .help("Emits binary data"))
+                        )
+                )
+                .subcommand(
+                    SubCommand::with_name("expire")
+                        .display_order(230)
+                        .about("Changes the expiration time of a key")
+                        .long_about(
+"Changes the expiration time of a key
+
+The primary key's direct key signature and the binding signatures of
+all User IDs are updated with the new expiration time.  When the
+primary key expires, the whole certificate expires, including the
+subkeys.  Use \"sq key subkey expire\" to change the expiration time
+of individual subkeys.
+")
+                        .after_help(
+"EXAMPLES:
+
+# Extend the validity of the key by two years
+$ sq key expire --expires-in 2y juliet.key.pgp
+
+# Make the key never expire
+$ sq key expire --expires never juliet.key.pgp
+")
+                        .group(ArgGroup::with_name("expiration-group")
+                               .args(&["expires", "expires-in"])
+                               .required(true))
+                        .arg(Arg::with_name("expires")
+                             .long("expires").value_name("TIME")
+                             .help("Makes the key expire at TIME (as ISO 8601)")
+                             .long_help(
+                                 "Makes the key expire at TIME (as ISO 8601). \
+                                  Use \"never\" to remove the expiration \
+                                  time."))
+                        .arg(Arg::with_name("expires-in")
+                             .long("expires-in").value_name("DURATION")
+                             // Catch negative numbers.
+                             .allow_hyphen_values(true)
+                             .help("Makes the key expire after DURATION \
+                                    (as N[ymwd])")
+                             .long_help(
+                                 "Makes the key expire after DURATION. \
+                                  Either \"N[ymwd]\", for N years, months, \
+                                  weeks, or days, or \"never\"."))
+                        .arg(Arg::with_name("key")
+                             .value_name("KEY")
+                             .help("Reads from KEY or stdin if omitted"))
+                        .arg(Arg::with_name("output")
+                             .short("o").long("output").value_name("FILE")
+                             .help("Writes to FILE or stdout if omitted"))
+                        .arg(Arg::with_name("binary")
+                             .short("B").long("binary")
+                             .help("Emits binary data"))
+                )
+        )
+
+        .subcommand(
+            SubCommand::with_name("keyring")
+                .display_order(310)
+                .about("Manages collections of keys or certs")
+                .long_about(
+"Manages collections of keys or certs
+
+Collections of keys or certficicates (also known as \"keyrings\" when
+they contain secret key material, and \"certrings\" when they don't) are
+any number of concatenated certificates.  This subcommand provides
+tools to list, split, join, merge, and filter keyrings.
+
+Note: In the documentation of this subcommand, we sometimes use the
+terms keys and certs interchangeably.
 ")
                 .setting(AppSettings::SubcommandRequiredElseHelp)
                 .subcommand(
@@ -1209,6 +2200,153 @@ $ sq keyring filter --domain example.org certs.pgp | sq keyring list
                                   expired, revoked, or not valid under the \
                                   standard policy."))
                 )
+                .subcommand(
+                    SubCommand::with_name("lint")
+                        .display_order(400)
+                        .about("Detects and repairs weak self-signatures")
+                        .long_about(
+"Detects and repairs weak self-signatures
+
+Older certificates often have self-signatures using SHA-1, or
+signing-capable subkeys without a primary key binding signature
+(\"backsig\").  The standard policy rejects these, which renders the
+affected User IDs and subkeys, or even the whole certificate,
+unusable.
+
+This subcommand reports every component with such a self-signature,
+and the reason why the policy rejects it.  Revoked components are
+ignored.  It fails if any certificate has weak self-signatures that
+are not fixed.
+
+With --fix, the affected self-signatures are re-issued using SHA-512,
+keeping all other subpackets, and the fixed certificates are written
+out.  This requires the primary key's secret key material, and, to
+make a new primary key binding signature, that of the subkey.  It is
+looked for in the certificates themselves, in the keys given using
+--secret-key-file, in Fortanix DSM (see --dsm-key), and, finally, in
+the gpg-agent (see --gpg-agent), in that order.
+")
+                        .after_help(
+"EXAMPLES:
+
+# Reports weak self-signatures
+$ sq keyring lint certs.pgp
+
+# Lists the certificates with weak self-signatures
+$ sq keyring lint --list-keys certs.pgp
+
+# Fixes a key
+$ sq keyring lint --fix juliet.pgp > juliet-fixed.pgp
+
+# Fixes a certificate using the secrets in the gpg-agent
+$ gpg --export juliet@example.org | sq keyring lint --fix --gpg-agent | gpg --import
+")
+                        .arg(Arg::with_name("input")
+                             .value_name("FILE")
+                             .multiple(true)
+                             .help("Reads from FILE or stdin if omitted"))
+                        .arg(Arg::with_name("fix")
+                             .long("fix")
+                             .help("Re-issues weak self-signatures, and \
+                                    writes out the fixed certificates"))
+                        .arg(Arg::with_name("list-keys")
+                             .short("k").long("list-keys")
+                             .conflicts_with("fix")
+                             .help("Lists the fingerprints of the \
+                                    certificates with weak self-signatures \
+                                    instead of the details"))
+                        .arg(Arg::with_name("output")
+                             .short("o").long("output").value_name("FILE")
+                             .requires("fix")
+                             .help("Writes to FILE or stdout if omitted"))
+                        .arg(Arg::with_name("binary")
+                             .short("B").long("binary")
+                             .requires("fix")
+                             .help("Emits binary data"))
+                        .arg(Arg::with_name("secret-key-file")
+                             .long("secret-key-file").value_name("TSK-FILE")
+                             .multiple(true).number_of_values(1)
+                             .requires("fix")
+                             .help("Signs using the secret keys in TSK-FILE"))
+                        .arg(Arg::with_name("gpg-agent")
+                             .long("gpg-agent")
+                             .requires("fix")
+                             .help("Signs using the secret keys in the \
+                                    gpg-agent"))
+                        .arg(Arg::with_name("gpg-homedir")
+                             .long("gpg-homedir").value_name("DIR")
+                             .requires("gpg-agent")
+                             .help("Uses the gpg-agent of the GnuPG home \
+                                    directory DIR"))
+                        .arg(Arg::with_name("api-key")
+                            .long("api-key").value_name("API-KEY")
+                            .help("Authenticates to Fortanix DSM using the given \
+                                   API key"))
+                        .arg(Arg::with_name("client-cert")
+                            .long("client-cert").value_name("P12-FILE")
+                            .help("Authenticates to Fortanix DSM with the given client \
+                                   certificate"))
+                        .arg(Arg::with_name("app-uuid")
+                            .long("app-uuid").value_name("APP-UUID")
+                            .help("Authenticates to Fortanix DSM with the given App  \
+                            (cert-based authentication)"))
+                        .arg(Arg::with_name("pkcs12-passphrase")
+                            .long("pkcs12-passphrase").value_name("PKCS12-PASSPHRASE")
+                            .help("Passphrase for unlocking the PKCS12 identity file \
+                            (cert-based authentication)"))
+                        .args(&dsm_auth_args())
+                        .arg(Arg::with_name("dsm-key")
+                            .long("dsm-key").value_name("DSM-KEY-NAME")
+                            .multiple(true).number_of_values(1)
+                            .requires("fix")
+                            .help("Signs using the key DSM-KEY-NAME stored \
+                                   inside the Fortanix Self-Defending \
+                                   Key-Management System"))
+                )
+                .subcommand(
+                    SubCommand::with_name("diff")
+                        .display_order(410)
+                        .about("Compares certificates")
+                        .long_about(
+"Compares certificates
+
+Compares two versions of certificates, e.g. a certificate before and
+after it was updated from a keyserver or merged using \"sq keyring
+merge\".  If A and B are keyrings, certificates are matched by their
+fingerprints.
+
+For every certificate that differs, lists the subkeys, User IDs, and
+user attributes that were added or removed, and the self-signatures,
+third-party certifications, and revocations that were added or
+removed.  Signatures are compared ignoring their unhashed subpacket
+areas.  For every signature, its creation time and whether it is
+valid under the policy are shown.  Third-party signatures are only
+checked against the policy, they are not cryptographically verified.
+
+With \"--output-format json\", emits a JSON document instead.
+")
+                        .after_help(
+"EXAMPLES:
+
+# Shows what changed when updating a certificate
+$ sq keyring diff juliet.pgp juliet-updated.pgp
+
+# Shows what merging two keyrings changes in the first one
+$ sq keyring merge certs.pgp new.pgp > merged.pgp
+$ sq keyring diff certs.pgp merged.pgp
+
+# Emits the differences as JSON
+$ sq --output-format json keyring diff juliet.pgp juliet-updated.pgp
+")
+                        .arg(Arg::with_name("a")
+                             .value_name("A")
+                             .required(true)
+                             .help("Reads the first version from A"))
+                        .arg(Arg::with_name("b")
+                             .value_name("B")
+                             .required(true)
+                             .help("Reads the second version from B"))
+                )
                 .subcommand(
                     SubCommand::with_name("split")
                         .display_order(200)
@@ -1245,6 +2383,108 @@ $ sq keyring merge certs.pgp | sq keyring split
                 )
         )
 
+        .subcommand(SubCommand::with_name("import")
+                    .display_order(311)
+                    .about("Imports certificates into the certificate store")
+                    .long_about(
+"Imports certificates into the certificate store
+
+Certificates that are already in the store are merged with the
+imported version.  Secret key material is not imported.
+
+The converse operation is \"sq export\".
+")
+                    .after_help(
+"EXAMPLES:
+
+# Import a certificate
+$ sq import juliet.pgp
+
+# Import certificates from a keyserver
+$ sq keyserver get romeo@example.org | sq import
+")
+                    .arg(Arg::with_name("input")
+                         .value_name("FILE")
+                         .multiple(true)
+                         .help("Reads from FILE or stdin if omitted"))
+        )
+
+        .subcommand(SubCommand::with_name("export")
+                    .display_order(312)
+                    .about("Exports certificates from the certificate store")
+                    .long_about(
+"Exports certificates from the certificate store
+
+Exports the certificates matching any of the given queries, or all
+certificates if no query is given.  See \"sq lookup\" for the
+supported queries.
+
+The converse operation is \"sq import\".
+")
+                    .after_help(
+"EXAMPLES:
+
+# Export all certificates
+$ sq export > certs.pgp
+
+# Export the certificates with a user ID containing an email address
+$ sq export juliet@example.org
+")
+                    .arg(Arg::with_name("query")
+                         .value_name("QUERY")
+                         .multiple(true)
+                         .help("Exports certificates matching QUERY"))
+                    .arg(Arg::with_name("output")
+                         .short("o").long("output").value_name("FILE")
+                         .help("Writes to FILE or stdout if omitted"))
+                    .arg(Arg::with_name("binary")
+                         .short("B").long("binary")
+                         .help("Emits binary data"))
+        )
+
+        .subcommand(SubCommand::with_name("lookup")
+                    .display_order(313)
+                    .about("Looks up certificates in the certificate store")
+                    .long_about(
+"Looks up certificates in the certificate store
+
+Lists the certificates matching QUERY, like \"sq keyring list\".
+QUERY may be a fingerprint or key ID of a primary key or subkey, an
+email address, or a User ID.  Fingerprints and key IDs may be prefixed
+with \"0x\".  Email addresses are enclosed in angle brackets, or
+contain an \"@\" and no whitespace.  Everything else is matched
+exactly against the User IDs.
+
+The options can be used to force the interpretation of QUERY.
+")
+                    .after_help(
+"EXAMPLES:
+
+# Look up a certificate by subkey
+$ sq lookup 0xC2B819056C652598
+
+# Look up certificates by email address
+$ sq lookup juliet@example.org
+
+# Look up certificates by User ID
+$ sq lookup 'Juliet Capulet <juliet@example.org>'
+")
+                    .arg(Arg::with_name("query")
+                         .value_name("QUERY")
+                         .required(true)
+                         .help("Looks up certificates matching QUERY"))
+                    .arg(Arg::with_name("email")
+                         .long("email")
+                         .conflicts_with("userid")
+                         .help("Interprets QUERY as an email address"))
+                    .arg(Arg::with_name("userid")
+                         .long("userid")
+                         .help("Interprets QUERY as a User ID"))
+                    .arg(Arg::with_name("all-userids")
+                         .long("all-userids")
+                         .help("Lists all user ids"))
+        )
+
         .subcommand(SubCommand::with_name("certify")
                     .display_order(320)
                     .about("Certifies a User ID for a Certificate")
@@ -1268,6 +2508,9 @@ attest-certification\".
 
 # Juliet certifies that Romeo controls romeo.pgp and romeo@example.org
 $ sq certify juliet.pgp romeo.pgp \"<romeo@example.org>\"
+
+# Juliet certifies using her key in the gpg-agent
+$ sq certify --gpg-agent 0123456789ABCDEF romeo.pgp \"<romeo@example.org>\"
 ")
                     .arg(Arg::with_name("output")
                          .short("o").long("output").value_name("FILE")
@@ -1366,6 +2609,21 @@ $ sq certify juliet.pgp romeo.pgp \"<romeo@example.org>\"
                          .required(true)
                          .index(1)
                          .help("Creates the certificate using CERTIFIER-KEY."))
+                    .arg(Arg::with_name("gpg-agent")
+                         .long("gpg-agent")
+                         .help("Certifies using the secret key in the \
+                                gpg-agent")
+                         .long_help(
+                             "Certifies using the secret key in the \
+                              gpg-agent.  CERTIFIER-KEY may then be a \
+                              certificate without secret key material, \
+                              or the fingerprint or Key ID of a \
+                              certificate in the GnuPG keybox."))
+                    .arg(Arg::with_name("gpg-homedir")
+                         .long("gpg-homedir").value_name("DIR")
+                         .requires("gpg-agent")
+                         .help("Uses the gpg-agent of the GnuPG home \
+                                directory DIR"))
                     .arg(Arg::with_name("certificate")
                          .value_name("CERTIFICATE")
                          .required(true)
@@ -1378,6 +2636,391 @@ $ sq certify juliet.pgp romeo.pgp \"<romeo@example.org>\"
                          .help("Certifies USERID for CERTIFICATE."))
         )
 
+        .subcommand(
+            SubCommand::with_name("wot")
+                .display_order(325)
+                .about("Authenticates bindings using the web of trust")
+                .long_about(
+"Authenticates bindings using the web of trust
+
+Certifications, see \"sq certify\", vouch for bindings between
+certificates and User IDs.  Starting from the certificates that you
+trust, the trust roots, this subcommand follows the certifications
+to authenticate bindings.
+
+A trust root authenticates its own User IDs, and vouches for the
+bindings that it certifies.  A certification that is a trust
+signature (see \"sq certify --depth\") also makes the certified
+certificate a trusted introducer, that may in turn vouch for
+bindings, possibly constrained by regular expressions
+(\"sq certify --regex\").
+
+Every certification has a trust amount, and a binding is only fully
+authenticated if the certifications vouching for it add up to a
+trust amount of 120.  The trust amount of a path is the minimum of
+the trust amounts of its certifications, and the trust amounts of
+several independent paths are added.
+
+Only certifications, certificates and User IDs that are valid at
+the reference time are considered.  Expired and revoked ones are
+ignored.
+
+The certificates are read from the keyrings given using --keyring
+and from the certificate store (see \"sq import\").
+")
+                .after_help(
+"EXAMPLES:
+
+# Authenticate the binding between a certificate and a User ID
+$ sq wot authenticate --trust-root 8F17777118A33DDA9BA48E62AACB3243630052D9 \\
+     --keyring keyring.pgp \\
+     C2B819056C652598A0D8C1FEE5C7D0D86B00AD4F 'Juliet <juliet@example.org>'
+
+# Find certificates with an authenticated email address
+$ sq wot lookup --trust-root 8F17777118A33DDA9BA48E62AACB3243630052D9 \\
+     --keyring keyring.pgp --email juliet@example.org
+
+# Check a given path
+$ sq wot path --keyring keyring.pgp \\
+     8F17777118A33DDA9BA48E62AACB3243630052D9 \\
+     C2B819056C652598A0D8C1FEE5C7D0D86B00AD4F 'Juliet <juliet@example.org>'
+")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("authenticate")
+                        .display_order(100)
+                        .about("Authenticates a binding")
+                        .long_about(
+"Authenticates a binding
+
+Authenticates the binding between the certificate FINGERPRINT and
+USERID, and prints the paths that were found.  Fails if the binding
+cannot be authenticated.
+")
+                        .arg(Arg::with_name("trust-root")
+                             .long("trust-root").value_name("FINGERPRINT")
+                             .required(true)
+                             .multiple(true).number_of_values(1)
+                             .help("Trusts the certificate FINGERPRINT"))
+                        .args(&wot_args())
+                        .arg(Arg::with_name("email")
+                             .long("email")
+                             .help("Authenticates all User IDs with the \
+                                    email address USERID"))
+                        .arg(Arg::with_name("fingerprint")
+                             .value_name("FINGERPRINT")
+                             .required(true)
+                             .help("Authenticates the certificate \
+                                    FINGERPRINT"))
+                        .arg(Arg::with_name("userid")
+                             .value_name("USERID")
+                             .required(true)
+                             .help("Authenticates USERID"))
+                )
+                .subcommand(
+                    SubCommand::with_name("lookup")
+                        .display_order(200)
+                        .about("Looks up authenticated certificates by \
+                                User ID")
+                        .long_about(
+"Looks up authenticated certificates by User ID
+
+Authenticates the bindings of all certificates with USERID, and
+prints the paths that were found.  Fails if no binding can be
+authenticated.
+")
+                        .arg(Arg::with_name("trust-root")
+                             .long("trust-root").value_name("FINGERPRINT")
+                             .required(true)
+                             .multiple(true).number_of_values(1)
+                             .help("Trusts the certificate FINGERPRINT"))
+                        .args(&wot_args())
+                        .arg(Arg::with_name("email")
+                             .long("email")
+                             .help("Looks up certificates with User IDs \
+                                    with the email address USERID"))
+                        .arg(Arg::with_name("userid")
+                             .value_name("USERID")
+                             .required(true)
+                             .help("Looks up USERID"))
+                )
+                .subcommand(
+                    SubCommand::with_name("path")
+                        .display_order(300)
+                        .about("Checks a path")
+                        .long_about(
+"Checks a path
+
+Checks that the certificates FINGERPRINT... form a path from the
+first one, which is considered to be the trust root, to the binding
+between the last one and USERID.  If the path is not valid, explains
+why.
+")
+                        .args(&wot_args())
+                        .arg(Arg::with_name("fingerprint")
+                             .value_name("FINGERPRINT")
+                             .required(true)
+                             .multiple(true)
+                             .help("Follows the path FINGERPRINT..."))
+                        .arg(Arg::with_name("userid")
+                             .value_name("USERID")
+                             .required(true)
+                             .help("Checks the binding to USERID"))
+                )
+        )
+
+        .subcommand(SubCommand::with_name("revoke")
+                    .display_order(330)
+                    .about("Revokes a certificate, subkey or User ID")
+                    .long_about(
+"
+Revokes a certificate, subkey or User ID
+
+A revocation states that a certificate, one of its subkeys, or one of
+its User IDs should no longer be used, and why.  It is signed by the
+certificate's primary key, which must be available, or by a designated
+revoker (see \"--revocation-key\").
+
+By default, the revocation is emitted together with the primary key and,
+for subkeys and User IDs, the revoked component, so that it can be merged
+into the certificate by anyone holding it.  Use \"--full-certificate\"
+to emit the revoked certificate instead.
+")
+                    .setting(AppSettings::SubcommandRequiredElseHelp)
+                    .subcommand(SubCommand::with_name("certificate")
+                                .display_order(100)
+                                .about("Revokes a certificate")
+                                .after_help(
+"EXAMPLES:
+
+# Revoke Juliet's certificate, whose key was compromised
+$ sq revoke certificate --certificate juliet.pgp compromised \\
+    \"My laptop was stolen\"
+")
+                                .arg(Arg::with_name("certificate")
+                                     .long("certificate").value_name("FILE")
+                                     .help("Reads the certificate to revoke from FILE \
+                                            or stdin if omitted"))
+                                .arg(Arg::with_name("revocation-key")
+                                     .long("revocation-key").value_name("KEY")
+                                     .help("Signs the revocation with KEY")
+                                     .long_help(
+                                         "Signs the revocation with the primary key \
+                                          of KEY instead of the certificate's own \
+                                          primary key.  Such a third-party revocation \
+                                          is only honored if KEY is a designated \
+                                          revoker of the certificate."))
+                                .arg(Arg::with_name("notation")
+                                     .value_names(&["NAME", "VALUE"])
+                                     .long("notation")
+                                     .multiple(true).number_of_values(2)
+                                     .help("Adds a notation to the revocation.")
+                                     .long_help(
+                                         "Adds a notation to the revocation.  \
+                                          A user-defined notation's name must be of \
+                                          the form \"name@a.domain.you.control.org\". \
+                                          If the notation's name starts with a !, \
+                                          then the notation is marked as being \
+                                          critical.  If a consumer of a signature \
+                                          doesn't understand a critical notation, \
+                                          then it will ignore the signature.  The \
+                                          notation is marked as being human readable."))
+                                .arg(Arg::with_name("time")
+                                     .short("t").long("time").value_name("TIME")
+                                     .help("Sets the revocation's creation time \
+                                            (as ISO 8601)"))
+                                .arg(Arg::with_name("full-certificate")
+                                     .long("full-certificate")
+                                     .help("Emits the revoked certificate instead of \
+                                            the revocation alone")
+                                     .long_help(
+                                         "Emits the certificate with the revocation \
+                                          instead of the revocation alone.  Secret key \
+                                          material is not emitted."))
+                                .arg(Arg::with_name("output")
+                                     .short("o").long("output").value_name("FILE")
+                                     .help("Writes to FILE or stdout if omitted"))
+                                .arg(Arg::with_name("binary")
+                                     .short("B").long("binary")
+                                     .help("Emits binary data"))
+                                .arg(Arg::with_name("reason")
+                                     .value_name("REASON")
+                                     .required(true)
+                                     .index(1)
+                                     .possible_values(&[
+                                         "compromised", "superseded", "retired",
+                                         "unspecified",
+                                     ])
+                                     .help("Gives the reason for the revocation")
+                                     .long_help(
+                                         "Gives the reason for the revocation.  \
+                                          \"compromised\" means that the secret key \
+                                          material may have been exposed; signatures \
+                                          made at any time are then considered \
+                                          suspicious.  \"superseded\" means that a new \
+                                          certificate replaces this one, and \
+                                          \"retired\" that it is no longer used."))
+                                .arg(Arg::with_name("message")
+                                     .value_name("MESSAGE")
+                                     .required(true)
+                                     .index(2)
+                                     .help("Explains the revocation in a human-readable \
+                                            message"))
+                    )
+                    .subcommand(SubCommand::with_name("subkey")
+                                .display_order(110)
+                                .about("Revokes a subkey")
+                                .after_help(
+"EXAMPLES:
+
+# Revoke a subkey of Juliet's certificate that is no longer used
+$ sq revoke subkey --certificate juliet.pgp \\
+    8C6C0A7E0A5F5C5D retired \"Replaced by a new encryption subkey\"
+")
+                                .arg(Arg::with_name("certificate")
+                                     .long("certificate").value_name("FILE")
+                                     .help("Reads the certificate to revoke from FILE \
+                                            or stdin if omitted"))
+                                .arg(Arg::with_name("revocation-key")
+                                     .long("revocation-key").value_name("KEY")
+                                     .help("Signs the revocation with KEY")
+                                     .long_help(
+                                         "Signs the revocation with the primary key \
+                                          of KEY instead of the certificate's own \
+                                          primary key.  Such a third-party revocation \
+                                          is only honored if KEY is a designated \
+                                          revoker of the certificate."))
+                                .arg(Arg::with_name("notation")
+                                     .value_names(&["NAME", "VALUE"])
+                                     .long("notation")
+                                     .multiple(true).number_of_values(2)
+                                     .help("Adds a notation to the revocation.")
+                                     .long_help(
+                                         "Adds a notation to the revocation.  \
+                                          A user-defined notation's name must be of \
+                                          the form \"name@a.domain.you.control.org\". \
+                                          If the notation's name starts with a !, \
+                                          then the notation is marked as being \
+                                          critical.  If a consumer of a signature \
+                                          doesn't understand a critical notation, \
+                                          then it will ignore the signature.  The \
+                                          notation is marked as being human readable."))
+                                .arg(Arg::with_name("time")
+                                     .short("t").long("time").value_name("TIME")
+                                     .help("Sets the revocation's creation time \
+                                            (as ISO 8601)"))
+                                .arg(Arg::with_name("full-certificate")
+                                     .long("full-certificate")
+                                     .help("Emits the revoked certificate instead of \
+                                            the revocation alone")
+                                     .long_help(
+                                         "Emits the certificate with the revocation \
+                                          instead of the revocation alone.  Secret key \
+                                          material is not emitted."))
+                                .arg(Arg::with_name("output")
+                                     .short("o").long("output").value_name("FILE")
+                                     .help("Writes to FILE or stdout if omitted"))
+                                .arg(Arg::with_name("binary")
+                                     .short("B").long("binary")
+                                     .help("Emits binary data"))
+                                .arg(Arg::with_name("subkey")
+                                     .value_name("SUBKEY")
+                                     .required(true)
+                                     .index(1)
+                                     .help("Revokes the subkey with this fingerprint \
+                                            or key ID"))
+                                .arg(Arg::with_name("reason")
+                                     .value_name("REASON")
+                                     .required(true)
+                                     .index(2)
+                                     .possible_values(&[
+                                         "compromised", "superseded", "retired",
+                                         "unspecified",
+                                     ])
+                                     .help("Gives the reason for the revocation"))
+                                .arg(Arg::with_name("message")
+                                     .value_name("MESSAGE")
+                                     .required(true)
+                                     .index(3)
+                                     .help("Explains the revocation in a human-readable \
+                                            message"))
+                    )
+                    .subcommand(SubCommand::with_name("userid")
+                                .display_order(120)
+                                .about("Revokes a User ID")
+                                .after_help(
+"EXAMPLES:
+
+# Juliet no longer uses her work address
+$ sq revoke userid --certificate juliet.pgp \\
+    \"Juliet <juliet@work.example.org>\" retired \"I left this job\"
+")
+                                .arg(Arg::with_name("certificate")
+                                     .long("certificate").value_name("FILE")
+                                     .help("Reads the certificate to revoke from FILE \
+                                            or stdin if omitted"))
+                                .arg(Arg::with_name("revocation-key")
+                                     .long("revocation-key").value_name("KEY")
+                                     .help("Signs the revocation with KEY")
+                                     .long_help(
+                                         "Signs the revocation with the primary key \
+                                          of KEY instead of the certificate's own \
+                                          primary key.  Such a third-party revocation \
+                                          is only honored if KEY is a designated \
+                                          revoker of the certificate."))
+                                .arg(Arg::with_name("notation")
+                                     .value_names(&["NAME", "VALUE"])
+                                     .long("notation")
+                                     .multiple(true).number_of_values(2)
+                                     .help("Adds a notation to the revocation.")
+                                     .long_help(
+                                         "Adds a notation to the revocation.  \
+                                          A user-defined notation's name must be of \
+                                          the form \"name@a.domain.you.control.org\". \
+                                          If the notation's name starts with a !, \
+                                          then the notation is marked as being \
+                                          critical.  If a consumer of a signature \
+                                          doesn't understand a critical notation, \
+                                          then it will ignore the signature.  The \
+                                          notation is marked as being human readable."))
+                                .arg(Arg::with_name("time")
+                                     .short("t").long("time").value_name("TIME")
+                                     .help("Sets the revocation's creation time \
+                                            (as ISO 8601)"))
+                                .arg(Arg::with_name("full-certificate")
+                                     .long("full-certificate")
+                                     .help("Emits the revoked certificate instead of \
+                                            the revocation alone")
+                                     .long_help(
+                                         "Emits the certificate with the revocation \
+                                          instead of the revocation alone.  Secret key \
+                                          material is not emitted."))
+                                .arg(Arg::with_name("output")
+                                     .short("o").long("output").value_name("FILE")
+                                     .help("Writes to FILE or stdout if omitted"))
+                                .arg(Arg::with_name("binary")
+                                     .short("B").long("binary")
+                                     .help("Emits binary data"))
+                                .arg(Arg::with_name("userid")
+                                     .value_name("USERID")
+                                     .required(true)
+                                     .index(1)
+                                     .help("Revokes this User ID"))
+                                .arg(Arg::with_name("reason")
+                                     .value_name("REASON")
+                                     .required(true)
+                                     .index(2)
+                                     .possible_values(&["retired", "unspecified"])
+                                     .help("Gives the reason for the revocation"))
+                                .arg(Arg::with_name("message")
+                                     .value_name("MESSAGE")
+                                     .required(true)
+                                     .index(3)
+                                     .help("Explains the revocation in a human-readable \
+                                            message"))
+                    )
+        )
+
         .subcommand(SubCommand::with_name("packet")
                     .display_order(610)
                     .about("Low-level packet manipulation")
@@ -1404,8 +3047,18 @@ Additionally, it can print cryptographic artifacts, and print the raw
 octet stream similar to hexdump(1), annotating specifically which
 bytes are parsed into OpenPGP values.
 
-To inspect encrypted messages, either supply the session key, or see
-\"sq decrypt --dump\" or \"sq packet decrypt\".
+To inspect encrypted messages, either supply the session key or the
+recipient's key, or see \"sq decrypt --dump\" or \"sq packet decrypt\".
+
+With \"--output-format json\", emits a JSON document describing the
+packet tree, including the offset and header length of every packet,
+its fields, signature subpackets, and MPIs.  Secret key material is
+only included with \"--mpis\".
+
+Packets are addressed by their path in the packet tree, which starts
+at 0 for the first top-level packet.  For example, \"1.0\" is the first
+packet inside the second top-level packet.  These are the same numbers
+used by \"sq packet split\".
 ")
                                 .after_help(
 "EXAMPLES:
@@ -1413,6 +3066,15 @@ To inspect encrypted messages, either supply the session key, or see
 # Prints the packets of a certificate
 $ sq packet dump juliet.pgp
 
+# Emits a JSON description of a certificate's packets
+$ sq --output-format json packet dump juliet.pgp
+
+# Prints only the packets inside the encryption container
+$ sq packet dump --recipient-key juliet.pgp --packet 1 ciphertext.pgp
+
+# Prints the packets of a large message, omitting the literal data
+$ sq packet dump --skip-literal-bodies --hex message.pgp
+
 # Prints cryptographic artifacts of a certificate
 $ sq packet dump --mpis juliet.pgp
 
@@ -1432,6 +3094,47 @@ $ sq packet dump --session-key AAAABBBBCCCC... ciphertext.pgp
                                      .long("session-key").value_name("SESSION-KEY")
                                      .help("Decrypts an encrypted message using \
                                             SESSION-KEY"))
+                                .arg(Arg::with_name("recipient-key")
+                                     .long("recipient-key").value_name("KEY")
+                                     .multiple(true).number_of_values(1)
+                                     .conflicts_with("session-key")
+                                     .help("Decrypts an encrypted message with KEY"))
+                                .arg(Arg::with_name("api-key")
+                                     .long("api-key").value_name("API-KEY")
+                                     .help("Authenticates to Fortanix DSM using \
+                                            the given API key"))
+                                .arg(Arg::with_name("client-cert")
+                                     .long("client-cert").value_name("P12-FILE")
+                                     .help("Authenticates to Fortanix DSM with \
+                                            the given client certificate"))
+                                .arg(Arg::with_name("app-uuid")
+                                     .long("app-uuid").value_name("APP-UUID")
+                                     .help("Authenticates to Fortanix DSM with \
+                                            the given App (cert-based \
+                                            authentication)"))
+                                .arg(Arg::with_name("pkcs12-passphrase")
+                                     .long("pkcs12-passphrase")
+                                     .value_name("PKCS12-PASSPHRASE")
+                                     .help("Passphrase for unlocking the PKCS12 \
+                                            identity file (cert-based \
+                                            authentication)"))
+                                .args(&dsm_auth_args())
+                                .arg(Arg::with_name("dsm-key")
+                                     .long("dsm-key").value_name("DSM-KEY-NAME")
+                                     .conflicts_with("session-key")
+                                     .help("Decrypts an encrypted message with \
+                                            the secret stored inside the Fortanix \
+                                            Self-Defending Key-Management System"))
+                                .arg(Arg::with_name("packet")
+                                     .long("packet").value_name("PATH")
+                                     .multiple(true).number_of_values(1)
+                                     .help("Dumps only the packet at PATH, \
+                                            e.g. \"1.0\", and the packets it \
+                                            contains"))
+                                .arg(Arg::with_name("skip-literal-bodies")
+                                     .long("skip-literal-bodies")
+                                     .help("Omits the content of literal data \
+                                            packets"))
                                 .arg(Arg::with_name("mpis")
                                      .long("mpis")
                                      .help("Prints cryptographic artifacts"))
@@ -1746,3 +3449,48 @@ $ sq autocrypt encode-sender --prefer-encrypt mutual juliet.pgp
 
     app
 }
+
+/// Returns the arguments common to the "sq wot" subcommands.
+fn wot_args() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name("keyring")
+            .long("keyring").value_name("FILE")
+            .multiple(true).number_of_values(1)
+            .help("Uses the certificates in FILE"),
+        Arg::with_name("time")
+            .long("time").value_name("TIME")
+            .help("Uses TIME (as ISO 8601) as the reference time \
+                   [default: now]"),
+        Arg::with_name("amount")
+            .long("amount").value_name("TRUST_AMOUNT")
+            .help("Requires a trust amount of TRUST_AMOUNT \
+                   [default: 120]")
+            .long_help("Requires a trust amount of TRUST_AMOUNT.  \
+                        120 means fully authenticated.  \
+                        [default: 120]"),
+    ]
+}
+
+/// Returns the Fortanix DSM authentication options based on tokens and
+/// user logins.
+fn dsm_auth_args() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name("dsm-bearer-token-file")
+            .long("dsm-bearer-token-file").value_name("FILE")
+            .help("Authenticates to Fortanix DSM with the access token \
+                   in FILE"),
+        Arg::with_name("dsm-jwt-file")
+            .long("dsm-jwt-file").value_name("FILE")
+            .help("Authenticates to Fortanix DSM with the JWT in FILE \
+                   (requires the App UUID)"),
+        Arg::with_name("dsm-username")
+            .long("dsm-username").value_name("USERNAME")
+            .help("Logs in to Fortanix DSM as USERNAME")
+            .long_help("Logs in to Fortanix DSM as USERNAME.  The \
+                        password is taken from FORTANIX_PASSWORD, or \
+                        asked for."),
+        Arg::with_name("dsm-account-id")
+            .long("dsm-account-id").value_name("ACCOUNT-UUID")
+            .help("Selects the Fortanix DSM account after logging in"),
+    ]
+}
@@ -0,0 +1,518 @@
+//! Web of trust.
+//!
+//! Authenticates bindings between certificates and User IDs using
+//! the certifications in a keyring, starting from a set of trust
+//! roots, following the OpenPGP web of trust rules:
+//!
+//!   - A certification of a binding authenticates the binding with
+//!     the certification's trust amount, if the issuer is
+//!     authenticated.
+//!
+//!   - A trust signature with depth `d` makes the certified
+//!     certificate a trusted introducer: it may authenticate
+//!     bindings up to `d` hops away.  The trust signature's regular
+//!     expressions, if any, limit the User IDs that the introducer
+//!     may authenticate.
+//!
+//!   - The trust amount of a path is the minimum trust amount of its
+//!     certifications.  Several paths are combined by adding their
+//!     trust amounts, where each certification's trust amount may
+//!     only be used once.
+//!
+//! Only certifications that are valid under the policy at the
+//! reference time are considered: the certification must not be
+//! expired, not be revoked by its issuer, and both the issuer and
+//! the certified certificate must be valid, alive and not revoked.
+//! Of several certifications of a binding by the same issuer, only
+//! the newest is considered.
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::time::{Duration, SystemTime};
+
+use sequoia_openpgp as openpgp;
+use openpgp::{Cert, Fingerprint, KeyID, Result};
+use openpgp::packet::{Signature, UserID};
+use openpgp::policy::{HashAlgoSecurity, Policy};
+use openpgp::regex::RegexSet;
+use openpgp::types::{RevocationStatus, SignatureType};
+
+/// The trust amount that fully authenticates a binding.
+pub const FULLY_TRUSTED: usize = 120;
+
+/// A valid certification of a binding.
+#[derive(Clone, Debug)]
+pub struct Certification {
+    /// The certificate that issued the certification.
+    pub issuer: Fingerprint,
+    /// The certified certificate.
+    pub target: Fingerprint,
+    /// The certified User ID.
+    pub userid: UserID,
+    /// When the certification was made.
+    pub creation_time: SystemTime,
+    /// The trust depth.  Zero for normal certifications.
+    pub depth: u8,
+    /// The trust amount.
+    pub amount: u8,
+    /// The scope of a trusted introducer.
+    regex: Option<RegexSet>,
+}
+
+impl Certification {
+    /// Returns whether the introducer may authenticate `userid`.
+    fn in_scope(&self, userid: &UserID) -> bool {
+        self.regex.as_ref().map(|r| r.matches_userid(userid)).unwrap_or(true)
+    }
+}
+
+/// A path from a trust root to a binding.
+#[derive(Clone, Debug)]
+pub struct Path {
+    /// The trust root.
+    pub root: Fingerprint,
+    /// The certifications, starting at the trust root.
+    ///
+    /// If the target is a trust root, this is empty.
+    pub certifications: Vec<Certification>,
+    /// The trust amount of this path.
+    pub amount: usize,
+}
+
+/// The result of authenticating a binding.
+#[derive(Debug)]
+pub struct Authentication {
+    /// The paths that were used.
+    pub paths: Vec<Path>,
+    /// The aggregated trust amount, at most the required amount.
+    pub amount: usize,
+}
+
+/// A certificate that can take part in the web of trust.
+struct Node {
+    /// The User IDs that are valid at the reference time.
+    userids: Vec<UserID>,
+    /// The valid certifications of this certificate's bindings.
+    certified_by: Vec<Certification>,
+}
+
+/// A web of trust network.
+pub struct Network {
+    roots: HashSet<Fingerprint>,
+    nodes: HashMap<Fingerprint, Node>,
+}
+
+impl Network {
+    /// Builds the network from `certs` as of `time`.
+    ///
+    /// Trust roots that are not in `certs`, or that are not valid,
+    /// are ignored.
+    pub fn new(certs: &[Cert], policy: &dyn Policy, time: SystemTime,
+               roots: &[Fingerprint])
+               -> Self
+    {
+        // The certificates that are valid, alive, and not revoked.
+        let live: HashMap<Fingerprint, &Cert> = certs.iter()
+            .filter(|cert| match cert.with_policy(policy, time) {
+                Ok(vc) => vc.alive().is_ok()
+                    && ! matches!(vc.revocation_status(),
+                                  RevocationStatus::Revoked(_)),
+                Err(_) => false,
+            })
+            .map(|cert| (cert.fingerprint(), cert))
+            .collect();
+
+        // Maps key IDs of certification capable keys to certificates.
+        let mut by_keyid: HashMap<KeyID, Vec<&Cert>> = HashMap::new();
+        for cert in live.values() {
+            let vc = cert.with_policy(policy, time).expect("valid");
+            for ka in vc.keys().for_certification().alive().revoked(false) {
+                by_keyid.entry(ka.key().keyid()).or_default().push(cert);
+            }
+        }
+
+        let mut nodes = HashMap::new();
+        for (fp, cert) in live.iter() {
+            let vc = cert.with_policy(policy, time).expect("valid");
+            let mut userids = Vec::new();
+            let mut certified_by = Vec::new();
+
+            for ua in vc.userids() {
+                if let RevocationStatus::Revoked(_) = ua.revocation_status() {
+                    continue;
+                }
+                userids.push(ua.userid().clone());
+
+                // The newest valid certification by every issuer.
+                let mut newest: HashMap<Fingerprint, Certification> =
+                    HashMap::new();
+                for sig in ua.certifications() {
+                    let issuer = match verify_certification(
+                        sig, cert, ua.userid(), &by_keyid, policy, time)
+                    {
+                        Some(issuer) => issuer,
+                        None => continue,
+                    };
+                    let creation_time = sig.signature_creation_time()
+                        .expect("valid signatures have a creation time");
+                    if newest.get(&issuer)
+                        .map(|c| c.creation_time >= creation_time)
+                        .unwrap_or(false)
+                    {
+                        continue;
+                    }
+
+                    let (depth, amount) =
+                        sig.trust_signature().unwrap_or((0, 120));
+                    let regex = if depth > 0 {
+                        match RegexSet::from_signature(sig) {
+                            Ok(r) if r.matches_everything() => None,
+                            Ok(r) => Some(r),
+                            // Unparsable scopes match nothing.
+                            Err(_) => continue,
+                        }
+                    } else {
+                        None
+                    };
+
+                    newest.insert(issuer.clone(), Certification {
+                        issuer,
+                        target: fp.clone(),
+                        userid: ua.userid().clone(),
+                        creation_time,
+                        depth,
+                        amount,
+                        regex,
+                    });
+                }
+
+                // Drop certifications revoked by their issuer.
+                for c in newest.into_values() {
+                    let revoked = ua.other_revocations().any(|rev| {
+                        rev.typ() == SignatureType::CertificationRevocation
+                            && rev.signature_creation_time()
+                                .map(|t| t >= c.creation_time && t <= time)
+                                .unwrap_or(false)
+                            && verify_certification(
+                                rev, cert, ua.userid(), &by_keyid,
+                                policy, time).as_ref() == Some(&c.issuer)
+                    });
+                    if ! revoked && c.amount > 0 {
+                        certified_by.push(c);
+                    }
+                }
+            }
+
+            nodes.insert(fp.clone(), Node { userids, certified_by });
+        }
+
+        Network {
+            roots: roots.iter().filter(|r| nodes.contains_key(r))
+                .cloned().collect(),
+            nodes,
+        }
+    }
+
+    /// Returns whether `fp` is a (valid) trust root.
+    pub fn is_root(&self, fp: &Fingerprint) -> bool {
+        self.roots.contains(fp)
+    }
+
+    /// Returns whether the binding is valid at the reference time.
+    pub fn has_binding(&self, fp: &Fingerprint, userid: &UserID) -> bool {
+        self.nodes.get(fp).map(|n| n.userids.contains(userid)).unwrap_or(false)
+    }
+
+    /// Returns the User IDs of `fp` that are valid at the reference
+    /// time.
+    pub fn userids(&self, fp: &Fingerprint) -> &[UserID] {
+        self.nodes.get(fp).map(|n| &n.userids[..]).unwrap_or(&[])
+    }
+
+    /// Returns the certificates that have the given valid User ID.
+    pub fn certs_with_userid<F>(&self, matches: F)
+                                -> Vec<(Fingerprint, UserID)>
+        where F: Fn(&UserID) -> bool
+    {
+        let mut bindings: Vec<_> = self.nodes.iter()
+            .flat_map(|(fp, node)| node.userids.iter()
+                      .filter(|u| matches(u))
+                      .map(move |u| (fp.clone(), u.clone())))
+            .collect();
+        bindings.sort_by(|a, b| a.0.cmp(&b.0));
+        bindings
+    }
+
+    /// Authenticates the binding between `target` and `userid`.
+    ///
+    /// Stops looking for more paths once the `required` trust amount
+    /// is reached.
+    pub fn authenticate(&self, target: &Fingerprint, userid: &UserID,
+                        required: usize)
+                        -> Authentication
+    {
+        let mut auth = Authentication { paths: Vec::new(), amount: 0 };
+        if ! self.has_binding(target, userid) {
+            return auth;
+        }
+
+        // Trust roots authenticate their own User IDs.
+        if self.is_root(target) {
+            auth.paths.push(Path {
+                root: target.clone(),
+                certifications: Vec::new(),
+                amount: FULLY_TRUSTED,
+            });
+            auth.amount = FULLY_TRUSTED.min(required);
+            return auth;
+        }
+
+        // The remaining trust amount of every certification.
+        let mut residual: HashMap<(Fingerprint, Fingerprint), usize> =
+            HashMap::new();
+        while auth.amount < required {
+            let path = match self.best_path(target, userid, &residual) {
+                Some(path) => path,
+                None => break,
+            };
+            for c in &path.certifications {
+                let r = residual.entry((c.issuer.clone(), c.target.clone()))
+                    .or_insert(c.amount as usize);
+                *r -= path.amount;
+            }
+            auth.amount = (auth.amount + path.amount).min(required);
+            auth.paths.push(path);
+        }
+
+        auth
+    }
+
+    /// Checks that `path` is a valid path from its first element to
+    /// the binding between its last element and `userid`.
+    ///
+    /// The first element is considered to be a trust root.
+    pub fn check_path(&self, path: &[Fingerprint], userid: &UserID)
+                      -> Result<Path>
+    {
+        let (root, target) = match (path.first(), path.last()) {
+            (Some(root), Some(target)) => (root, target),
+            _ => return Err(anyhow::anyhow!("The path is empty")),
+        };
+        for fp in path {
+            if ! self.nodes.contains_key(fp) {
+                return Err(anyhow::anyhow!(
+                    "{} is not a valid certificate at the reference time", fp));
+            }
+        }
+        if ! self.has_binding(target, userid) {
+            return Err(anyhow::anyhow!(
+                "{} has no valid User ID {:?}", target,
+                String::from_utf8_lossy(userid.value())));
+        }
+        if path.len() == 1 {
+            return Ok(Path {
+                root: root.clone(),
+                certifications: Vec::new(),
+                amount: FULLY_TRUSTED,
+            });
+        }
+
+        let mut certifications = Vec::new();
+        for (i, hop) in path.windows(2).enumerate() {
+            let (issuer, certified) = (&hop[0], &hop[1]);
+            // The number of hops after this one.
+            let remaining = path.len() - 2 - i;
+            let candidates: Vec<&Certification> =
+                self.nodes[certified].certified_by.iter()
+                .filter(|c| &c.issuer == issuer)
+                .filter(|c| remaining > 0 || &c.userid == userid)
+                .collect();
+            if candidates.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "{} did not certify {}{}", issuer, certified,
+                    if remaining == 0 {
+                        format!(" with the User ID {:?}",
+                                String::from_utf8_lossy(userid.value()))
+                    } else {
+                        "".into()
+                    }));
+            }
+
+            let c = candidates.iter()
+                .filter(|c| c.depth as usize >= remaining)
+                .filter(|c| remaining == 0 || c.in_scope(userid))
+                .max_by_key(|c| c.amount)
+                .ok_or_else(|| {
+                    if candidates.iter().all(|c| (c.depth as usize) < remaining) {
+                        anyhow::anyhow!(
+                            "{} did not make {} a trusted introducer \
+                             for {} more hop(s)", issuer, certified, remaining)
+                    } else {
+                        anyhow::anyhow!(
+                            "{} may not authenticate {:?} on behalf of {}",
+                            certified,
+                            String::from_utf8_lossy(userid.value()),
+                            issuer)
+                    }
+                })?;
+            certifications.push((*c).clone());
+        }
+
+        Ok(Path {
+            root: root.clone(),
+            amount: certifications.iter().map(|c| c.amount as usize)
+                .min().unwrap_or(FULLY_TRUSTED),
+            certifications,
+        })
+    }
+
+    /// Finds the path to the binding with the largest trust amount,
+    /// preferring shorter paths.
+    fn best_path(&self, target: &Fingerprint, userid: &UserID,
+                 residual: &HashMap<(Fingerprint, Fingerprint), usize>)
+                 -> Option<Path>
+    {
+        let capacity = |c: &Certification| {
+            residual.get(&(c.issuer.clone(), c.target.clone()))
+                .cloned()
+                .unwrap_or(c.amount as usize)
+        };
+
+        // We search backwards from the target.  A state is an
+        // introducer together with the certifications from it to
+        // the target.
+        let mut queue = BinaryHeap::new();
+        for c in &self.nodes[target].certified_by {
+            if &c.userid == userid && capacity(c) > 0 {
+                queue.push(State {
+                    amount: capacity(c),
+                    node: c.issuer.clone(),
+                    path: vec![c.clone()],
+                });
+            }
+        }
+
+        // The best states that we have expanded, for every node.
+        let mut done: HashMap<Fingerprint, Vec<(usize, usize)>> =
+            HashMap::new();
+
+        while let Some(state) = queue.pop() {
+            if self.is_root(&state.node) {
+                let mut certifications = state.path;
+                certifications.reverse();
+                return Some(Path {
+                    root: state.node,
+                    certifications,
+                    amount: state.amount,
+                });
+            }
+
+            // Skip states that are not better than one we expanded.
+            let hops = state.path.len();
+            let expanded = done.entry(state.node.clone()).or_default();
+            if expanded.iter().any(|(a, h)| *a >= state.amount && *h <= hops) {
+                continue;
+            }
+            expanded.push((state.amount, hops));
+
+            for c in &self.nodes[&state.node].certified_by {
+                // The introducer must be trusted for the remaining
+                // hops, and authenticate the User ID.
+                if (c.depth as usize) < hops || ! c.in_scope(userid) {
+                    continue;
+                }
+                if c.issuer == *target
+                    || state.path.iter().any(|p| p.target == c.issuer)
+                {
+                    continue;
+                }
+                let amount = state.amount.min(capacity(c));
+                if amount == 0 {
+                    continue;
+                }
+                let mut path = state.path.clone();
+                path.push(c.clone());
+                queue.push(State { amount, node: c.issuer.clone(), path });
+            }
+        }
+
+        None
+    }
+}
+
+/// A partial path in the search.
+struct State {
+    amount: usize,
+    node: Fingerprint,
+    /// The certifications from `node` to the target, in reverse.
+    path: Vec<Certification>,
+}
+
+impl Ord for State {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Larger amounts first, then shorter paths.
+        self.amount.cmp(&other.amount)
+            .then_with(|| other.path.len().cmp(&self.path.len()))
+    }
+}
+
+impl PartialOrd for State {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for State {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for State {}
+
+/// Verifies the third-party certification or certification
+/// revocation `sig` of the binding between `cert` and `userid`.
+///
+/// Returns the issuer's fingerprint, if the signature is valid at
+/// `time`.
+fn verify_certification(sig: &Signature, cert: &Cert, userid: &UserID,
+                        by_keyid: &HashMap<KeyID, Vec<&Cert>>,
+                        policy: &dyn Policy, time: SystemTime)
+                        -> Option<Fingerprint>
+{
+    if policy.signature(sig, HashAlgoSecurity::CollisionResistance).is_err()
+        || sig.signature_alive(time, Duration::new(0, 0)).is_err()
+    {
+        return None;
+    }
+
+    for issuer in sig.get_issuers() {
+        for candidate in by_keyid.get(&KeyID::from(&issuer)).into_iter().flatten() {
+            if candidate.fingerprint() == cert.fingerprint() {
+                // Not a third-party certification.
+                continue;
+            }
+            let vc = match candidate.with_policy(policy, time) {
+                Ok(vc) => vc,
+                Err(_) => continue,
+            };
+            for ka in vc.keys().for_certification()
+                .key_handle(issuer.clone())
+            {
+                let mut sig = sig.clone();
+                let ok = if sig.typ() == SignatureType::CertificationRevocation {
+                    sig.verify_userid_revocation(
+                        ka.key(), cert.primary_key().key(), userid)
+                } else {
+                    sig.verify_userid_binding(
+                        ka.key(), cert.primary_key().key(), userid)
+                };
+                if ok.is_ok() {
+                    return Some(candidate.fingerprint());
+                }
+            }
+        }
+    }
+
+    None
+}
@@ -0,0 +1,56 @@
+#!/bin/bash -e
+
+sq=""
+
+SCRIPT_DIR=$( cd -- "$( dirname -- "${BASH_SOURCE[0]}" )" &> /dev/null && pwd )
+source $SCRIPT_DIR/common.sh
+
+random=$(head /dev/urandom | tr -dc 'a-zA-Z0-9' | fold -w "10" | head -n 1)
+
+create_tmp_dir data
+trap "erase_tmp_dir $data" EXIT
+
+key_name="audit-test-$random"
+log="$data/audit.log"
+message="$data/message.txt"
+echo "Audit me" > "$message"
+
+comm "generate key"
+$sq key generate --cipher-suite=rsa2k --userid="Audit Test <xyz@xyz.xyz>" --dsm-key="$key_name"
+
+comm "sign and decrypt with audit log"
+$sq key extract-cert --dsm-key="$key_name" > "$data/cert.asc"
+export SQ_DSM_AUDIT_LOG="$log"
+$sq sign --dsm-key="$key_name" "$message" > "$data/message.sig"
+$sq encrypt --recipient-cert="$data/cert.asc" "$message" \
+    | $sq decrypt --dsm-key="$key_name" > "$data/message.dec"
+unset SQ_DSM_AUDIT_LOG
+diff "$message" "$data/message.dec"
+
+comm "check records"
+grep -q '"operation":"sign"' "$log"
+grep -q '"operation":"decrypt"' "$log"
+grep -q '"outcome":"success"' "$log"
+if grep -q '"outcome":"failure"' "$log"
+then
+	echo "unexpected failure in audit log"
+	exit 1
+fi
+
+comm "check hash chain"
+prev=0000000000000000000000000000000000000000000000000000000000000000
+while IFS= read -r line
+do
+	echo "$line" | grep -q "\"prev_sha256\":\"$prev\""
+	prev=$(printf '%s' "$line" | sha256sum | cut -d' ' -f1)
+done < "$log"
+
+comm "dsm-audit-verify"
+$sq key dsm-audit-verify "$log"
+SQ_DSM_AUDIT_LOG="$log" $sq key dsm-audit-verify
+sed -i '1s/"sign"/"decrypt"/' "$log"
+if $sq key dsm-audit-verify "$log"
+then
+	echo "tampered audit log verified"
+	exit 1
+fi
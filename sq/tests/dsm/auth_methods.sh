@@ -0,0 +1,49 @@
+#!/bin/bash -e
+
+sq=""
+
+SCRIPT_DIR=$( cd -- "$( dirname -- "${BASH_SOURCE[0]}" )" &> /dev/null && pwd )
+# shellcheck source=./common.sh
+source $SCRIPT_DIR/common.sh
+
+data=""
+create_tmp_dir data
+trap 'erase_tmp_dir $data' EXIT
+
+random=$(head /dev/urandom | tr -dc 'a-zA-Z0-9' | fold -w "10" | head -n 1)
+key_name="test-sq-auth-methods-$random"
+message="$data/message.txt"
+
+comm "generate key (API key)"
+$sq key generate --dsm-key="$key_name" --userid="Alice <alice@openpgp.example>" --cipher-suite="${cipher_suite:-cv25519}"
+$sq key extract-cert --dsm-key="$key_name" > "$data/alice.asc"
+printf "Y el verso cae al alma como al pasto el rocío.\n" > "$message"
+
+comm "obtain a bearer token"
+curl --silent --fail -X POST \
+    -H "Authorization: Basic $FORTANIX_API_KEY" \
+    "$FORTANIX_API_ENDPOINT/sys/v1/session/auth" \
+    | sed -n 's/.*"access_token" *: *"\([^"]*\)".*/\1/p' > "$data/token"
+test -s "$data/token"
+
+comm "sign with bearer token from file"
+env -u FORTANIX_API_KEY FORTANIX_BEARER_TOKEN_FILE="$data/token" \
+    $sq sign --dsm-key="$key_name" "$message" > "$message.signed"
+$sq verify --signer-cert="$data/alice.asc" "$message.signed"
+
+comm "sign with bearer token from env"
+env -u FORTANIX_API_KEY FORTANIX_BEARER_TOKEN="$(cat "$data/token")" \
+    $sq sign --dsm-key="$key_name" "$message" > "$message.signed2"
+$sq verify --signer-cert="$data/alice.asc" "$message.signed2"
+
+comm "API key takes precedence over a bad bearer token"
+FORTANIX_BEARER_TOKEN="invalid" \
+    $sq sign --dsm-key="$key_name" "$message" > "$message.signed3"
+$sq verify --signer-cert="$data/alice.asc" "$message.signed3"
+
+comm "bad bearer token fails"
+if env -u FORTANIX_API_KEY FORTANIX_BEARER_TOKEN="invalid" \
+    $sq sign --dsm-key="$key_name" "$message" > /dev/null 2>&1; then
+    echo "signing with an invalid token succeeded"
+    exit 1
+fi
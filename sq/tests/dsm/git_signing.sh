@@ -0,0 +1,52 @@
+#!/bin/bash -e
+
+sq=""
+
+SCRIPT_DIR=$( cd -- "$( dirname -- "${BASH_SOURCE[0]}" )" &> /dev/null && pwd )
+# shellcheck source=./common.sh
+source $SCRIPT_DIR/common.sh
+
+data=""
+create_tmp_dir data
+
+trap 'erase_tmp_dir $data' EXIT
+
+random=$(head /dev/urandom | tr -dc 'a-zA-Z0-9' | fold -w "10" | head -n 1)
+key_name="test-sq-git-signing-$random"
+
+# git looks for gpg.program in PATH, sq acts like gpg if invoked as sq-gpg
+ln -s "$(realpath $sq)" "$data/sq-gpg"
+export PATH="$data:$PATH"
+
+comm "generate-keys"
+$sq key generate --dsm-key="$key_name" --userid="Alice <alice@openpgp.example>"
+$sq key extract-cert --dsm-key="$key_name" > "$data/alice.asc"
+export SQ_GPG_KEYRING="$data/alice.asc"
+
+comm "signed commit"
+git init -q "$data/repo"
+cd "$data/repo"
+git config user.name "Alice"
+git config user.email "alice@openpgp.example"
+git config user.signingkey "$key_name"
+git config gpg.program sq-gpg
+echo "Y el verso cae al alma como al pasto el rocío." > message.txt
+git add message.txt
+git commit -q -S -m "Signed commit"
+
+comm "verify-commit"
+git verify-commit HEAD
+git log --show-signature -1 | grep -q "Good"
+
+comm "signed tag"
+git tag -s -m "Signed tag" v1
+git verify-tag v1
+
+comm "tampered signature"
+sq-gpg --status-fd=1 -bsau "$key_name" < message.txt > message.sig
+echo "tampered" >> message.txt
+if sq-gpg --status-fd=1 --verify message.sig - < message.txt | grep -q GOODSIG
+then
+    echo "Tampered data verified"
+    exit 1
+fi
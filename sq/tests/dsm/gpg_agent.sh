@@ -0,0 +1,43 @@
+#!/bin/bash -e
+
+sq=""
+
+SCRIPT_DIR=$( cd -- "$( dirname -- "${BASH_SOURCE[0]}" )" &> /dev/null && pwd )
+# shellcheck source=./common.sh
+source $SCRIPT_DIR/common.sh
+
+data=""
+create_tmp_dir data
+
+export GNUPGHOME="$data/gnupg"
+mkdir -m 700 "$GNUPGHOME"
+
+agent_pid=""
+trap 'kill $agent_pid 2> /dev/null; erase_tmp_dir $data' EXIT
+
+random=$(head /dev/urandom | tr -dc 'a-zA-Z0-9' | fold -w "10" | head -n 1)
+key_name="test-sq-gpg-agent-$random"
+message="$data/message.txt"
+
+comm "generate-keys"
+$sq key generate --dsm-key="$key_name" --userid="Alice <alice@openpgp.example>" --cipher-suite="${cipher_suite:-cv25519}"
+$sq key extract-cert --dsm-key="$key_name" > "$data/alice.asc"
+gpg --batch --import "$data/alice.asc"
+
+comm "start agent"
+socket=$(gpgconf --list-dirs agent-socket)
+$sq --force key gpg-agent --dsm-key="$key_name" --socket="$socket" &
+agent_pid=$!
+while [ ! -S "$socket" ]; do sleep 1; done
+
+printf "Y el verso cae al alma como al pasto el rocío.\n" > "$message"
+
+comm "gpg sign, sq verify"
+gpg --batch --no-autostart --local-user "alice@openpgp.example" \
+    --detach-sign --output "$message.sig" "$message"
+$sq verify --signer-cert="$data/alice.asc" --detached="$message.sig" "$message"
+
+comm "sq encrypt, gpg decrypt"
+$sq encrypt --recipient-cert="$data/alice.asc" "$message" > "$message.pgp"
+gpg --batch --no-autostart --decrypt --output "$data/decrypted.txt" "$message.pgp"
+diff "$message" "$data/decrypted.txt"
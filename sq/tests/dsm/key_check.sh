@@ -0,0 +1,24 @@
+#!/bin/bash -e
+
+sq=""
+
+SCRIPT_DIR=$( cd -- "$( dirname -- "${BASH_SOURCE[0]}" )" &> /dev/null && pwd )
+source $SCRIPT_DIR/common.sh
+
+random=$(head /dev/urandom | tr -dc 'a-zA-Z0-9' | fold -w "10" | head -n 1)
+
+for cs in rsa2k nistp256 cv25519
+do
+	key_name="check-test-$cs-$random"
+
+	comm "generate $cs key"
+	$sq key generate --cipher-suite="$cs" --userid="Check Test <xyz@xyz.xyz>" --dsm-key="$key_name"
+
+	comm "check $cs key"
+	$sq key dsm-check --dsm-key="$key_name" | grep "^$key_name: OK$"
+
+	comm "repair is a no-op on a consistent key"
+	$sq key dsm-check --repair --dsm-key="$key_name" | grep "^$key_name: OK$"
+
+	$sq key dsm-delete --yes --dsm-key="$key_name"
+done
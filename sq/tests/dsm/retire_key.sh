@@ -0,0 +1,38 @@
+#!/bin/bash -e
+
+sq=""
+
+SCRIPT_DIR=$( cd -- "$( dirname -- "${BASH_SOURCE[0]}" )" &> /dev/null && pwd )
+source $SCRIPT_DIR/common.sh
+
+random=$(head /dev/urandom | tr -dc 'a-zA-Z0-9' | fold -w "10" | head -n 1)
+
+comm "generate keys"
+for what in disable deactivate delete
+do
+	$sq key generate --userid="Retire-Test-$what <xyz@xyz.xyz>" --dsm-key="retire-test-$what-$random"
+done
+
+comm "disable"
+$sq key dsm-disable --yes --dsm-key="retire-test-disable-$random"
+
+comm "deactivate"
+$sq key dsm-disable --yes --deactivate --dsm-key="retire-test-deactivate-$random"
+
+comm "delete"
+$sq key dsm-delete --yes --dsm-key="retire-test-delete-$random"
+if $sq key info --dsm-key="retire-test-delete-$random" 2> /dev/null
+then
+	echo "key retire-test-delete-$random still exists"
+	exit 1
+fi
+
+comm "refuse without confirmation"
+$sq key generate --userid="Retire-Test-abort <xyz@xyz.xyz>" --dsm-key="retire-test-abort-$random"
+if echo n | $sq key dsm-delete --dsm-key="retire-test-abort-$random"
+then
+	echo "dsm-delete proceeded without confirmation"
+	exit 1
+fi
+$sq key info --dsm-key="retire-test-abort-$random"
+$sq key dsm-delete --yes --dsm-key="retire-test-abort-$random"
@@ -0,0 +1,62 @@
+#!/bin/bash -e
+
+sq=""
+
+SCRIPT_DIR=$( cd -- "$( dirname -- "${BASH_SOURCE[0]}" )" &> /dev/null && pwd )
+# shellcheck source=./common.sh
+source $SCRIPT_DIR/common.sh
+
+data=""
+create_tmp_dir data
+trap 'erase_tmp_dir $data' EXIT
+
+random=$(head /dev/urandom | tr -dc 'a-zA-Z0-9' | fold -w "10" | head -n 1)
+key_name="test-sq-cache-$random"
+message="$data/message.txt"
+
+export XDG_CACHE_HOME="$data/cache"
+export SQ_DSM_CACHE=disk
+# Runs a command with DSM out of reach.
+offline="env http_proxy=http://127.0.0.1:9 no_proxy="
+
+comm "generate key"
+$sq key generate --dsm-key="$key_name" --userid="Alice <alice@openpgp.example>" --cipher-suite="${cipher_suite:-cv25519}"
+printf "Y el verso cae al alma como al pasto el rocío.\n" > "$message"
+
+comm "warm the cache"
+$sq key extract-cert --dsm-key="$key_name" > "$data/alice.asc"
+test -s "$XDG_CACHE_HOME/sq-dsm/sobjects.json"
+
+comm "extract certificate from the cache, without reaching DSM"
+$offline $sq key extract-cert --dsm-key="$key_name" > "$data/alice-cached.asc"
+cmp "$data/alice.asc" "$data/alice-cached.asc"
+
+comm "entries are not shared with other identities"
+if FORTANIX_API_KEY="invalid" \
+    $sq key extract-cert --dsm-key="$key_name" > /dev/null 2>&1; then
+    echo "cache entry was used by another identity"
+    exit 1
+fi
+
+comm "signing still reaches DSM"
+$sq sign --dsm-key="$key_name" "$message" > "$message.signed"
+$sq verify --signer-cert="$data/alice.asc" "$message.signed"
+if $offline $sq sign --dsm-key="$key_name" "$message" > /dev/null 2>&1; then
+    echo "signing without reaching DSM succeeded"
+    exit 1
+fi
+
+comm "expired entries are fetched again"
+if SQ_DSM_CACHE_TTL=0 $offline \
+    $sq key extract-cert --dsm-key="$key_name" > /dev/null 2>&1; then
+    echo "expired cache entry was used"
+    exit 1
+fi
+
+comm "deleting the key drops its entries"
+$sq key extract-cert --dsm-key="$key_name" > /dev/null
+$sq key dsm-delete --yes --dsm-key="$key_name"
+if $sq key extract-cert --dsm-key="$key_name" > /dev/null 2>&1; then
+    echo "deleted key still in the cache"
+    exit 1
+fi
@@ -0,0 +1,52 @@
+#!/bin/bash -e
+
+sq=""
+
+SCRIPT_DIR=$( cd -- "$( dirname -- "${BASH_SOURCE[0]}" )" &> /dev/null && pwd )
+# shellcheck source=./common.sh
+source $SCRIPT_DIR/common.sh
+
+data=""
+create_tmp_dir data
+
+trap 'erase_tmp_dir $data' EXIT
+
+random=$(head /dev/urandom | tr -dc 'a-zA-Z0-9' | fold -w "10" | head -n 1)
+key_name="test-sq-sop-$random"
+
+# sq acts as a SOP implementation if invoked as sq-sop
+ln -s "$(realpath $sq)" "$data/sq-sop"
+sq="$(realpath $sq)"
+sop="$data/sq-sop"
+cd "$data"
+
+comm "generate-keys"
+$sq key generate --dsm-key="$key_name" --userid="Alice <alice@openpgp.example>"
+$sq key extract-cert --dsm-key="$key_name" > alice.cert
+echo "Y el verso cae al alma como al pasto el rocío." > message.txt
+
+comm "sign-verify"
+$sop sign "@DSM:$key_name" < message.txt > message.sig
+$sop verify message.sig alice.cert < message.txt
+$sop verify message.sig "@DSM:$key_name" < message.txt
+
+comm "encrypt-decrypt"
+$sop encrypt --sign-with "@DSM:$key_name" alice.cert \
+    < message.txt > message.pgp
+$sop decrypt --verifications-out verifications --verify-with alice.cert \
+    "@DSM:$key_name" < message.pgp > decrypted.txt
+diff message.txt decrypted.txt
+test "$(wc -l < verifications)" -eq 1
+
+comm "inline-sign-verify"
+$sop inline-sign "@DSM:$key_name" < message.txt > message.signed
+$sop inline-verify alice.cert < message.signed > verified.txt
+diff message.txt verified.txt
+
+comm "tampered signature"
+echo "tampered" >> message.txt
+if $sop verify message.sig alice.cert < message.txt
+then
+    echo "Tampered data verified"
+    exit 1
+fi
@@ -28,6 +28,8 @@ encrypted_nosign=$data/message.txt.encrypted.nosign
 encrypted_signed=$data/message.txt.encrypted.signed
 decrypted_nosign=$data/decrypted.txt.nosign
 decrypted_signed=$data/decrypted.txt.signed
+encrypted_dsm_cert=$data/message.txt.encrypted.dsm_cert
+decrypted_dsm_cert=$data/decrypted.txt.dsm_cert
 signed=$data/message.signed.asc
 
 random=$(head /dev/urandom | tr -dc 'a-zA-Z0-9' | fold -w "10" | head -n 1)
@@ -58,6 +60,17 @@ my_cat "$signed"
 comm "verify"
 $sq verify --signer-cert="$alice_public" "$signed"
 
+comm "verify with certificate from DSM"
+$sq verify $apikey --signer-dsm-key="$alice_key_name" "$signed"
+
+comm "batch sign"
+mkdir -p "$data/batch/sub"
+for f in a b sub/c; do cp "$message" "$data/batch/$f.txt"; done
+$sq sign $apikey --batch --jobs=2 --dsm-key="$alice_key_name" "$data/batch"
+for f in a b sub/c; do
+    $sq verify --signer-cert="$alice_public" --detached="$data/batch/$f.txt.asc" "$data/batch/$f.txt"
+done
+
 comm "encrypt to Alice, no signatures"
 $sq encrypt --recipient-cert "$alice_public" "$message" --output "$encrypted_nosign"
 my_cat "$encrypted_nosign"
@@ -76,4 +89,23 @@ $sq decrypt $apikey --signer-cert="$bob_dsm" --signer-cert="$bob_local_pub" --ds
 
 diff "$message" "$decrypted_signed"
 
+comm "encrypt to Alice and Bob with certificates from DSM"
+$sq encrypt $apikey --recipient-dsm-key="$alice_key_name" --recipient-dsm-key="$bob_key_name" --recipient-cert "$bob_local_pub" "$message" --output "$encrypted_dsm_cert"
+my_cat "$encrypted_dsm_cert"
+
+comm "decrypt"
+$sq decrypt $apikey --dsm-key="$bob_key_name" "$encrypted_dsm_cert" --output "$decrypted_dsm_cert"
+
+diff "$message" "$decrypted_dsm_cert"
+
+comm "decrypt, trying several DSM keys"
+$sq decrypt $apikey --dsm-key="$alice_key_name" --dsm-key="$bob_key_name" "$encrypted_nosign" --output "$decrypted_dsm_cert.several"
+
+diff "$message" "$decrypted_dsm_cert.several"
+
+comm "decrypt, discovering the DSM key"
+XDG_CACHE_HOME="$data/cache" $sq decrypt $apikey --dsm-auto "$encrypted_nosign" --output "$decrypted_dsm_cert.auto"
+diff "$message" "$decrypted_dsm_cert.auto"
+test -s "$data/cache/sq-dsm/recipients.json"
+
 echo "SUCCESS"
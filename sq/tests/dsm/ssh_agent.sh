@@ -0,0 +1,60 @@
+#!/bin/bash -e
+
+sq=""
+
+SCRIPT_DIR=$( cd -- "$( dirname -- "${BASH_SOURCE[0]}" )" &> /dev/null && pwd )
+# shellcheck source=./common.sh
+source $SCRIPT_DIR/common.sh
+
+data=""
+create_tmp_dir data
+
+export GNUPGHOME="$data/gnupg"
+mkdir -m 700 "$GNUPGHOME"
+gpg="gpg --batch --pinentry-mode=loopback --passphrase="
+
+agent_pid=""
+trap 'kill $agent_pid 2> /dev/null; erase_tmp_dir $data' EXIT
+
+case "${cipher_suite:-cv25519}" in
+    cv25519) algo=ed25519 ;;
+    nistp256) algo=nistp256 ;;
+    rsa2k) algo=rsa2048 ;;
+    *) echo "unsupported cipher suite $cipher_suite"; exit 1 ;;
+esac
+
+random=$(head /dev/urandom | tr -dc 'a-zA-Z0-9' | fold -w "10" | head -n 1)
+key_name="test-sq-ssh-agent-$random"
+socket="$data/ssh-agent.sock"
+message="$data/message.txt"
+
+comm "generate key with authentication subkey"
+$gpg --quick-gen-key "Alice <alice@openpgp.example>" "$algo" cert,sign never
+fpr=$($gpg --list-keys --with-colons | awk -F: '/^fpr:/ { print $10; exit }')
+$gpg --quick-add-key "$fpr" "$algo" auth never
+$gpg --export-secret-keys > "$data/alice.pgp"
+$gpg --export-ssh-key "$fpr" | cut -d' ' -f1,2 > "$data/expected.pub"
+
+comm "import key into DSM"
+$sq key dsm-import --dsm-key="$key_name" < "$data/alice.pgp"
+
+comm "export-ssh"
+$sq key export-ssh --dsm-key="$key_name" > "$data/alice.pub"
+cut -d' ' -f1,2 "$data/alice.pub" | diff "$data/expected.pub" -
+$sq key export-ssh "$data/alice.pgp" | diff "$data/alice.pub" -
+
+comm "start agent"
+$sq key ssh-agent --dsm-key="$key_name" --socket="$socket" &
+agent_pid=$!
+while [ ! -S "$socket" ]; do sleep 1; done
+export SSH_AUTH_SOCK="$socket"
+
+comm "ssh-add -L"
+ssh-add -L | cut -d' ' -f1,2 | diff "$data/expected.pub" -
+
+comm "ssh-keygen sign and verify"
+printf "Y el verso cae al alma como al pasto el rocío.\n" > "$message"
+ssh-keygen -Y sign -f "$data/alice.pub" -n file "$message"
+echo "alice@openpgp.example $(cat "$data/expected.pub")" > "$data/allowed_signers"
+ssh-keygen -Y verify -f "$data/allowed_signers" -I alice@openpgp.example \
+    -n file -s "$message.sig" < "$message"
@@ -0,0 +1,78 @@
+#!/bin/bash -e
+
+sq=""
+
+SCRIPT_DIR=$( cd -- "$( dirname -- "${BASH_SOURCE[0]}" )" &> /dev/null && pwd )
+# shellcheck source=./common.sh
+source $SCRIPT_DIR/common.sh
+
+data=""
+create_tmp_dir data
+
+random=$(head /dev/urandom | tr -dc 'a-zA-Z0-9' | fold -w "10" | head -n 1)
+aes_name="test-sq-symmetric-$random"
+other_name="test-sq-symmetric-other-$random"
+message="$data/message.txt"
+printf "Y el verso cae al alma como al pasto el rocío.\n" > "$message"
+
+# sq cannot create AES keys, so we talk to the DSM API directly.
+token=$(curl --silent --fail -X POST \
+    -H "Authorization: Basic $FORTANIX_API_KEY" \
+    "$FORTANIX_API_ENDPOINT/sys/v1/session/auth" \
+    | sed -n 's/.*"access_token" *: *"\([^"]*\)".*/\1/p')
+test -n "$token"
+
+create_aes_key() {
+    curl --silent --fail -X POST \
+        -H "Authorization: Bearer $token" \
+        -H "Content-Type: application/json" \
+        -d "{\"name\": \"$1\", \"obj_type\": \"AES\", \"key_size\": 256,
+             \"key_ops\": [\"ENCRYPT\", \"DECRYPT\", \"APPMANAGEABLE\"]}" \
+        "$FORTANIX_API_ENDPOINT/crypto/v1/keys" \
+        | sed -n 's/.*"kid" *: *"\([^"]*\)".*/\1/p'
+}
+
+delete_key() {
+    if [ -n "$1" ]; then
+        curl --silent -X DELETE -H "Authorization: Bearer $token" \
+            "$FORTANIX_API_ENDPOINT/crypto/v1/keys/$1" > /dev/null || true
+    fi
+}
+
+comm "create AES keys"
+aes_kid=$(create_aes_key "$aes_name")
+other_kid=$(create_aes_key "$other_name")
+trap 'delete_key $aes_kid; delete_key $other_kid; erase_tmp_dir $data' EXIT
+test -n "$aes_kid"
+test -n "$other_kid"
+
+comm "encrypt with --symmetric-dsm-key"
+$sq encrypt --symmetric-dsm-key="$aes_name" "$message" > "$data/message.pgp"
+$sq packet dump "$data/message.pgp" > "$data/dump.txt"
+my_cat "$data/dump.txt"
+grep -q "Pk algo: Private/Experimental public key algorithm 100" "$data/dump.txt"
+
+comm "decrypt with --dsm-key"
+$sq decrypt --dsm-key="$aes_name" "$data/message.pgp" > "$data/decrypted.txt"
+diff "$message" "$data/decrypted.txt"
+
+comm "decrypt with --dsm-auto"
+$sq decrypt --dsm-auto "$data/message.pgp" > "$data/decrypted.txt"
+diff "$message" "$data/decrypted.txt"
+
+comm "encrypt with two AES keys"
+$sq encrypt --symmetric-dsm-key="$aes_name" \
+    --symmetric-dsm-key="$other_name" \
+    "$message" > "$data/message2.pgp"
+delete_key "$aes_kid"
+aes_kid=""
+$sq decrypt --dsm-key="$other_name" "$data/message2.pgp" \
+    > "$data/decrypted.txt"
+diff "$message" "$data/decrypted.txt"
+
+comm "decryption fails without the AES key"
+if $sq decrypt --dsm-key="$other_name" "$data/message.pgp" \
+    > /dev/null 2>&1; then
+    echo "decrypted without the AES key"
+    exit 1
+fi
@@ -0,0 +1,220 @@
+use std::fs::File;
+use std::io::Write;
+
+use assert_cli::Assert;
+use tempfile::TempDir;
+
+use sequoia_openpgp as openpgp;
+use openpgp::Result;
+use openpgp::cert::prelude::*;
+use openpgp::parse::Parse;
+use openpgp::serialize::Serialize;
+use openpgp::types::ReasonForRevocation;
+
+#[test]
+fn sq_import_export_lookup() -> Result<()> {
+    let tmp_dir = TempDir::new().unwrap();
+    let store = tmp_dir.path().join("pgp.cert.d");
+    let store = store.to_str().unwrap();
+
+    let (cert, _) = CertBuilder::general_purpose(
+        None, Some("Alice <alice@example.org>"))
+        .generate()?;
+    let key_pgp = tmp_dir.path().join("alice.pgp");
+    cert.as_tsk().serialize(&mut File::create(&key_pgp)?)?;
+    let key_pgp = key_pgp.to_str().unwrap();
+
+    Assert::cargo_binary("sq")
+        .with_args(&["--cert-store", store, "import", key_pgp])
+        .stderr().contains("imported")
+        .unwrap();
+
+    // The certificate is stored under its fingerprint, without
+    // secrets.
+    let hex = cert.fingerprint().to_hex().to_lowercase();
+    let stored = Cert::from_file(
+        tmp_dir.path().join("pgp.cert.d").join(&hex[..2]).join(&hex[2..]))?;
+    assert_eq!(stored, cert.clone().strip_secret_key_material());
+
+    // Importing again changes nothing.
+    Assert::cargo_binary("sq")
+        .with_args(&["--cert-store", store, "import", key_pgp])
+        .stderr().contains("unchanged")
+        .unwrap();
+
+    let fpr = cert.fingerprint().to_hex();
+    let subkey = cert.keys().subkeys().next().unwrap().keyid().to_hex();
+    for query in &[fpr.as_str(), subkey.as_str(),
+                   "alice@example.org", "<ALICE@example.org>",
+                   "Alice <alice@example.org>"] {
+        Assert::cargo_binary("sq")
+            .with_args(&["--cert-store", store, "lookup", query])
+            .stdout().contains(fpr.as_str())
+            .unwrap();
+    }
+
+    Assert::cargo_binary("sq")
+        .with_args(&["--cert-store", store, "lookup", "bob@example.org"])
+        .fails()
+        .unwrap();
+
+    let export = tmp_dir.path().join("export.pgp");
+    Assert::cargo_binary("sq")
+        .with_args(&["--cert-store", store, "export",
+                     "--output", export.to_str().unwrap()])
+        .unwrap();
+    assert_eq!(Cert::from_file(&export)?, stored);
+
+    Ok(())
+}
+
+#[test]
+fn sq_cert_store_verify_encrypt() -> Result<()> {
+    let tmp_dir = TempDir::new().unwrap();
+    let store = tmp_dir.path().join("pgp.cert.d");
+    let store = store.to_str().unwrap();
+
+    let (cert, _) = CertBuilder::general_purpose(
+        None, Some("Alice <alice@example.org>"))
+        .generate()?;
+    let key_pgp = tmp_dir.path().join("alice.pgp");
+    cert.as_tsk().serialize(&mut File::create(&key_pgp)?)?;
+    let key_pgp = key_pgp.to_str().unwrap();
+
+    let message = tmp_dir.path().join("message.txt");
+    File::create(&message)?.write_all(b"Hello world.\n")?;
+    let message = message.to_str().unwrap();
+    let sig = tmp_dir.path().join("message.sig");
+    let sig = sig.to_str().unwrap();
+    let encrypted = tmp_dir.path().join("message.pgp");
+    let encrypted = encrypted.to_str().unwrap();
+
+    Assert::cargo_binary("sq")
+        .with_args(&["--cert-store", store, "import", key_pgp])
+        .unwrap();
+    Assert::cargo_binary("sq")
+        .with_args(&["sign", "--detached", "--signer-key", key_pgp,
+                     "--output", sig, message])
+        .unwrap();
+
+    // Certificates from the store are not authenticated.
+    Assert::cargo_binary("sq")
+        .with_args(&["--cert-store", store, "verify", "--detached", sig,
+                     message])
+        .fails()
+        .stderr().contains("Good checksum")
+        .unwrap();
+
+    // Unless they are named explicitly.
+    Assert::cargo_binary("sq")
+        .with_args(&["--cert-store", store, "verify", "--detached", sig,
+                     "--signer-cert", &cert.fingerprint().to_hex(), message])
+        .stderr().contains("Good signature")
+        .unwrap();
+
+    Assert::cargo_binary("sq")
+        .with_args(&["--no-cert-store", "verify", "--detached", sig,
+                     "--signer-cert", &cert.fingerprint().to_hex(), message])
+        .fails()
+        .unwrap();
+
+    Assert::cargo_binary("sq")
+        .with_args(&["--cert-store", store, "encrypt",
+                     "--recipient-cert", &cert.fingerprint().to_hex(),
+                     "--output", encrypted, message])
+        .unwrap();
+    Assert::cargo_binary("sq")
+        .with_args(&["decrypt", "--recipient-key", key_pgp, encrypted])
+        .stdout().contains("Hello world.")
+        .unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn sq_cert_store_resolve_certs() -> Result<()> {
+    let tmp_dir = TempDir::new().unwrap();
+    let store = tmp_dir.path().join("pgp.cert.d");
+    let store = store.to_str().unwrap();
+
+    let message = tmp_dir.path().join("message.txt");
+    File::create(&message)?.write_all(b"Hello world.\n")?;
+    let message = message.to_str().unwrap();
+    let sig = tmp_dir.path().join("message.sig");
+    let sig = sig.to_str().unwrap();
+
+    let (alice, _) = CertBuilder::general_purpose(
+        None, Some("Alice <alice@example.org>"))
+        .generate()?;
+    let alice_pgp = tmp_dir.path().join("alice.pgp");
+    alice.as_tsk().serialize(&mut File::create(&alice_pgp)?)?;
+    let alice_pgp = alice_pgp.to_str().unwrap();
+
+    Assert::cargo_binary("sq")
+        .with_args(&["sign", "--detached", "--signer-key", alice_pgp,
+                     "--output", sig, message])
+        .unwrap();
+
+    // Bob revokes his User ID.
+    let (bob, _) = CertBuilder::general_purpose(
+        None, Some("Bob <bob@example.org>"))
+        .generate()?;
+    let mut signer = bob.primary_key().key().clone()
+        .parts_into_secret()?.into_keypair()?;
+    let userid = bob.userids().next().unwrap().userid().clone();
+    let rev = UserIDRevocationBuilder::new()
+        .set_reason_for_revocation(ReasonForRevocation::UIDRetired, b"")?
+        .build(&mut signer, &bob, &userid, None)?;
+    let bob = bob.insert_packets(rev)?;
+    let bob_pgp = tmp_dir.path().join("bob.pgp");
+    bob.serialize(&mut File::create(&bob_pgp)?)?;
+
+    Assert::cargo_binary("sq")
+        .with_args(&["--cert-store", store, "import", alice_pgp,
+                     bob_pgp.to_str().unwrap()])
+        .unwrap();
+
+    // Revoked User IDs are not matched.
+    Assert::cargo_binary("sq")
+        .with_args(&["--cert-store", store, "lookup", "bob@example.org"])
+        .fails()
+        .unwrap();
+
+    // Certificates are only named by their fingerprint.
+    let keyid = alice.keyid().to_hex();
+    for query in &["alice@example.org", "Alice <alice@example.org>",
+                   &keyid[..]] {
+        Assert::cargo_binary("sq")
+            .with_args(&["--cert-store", store, "verify", "--detached", sig,
+                         "--signer-cert", *query, message])
+            .fails()
+            .stderr().contains("Hint: Use \"sq lookup")
+            .unwrap();
+    }
+
+    // A second certificate claiming Alice's address does not interfere.
+    let (mallory, _) = CertBuilder::general_purpose(
+        None, Some("Alice <alice@example.org>"))
+        .generate()?;
+    let mallory_pgp = tmp_dir.path().join("mallory.pgp");
+    mallory.serialize(&mut File::create(&mallory_pgp)?)?;
+    Assert::cargo_binary("sq")
+        .with_args(&["--cert-store", store, "import",
+                     mallory_pgp.to_str().unwrap()])
+        .unwrap();
+
+    Assert::cargo_binary("sq")
+        .with_args(&["--cert-store", store, "verify", "--detached", sig,
+                     "--signer-cert", &alice.fingerprint().to_hex(), message])
+        .stderr().contains("Good signature")
+        .unwrap();
+
+    Assert::cargo_binary("sq")
+        .with_args(&["--cert-store", store, "verify", "--detached", sig,
+                     "--signer-cert", &mallory.fingerprint().to_hex(),
+                     message])
+        .fails()
+        .unwrap();
+
+    Ok(())
+}
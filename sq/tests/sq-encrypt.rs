@@ -0,0 +1,148 @@
+use std::fs::File;
+use std::io::Write;
+use std::time::{Duration, SystemTime};
+
+use assert_cli::Assert;
+use tempfile::TempDir;
+
+use sequoia_openpgp as openpgp;
+use openpgp::Result;
+use openpgp::cert::prelude::*;
+use openpgp::policy::StandardPolicy;
+use openpgp::serialize::Serialize;
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Writes the given certs to a keyring in `tmp_dir`.
+fn keyring(tmp_dir: &TempDir, name: &str, certs: &[&Cert]) -> Result<String> {
+    let path = tmp_dir.path().join(name);
+    let mut file = File::create(&path)?;
+    for cert in certs {
+        cert.serialize(&mut file)?;
+    }
+    Ok(path.to_str().unwrap().to_string())
+}
+
+#[test]
+fn sq_encrypt_recipient_selection() -> Result<()> {
+    let p = &StandardPolicy::new();
+    let tmp_dir = TempDir::new().unwrap();
+
+    let (alice, _) = CertBuilder::general_purpose(
+        None, Some("Alice <alice@example.org>"))
+        .generate()?;
+    let (bob, _) = CertBuilder::general_purpose(
+        None, Some("Bob <bob@example.org>"))
+        .set_creation_time(SystemTime::now() - 2 * DAY)
+        .set_validity_period(DAY)
+        .generate()?;
+    let (carol, revocation) = CertBuilder::general_purpose(
+        None, Some("Carol <carol@example.org>"))
+        .generate()?;
+    let carol = carol.insert_packets(Some(revocation))?;
+    assert!(matches!(carol.revocation_status(p, None),
+                     openpgp::types::RevocationStatus::Revoked(_)));
+
+    let alice_key = tmp_dir.path().join("alice.pgp");
+    alice.as_tsk().serialize(&mut File::create(&alice_key)?)?;
+    let alice_key = alice_key.to_str().unwrap();
+
+    let certs = keyring(&tmp_dir, "certs.pgp", &[&alice, &bob, &carol])?;
+
+    let message = tmp_dir.path().join("message.txt");
+    File::create(&message)?.write_all(b"Hello world.\n")?;
+    let message = message.to_str().unwrap();
+    let encrypted = tmp_dir.path().join("message.pgp");
+    let encrypted = encrypted.to_str().unwrap();
+
+    let subkey = alice.keys().subkeys()
+        .find(|ka| ka.key().pk_algo().for_encryption())
+        .unwrap().fingerprint().to_hex();
+    for (option, value) in &[("--recipient-email", "alice@example.org"),
+                             ("--recipient-email", "ALICE@example.org"),
+                             ("--recipient-userid", "Alice <alice@example.org>"),
+                             ("--recipient-fingerprint", &subkey[..])] {
+        Assert::cargo_binary("sq")
+            .with_args(&["--no-cert-store", "--force", "encrypt",
+                         *option, *value, "--keyring", &certs,
+                         "--output", encrypted, message])
+            .unwrap();
+        Assert::cargo_binary("sq")
+            .with_args(&["decrypt", "--recipient-key", alice_key, encrypted])
+            .stdout().contains("Hello world.")
+            .unwrap();
+    }
+
+    // Unknown recipients.
+    Assert::cargo_binary("sq")
+        .with_args(&["--no-cert-store", "encrypt",
+                     "--recipient-email", "dave@example.org",
+                     "--keyring", &certs, message])
+        .fails()
+        .unwrap();
+
+    // Expired and revoked recipients are refused, unless overridden.
+    for (email, option) in &[("bob@example.org", "--use-expired-recipient"),
+                             ("carol@example.org", "--use-revoked-recipient")] {
+        Assert::cargo_binary("sq")
+            .with_args(&["--no-cert-store", "encrypt",
+                         "--recipient-email", *email,
+                         "--keyring", &certs, message])
+            .fails()
+            .stderr().contains(*option)
+            .unwrap();
+        Assert::cargo_binary("sq")
+            .with_args(&["--no-cert-store", "encrypt", *option,
+                         "--recipient-email", *email,
+                         "--keyring", &certs, message])
+            .unwrap();
+    }
+
+    // Ambiguous recipients are refused.
+    let (mallory, _) = CertBuilder::general_purpose(
+        None, Some("Not Alice <alice@example.org>"))
+        .generate()?;
+    let certs = keyring(&tmp_dir, "more-certs.pgp", &[&alice, &mallory])?;
+    Assert::cargo_binary("sq")
+        .with_args(&["--no-cert-store", "encrypt",
+                     "--recipient-email", "alice@example.org",
+                     "--keyring", &certs, message])
+        .fails()
+        .stderr().contains("--recipient-fingerprint")
+        .unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn sq_encrypt_expired_cert_file() -> Result<()> {
+    let tmp_dir = TempDir::new().unwrap();
+
+    // The primary key expired, and with it the encryption subkey.
+    let (bob, _) = CertBuilder::general_purpose(
+        None, Some("Bob <bob@example.org>"))
+        .set_creation_time(SystemTime::now() - 2 * DAY)
+        .set_validity_period(DAY)
+        .generate()?;
+    let bob_pgp = keyring(&tmp_dir, "bob.pgp", &[&bob])?;
+
+    let message = tmp_dir.path().join("message.txt");
+    File::create(&message)?.write_all(b"Hello world.\n")?;
+    let message = message.to_str().unwrap();
+
+    Assert::cargo_binary("sq")
+        .with_args(&["--no-cert-store", "encrypt",
+                     "--recipient-cert", &bob_pgp, message])
+        .fails()
+        .stderr().contains("--use-expired-subkey")
+        .unwrap();
+
+    // Certificates given by file are not subject to the recipient
+    // checks, so falling back to the expired subkey is enough.
+    Assert::cargo_binary("sq")
+        .with_args(&["--no-cert-store", "encrypt", "--use-expired-subkey",
+                     "--recipient-cert", &bob_pgp, message])
+        .unwrap();
+
+    Ok(())
+}
@@ -0,0 +1,123 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use assert_cli::Assert;
+use tempfile::TempDir;
+
+use sequoia_openpgp as openpgp;
+use openpgp::{Cert, Result};
+use openpgp::cert::prelude::*;
+use openpgp::parse::Parse;
+use openpgp::policy::StandardPolicy;
+use openpgp::serialize::Serialize;
+
+use sequoia_ipc::gnupg::Context;
+
+/// Imports `tsk` into the GnuPG home directory of `ctx`.
+fn gpg_import(ctx: &Context, tsk: &Cert) -> Result<()> {
+    let mut gpg = Command::new("gpg")
+        .stdin(Stdio::piped())
+        .stderr(Stdio::null())
+        .arg("--homedir").arg(ctx.homedir().unwrap())
+        .arg("--batch")
+        .arg("--import")
+        .spawn()?;
+    tsk.as_tsk().serialize(gpg.stdin.as_mut().unwrap())?;
+    assert!(gpg.wait()?.success());
+    Ok(())
+}
+
+#[test]
+fn sq_gpg_agent() -> Result<()> {
+    let p = &StandardPolicy::new();
+    let ctx = match Context::ephemeral() {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("SKIP: Failed to create GnuPG context: {}\n\
+                       SKIP: Is GnuPG installed?", e);
+            return Ok(());
+        },
+    };
+    if let Err(e) = ctx.start("gpg-agent") {
+        eprintln!("SKIP: Failed to start gpg-agent: {}\n\
+                   SKIP: Is the GnuPG agent installed?", e);
+        return Ok(());
+    }
+    let homedir = ctx.homedir().unwrap().to_str().unwrap().to_string();
+
+    let (alice, _) = CertBuilder::general_purpose(None, Some("alice@example.org"))
+        .generate()?;
+    gpg_import(&ctx, &alice)?;
+    let fpr = alice.fingerprint().to_hex();
+
+    let (bob, _) = CertBuilder::general_purpose(None, Some("bob@example.org"))
+        .generate()?;
+
+    let tmp_dir = TempDir::new().unwrap();
+    let path = |name: &str| {
+        tmp_dir.path().join(name).to_str().unwrap().to_string()
+    };
+    let alice_cert = path("alice.pgp");
+    alice.serialize(&mut File::create(&alice_cert)?)?;
+    let bob_cert = path("bob.pgp");
+    bob.serialize(&mut File::create(&bob_cert)?)?;
+    let message = path("message.txt");
+    fs::File::create(&message)?.write_all(b"Hello, world!\n")?;
+
+    // Sign, using the certificate from a file, and from the keybox.
+    for signer in &[&alice_cert, &fpr] {
+        let signed = path("signed.pgp");
+        Assert::cargo_binary("sq")
+            .with_args(&["--force", "sign", "--gpg-agent",
+                         "--gpg-homedir", &homedir, "--signer-key", signer,
+                         "--output", &signed, &message])
+            .unwrap();
+        Assert::cargo_binary("sq")
+            .with_args(&["--no-cert-store", "verify",
+                         "--signer-cert", &alice_cert, &signed])
+            .stdout().contains("Hello, world!")
+            .unwrap();
+    }
+
+    // Without --gpg-agent, the key must contain secrets.
+    Assert::cargo_binary("sq")
+        .with_args(&["sign", "--signer-key", &alice_cert, &message])
+        .fails()
+        .unwrap();
+
+    // Decrypt.
+    let encrypted = path("encrypted.pgp");
+    Assert::cargo_binary("sq")
+        .with_args(&["--no-cert-store", "encrypt",
+                     "--recipient-cert", &alice_cert,
+                     "--gpg-agent", "--gpg-homedir", &homedir,
+                     "--signer-key", &fpr,
+                     "--output", &encrypted, &message])
+        .unwrap();
+    Assert::cargo_binary("sq")
+        .with_args(&["--no-cert-store", "decrypt",
+                     "--gpg-agent", "--gpg-homedir", &homedir,
+                     "--recipient-key", &fpr,
+                     "--signer-cert", &alice_cert, &encrypted])
+        .stdout().contains("Hello, world!")
+        .unwrap();
+
+    // Certify.
+    let certified = path("certified.pgp");
+    Assert::cargo_binary("sq")
+        .with_args(&["certify", "--gpg-agent", "--gpg-homedir", &homedir,
+                     "--output", &certified,
+                     &fpr, &bob_cert, "<bob@example.org>"])
+        .unwrap();
+    let bob = Cert::from_file(&certified)?;
+    let vc = bob.with_policy(p, None)?;
+    let uid = vc.userids().next().unwrap();
+    assert_eq!(uid.certifications().count(), 1);
+    uid.certifications().next().unwrap()
+        .clone().verify_userid_binding(alice.primary_key().key(),
+                                       bob.primary_key().key(),
+                                       uid.userid())?;
+
+    Ok(())
+}
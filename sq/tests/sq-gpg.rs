@@ -0,0 +1,153 @@
+#![cfg(unix)]
+
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::process::{Command, Output};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tempfile::TempDir;
+
+use sequoia_openpgp as openpgp;
+use openpgp::{Packet, Result};
+use openpgp::cert::prelude::*;
+use openpgp::packet::signature::SignatureBuilder;
+use openpgp::policy::StandardPolicy;
+use openpgp::serialize::Serialize;
+use openpgp::types::SignatureType;
+
+/// Returns the path to `sq`, invoked as `sq-gpg`.
+fn gpg(tmp_dir: &TempDir) -> PathBuf {
+    let gpg = tmp_dir.path().join("sq-gpg");
+    if ! gpg.exists() {
+        std::os::unix::fs::symlink(env!("CARGO_BIN_EXE_sq"), &gpg).unwrap();
+    }
+    gpg
+}
+
+/// Asserts that `output` indicates success, and returns stdout.
+fn ok(output: Output) -> String {
+    assert!(output.status.success(), "sq-gpg failed: {}",
+            String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn sq_gpg_verify_status_fd() -> Result<()> {
+    let tmp_dir = TempDir::new().unwrap();
+
+    let (cert, _) = CertBuilder::general_purpose(
+        None, Some("Alice <alice@example.org>"))
+        .generate()?;
+    let key = tmp_dir.path().join("alice.key");
+    cert.as_tsk().serialize(&mut File::create(&key)?)?;
+    let keyring = tmp_dir.path().join("alice.pgp");
+    cert.serialize(&mut File::create(&keyring)?)?;
+    let keyring = keyring.to_str().unwrap();
+
+    let message = tmp_dir.path().join("message.txt");
+    fs::write(&message, b"Hello world.\n")?;
+    let message = message.to_str().unwrap();
+    let sig = tmp_dir.path().join("message.sig");
+    let sig = sig.to_str().unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_sq"))
+        .args(&["sign", "--detached", "--signer-key", key.to_str().unwrap(),
+                "--output", sig, message])
+        .output()?;
+    assert!(output.status.success(), "sq sign failed: {}",
+            String::from_utf8_lossy(&output.stderr));
+
+    let signer = cert.keys().with_policy(&StandardPolicy::new(), None)
+        .for_signing().next().unwrap().fingerprint().to_hex();
+    let check = |status: &str| {
+        assert!(status.contains("[GNUPG:] NEWSIG"), "{}", status);
+        assert!(status.contains("[GNUPG:] GOODSIG"), "{}", status);
+        let validsig = status.lines()
+            .find(|l| l.starts_with("[GNUPG:] VALIDSIG "))
+            .unwrap_or_else(|| panic!("no VALIDSIG in {:?}", status));
+        let fields: Vec<&str> = validsig.split(' ').skip(2).collect();
+        assert_eq!(fields[0], signer);
+        // The signature does not expire.
+        assert_eq!(fields[3], "0");
+        // The signature version.
+        assert_eq!(fields[4], "4");
+        assert_eq!(fields[9], cert.fingerprint().to_hex());
+    };
+
+    // Status lines on stdout.
+    let status = ok(Command::new(gpg(&tmp_dir))
+                    .args(&["--status-fd", "1", "--keyring", keyring,
+                            "--verify", sig, message])
+                    .output()?);
+    check(&status);
+
+    // Status lines on an inherited descriptor.
+    let status_file = tmp_dir.path().join("status");
+    ok(Command::new("sh")
+       .arg("-c")
+       .arg("exec 3>\"$1\"; shift; exec \"$@\"")
+       .arg("sh")
+       .arg(&status_file)
+       .arg(gpg(&tmp_dir))
+       .args(&["--status-fd", "3", "--keyring", keyring,
+               "--verify", sig, message])
+       .output()?);
+    check(&fs::read_to_string(&status_file)?);
+
+    // Descriptors that are not open are refused.
+    let output = Command::new(gpg(&tmp_dir))
+        .args(&["--status-fd", "9", "--keyring", keyring,
+                "--verify", sig, message])
+        .output()?;
+    assert!(! output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr)
+            .contains("--status-fd 9"));
+
+    Ok(())
+}
+
+#[test]
+fn sq_gpg_verify_status_fd_expiration() -> Result<()> {
+    let tmp_dir = TempDir::new().unwrap();
+
+    let (cert, _) = CertBuilder::general_purpose(
+        None, Some("Alice <alice@example.org>"))
+        .set_creation_time(
+            SystemTime::now() - Duration::from_secs(24 * 60 * 60))
+        .generate()?;
+    let keyring = tmp_dir.path().join("alice.pgp");
+    cert.serialize(&mut File::create(&keyring)?)?;
+    let keyring = keyring.to_str().unwrap();
+
+    let message = tmp_dir.path().join("message.txt");
+    fs::write(&message, b"Hello world.\n")?;
+    let message = message.to_str().unwrap();
+
+    // Make a signature that expires in a week.
+    let creation = UNIX_EPOCH + Duration::from_secs(
+        SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() - 60);
+    let validity = Duration::from_secs(7 * 24 * 60 * 60);
+    let mut signer = cert.keys().with_policy(&StandardPolicy::new(), None)
+        .for_signing().secret().next().unwrap()
+        .key().clone().into_keypair()?;
+    let sig = SignatureBuilder::new(SignatureType::Binary)
+        .set_signature_creation_time(creation)?
+        .set_signature_validity_period(validity)?
+        .sign_message(&mut signer, b"Hello world.\n")?;
+    let sig_file = tmp_dir.path().join("message.sig");
+    Packet::from(sig).serialize(&mut File::create(&sig_file)?)?;
+
+    let status = ok(Command::new(gpg(&tmp_dir))
+                    .args(&["--status-fd", "1", "--keyring", keyring,
+                            "--verify", sig_file.to_str().unwrap(),
+                            message])
+                    .output()?);
+    let validsig = status.lines()
+        .find(|l| l.starts_with("[GNUPG:] VALIDSIG "))
+        .unwrap_or_else(|| panic!("no VALIDSIG in {:?}", status));
+    let fields: Vec<&str> = validsig.split(' ').skip(2).collect();
+    let expiration = (creation + validity).duration_since(UNIX_EPOCH)?;
+    assert_eq!(fields[3], expiration.as_secs().to_string());
+
+    Ok(())
+}
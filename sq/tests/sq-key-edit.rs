@@ -0,0 +1,137 @@
+use std::time::{Duration, SystemTime};
+
+use assert_cli::Assert;
+use tempfile::TempDir;
+
+use sequoia_openpgp as openpgp;
+use openpgp::{Cert, Result};
+use openpgp::packet::UserID;
+use openpgp::parse::Parse;
+use openpgp::policy::StandardPolicy;
+use openpgp::types::KeyFlags;
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[test]
+fn sq_key_edit() -> Result<()> {
+    let p = &StandardPolicy::new();
+    let tmp_dir = TempDir::new().unwrap();
+    let path = |name: &str| {
+        tmp_dir.path().join(name).to_str().unwrap().to_string()
+    };
+    // New self signatures must be newer than the ones they replace,
+    // and signature creation times have a resolution of one second.
+    let tick = || std::thread::sleep(Duration::from_secs(1));
+
+    let key = path("key.pgp");
+    Assert::cargo_binary("sq")
+        .with_args(&["key", "generate", "--userid", "Alice <alice@example.org>",
+                     "--cannot-sign", "--cannot-encrypt",
+                     "--expires-in", "1y", "--export", &key])
+        .unwrap();
+    let original = Cert::from_file(&key)?;
+
+    // Add a signing subkey.
+    let signing = path("signing.pgp");
+    Assert::cargo_binary("sq")
+        .with_args(&["key", "subkey", "add", "--can-sign", "--expires-in", "1m",
+                     "--output", &signing, &key])
+        .unwrap();
+    let cert = Cert::from_file(&signing)?;
+    assert!(cert.is_tsk());
+    let vc = cert.with_policy(p, None)?;
+    let subkey = vc.keys().subkeys().for_signing().next().unwrap();
+    assert!(subkey.key_expiration_time().is_some());
+    let subkey_fpr = subkey.fingerprint().to_hex();
+
+    // Add an encryption subkey.
+    let encryption = path("encryption.pgp");
+    Assert::cargo_binary("sq")
+        .with_args(&["key", "subkey", "add", "--can-encrypt", "storage",
+                     "--cipher-suite", "rsa2k",
+                     "--output", &encryption, &signing])
+        .unwrap();
+    let cert = Cert::from_file(&encryption)?;
+    let vc = cert.with_policy(p, None)?;
+    assert_eq!(vc.keys().subkeys().count(), 2);
+    assert_eq!(vc.keys().subkeys()
+               .key_flags(KeyFlags::empty().set_storage_encryption())
+               .count(), 1);
+
+    // Let the signing subkey never expire.
+    tick();
+    let expired = path("subkey-expired.pgp");
+    Assert::cargo_binary("sq")
+        .with_args(&["key", "subkey", "expire", "--subkey", &subkey_fpr,
+                     "--expires", "never", "--output", &expired, &encryption])
+        .unwrap();
+    let cert = Cert::from_file(&expired)?;
+    let vc = cert.with_policy(p, None)?;
+    let subkey = vc.keys().subkeys().for_signing().next().unwrap();
+    assert!(subkey.key_expiration_time().is_none());
+
+    Assert::cargo_binary("sq")
+        .with_args(&["key", "subkey", "expire", "--subkey", "0123456789ABCDEF",
+                     "--expires", "never", &encryption])
+        .fails()
+        .stderr().contains("Subkeys not found: 0123456789ABCDEF")
+        .unwrap();
+
+    // Add a User ID and make it primary.
+    let added = path("userid-added.pgp");
+    Assert::cargo_binary("sq")
+        .with_args(&["key", "userid", "add", "--userid", "Alice <alice@work.example>",
+                     "--output", &added, &expired])
+        .unwrap();
+    let cert = Cert::from_file(&added)?;
+    let vc = cert.with_policy(p, None)?;
+    assert_eq!(vc.userids().count(), 2);
+    assert_eq!(vc.primary_userid()?.userid(),
+               &UserID::from("Alice <alice@example.org>"));
+
+    Assert::cargo_binary("sq")
+        .with_args(&["key", "userid", "add", "--userid", "Alice <alice@work.example>",
+                     &added])
+        .fails()
+        .unwrap();
+
+    tick();
+    let primary = path("userid-primary.pgp");
+    Assert::cargo_binary("sq")
+        .with_args(&["key", "userid", "set-primary",
+                     "--userid", "Alice <alice@work.example>",
+                     "--output", &primary, &added])
+        .unwrap();
+    let cert = Cert::from_file(&primary)?;
+    let vc = cert.with_policy(p, None)?;
+    assert_eq!(vc.primary_userid()?.userid(),
+               &UserID::from("Alice <alice@work.example>"));
+
+    // Strip the original User ID.
+    let stripped = path("userid-stripped.pgp");
+    Assert::cargo_binary("sq")
+        .with_args(&["key", "userid", "strip",
+                     "--userid", "Alice <alice@example.org>",
+                     "--output", &stripped, &primary])
+        .unwrap();
+    let cert = Cert::from_file(&stripped)?;
+    assert_eq!(cert.userids().count(), 1);
+    assert_eq!(cert.keys().subkeys().count(), 2);
+
+    // Change the expiration time of the key.
+    tick();
+    let extended = path("extended.pgp");
+    Assert::cargo_binary("sq")
+        .with_args(&["key", "expire", "--expires-in", "3y",
+                     "--output", &extended, &stripped])
+        .unwrap();
+    let cert = Cert::from_file(&extended)?;
+    let vc = cert.with_policy(p, None)?;
+    assert!(vc.primary_key().key_expiration_time().unwrap()
+            > SystemTime::now() + 2 * 365 * DAY);
+    assert!(vc.with_policy(p, SystemTime::now() + 500 * DAY)?.alive().is_ok());
+    assert!(original.with_policy(p, SystemTime::now() + 500 * DAY)?
+            .alive().is_err());
+
+    Ok(())
+}
@@ -0,0 +1,107 @@
+use std::fs::File;
+
+use assert_cli::Assert;
+use serde_json::Value;
+use tempfile::TempDir;
+
+use sequoia_openpgp as openpgp;
+use openpgp::{Packet, Result};
+use openpgp::cert::prelude::*;
+use openpgp::packet::UserID;
+use openpgp::packet::signature::SignatureBuilder;
+use openpgp::serialize::Serialize;
+use openpgp::types::SignatureType;
+
+#[test]
+fn sq_keyring_diff() -> Result<()> {
+    let tmp_dir = TempDir::new().unwrap();
+    let path = |name: &str| {
+        tmp_dir.path().join(name).to_str().unwrap().to_string()
+    };
+
+    let (alice, _) = CertBuilder::general_purpose(
+        None, Some("Alice <alice@example.org>"))
+        .generate()?;
+    let (bob, _) = CertBuilder::general_purpose(
+        None, Some("Bob <bob@example.org>"))
+        .generate()?;
+
+    let alice_pgp = path("alice.pgp");
+    alice.serialize(&mut File::create(&alice_pgp)?)?;
+
+    // Alice adds a User ID, and Bob certifies her original one.
+    let mut alice_signer = alice.primary_key().key().clone()
+        .parts_into_secret()?.into_keypair()?;
+    let mut bob_signer = bob.primary_key().key().clone()
+        .parts_into_secret()?.into_keypair()?;
+    let work = UserID::from("Alice <alice@work.example>");
+    let work_sig = work.bind(
+        &mut alice_signer, &alice,
+        SignatureBuilder::new(SignatureType::PositiveCertification))?;
+    let certification = alice.userids().next().unwrap().userid()
+        .certify(&mut bob_signer, &alice,
+                 SignatureType::GenericCertification, None, None)?;
+    let updated = alice.clone().insert_packets(vec![
+        Packet::from(work),
+        work_sig.into(),
+        alice.userids().next().unwrap().userid().clone().into(),
+        certification.into(),
+    ])?;
+
+    let updated_pgp = path("alice-updated.pgp");
+    let mut sink = File::create(&updated_pgp)?;
+    updated.serialize(&mut sink)?;
+    bob.serialize(&mut sink)?;
+    drop(sink);
+
+    Assert::cargo_binary("sq")
+        .with_args(&["keyring", "diff", &alice_pgp, &alice_pgp])
+        .stdout().is("")
+        .stderr().contains("No differences.")
+        .unwrap();
+
+    Assert::cargo_binary("sq")
+        .with_args(&["keyring", "diff", &alice_pgp, &updated_pgp])
+        .stdout().contains(
+            &format!("Certificate {}: changed", alice.fingerprint().to_hex()))
+        .stdout().contains("User ID \"Alice <alice@work.example>\": added")
+        .stdout().contains("+ certification GenericCertification by ")
+        .stdout().contains(
+            &format!("Certificate {}: added", bob.fingerprint().to_hex()))
+        .stderr().contains("2 certificates differ.")
+        .unwrap();
+
+    Assert::cargo_binary("sq")
+        .with_args(&["--output-format", "json", "keyring", "diff",
+                     &updated_pgp, &alice_pgp])
+        .stdout().satisfies(|output| {
+            let v: Value = serde_json::from_str(output).unwrap();
+            let certs = v["certificates"].as_array().unwrap();
+            assert_eq!(certs.len(), 2);
+
+            let alice_diff = &certs[0];
+            assert_eq!(alice_diff["fingerprint"], alice.fingerprint().to_hex());
+            assert_eq!(alice_diff["status"], "changed");
+            let components = alice_diff["components"].as_array().unwrap();
+            assert_eq!(components.len(), 2);
+            assert_eq!(components[0]["type"], "userid");
+            assert_eq!(components[0]["id"], "Alice <alice@example.org>");
+            assert_eq!(components[0]["status"], "changed");
+            let sig = &components[0]["signatures"][0];
+            assert_eq!(sig["status"], "removed");
+            assert_eq!(sig["kind"], "certification");
+            assert_eq!(sig["verified"], false);
+            assert_eq!(sig["valid"], true);
+            assert_eq!(components[1]["id"], "Alice <alice@work.example>");
+            assert_eq!(components[1]["status"], "removed");
+            assert_eq!(components[1]["signatures"][0]["kind"],
+                       "self-signature");
+
+            assert_eq!(certs[1]["fingerprint"], bob.fingerprint().to_hex());
+            assert_eq!(certs[1]["status"], "removed");
+            true
+        }, "Bad keyring diff output")
+        .unwrap();
+
+    Ok(())
+}
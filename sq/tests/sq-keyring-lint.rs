@@ -0,0 +1,115 @@
+use std::fs::File;
+
+use assert_cli::Assert;
+use tempfile::TempDir;
+
+use sequoia_openpgp as openpgp;
+use openpgp::{Cert, Packet, Result};
+use openpgp::cert::prelude::*;
+use openpgp::packet::signature::SignatureBuilder;
+use openpgp::parse::Parse;
+use openpgp::policy::StandardPolicy;
+use openpgp::serialize::Serialize;
+use openpgp::types::{HashAlgorithm, SignatureType};
+
+/// Returns a key whose self-signatures all use SHA-1.
+fn sha1_key() -> Result<Cert> {
+    let p = &StandardPolicy::new();
+    let (cert, _) = CertBuilder::new()
+        .add_userid("Alice <alice@example.org>")
+        .add_signing_subkey()
+        .generate()?;
+
+    let primary = cert.primary_key().key().clone().parts_into_secret()?;
+    let mut signer = primary.clone().into_keypair()?;
+    let vc = cert.with_policy(p, None)?;
+
+    let uid = vc.userids().next().unwrap();
+    let uid_sig = uid.userid().bind(
+        &mut signer, &cert,
+        SignatureBuilder::from(uid.binding_signature().clone())
+            .set_hash_algo(HashAlgorithm::SHA1))?;
+
+    let subkey = vc.keys().subkeys().next().unwrap();
+    let mut subkey_signer =
+        subkey.key().clone().parts_into_secret()?.into_keypair()?;
+    let backsig = SignatureBuilder::new(SignatureType::PrimaryKeyBinding)
+        .set_hash_algo(HashAlgorithm::SHA1)
+        .sign_primary_key_binding(&mut subkey_signer, &primary, subkey.key())?;
+    let subkey_sig = subkey.key().bind(
+        &mut signer, &cert,
+        SignatureBuilder::from(subkey.binding_signature().clone())
+            .set_hash_algo(HashAlgorithm::SHA1)
+            .set_embedded_signature(backsig)?)?;
+
+    Cert::from_packets(vec![
+        Packet::from(primary.role_into_primary()),
+        uid.userid().clone().into(),
+        uid_sig.into(),
+        subkey.key().clone().parts_into_secret()?.into(),
+        subkey_sig.into(),
+    ].into_iter())
+}
+
+#[test]
+fn sq_keyring_lint() -> Result<()> {
+    let p = &StandardPolicy::new();
+    let tmp_dir = TempDir::new().unwrap();
+
+    let key = sha1_key()?;
+    assert!(key.with_policy(p, None).is_err());
+    let fpr = key.fingerprint().to_hex();
+
+    let key_pgp = tmp_dir.path().join("key.pgp");
+    key.as_tsk().serialize(&mut File::create(&key_pgp)?)?;
+    let key_pgp = key_pgp.to_str().unwrap();
+    let cert_pgp = tmp_dir.path().join("cert.pgp");
+    key.serialize(&mut File::create(&cert_pgp)?)?;
+    let cert_pgp = cert_pgp.to_str().unwrap();
+    let fixed = tmp_dir.path().join("fixed.pgp");
+
+    Assert::cargo_binary("sq")
+        .with_args(&["keyring", "lint", cert_pgp])
+        .fails()
+        .stderr().contains("User ID \"Alice <alice@example.org>\"")
+        .stderr().contains("SHA1")
+        .unwrap();
+
+    Assert::cargo_binary("sq")
+        .with_args(&["keyring", "lint", "--list-keys", cert_pgp])
+        .fails()
+        .stdout().contains(fpr.as_str())
+        .unwrap();
+
+    // Fixing requires the secret key material.
+    Assert::cargo_binary("sq")
+        .with_args(&["keyring", "lint", "--fix", cert_pgp])
+        .fails()
+        .stderr().contains("No secret key material")
+        .unwrap();
+
+    Assert::cargo_binary("sq")
+        .with_args(&["keyring", "lint", "--fix", "--secret-key-file", key_pgp,
+                     "--output", fixed.to_str().unwrap(), cert_pgp])
+        .unwrap();
+    let cert = Cert::from_file(&fixed)?;
+    assert!(! cert.is_tsk());
+    let vc = cert.with_policy(p, None)?;
+    assert_eq!(vc.userids().count(), 1);
+    assert_eq!(vc.keys().for_signing().count(), 1);
+
+    Assert::cargo_binary("sq")
+        .with_args(&["keyring", "lint", fixed.to_str().unwrap()])
+        .unwrap();
+
+    // Keys are fixed using their own secrets.
+    Assert::cargo_binary("sq")
+        .with_args(&["--force", "keyring", "lint", "--fix",
+                     "--output", fixed.to_str().unwrap(), key_pgp])
+        .unwrap();
+    let key = Cert::from_file(&fixed)?;
+    assert!(key.is_tsk());
+    key.with_policy(p, None)?;
+
+    Ok(())
+}
@@ -0,0 +1,187 @@
+use std::fs;
+
+use assert_cli::Assert;
+use tempfile::TempDir;
+
+use sequoia_openpgp as openpgp;
+use openpgp::Result;
+use openpgp::cert::prelude::*;
+use openpgp::serialize::Serialize;
+
+#[test]
+fn sq_sign_verify_manifest() -> Result<()> {
+    let tmp_dir = TempDir::new().unwrap();
+
+    let (key, _) = CertBuilder::general_purpose(None, Some("alice@example.org"))
+        .generate()?;
+    let key_pgp = tmp_dir.path().join("key.pgp");
+    key.as_tsk().serialize(&mut fs::File::create(&key_pgp)?)?;
+    let key_pgp = key_pgp.to_str().unwrap();
+    let cert_pgp = tmp_dir.path().join("cert.pgp");
+    key.serialize(&mut fs::File::create(&cert_pgp)?)?;
+    let cert_pgp = cert_pgp.to_str().unwrap();
+
+    let dir = tmp_dir.path().join("dist");
+    fs::create_dir_all(dir.join("bin"))?;
+    fs::write(dir.join("README"), "Read me.\n")?;
+    fs::write(dir.join("bin").join("tool"), "#!/bin/sh\n")?;
+    let dir = dir.to_str().unwrap();
+
+    let verify = |manifest: &str| {
+        Assert::cargo_binary("sq")
+            .with_args(&["--no-cert-store", "verify", "--signer-cert", cert_pgp,
+                         "--manifest", dir, manifest])
+    };
+
+    // The manifest is stored outside the directory.
+    let manifest = tmp_dir.path().join("dist.manifest");
+    let manifest = manifest.to_str().unwrap();
+    Assert::cargo_binary("sq")
+        .with_args(&["sign", "--signer-key", key_pgp,
+                     "--manifest", dir, "--output", manifest])
+        .unwrap();
+    verify(manifest)
+        .stderr().contains("2 files match the manifest.")
+        .unwrap();
+
+    // The manifest is stored inside the directory.
+    let inside = tmp_dir.path().join("dist").join("MANIFEST.asc");
+    let inside = inside.to_str().unwrap();
+    Assert::cargo_binary("sq")
+        .with_args(&["sign", "--signer-key", key_pgp, "--hash", "SHA512",
+                     "--manifest", dir, "--output", inside])
+        .unwrap();
+    verify(inside)
+        .stderr().contains("2 files match the manifest.")
+        .unwrap();
+
+    // A signature by another key is not accepted.
+    let (other, _) = CertBuilder::general_purpose(None, Some("bob@example.org"))
+        .generate()?;
+    let other_pgp = tmp_dir.path().join("other.pgp");
+    other.serialize(&mut fs::File::create(&other_pgp)?)?;
+    Assert::cargo_binary("sq")
+        .with_args(&["--no-cert-store", "verify",
+                     "--signer-cert", other_pgp.to_str().unwrap(),
+                     "--manifest", dir, manifest])
+        .fails()
+        .unwrap();
+
+    // Modify, add, and remove files.
+    fs::write(tmp_dir.path().join("dist").join("README"), "Don't.\n")?;
+    fs::write(tmp_dir.path().join("dist").join("NEWS"), "Nothing.\n")?;
+    fs::remove_file(tmp_dir.path().join("dist").join("bin").join("tool"))?;
+    verify(manifest)
+        .fails()
+        .stderr().contains("Added: NEWS")
+        .stderr().contains("Removed: bin/tool")
+        .stderr().contains("Modified: README")
+        .stderr().contains("does not match the manifest")
+        .unwrap();
+
+    // SHA-1 is not collision resistant.
+    Assert::cargo_binary("sq")
+        .with_args(&["sign", "--signer-key", key_pgp, "--hash", "SHA1",
+                     "--manifest", dir])
+        .fails()
+        .unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn sq_sign_without_manifest() -> Result<()> {
+    let tmp_dir = TempDir::new().unwrap();
+
+    let (key, _) = CertBuilder::general_purpose(None, Some("alice@example.org"))
+        .generate()?;
+    let key_pgp = tmp_dir.path().join("key.pgp");
+    key.as_tsk().serialize(&mut fs::File::create(&key_pgp)?)?;
+    let key_pgp = key_pgp.to_str().unwrap();
+    let message = tmp_dir.path().join("message.txt");
+    fs::write(&message, "Hello world.\n")?;
+    let message = message.to_str().unwrap();
+
+    // --hash only applies to manifests, but must not be required.
+    Assert::cargo_binary("sq")
+        .with_args(&["sign", "--signer-key", key_pgp, message])
+        .unwrap();
+
+    Assert::cargo_binary("sq")
+        .with_args(&["sign", "--signer-key", key_pgp, "--hash", "SHA512",
+                     message])
+        .fails()
+        .unwrap();
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn sq_sign_verify_manifest_symlinks() -> Result<()> {
+    use std::os::unix::fs::symlink;
+    use std::os::unix::net::UnixListener;
+
+    let tmp_dir = TempDir::new().unwrap();
+
+    let (key, _) = CertBuilder::general_purpose(None, Some("alice@example.org"))
+        .generate()?;
+    let key_pgp = tmp_dir.path().join("key.pgp");
+    key.as_tsk().serialize(&mut fs::File::create(&key_pgp)?)?;
+    let key_pgp = key_pgp.to_str().unwrap();
+    let cert_pgp = tmp_dir.path().join("cert.pgp");
+    key.serialize(&mut fs::File::create(&cert_pgp)?)?;
+    let cert_pgp = cert_pgp.to_str().unwrap();
+
+    let dist = tmp_dir.path().join("dist");
+    fs::create_dir_all(dist.join("bin"))?;
+    fs::write(dist.join("bin").join("tool"), "#!/bin/sh\n")?;
+    fs::write(dist.join("bin").join("other"), "#!/bin/sh\n")?;
+    symlink("bin/tool", dist.join("tool"))?;
+    // Links to directories are recorded, not followed.
+    symlink(".", dist.join("loop"))?;
+    let dir = dist.to_str().unwrap();
+
+    let manifest = tmp_dir.path().join("dist.manifest");
+    let manifest = manifest.to_str().unwrap();
+    Assert::cargo_binary("sq")
+        .with_args(&["sign", "--signer-key", key_pgp,
+                     "--manifest", dir, "--output", manifest])
+        .unwrap();
+
+    let verify = || {
+        Assert::cargo_binary("sq")
+            .with_args(&["--no-cert-store", "verify", "--signer-cert", cert_pgp,
+                         "--manifest", dir, manifest])
+    };
+    verify()
+        .stderr().contains("4 files match the manifest.")
+        .unwrap();
+
+    // Pointing a link elsewhere is detected.
+    fs::remove_file(dist.join("tool"))?;
+    symlink("bin/other", dist.join("tool"))?;
+    verify()
+        .fails()
+        .stderr().contains("Modified: tool")
+        .unwrap();
+
+    // So is replacing a file with a link to an identical file.
+    fs::remove_file(dist.join("tool"))?;
+    symlink("bin/tool", dist.join("tool"))?;
+    fs::remove_file(dist.join("bin").join("other"))?;
+    symlink("tool", dist.join("bin").join("other"))?;
+    verify()
+        .fails()
+        .stderr().contains("Modified: bin/other")
+        .unwrap();
+
+    // Special files are refused.
+    let _socket = UnixListener::bind(dist.join("socket"))?;
+    verify()
+        .fails()
+        .stderr().contains("is neither a regular file")
+        .unwrap();
+
+    Ok(())
+}
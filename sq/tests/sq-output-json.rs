@@ -0,0 +1,305 @@
+use std::fs::File;
+use std::io::Write;
+
+use assert_cli::Assert;
+use serde_json::Value;
+use tempfile::TempDir;
+
+use sequoia_openpgp as openpgp;
+use openpgp::Result;
+use openpgp::cert::prelude::*;
+use openpgp::serialize::Serialize;
+
+/// Asserts that `value` is an object with exactly the given keys.
+fn assert_keys(value: &Value, keys: &[&str]) {
+    let mut actual: Vec<&str> = value.as_object()
+        .unwrap_or_else(|| panic!("not an object: {}", value))
+        .keys().map(|k| k.as_str()).collect();
+    actual.sort_unstable();
+    let mut expected = keys.to_vec();
+    expected.sort_unstable();
+    assert_eq!(actual, expected);
+}
+
+/// Parses the JSON document that the output ends with.
+fn parse(output: &str) -> Value {
+    let start = output.find('{').expect("no JSON document");
+    serde_json::from_str(&output[start..]).expect("malformed JSON")
+}
+
+fn setup(tmp_dir: &TempDir) -> Result<(Cert, String, String)> {
+    let key_pgp = tmp_dir.path().join("alice.pgp");
+    let (cert, _) = CertBuilder::general_purpose(
+        None, Some("Alice <alice@example.org>"))
+        .generate()?;
+    cert.as_tsk().serialize(&mut File::create(&key_pgp)?)?;
+
+    let cert_pgp = tmp_dir.path().join("alice-cert.pgp");
+    cert.serialize(&mut File::create(&cert_pgp)?)?;
+
+    Ok((cert,
+        key_pgp.to_str().unwrap().to_string(),
+        cert_pgp.to_str().unwrap().to_string()))
+}
+
+#[test]
+fn sq_inspect_json() -> Result<()> {
+    let tmp_dir = TempDir::new().unwrap();
+    let (cert, _, cert_pgp) = setup(&tmp_dir)?;
+
+    let output = Assert::cargo_binary("sq")
+        .with_args(&["--output-format", "json", "inspect", &cert_pgp])
+        .stdout().satisfies(|output| {
+            let v = parse(output);
+            assert_keys(&v, &["sq_output_version", "input", "type",
+                              "certificates"]);
+            assert_eq!(v["sq_output_version"], "1.2.0");
+            assert_eq!(v["type"], "certificate");
+
+            let c = &v["certificates"][0];
+            assert_keys(c, &["fingerprint", "secret", "revocation",
+                             "primary_key", "subkeys", "userids",
+                             "user_attributes", "bad_signatures"]);
+            assert_eq!(c["fingerprint"], cert.fingerprint().to_hex());
+            assert_eq!(c["secret"], false);
+            assert_keys(&c["revocation"], &["status", "reasons"]);
+            assert_eq!(c["revocation"]["status"], "not-revoked");
+
+            let k = &c["primary_key"];
+            assert_keys(k, &["fingerprint", "algorithm", "bits", "secret",
+                             "creation_time", "expiration_time", "flags",
+                             "valid", "errors", "revocation",
+                             "certifications"]);
+            assert_eq!(k["valid"], true);
+            assert_eq!(k["flags"], serde_json::json!(["certification"]));
+            assert_eq!(c["subkeys"].as_array().unwrap().len(), 2);
+
+            let u = &c["userids"][0];
+            assert_keys(u, &["userid", "valid", "errors", "revocation",
+                             "certifications"]);
+            assert_eq!(u["userid"], "Alice <alice@example.org>");
+            true
+        }, "Bad inspect output");
+    output.unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn sq_keyring_list_json() -> Result<()> {
+    let tmp_dir = TempDir::new().unwrap();
+    let (cert, _, cert_pgp) = setup(&tmp_dir)?;
+
+    Assert::cargo_binary("sq")
+        .with_args(&["--output-format", "json", "keyring", "list", &cert_pgp])
+        .stdout().satisfies(|output| {
+            let v = parse(output);
+            assert_keys(&v, &["sq_output_version", "certificates"]);
+            let e = &v["certificates"][0];
+            assert_keys(e, &["index", "fingerprint", "primary_userid",
+                             "userids"]);
+            assert_eq!(e["index"], 0);
+            assert_eq!(e["fingerprint"], cert.fingerprint().to_hex());
+            assert_eq!(e["primary_userid"], "Alice <alice@example.org>");
+            true
+        }, "Bad keyring list output")
+        .unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn sq_verify_decrypt_json() -> Result<()> {
+    let tmp_dir = TempDir::new().unwrap();
+    let (cert, key_pgp, cert_pgp) = setup(&tmp_dir)?;
+
+    let message = tmp_dir.path().join("message.txt");
+    File::create(&message)?.write_all(b"Hello world.\n")?;
+    let message = message.to_str().unwrap();
+    let sig = tmp_dir.path().join("message.sig");
+    let sig = sig.to_str().unwrap();
+    let encrypted = tmp_dir.path().join("message.pgp");
+    let encrypted = encrypted.to_str().unwrap();
+
+    Assert::cargo_binary("sq")
+        .with_args(&["sign", "--detached", "--signer-key", &key_pgp,
+                     "--output", sig, message])
+        .unwrap();
+
+    let signature_keys = ["status", "level", "key", "signer",
+                          "alleged_issuers", "creation_time", "errors"];
+    let verification_keys = ["valid", "signatures", "summary"];
+    let summary_keys = ["good_signatures", "good_checksums",
+                        "unknown_checksums", "bad_signatures",
+                        "bad_checksums", "broken_signatures"];
+
+    Assert::cargo_binary("sq")
+        .with_args(&["--output-format", "json", "verify",
+                     "--detached", sig, "--signer-cert", &cert_pgp, message])
+        .stderr().satisfies(|output| {
+            let v = parse(output);
+            let mut keys = verification_keys.to_vec();
+            keys.push("sq_output_version");
+            assert_keys(&v, &keys);
+            assert_eq!(v["valid"], true);
+            assert_keys(&v["summary"], &summary_keys);
+            assert_eq!(v["summary"]["good_signatures"], 1);
+
+            let s = &v["signatures"][0];
+            assert_keys(s, &signature_keys);
+            assert_eq!(s["status"], "good");
+            assert_eq!(s["signer"], cert.fingerprint().to_hex());
+            true
+        }, "Bad verify output")
+        .unwrap();
+
+    // Without the signer's certificate, verification fails, and the
+    // results are still reported.
+    Assert::cargo_binary("sq")
+        .with_args(&["--output-format", "json", "verify",
+                     "--detached", sig, message])
+        .fails()
+        .stderr().satisfies(|output| {
+            let v = parse(output);
+            assert_eq!(v["valid"], false);
+            assert_eq!(v["signatures"][0]["status"], "missing-key");
+            true
+        }, "Bad verify output")
+        .unwrap();
+
+    Assert::cargo_binary("sq")
+        .with_args(&["encrypt", "--recipient-cert", &cert_pgp,
+                     "--signer-key", &key_pgp,
+                     "--output", encrypted, message])
+        .unwrap();
+
+    let encryption_subkey = cert.keys().subkeys()
+        .find(|ka| ka.key().pk_algo().for_encryption())
+        .unwrap().fingerprint();
+
+    Assert::cargo_binary("sq")
+        .with_args(&["--output-format", "json", "decrypt",
+                     "--recipient-key", &key_pgp,
+                     "--signer-cert", &cert_pgp, encrypted])
+        .stdout().contains("Hello world.")
+        .stderr().satisfies(|output| {
+            let v = parse(output);
+            assert_keys(&v, &["sq_output_version", "symmetric_algorithm",
+                              "aead_algorithm", "compression_algorithm",
+                              "recipients", "passwords", "decryption_key",
+                              "verification"]);
+            assert!(v["symmetric_algorithm"].is_string());
+            assert_eq!(v["recipients"].as_array().unwrap().len(), 1);
+            assert_eq!(v["passwords"], 0);
+            assert_eq!(v["decryption_key"], encryption_subkey.to_hex());
+            assert_keys(&v["verification"], &verification_keys);
+            assert_eq!(v["verification"]["signatures"][0]["status"], "good");
+            true
+        }, "Bad decrypt output")
+        .unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn sq_packet_dump_json() -> Result<()> {
+    let tmp_dir = TempDir::new().unwrap();
+    let (cert, key_pgp, cert_pgp) = setup(&tmp_dir)?;
+
+    Assert::cargo_binary("sq")
+        .with_args(&["--output-format", "json", "packet", "dump", &cert_pgp])
+        .stdout().satisfies(|output| {
+            let v = parse(output);
+            assert_keys(&v, &["sq_output_version", "packets"]);
+            let packets = v["packets"].as_array().unwrap();
+            let primary = &packets[0];
+            assert_eq!(primary["path"], "0");
+            assert_eq!(primary["offset"], 0);
+            assert_eq!(primary["tag"], 6);
+            assert_eq!(primary["fields"]["fingerprint"],
+                       cert.fingerprint().to_hex());
+            assert!(! primary["mpis"].as_array().unwrap().is_empty());
+
+            // The packets follow each other.
+            let next = primary["header_length"].as_u64().unwrap()
+                + primary["body_length"].as_u64().unwrap();
+            assert_eq!(packets[1]["path"], "1");
+            assert_eq!(packets[1]["offset"], next);
+            true
+        }, "Bad packet dump output")
+        .unwrap();
+
+    let message = tmp_dir.path().join("message.txt");
+    File::create(&message)?.write_all(b"Hello world.\n")?;
+    let message = message.to_str().unwrap();
+    let encrypted = tmp_dir.path().join("message.pgp");
+    let encrypted = encrypted.to_str().unwrap();
+    Assert::cargo_binary("sq")
+        .with_args(&["encrypt", "--recipient-cert", &cert_pgp,
+                     "--compression", "none",
+                     "--output", encrypted, message])
+        .unwrap();
+
+    // Without a key, the container can't be expanded.
+    Assert::cargo_binary("sq")
+        .with_args(&["--output-format", "json", "packet", "dump",
+                     "--packet", "1", encrypted])
+        .stdout().satisfies(|output| {
+            let v = parse(output);
+            let packets = v["packets"].as_array().unwrap();
+            assert_eq!(packets.len(), 1);
+            assert_eq!(packets[0]["tag"], 18);
+            assert_eq!(packets[0]["fields"]["decryption"]["status"],
+                       "no-session-key");
+            assert!(packets[0].get("children").is_none());
+            true
+        }, "Bad packet dump output")
+        .unwrap();
+
+    Assert::cargo_binary("sq")
+        .with_args(&["--output-format", "json", "packet", "dump",
+                     "--recipient-key", &key_pgp, "--packet", "1",
+                     encrypted])
+        .stdout().satisfies(|output| {
+            let v = parse(output);
+            let seip = &v["packets"][0];
+            assert_eq!(seip["fields"]["decryption"]["status"], "decrypted");
+            let literal = &seip["children"][0];
+            assert_eq!(literal["path"], "1.0");
+            assert_eq!(literal["offset"], 0);
+            assert_eq!(literal["tag"], 11);
+            assert_eq!(literal["fields"]["content"], "Hello world.\n");
+            true
+        }, "Bad packet dump output")
+        .unwrap();
+
+    // Select only the literal data packet, and skip its body.
+    Assert::cargo_binary("sq")
+        .with_args(&["--output-format", "json", "packet", "dump",
+                     "--recipient-key", &key_pgp, "--packet", "1.0",
+                     "--skip-literal-bodies", encrypted])
+        .stdout().satisfies(|output| {
+            let v = parse(output);
+            let packets = v["packets"].as_array().unwrap();
+            assert_eq!(packets.len(), 1);
+            assert_eq!(packets[0]["path"], "1.0");
+            assert!(packets[0]["fields"].get("content").is_none());
+            true
+        }, "Bad packet dump output")
+        .unwrap();
+
+    Assert::cargo_binary("sq")
+        .with_args(&["packet", "dump", "--recipient-key", &key_pgp,
+                     encrypted])
+        .stdout().contains("Decryption successful")
+        .stdout().contains("Hello world.")
+        .unwrap();
+
+    Assert::cargo_binary("sq")
+        .with_args(&["packet", "dump", "--packet", "1.x", encrypted])
+        .fails()
+        .stderr().contains("Malformed packet path")
+        .unwrap();
+
+    Ok(())
+}
@@ -0,0 +1,187 @@
+use std::fs::File;
+
+use assert_cli::Assert;
+use tempfile::TempDir;
+
+use sequoia_openpgp as openpgp;
+use openpgp::Result;
+use openpgp::cert::prelude::*;
+use openpgp::parse::Parse;
+use openpgp::policy::StandardPolicy;
+use openpgp::serialize::Serialize;
+use openpgp::types::{ReasonForRevocation, RevocationStatus};
+
+fn generate(tmp_dir: &TempDir, name: &str) -> Result<(Cert, String)> {
+    let path = tmp_dir.path().join(format!("{}.pgp", name));
+    let (cert, _) = CertBuilder::general_purpose(
+        None, Some(format!("{}@example.org", name)))
+        .add_userid(format!("{}@example.net", name))
+        .generate()?;
+    let mut file = File::create(&path)?;
+    cert.as_tsk().serialize(&mut file)?;
+    Ok((cert, path.to_str().unwrap().to_string()))
+}
+
+#[test]
+fn sq_revoke_certificate() -> Result<()> {
+    let tmp_dir = TempDir::new().unwrap();
+    let (alice, alice_pgp) = generate(&tmp_dir, "alice")?;
+
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["revoke", "certificate",
+              "--certificate", &alice_pgp,
+              "--notation", "reason@example.org", "moved on",
+              "compromised", "Lost my laptop",
+            ])
+        .stdout().satisfies(|output| {
+            let p = &StandardPolicy::new();
+
+            // The revocation alone merges into the certificate.
+            let revocation = Cert::from_bytes(output).unwrap();
+            assert_eq!(revocation.userids().count(), 0);
+            let cert = alice.clone().merge_public(revocation).unwrap();
+
+            match cert.revocation_status(p, None) {
+                RevocationStatus::Revoked(sigs) => {
+                    assert_eq!(sigs.len(), 1);
+                    assert_eq!(sigs[0].reason_for_revocation(),
+                               Some((ReasonForRevocation::KeyCompromised,
+                                     &b"Lost my laptop"[..])));
+                    assert_eq!(sigs[0].notation("reason@example.org")
+                               .collect::<Vec<_>>(),
+                               vec![&b"moved on"[..]]);
+                    true
+                },
+                _ => false,
+            }
+        }, "Certificate is not revoked")
+        .unwrap();
+
+    // Without secret key material, the certificate cannot be revoked.
+    let cert_pgp = tmp_dir.path().join("alice-cert.pgp");
+    alice.serialize(&mut File::create(&cert_pgp)?)?;
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["revoke", "certificate",
+              "--certificate", cert_pgp.to_str().unwrap(),
+              "retired", "Bye",
+            ])
+        .fails()
+        .unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn sq_revoke_subkey() -> Result<()> {
+    let tmp_dir = TempDir::new().unwrap();
+    let (alice, alice_pgp) = generate(&tmp_dir, "alice")?;
+    let subkey = alice.keys().subkeys().next().unwrap().fingerprint();
+
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["revoke", "subkey",
+              "--certificate", &alice_pgp,
+              "--full-certificate",
+              &subkey.to_hex(), "superseded", "New subkey",
+            ])
+        .stdout().satisfies(|output| {
+            let p = &StandardPolicy::new();
+
+            let cert = Cert::from_bytes(output).unwrap();
+            assert!(! cert.is_tsk());
+            assert!(matches!(cert.revocation_status(p, None),
+                             RevocationStatus::NotAsFarAsWeKnow));
+            let ka = cert.keys().subkeys()
+                .find(|ka| ka.fingerprint() == subkey).unwrap();
+            match ka.revocation_status(p, None) {
+                RevocationStatus::Revoked(sigs) =>
+                    sigs[0].reason_for_revocation().map(|(r, _)| r)
+                    == Some(ReasonForRevocation::KeySuperseded),
+                _ => false,
+            }
+        }, "Subkey is not revoked")
+        .unwrap();
+
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["revoke", "subkey",
+              "--certificate", &alice_pgp,
+              "0123456789ABCDEF", "retired", "No such subkey",
+            ])
+        .fails()
+        .unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn sq_revoke_userid() -> Result<()> {
+    let tmp_dir = TempDir::new().unwrap();
+    let (alice, alice_pgp) = generate(&tmp_dir, "alice")?;
+
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["revoke", "userid",
+              "--certificate", &alice_pgp,
+              "alice@example.net", "retired", "Left the company",
+            ])
+        .stdout().satisfies(|output| {
+            let p = &StandardPolicy::new();
+
+            let revocation = Cert::from_bytes(output).unwrap();
+            let cert = alice.clone().merge_public(revocation).unwrap();
+            cert.userids().all(|ua| {
+                let revoked = matches!(ua.revocation_status(p, None),
+                                       RevocationStatus::Revoked(_));
+                revoked == (ua.userid().value() == b"alice@example.net")
+            })
+        }, "User ID is not revoked")
+        .unwrap();
+
+    // Only User ID reasons are accepted.
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["revoke", "userid",
+              "--certificate", &alice_pgp,
+              "alice@example.net", "compromised", "Oops",
+            ])
+        .fails()
+        .unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn sq_revoke_third_party() -> Result<()> {
+    let tmp_dir = TempDir::new().unwrap();
+    let (bob, bob_pgp) = generate(&tmp_dir, "bob")?;
+
+    // Bob is a designated revoker of Carol's certificate.
+    let carol_pgp = tmp_dir.path().join("carol.pgp");
+    let (carol, _) = CertBuilder::general_purpose(
+        None, Some("carol@example.org"))
+        .set_revocation_keys(vec![(&bob).into()])
+        .generate()?;
+    carol.serialize(&mut File::create(&carol_pgp)?)?;
+
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["revoke", "certificate",
+              "--certificate", carol_pgp.to_str().unwrap(),
+              "--revocation-key", &bob_pgp,
+              "retired", "Carol asked me to",
+            ])
+        .stdout().satisfies(|output| {
+            let p = &StandardPolicy::new();
+
+            let revocation = Cert::from_bytes(output).unwrap();
+            let cert = carol.clone().merge_public(revocation).unwrap();
+            matches!(cert.revocation_status(p, None),
+                     RevocationStatus::CouldBe(_))
+        }, "No third-party revocation")
+        .unwrap();
+
+    Ok(())
+}
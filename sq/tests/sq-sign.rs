@@ -439,6 +439,146 @@ fn sq_sign_detached() {
         .unwrap();
 }
 
+#[test]
+fn sq_sign_batch() {
+    let tmp_dir = TempDir::new().unwrap();
+    let sub_dir = tmp_dir.path().join("sub");
+    fs::create_dir(&sub_dir).unwrap();
+    let files = [
+        tmp_dir.path().join("a.txt"),
+        tmp_dir.path().join("b.txt"),
+        sub_dir.join("c.txt"),
+    ];
+    for f in files.iter() {
+        fs::copy(artifact("messages/a-cypherpunks-manifesto.txt"), f)
+            .unwrap();
+    }
+
+    // Sign all files in the directory.
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["sign",
+              "--batch",
+              "--jobs", "2",
+              "--signer-key",
+              &artifact("keys/dennis-simon-anton-private.pgp"),
+              &tmp_dir.path().to_string_lossy()])
+        .unwrap();
+
+    for f in files.iter() {
+        let sig = f.with_extension("txt.asc");
+        let content = fs::read(&sig).unwrap();
+        assert!(&content[..].starts_with(
+            b"-----BEGIN PGP SIGNATURE-----\n\n"));
+
+        Assert::cargo_binary("sq")
+            .with_args(
+                &["verify",
+                  "--signer-cert",
+                  &artifact("keys/dennis-simon-anton.pgp"),
+                  "--detached",
+                  &sig.to_string_lossy(),
+                  &f.to_string_lossy()])
+            .unwrap();
+    }
+
+    // Existing signatures are not overwritten, and the other files are
+    // still signed.
+    let d = tmp_dir.path().join("d.txt");
+    fs::copy(artifact("messages/a-cypherpunks-manifesto.txt"), &d).unwrap();
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["sign",
+              "--batch",
+              "--binary",
+              "--signer-key",
+              &artifact("keys/dennis-simon-anton-private.pgp"),
+              &files[0].to_string_lossy(),
+              &d.to_string_lossy()])
+        .unwrap();
+    assert!(files[0].with_extension("txt.sig").exists());
+    assert!(d.with_extension("txt.sig").exists());
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["sign",
+              "--batch",
+              "--binary",
+              "--signer-key",
+              &artifact("keys/dennis-simon-anton-private.pgp"),
+              &files[0].to_string_lossy(),
+              &files[1].to_string_lossy()])
+        .fails()
+        .unwrap();
+    assert!(files[1].with_extension("txt.sig").exists());
+
+    // Neither armored nor binary signatures are signed themselves.
+    for f in files.iter().chain(Some(&d)) {
+        let _ = fs::remove_file(f.with_extension("txt.sig"));
+    }
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["sign",
+              "--batch",
+              "--binary",
+              "--signer-key",
+              &artifact("keys/dennis-simon-anton-private.pgp"),
+              &tmp_dir.path().to_string_lossy()])
+        .unwrap();
+    for f in files.iter() {
+        assert!(f.with_extension("txt.sig").exists());
+        assert!(! f.with_extension("txt.asc.sig").exists());
+    }
+
+    // Files that are merely named like signatures are signed, and the
+    // skipped signatures are reported.
+    for f in files.iter().chain(Some(&d)) {
+        let _ = fs::remove_file(f.with_extension("txt.sig"));
+    }
+    let notes = tmp_dir.path().join("notes.asc");
+    fs::write(&notes, b"Not a signature.\n").unwrap();
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["sign",
+              "--batch",
+              "--binary",
+              "--signer-key",
+              &artifact("keys/dennis-simon-anton-private.pgp"),
+              &tmp_dir.path().to_string_lossy()])
+        .stderr().contains("a.txt.asc, it is a detached signature")
+        .unwrap();
+    assert!(notes.with_extension("asc.sig").exists());
+}
+
+#[test]
+#[cfg(unix)]
+fn sq_sign_batch_symlinks() {
+    let tmp_dir = TempDir::new().unwrap();
+    let sub_dir = tmp_dir.path().join("sub");
+    fs::create_dir(&sub_dir).unwrap();
+    let file = sub_dir.join("a.txt");
+    fs::copy(artifact("messages/a-cypherpunks-manifesto.txt"), &file)
+        .unwrap();
+    // A link back to the top, which would loop if followed.
+    std::os::unix::fs::symlink(tmp_dir.path(), sub_dir.join("loop"))
+        .unwrap();
+    // Links to files are signed.
+    std::os::unix::fs::symlink(&file, tmp_dir.path().join("b.txt"))
+        .unwrap();
+
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["sign",
+              "--batch",
+              "--binary",
+              "--signer-key",
+              &artifact("keys/dennis-simon-anton-private.pgp"),
+              &tmp_dir.path().to_string_lossy()])
+        .stderr().contains("it is a symbolic link to a directory")
+        .unwrap();
+    assert!(file.with_extension("txt.sig").exists());
+    assert!(tmp_dir.path().join("b.txt.sig").exists());
+}
+
 #[test]
 fn sq_sign_detached_append() {
     let tmp_dir = TempDir::new().unwrap();
@@ -0,0 +1,87 @@
+use std::fs::File;
+use std::io::Write;
+use std::time::{Duration, UNIX_EPOCH};
+
+use assert_cli::Assert;
+use tempfile::TempDir;
+
+use sequoia_openpgp as openpgp;
+use openpgp::Result;
+use openpgp::cert::prelude::*;
+use openpgp::policy::StandardPolicy;
+use openpgp::serialize::Serialize;
+use openpgp::serialize::stream::{LiteralWriter, Message, Signer};
+use openpgp::types::HashAlgorithm;
+
+#[test]
+fn sq_verify_explain() -> Result<()> {
+    let p = &StandardPolicy::new();
+    let tmp_dir = TempDir::new().unwrap();
+    let path = |name: &str| {
+        tmp_dir.path().join(name).to_str().unwrap().to_string()
+    };
+
+    // 2011-01-01 and 2012-01-01, i.e. before SHA-1 was phased out.
+    let y2011 = UNIX_EPOCH + Duration::from_secs(1293840000);
+    let y2012 = UNIX_EPOCH + Duration::from_secs(1325376000);
+
+    let (alice, _) = CertBuilder::general_purpose(None, Some("alice@example.org"))
+        .set_creation_time(y2011)
+        .generate()?;
+    let alice_pgp = path("alice.pgp");
+    alice.serialize(&mut File::create(&alice_pgp)?)?;
+
+    // Sign a message using SHA-1, back in 2012.
+    let signed = path("signed.pgp");
+    {
+        let keypair = alice.keys().with_policy(p, y2012).for_signing()
+            .secret().next().unwrap().key().clone().into_keypair()?;
+        let message = Message::new(File::create(&signed)?);
+        let message = Signer::new(message, keypair)
+            .hash_algo(HashAlgorithm::SHA1)?
+            .creation_time(y2012)
+            .build()?;
+        let mut message = LiteralWriter::new(message).build()?;
+        message.write_all(b"Hello, world!\n")?;
+        message.finalize()?;
+    }
+
+    Assert::cargo_binary("sq")
+        .with_args(&["--no-cert-store", "verify",
+                     "--signer-cert", &alice_pgp, &signed])
+        .fails()
+        .unwrap();
+
+    Assert::cargo_binary("sq")
+        .with_args(&["--no-cert-store", "verify", "--explain",
+                     "--signer-cert", &alice_pgp, &signed])
+        .fails()
+        .stderr().contains("Failed component: the signature")
+        .stderr().contains("SHA1 is not considered secure since")
+        .stderr().contains("--policy-as-of")
+        .unwrap();
+
+    // Under the policy in effect back then, the signature is good.
+    Assert::cargo_binary("sq")
+        .with_args(&["--no-cert-store", "verify",
+                     "--policy-as-of", "2012-06-01",
+                     "--signer-cert", &alice_pgp, &signed])
+        .stdout().contains("Hello, world!")
+        .stderr().contains("1 good signature")
+        .unwrap();
+
+    // The signer's certificate is missing.
+    let (bob, _) = CertBuilder::general_purpose(None, Some("bob@example.org"))
+        .generate()?;
+    let bob_pgp = path("bob.pgp");
+    bob.serialize(&mut File::create(&bob_pgp)?)?;
+    Assert::cargo_binary("sq")
+        .with_args(&["--no-cert-store", "verify", "--explain",
+                     "--policy-as-of", "2012-06-01",
+                     "--signer-cert", &bob_pgp, &signed])
+        .fails()
+        .stderr().contains("Failed component: the signer's certificate")
+        .unwrap();
+
+    Ok(())
+}
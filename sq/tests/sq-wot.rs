@@ -0,0 +1,172 @@
+use std::fs::File;
+use std::time::{Duration, SystemTime};
+
+use assert_cli::Assert;
+use tempfile::TempDir;
+
+use sequoia_openpgp as openpgp;
+use openpgp::{Cert, Result};
+use openpgp::cert::prelude::*;
+use openpgp::crypto::KeyPair;
+use openpgp::packet::signature::SignatureBuilder;
+use openpgp::serialize::Serialize;
+use openpgp::types::SignatureType;
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Generates a certificate with a single User ID.
+fn cert(userid: &str, creation_time: SystemTime) -> Result<(Cert, KeyPair)> {
+    let (cert, _) = CertBuilder::general_purpose(None, Some(userid))
+        .set_creation_time(creation_time)
+        .generate()?;
+    let keypair = cert.primary_key().key().clone()
+        .parts_into_secret()?.into_keypair()?;
+    Ok((cert, keypair))
+}
+
+/// Certifies the first User ID of `cert`.
+fn certify(signer: &mut KeyPair, cert: Cert, creation_time: SystemTime,
+           depth: u8, amount: u8, regex: Option<&str>)
+           -> Result<Cert>
+{
+    let userid = cert.userids().next().unwrap().userid().clone();
+    let mut builder = SignatureBuilder::new(SignatureType::GenericCertification)
+        .set_signature_creation_time(creation_time)?;
+    if depth > 0 || amount != 120 {
+        builder = builder.set_trust_signature(depth, amount)?;
+    }
+    if let Some(regex) = regex {
+        builder = builder.add_regular_expression(regex)?;
+    }
+    let sig = userid.bind(signer, &cert, builder)?;
+    cert.insert_packets(sig)
+}
+
+#[test]
+fn sq_wot() -> Result<()> {
+    let tmp_dir = TempDir::new().unwrap();
+    let t0 = SystemTime::now() - 2 * DAY;
+    let t1 = SystemTime::now() - DAY;
+
+    let (root, mut root_signer) = cert("Root <root@example.org>", t0)?;
+    let (ca, mut ca_signer) = cert("CA <ca@example.org>", t0)?;
+    let (alice, _) = cert("Alice <alice@example.org>", t0)?;
+    let (bob, _) = cert("Bob <bob@other.org>", t0)?;
+    let (carol, _) = cert("Carol <carol@example.org>", t0)?;
+
+    // The root makes the CA a trusted introducer for example.org.
+    let ca = certify(&mut root_signer, ca, t1, 1, 120,
+                     Some(r"<[^>]+[@.]example\.org>$"))?;
+    let alice = certify(&mut ca_signer, alice, t1, 0, 120, None)?;
+    let bob = certify(&mut ca_signer, bob, t1, 0, 120, None)?;
+    // The root partially authenticates Carol.
+    let carol = certify(&mut root_signer, carol, t1, 0, 60, None)?;
+
+    let keyring = tmp_dir.path().join("keyring.pgp");
+    {
+        let mut f = File::create(&keyring)?;
+        for cert in &[&root, &ca, &alice, &bob, &carol] {
+            cert.serialize(&mut f)?;
+        }
+    }
+    let keyring = keyring.to_str().unwrap();
+
+    let root_fp = root.fingerprint().to_hex();
+    let ca_fp = ca.fingerprint().to_hex();
+    let alice_fp = alice.fingerprint().to_hex();
+    let bob_fp = bob.fingerprint().to_hex();
+    let carol_fp = carol.fingerprint().to_hex();
+
+    let authenticate = |args: &[&str]| {
+        let mut a = vec!["--no-cert-store", "wot", "authenticate",
+                         "--trust-root", &root_fp, "--keyring", keyring];
+        a.extend_from_slice(args);
+        Assert::cargo_binary("sq").with_args(&a)
+    };
+
+    // Via the CA.
+    authenticate(&[&alice_fp, "Alice <alice@example.org>"])
+        .stdout().contains(ca_fp.as_str())
+        .unwrap();
+    authenticate(&["--email", &alice_fp, "ALICE@example.org"])
+        .unwrap();
+
+    // The root authenticates its own User IDs.
+    authenticate(&[&root_fp, "Root <root@example.org>"])
+        .unwrap();
+
+    // The CA may not authenticate other domains.
+    authenticate(&[&bob_fp, "Bob <bob@other.org>"])
+        .fails()
+        .unwrap();
+
+    // Partial authentication.
+    authenticate(&[&carol_fp, "Carol <carol@example.org>"])
+        .fails()
+        .stdout().contains("partially authenticated (60/120)")
+        .unwrap();
+    authenticate(&["--amount", "60", &carol_fp, "Carol <carol@example.org>"])
+        .unwrap();
+
+    // Before the certifications were made.
+    let before = chrono::DateTime::<chrono::offset::Utc>::from(t1 - DAY / 2)
+        .format("%Y-%m-%dT%H:%M:%SZ").to_string();
+    authenticate(&["--time", &before, &alice_fp, "Alice <alice@example.org>"])
+        .fails()
+        .unwrap();
+
+    // Look up bindings by email address.
+    Assert::cargo_binary("sq")
+        .with_args(&["--no-cert-store", "wot", "lookup",
+                     "--trust-root", &root_fp, "--keyring", keyring,
+                     "--email", "alice@example.org"])
+        .stdout().contains(alice_fp.as_str())
+        .unwrap();
+    Assert::cargo_binary("sq")
+        .with_args(&["--no-cert-store", "wot", "lookup",
+                     "--trust-root", &root_fp, "--keyring", keyring,
+                     "--email", "bob@other.org"])
+        .fails()
+        .unwrap();
+
+    // Check paths.
+    Assert::cargo_binary("sq")
+        .with_args(&["--no-cert-store", "wot", "path", "--keyring", keyring,
+                     &root_fp, &ca_fp, &alice_fp, "Alice <alice@example.org>"])
+        .unwrap();
+    Assert::cargo_binary("sq")
+        .with_args(&["--no-cert-store", "wot", "path", "--keyring", keyring,
+                     &root_fp, &alice_fp, "Alice <alice@example.org>"])
+        .fails()
+        .stderr().contains("did not certify")
+        .unwrap();
+    Assert::cargo_binary("sq")
+        .with_args(&["--no-cert-store", "wot", "path", "--keyring", keyring,
+                     &root_fp, &ca_fp, &bob_fp, "Bob <bob@other.org>"])
+        .fails()
+        .stderr().contains("may not authenticate")
+        .unwrap();
+
+    // Revoking the CA breaks the path.
+    let revocation = CertRevocationBuilder::new()
+        .set_reason_for_revocation(
+            openpgp::types::ReasonForRevocation::KeyCompromised, b"")?
+        .build(&mut ca_signer, &ca, None)?;
+    let ca = ca.insert_packets(revocation)?;
+    let keyring = tmp_dir.path().join("revoked.pgp");
+    {
+        let mut f = File::create(&keyring)?;
+        for cert in &[&root, &ca, &alice] {
+            cert.serialize(&mut f)?;
+        }
+    }
+    Assert::cargo_binary("sq")
+        .with_args(&["--no-cert-store", "wot", "authenticate",
+                     "--trust-root", &root_fp,
+                     "--keyring", keyring.to_str().unwrap(),
+                     &alice_fp, "Alice <alice@example.org>"])
+        .fails()
+        .unwrap();
+
+    Ok(())
+}